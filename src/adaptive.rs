@@ -0,0 +1,185 @@
+use crate::config::AdaptiveConcurrencyConfig;
+use crate::metrics::METRICS;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// The server reports in this response header how long the request waited
+/// in its scheduler queue
+pub const QUEUE_TIME_HEADER: &str = "x-arango-queue-time-seconds";
+
+/// The limit is decreased at most once per this interval, so that a burst
+/// of rejections of requests sent at the same time counts as one signal
+const DECREASE_COOLDOWN: Duration = Duration::from_millis(200);
+
+/// Limits the number of requests in flight to one endpoint with an AIMD
+/// controller: every response without pushback raises the limit by one
+/// per window of requests, a 429 or 503 response or a queue time above the
+/// target multiplies it by `decrease_factor`. This keeps the load just
+/// below what the coordinator can take instead of overloading it.
+#[derive(Debug)]
+pub struct AdaptiveLimiter {
+    endpoint: String,
+    config: AdaptiveConcurrencyConfig,
+    state: Mutex<LimiterState>,
+    released: Notify,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    limit: f64,
+    in_flight: usize,
+    last_decrease: Option<Instant>,
+}
+
+/// A request slot, which is given back when dropped
+pub struct Permit<'a> {
+    limiter: &'a AdaptiveLimiter,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.limiter.state.lock().unwrap().in_flight -= 1;
+        self.limiter.released.notify_waiters();
+    }
+}
+
+impl AdaptiveLimiter {
+    pub fn new(endpoint: &str, config: &AdaptiveConcurrencyConfig) -> Self {
+        let limiter = AdaptiveLimiter {
+            endpoint: endpoint.to_string(),
+            config: config.clone(),
+            state: Mutex::new(LimiterState {
+                limit: config.initial.clamp(config.min.max(1), config.max.max(1)) as f64,
+                in_flight: 0,
+                last_decrease: None,
+            }),
+            released: Notify::new(),
+        };
+        limiter.publish(limiter.limit());
+        limiter
+    }
+
+    /// The current limit of requests in flight
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit as usize
+    }
+
+    /// Waits until a request may be sent
+    pub async fn acquire(&self) -> Permit<'_> {
+        loop {
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            {
+                let mut state = self.state.lock().unwrap();
+                if state.in_flight < state.limit as usize {
+                    state.in_flight += 1;
+                    return Permit { limiter: self };
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Adjusts the limit to a response with the given status code and
+    /// queue time, if the server reported one
+    pub fn on_response(&self, status: u16, queue_time_secs: Option<f64>) {
+        let pushback = matches!(status, 429 | 503)
+            || queue_time_secs.is_some_and(|t| t > self.config.target_queue_time_secs);
+        self.adjust(pushback);
+    }
+
+    /// Adjusts the limit to a request which timed out, which is pushback
+    /// like a 503
+    pub fn on_timeout(&self) {
+        self.adjust(true);
+    }
+
+    fn adjust(&self, pushback: bool) {
+        let limit = {
+            let mut state = self.state.lock().unwrap();
+            if pushback {
+                let now = Instant::now();
+                if state
+                    .last_decrease
+                    .is_some_and(|last| now.duration_since(last) < DECREASE_COOLDOWN)
+                {
+                    return;
+                }
+                state.last_decrease = Some(now);
+                state.limit =
+                    (state.limit * self.config.decrease_factor).max(self.config.min.max(1) as f64);
+            } else {
+                state.limit = (state.limit + 1.0 / state.limit).min(self.config.max.max(1) as f64);
+            }
+            state.limit as usize
+        };
+        self.publish(limit);
+        if !pushback {
+            self.released.notify_waiters();
+        }
+    }
+
+    fn publish(&self, limit: usize) {
+        METRICS
+            .concurrency_limit
+            .with_label_values(&[&self.endpoint])
+            .set(limit as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter() -> AdaptiveLimiter {
+        AdaptiveLimiter::new(
+            "http://a:8529",
+            &AdaptiveConcurrencyConfig {
+                initial: 8,
+                min: 2,
+                max: 10,
+                target_queue_time_secs: 0.1,
+                decrease_factor: 0.5,
+                comment: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_limit_grows_additively_and_shrinks_multiplicatively() {
+        let limiter = limiter();
+        for _ in 0..9 {
+            limiter.on_response(200, Some(0.01));
+        }
+        assert_eq!(limiter.limit(), 9);
+        for _ in 0..100 {
+            limiter.on_response(200, None);
+        }
+        assert_eq!(limiter.limit(), 10);
+
+        limiter.on_response(503, None);
+        assert_eq!(limiter.limit(), 5);
+        // Within the cooldown further pushback is ignored
+        limiter.on_response(200, Some(1.0));
+        assert_eq!(limiter.limit(), 5);
+
+        limiter.state.lock().unwrap().last_decrease = None;
+        limiter.on_response(429, None);
+        limiter.state.lock().unwrap().last_decrease = None;
+        limiter.on_response(429, None);
+        assert_eq!(limiter.limit(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_respects_limit() {
+        let limiter = limiter();
+        let permits: Vec<_> = futures::future::join_all((0..8).map(|_| limiter.acquire())).await;
+        let blocked = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(blocked.is_err());
+        drop(permits);
+        let unblocked = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(unblocked.is_ok());
+    }
+}
@@ -0,0 +1,159 @@
+use crate::arangodb::{
+    collection_count, drop_database, list_collections, list_databases, read_marker, ApiRequest,
+    Connection,
+};
+use crate::config::DatabaseConfig;
+use anyhow::Result;
+use log::info;
+use reqwest::Method;
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The outcome of checking one endpoint: the server version or the error
+pub struct EndpointCheck {
+    pub url: String,
+    pub result: Result<String>,
+}
+
+impl EndpointCheck {
+    pub fn format(&self) -> String {
+        match &self.result {
+            Ok(version) => format!("{:<32} ok, ArangoDB {}", self.url, version),
+            Err(e) => format!("{:<32} failed: {:#}", self.url, e),
+        }
+    }
+}
+
+/// The number of documents of one collection of the loader
+pub struct CollectionStatus {
+    pub database: String,
+    pub collection: String,
+    pub count: u64,
+}
+
+/// Asks every configured endpoint for its version, with the credentials of
+/// the configuration
+///
+/// # Errors
+/// Fails if the connection cannot be configured, failures of single
+/// endpoints are part of the result
+pub async fn check_endpoints(db_config: &DatabaseConfig) -> Result<Vec<EndpointCheck>> {
+    let conn = Connection::new(db_config).await?;
+    let mut checks = Vec::new();
+    for url in &db_config.endpoints {
+        let request = ApiRequest::new(Method::GET, "/_api/version").pinned_to(url);
+        let result = async {
+            let body: Value = conn
+                .send(request)
+                .await?
+                .error_for_status("get version")?
+                .json()?;
+            Ok(body["version"].as_str().unwrap_or("unknown").to_string())
+        }
+        .await;
+        checks.push(EndpointCheck {
+            url: url.clone(),
+            result,
+        });
+    }
+    Ok(checks)
+}
+
+/// Returns the databases whose name starts with the configured prefix
+async fn prefixed_databases(conn: &Connection, prefix: &str) -> Result<Vec<String>> {
+    if prefix.is_empty() {
+        return Err(anyhow::anyhow!(
+            "The database prefix is empty, the databases of the loader cannot be told apart"
+        ));
+    }
+    let mut databases: Vec<String> = list_databases(conn)
+        .await?
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    databases.sort();
+    Ok(databases)
+}
+
+/// Drops all databases whose name starts with the configured prefix and
+/// returns their names
+///
+/// # Errors
+/// Fails if the prefix is empty or a database cannot be listed or dropped
+pub async fn cleanup(db_config: &DatabaseConfig) -> Result<Vec<String>> {
+    let conn = Connection::new(db_config).await?;
+    let databases = prefixed_databases(&conn, &db_config.prefix).await?;
+    for name in &databases {
+        info!("Dropping database {}", name);
+        drop_database(&conn, name).await?;
+    }
+    Ok(databases)
+}
+
+/// Drops the databases with the configured prefix whose marker shows that
+/// another run created them more than `stale_after_secs` ago and returns
+/// their names. Databases without a marker are kept.
+///
+/// # Errors
+/// Fails if the prefix is empty or a database cannot be listed, checked
+/// or dropped
+pub async fn cleanup_stale(db_config: &DatabaseConfig) -> Result<Vec<String>> {
+    let conn = Connection::new(db_config).await?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let mut dropped = Vec::new();
+    for name in prefixed_databases(&conn, &db_config.prefix).await? {
+        let Some(marker) = read_marker(&conn, &name).await? else {
+            continue;
+        };
+        let age = now.saturating_sub(marker.created_at);
+        if Some(&marker.run_id) == db_config.run_id.as_ref() || age <= db_config.stale_after_secs {
+            continue;
+        }
+        info!(
+            "Dropping stale database {} of run {}, created {} seconds ago",
+            name, marker.run_id, age
+        );
+        drop_database(&conn, &name).await?;
+        dropped.push(name);
+    }
+    Ok(dropped)
+}
+
+/// Returns the collections and their document counts of all databases
+/// whose name starts with the configured prefix
+///
+/// # Errors
+/// Fails if the prefix is empty or the databases or collections cannot be
+/// listed or counted
+pub async fn status(db_config: &DatabaseConfig) -> Result<Vec<CollectionStatus>> {
+    let conn = Connection::new(db_config).await?;
+    let mut status = Vec::new();
+    for database in prefixed_databases(&conn, &db_config.prefix).await? {
+        let mut collections = list_collections(&conn, &database).await?;
+        collections.sort();
+        for collection in collections {
+            let count = collection_count(&conn, &database, &collection).await?;
+            status.push(CollectionStatus {
+                database: database.clone(),
+                collection,
+                count,
+            });
+        }
+    }
+    Ok(status)
+}
+
+/// Returns a table of the collections and their document counts
+pub fn format_status(status: &[CollectionStatus]) -> String {
+    let mut lines = vec![format!(
+        "{:<32} {:<32} {:>14}",
+        "Database", "Collection", "Documents"
+    )];
+    for collection in status {
+        lines.push(format!(
+            "{:<32} {:<32} {:>14}",
+            collection.database, collection.collection, collection.count
+        ));
+    }
+    lines.join("\n")
+}
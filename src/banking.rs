@@ -0,0 +1,422 @@
+use crate::arangodb::{
+    collection_exists, create_collection, create_database, database_exists, drop_database,
+    run_query, ApiRequest, Connection,
+};
+use crate::config::{BankingConfig, CollectionProperties, DatabaseConfig, Phase, UseCaseConfig};
+use crate::control;
+use crate::metrics::{observe_request, METRICS};
+use crate::pacer::Pacer;
+use crate::transactions::TRX_ID_HEADER;
+use crate::usecase::{run_workers, send_observed, CollectionCount, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{error, info, warn};
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "banking";
+
+/// The collection with the accounts
+const COLLECTION: &str = "accounts";
+
+/// Number of accounts created per request during seeding
+const SEED_BATCH_SIZE: u32 = 1000;
+
+/// The banking use case: transfers money between accounts in stream
+/// transactions and checks that the total balance never changes. Besides
+/// generating OLTP load, this is a correctness test, e.g. during failovers.
+pub struct BankingUseCase {
+    banking_config: BankingConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl BankingUseCase {
+    pub fn new(
+        banking_config: BankingConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        BankingUseCase {
+            banking_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Banking use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let banking_config = &self.banking_config;
+        info!("Starting banking use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!("Number of shards: {}", banking_config.number_of_shards);
+        info!("Replication factor: {}", banking_config.replication_factor);
+        info!(
+            "Number of accounts: {}, initial balance: {}",
+            banking_config.number_of_accounts, banking_config.initial_balance
+        );
+        info!("Maximal transfer: {}", banking_config.max_transfer);
+        info!("Concurrency: {}", banking_config.concurrency);
+        if banking_config.target_ops_per_sec > 0.0 {
+            info!(
+                "Target rate: {} transfers/s",
+                banking_config.target_ops_per_sec
+            );
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Check interval: {}s", banking_config.check_interval_secs);
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the banking use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("banking")
+}
+
+/// The total balance of all accounts, which transfers must not change
+fn expected_total(banking_config: &BankingConfig) -> u64 {
+    u64::from(banking_config.number_of_accounts) * banking_config.initial_balance
+}
+
+/// Creates the database and the collection if needed and opens the
+/// accounts which do not exist yet, existing accounts keep their balance
+async fn initialize_database(conn: &Connection, banking_config: &BankingConfig) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    if database_exists(conn, &db_name).await? && banking_config.drop_first {
+        info!("Dropping database {}", db_name);
+        drop_database(conn, &db_name).await?;
+    }
+    if !database_exists(conn, &db_name).await? {
+        info!("Creating database {}", db_name);
+        create_database(conn, &db_name).await?;
+    }
+    if !collection_exists(conn, &db_name, COLLECTION).await? {
+        create_collection(
+            conn,
+            &db_name,
+            COLLECTION,
+            banking_config.number_of_shards,
+            banking_config.replication_factor,
+            &CollectionProperties::default(),
+        )
+        .await?;
+    }
+
+    let path = format!("/_api/document/{}", COLLECTION);
+    let mut first = 1;
+    while first <= banking_config.number_of_accounts {
+        let last = (first + SEED_BATCH_SIZE - 1).min(banking_config.number_of_accounts);
+        let accounts: Vec<Value> = (first..=last)
+            .map(|account| {
+                json!({"_key": format!("A{}", account), "balance": banking_config.initial_balance})
+            })
+            .collect();
+        let request = ApiRequest::in_database(Method::POST, &db_name, &path)
+            .query("overwriteMode", "ignore")
+            .json(&accounts);
+        send_observed(conn, USECASE, "seed", request, &[]).await?;
+        first = last + 1;
+    }
+    info!(
+        "Opened {} accounts in {}/{}",
+        banking_config.number_of_accounts, db_name, COLLECTION
+    );
+    Ok(())
+}
+
+/// Transfers a random amount between two random accounts. Transfers which
+/// conflict with a concurrent one or exceed the balance are aborted.
+/// The latency of the whole transaction is recorded as operation
+/// `transfer`.
+async fn perform_transfer(
+    conn: &Connection,
+    banking_config: &BankingConfig,
+    mut rng: StdRng,
+) -> Result<()> {
+    let accounts = banking_config.number_of_accounts;
+    let from = rng.random_range(1..=accounts);
+    let mut to = rng.random_range(1..accounts);
+    if to >= from {
+        to += 1;
+    }
+    let amount = rng.random_range(1..=banking_config.max_transfer.max(1));
+
+    let start = Instant::now();
+    let result = run_transfer(conn, from, to, amount).await;
+    observe_request(USECASE, "transfer", start.elapsed(), result.is_ok());
+    result
+}
+
+async fn run_transfer(conn: &Connection, from: u32, to: u32, amount: u64) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    let request = ApiRequest::in_database(Method::POST, &db_name, "/_api/transaction/begin")
+        .json(&json!({"collections": {"write": [COLLECTION]}}));
+    let response = send_observed(conn, USECASE, "begin", request, &[]).await?;
+
+    // The transaction lives on the coordinator which started it, so all
+    // further requests must go there
+    let endpoint = response.endpoint.clone();
+    let trx_id = response.json::<Value>()?["result"]["id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Transaction begin returned no id"))?
+        .to_string();
+    let trx_header = HeaderValue::from_str(&trx_id)?;
+    let in_transaction = |request: ApiRequest| {
+        request
+            .header(HeaderName::from_static(TRX_ID_HEADER), trx_header.clone())
+            .pinned_to(&endpoint)
+    };
+
+    let mut result: Result<bool> = Ok(true);
+    for (account, change) in [(from, -(amount as i64)), (to, amount as i64)] {
+        let path = format!("/_api/document/{}/A{}", COLLECTION, account);
+        let request = in_transaction(ApiRequest::in_database(Method::GET, &db_name, &path));
+        let account_doc = match send_observed(conn, USECASE, "read", request, &[]).await {
+            Ok(response) => response.json::<Value>().map_err(anyhow::Error::from),
+            Err(e) => Err(e),
+        };
+        let balance = match account_doc.map(|account_doc| account_doc["balance"].as_i64()) {
+            Ok(Some(balance)) => balance,
+            // Taking a missing balance for 0 would create or destroy money
+            Ok(None) => {
+                result = Err(anyhow::anyhow!("Account A{} has no balance", account));
+                break;
+            }
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        };
+        if balance + change < 0 {
+            // Insufficient funds
+            result = Ok(false);
+            break;
+        }
+        // A concurrent transfer which changed the account after this
+        // transaction started makes the write fail with a conflict
+        let request = in_transaction(
+            ApiRequest::in_database(Method::PATCH, &db_name, &path)
+                .json(&json!({"balance": balance + change})),
+        );
+        match send_observed(conn, USECASE, "write", request, &[409]).await {
+            Ok(response) if response.status.as_u16() == 409 => {
+                METRICS.conflicts.with_label_values(&[USECASE]).inc();
+                result = Ok(false);
+                break;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+
+    // Transfers which could not be carried out completely are aborted
+    let (method, name) = if matches!(result, Ok(true)) {
+        (Method::PUT, "commit")
+    } else {
+        (Method::DELETE, "abort")
+    };
+    let request = in_transaction(ApiRequest::in_database(
+        method,
+        &db_name,
+        &format!("/_api/transaction/{}", trx_id),
+    ));
+    let finished = send_observed(conn, USECASE, name, request, &[]).await;
+    result?;
+    finished?;
+    Ok(())
+}
+
+/// Returns the sum of all balances and the number of accounts
+async fn total_balance(conn: &Connection, db_name: &str) -> Result<(u64, u64)> {
+    let mut bind_vars = Map::new();
+    bind_vars.insert("@collection".to_string(), json!(COLLECTION));
+    let mut totals = (0, 0);
+    run_query(
+        conn,
+        db_name,
+        "FOR a IN @@collection COLLECT AGGREGATE total = SUM(a.balance), accounts = COUNT(1) \
+         RETURN { total, accounts }",
+        &bind_vars,
+        1,
+        |batch| {
+            if let Some(row) = batch.first() {
+                totals = (
+                    row["total"].as_u64().unwrap_or(0),
+                    row["accounts"].as_u64().unwrap_or(0),
+                );
+            }
+        },
+    )
+    .await?;
+    Ok(totals)
+}
+
+/// Compares the total balance with the expected one and records the
+/// outcome as operation `check`. Returns whether the total was correct.
+async fn check_balance(conn: &Connection, banking_config: &BankingConfig) -> Result<bool> {
+    let db_name = database_name(conn.config());
+    let start = Instant::now();
+    let (total, accounts) = total_balance(conn, &db_name).await?;
+    let expected = expected_total(banking_config);
+    observe_request(USECASE, "check", start.elapsed(), total == expected);
+    if total == expected {
+        info!(
+            "Total balance of {} accounts is {} as expected",
+            accounts, total
+        );
+    } else {
+        warn!(
+            "Total balance of {} accounts is {}, expected {}",
+            accounts, total, expected
+        );
+    }
+    Ok(total == expected)
+}
+
+/// Periodically checks the total balance while transfers are running. In
+/// a cluster, a query does not read all shards at the same point in time,
+/// so a transfer between shards may make a single check fail. Only the
+/// final check after the load has stopped is authoritative.
+async fn check_periodically(
+    conn: Connection,
+    banking_config: BankingConfig,
+    shutdown: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(
+        banking_config.check_interval_secs.max(1),
+    ));
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => return,
+        }
+        if let Err(e) = check_balance(&conn, &banking_config).await {
+            warn!("Cannot check the total balance: {}", e);
+        }
+    }
+}
+
+#[async_trait]
+impl UseCase for BankingUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        if self.banking_config.number_of_accounts < 2 {
+            return Err(anyhow::anyhow!(
+                "The banking use case needs at least two accounts"
+            ));
+        }
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize_database(&connection, &self.banking_config).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let checker = tokio::spawn(check_periodically(
+            connection.clone(),
+            self.banking_config.clone(),
+            shutdown.clone(),
+        ));
+
+        let pacer = Arc::new(Pacer::new(self.banking_config.target_ops_per_sec));
+        let banking_config = Arc::new(RwLock::new(Arc::new(self.banking_config.clone())));
+        let reloaded = Arc::clone(&banking_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(banking) = &config.banking {
+                control::apply_load(USECASE, banking.concurrency, banking.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(banking.clone());
+            }
+        });
+        let worker_connection = connection.clone();
+        run_workers(
+            USECASE,
+            self.banking_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = worker_connection.clone();
+                let banking_config = Arc::clone(&banking_config.read().unwrap());
+                async move { perform_transfer(&connection, &banking_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        checker.await?;
+
+        if !check_balance(&connection, &self.banking_config).await? {
+            error!("The total balance changed, transfers were lost or duplicated");
+            return Err(anyhow::anyhow!(
+                "The total balance is not {}",
+                expected_total(&self.banking_config)
+            ));
+        }
+        Ok(())
+    }
+
+    async fn verify_seed(&self) -> Result<Vec<CollectionCount>> {
+        let db_name = database_name(&self.db_config);
+        let expected = u64::from(self.banking_config.number_of_accounts);
+        Ok(vec![
+            CollectionCount::fetch(&self.connection()?, &db_name, COLLECTION, expected).await?,
+        ])
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.banking_config.drop_on_exit {
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&self.connection()?, &db_name).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use crate::config::CircuitBreakerConfig;
+use crate::metrics::METRICS;
+use log::{info, warn};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Keeps a sick endpoint out of the rotation: after `failure_threshold`
+/// failed or slow requests in a row the breaker opens and no requests are
+/// sent to the endpoint for `open_secs`. Then it is half open and lets
+/// `probe_requests` requests through; if all of them succeed it closes
+/// again, otherwise it opens for another `open_secs`.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    endpoint: String,
+    config: CircuitBreakerConfig,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        since: Instant,
+    },
+    /// `probes` requests were let through since `since`, of which
+    /// `successes` succeeded
+    HalfOpen {
+        since: Instant,
+        probes: u32,
+        successes: u32,
+    },
+}
+
+impl CircuitBreaker {
+    pub fn new(endpoint: &str, config: &CircuitBreakerConfig) -> Self {
+        let breaker = CircuitBreaker {
+            endpoint: endpoint.to_string(),
+            config: config.clone(),
+            state: Mutex::new(State::Closed { failures: 0 }),
+        };
+        breaker.publish(State::Closed { failures: 0 });
+        breaker
+    }
+
+    fn open_duration(&self) -> Duration {
+        Duration::from_secs(self.config.open_secs)
+    }
+
+    /// Returns whether a request may be sent to the endpoint now. A half
+    /// open breaker counts the request as one of its probes. Probes which
+    /// never report back are given up after `open_secs`.
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let expired = |since: Instant| since.elapsed() >= self.open_duration();
+        match &mut *state {
+            State::Closed { .. } => return true,
+            State::Open { since } if !expired(*since) => return false,
+            State::HalfOpen { since, probes, .. } if !expired(*since) => {
+                if *probes >= self.config.probe_requests.max(1) {
+                    return false;
+                }
+                *probes += 1;
+                return true;
+            }
+            // Open long enough, or the probes got lost
+            _ => {}
+        }
+        let next = State::HalfOpen {
+            since: Instant::now(),
+            probes: 1,
+            successes: 0,
+        };
+        *state = next;
+        drop(state);
+        info!("Circuit breaker of {} half open", self.endpoint);
+        self.publish(next);
+        true
+    }
+
+    /// Records the outcome of a request to the endpoint which took
+    /// `latency`, successful requests slower than `slow_request_ms` count
+    /// as failed
+    pub fn on_result(&self, failed: bool, latency: Duration) {
+        let failed = failed
+            || self
+                .config
+                .slow_request_ms
+                .is_some_and(|ms| latency > Duration::from_millis(ms));
+        let mut state = self.state.lock().unwrap();
+        let next = match *state {
+            State::Closed { failures } if failed => {
+                if failures + 1 < self.config.failure_threshold.max(1) {
+                    *state = State::Closed {
+                        failures: failures + 1,
+                    };
+                    return;
+                }
+                warn!(
+                    "Circuit breaker of {} opened after {} failed requests in a row",
+                    self.endpoint,
+                    failures + 1
+                );
+                State::Open {
+                    since: Instant::now(),
+                }
+            }
+            State::Closed { failures } => {
+                if failures > 0 {
+                    *state = State::Closed { failures: 0 };
+                }
+                return;
+            }
+            // Requests sent before the breaker opened
+            State::Open { .. } => return,
+            State::HalfOpen { .. } if failed => {
+                warn!(
+                    "Circuit breaker of {} opened again, a probe request failed",
+                    self.endpoint
+                );
+                State::Open {
+                    since: Instant::now(),
+                }
+            }
+            State::HalfOpen {
+                since,
+                probes,
+                successes,
+            } => {
+                if successes + 1 < self.config.probe_requests.max(1) {
+                    *state = State::HalfOpen {
+                        since,
+                        probes,
+                        successes: successes + 1,
+                    };
+                    return;
+                }
+                info!("Circuit breaker of {} closed", self.endpoint);
+                State::Closed { failures: 0 }
+            }
+        };
+        *state = next;
+        drop(state);
+        self.publish(next);
+    }
+
+    /// Whether the breaker currently keeps requests away from the endpoint
+    pub fn is_open(&self) -> bool {
+        matches!(*self.state.lock().unwrap(), State::Open { .. })
+    }
+
+    fn publish(&self, state: State) {
+        let value = match state {
+            State::Closed { .. } => 0.0,
+            State::HalfOpen { .. } => 1.0,
+            State::Open { .. } => 2.0,
+        };
+        METRICS
+            .circuit_breaker_state
+            .with_label_values(&[&self.endpoint])
+            .set(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(open_secs: u64) -> CircuitBreaker {
+        CircuitBreaker::new(
+            "http://a:8529",
+            &CircuitBreakerConfig {
+                failure_threshold: 3,
+                slow_request_ms: Some(1000),
+                open_secs,
+                probe_requests: 2,
+                comment: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let breaker = breaker(60);
+        let fast = Duration::from_millis(5);
+        breaker.on_result(true, fast);
+        breaker.on_result(true, fast);
+        breaker.on_result(false, fast);
+        breaker.on_result(true, fast);
+        breaker.on_result(true, fast);
+        assert!(breaker.allow());
+        // A slow answer counts as a failure
+        breaker.on_result(false, Duration::from_secs(2));
+        assert!(breaker.is_open());
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn test_half_open_probes_close_or_reopen() {
+        let breaker = breaker(0);
+        let fast = Duration::from_millis(5);
+        for _ in 0..3 {
+            breaker.on_result(true, fast);
+        }
+        assert!(breaker.is_open());
+
+        // After `open_secs` a probe fails and the breaker opens again
+        assert!(breaker.allow());
+        breaker.on_result(true, fast);
+        assert!(breaker.is_open());
+
+        // Two successful probes close it
+        assert!(breaker.allow());
+        breaker.on_result(false, fast);
+        assert!(!breaker.is_open());
+        breaker.on_result(false, fast);
+        assert_eq!(
+            *breaker.state.lock().unwrap(),
+            State::Closed { failures: 0 }
+        );
+    }
+}
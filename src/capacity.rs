@@ -0,0 +1,185 @@
+use crate::config::CapacitySearchConfig;
+use crate::control;
+use crate::latency::LATENCIES;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// The outcome of a capacity search
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapacityResult {
+    pub usecase: String,
+    /// The highest rate which kept the p99 latency below the target, 0 if
+    /// no rate did
+    pub ops_per_sec: f64,
+    pub p99_ms: f64,
+    /// Whether the search converged or was cut short
+    pub complete: bool,
+}
+
+impl CapacityResult {
+    pub fn format(&self) -> String {
+        format!(
+            "{}: {:.1} ops/s with p99={:.2}ms{}",
+            self.usecase,
+            self.ops_per_sec,
+            self.p99_ms,
+            if self.complete {
+                ""
+            } else {
+                " (search incomplete)"
+            }
+        )
+    }
+}
+
+/// The search for the highest good rate: the rate doubles until it misses
+/// the target, afterwards the interval between the highest good and the
+/// lowest bad rate is bisected
+#[derive(Debug)]
+struct Search {
+    rate: f64,
+    good: f64,
+    bad: Option<f64>,
+    precision: f64,
+}
+
+impl Search {
+    fn new(initial: f64, precision_pct: f64) -> Self {
+        Search {
+            rate: initial.max(1.0),
+            good: 0.0,
+            bad: None,
+            precision: (precision_pct / 100.0).max(0.001),
+        }
+    }
+
+    /// Records whether the current rate met the target and returns the
+    /// next rate to try, or `None` once the search has converged
+    fn next(&mut self, met: bool) -> Option<f64> {
+        if met {
+            self.good = self.rate;
+        } else {
+            self.bad = Some(self.rate);
+        }
+        match self.bad {
+            None => self.rate *= 2.0,
+            Some(bad) => {
+                if bad - self.good <= bad * self.precision || bad < 1.0 {
+                    return None;
+                }
+                self.rate = (self.good + bad) / 2.0;
+            }
+        }
+        Some(self.rate)
+    }
+}
+
+/// Searches the highest rate of a use case at which its p99 latency stays
+/// below the target. Every rate runs for `step_secs`, it meets the target
+/// if the p99 latency is below it and the use case actually reached the
+/// rate. Once the search converges, the use case keeps running at the
+/// found rate. The search stops early when `shutdown` is cancelled.
+pub async fn search(config: CapacitySearchConfig, shutdown: CancellationToken) -> CapacityResult {
+    let mut result = CapacityResult {
+        usecase: config.usecase.clone(),
+        ops_per_sec: 0.0,
+        p99_ms: 0.0,
+        complete: false,
+    };
+    // The workers of the use case start after its setup
+    let control = loop {
+        if let Some(control) = control::get(&config.usecase) {
+            break control;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+            _ = shutdown.cancelled() => return result,
+        }
+    };
+    let window = format!("capacity/{}", config.usecase);
+    LATENCIES.watch(&window, &config.usecase);
+    info!(
+        "Searching the highest rate of use case {} with p99 below {}ms",
+        config.usecase, config.target_p99_ms
+    );
+
+    let step = Duration::from_secs(config.step_secs.max(1));
+    let mut search = Search::new(config.initial_ops_per_sec, config.precision_pct);
+    let mut rate = search.rate;
+    loop {
+        control.set_rate(rate);
+        LATENCIES.take_window(&window);
+        // The latency window counts requests, an operation may send several
+        let completed = control.completed();
+        tokio::select! {
+            _ = tokio::time::sleep(step) => {}
+            _ = shutdown.cancelled() => return result,
+        }
+        let Some(summary) = LATENCIES.take_window(&window) else {
+            return result;
+        };
+        let p99_ms = summary.p99 as f64 / 1000.0;
+        let achieved = control.completed().saturating_sub(completed) as f64 / step.as_secs_f64();
+        let met = p99_ms <= config.target_p99_ms && achieved >= rate * 0.9;
+        info!(
+            "Capacity search: {:.1} ops/s targeted, {:.1} ops/s achieved, p99={:.2}ms, {}",
+            rate,
+            achieved,
+            p99_ms,
+            if met { "good" } else { "too much" }
+        );
+        if met {
+            result.ops_per_sec = rate;
+            result.p99_ms = p99_ms;
+        }
+        match search.next(met) {
+            Some(next) => rate = next,
+            None => break,
+        }
+    }
+
+    result.complete = true;
+    if result.ops_per_sec > 0.0 {
+        control.set_rate(result.ops_per_sec);
+        info!("Capacity search finished, {}", result.format());
+    } else {
+        warn!(
+            "Capacity search finished, no rate of use case {} kept p99 below {}ms",
+            config.usecase, config.target_p99_ms
+        );
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_doubles_then_bisects() {
+        // The capacity is 700 ops/s
+        let mut search = Search::new(100.0, 5.0);
+        let mut rate = search.rate;
+        let mut tried = vec![rate];
+        while let Some(next) = search.next(rate <= 700.0) {
+            rate = next;
+            tried.push(rate);
+        }
+        assert_eq!(tried[..4], [100.0, 200.0, 400.0, 800.0]);
+        assert!(search.good <= 700.0);
+        assert!(search.good >= 700.0 * 0.95);
+    }
+
+    #[test]
+    fn test_search_gives_up_below_one_op_per_sec() {
+        let mut search = Search::new(10.0, 5.0);
+        let mut steps = 0;
+        while search.next(false).is_some() {
+            steps += 1;
+        }
+        assert_eq!(search.good, 0.0);
+        assert!(steps < 10);
+    }
+}
@@ -1,4 +1,7 @@
+use crate::crud::MIN_DOCUMENT_SIZE;
+use log::warn;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
@@ -10,6 +13,16 @@ pub enum ConfigError {
     Io(#[from] std::io::Error),
     #[error("YAML parsing error: {0}")]
     Yaml(#[from] serde_yaml::Error),
+    #[error("JSON parsing error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("TOML parsing error: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("Invalid configuration: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+    #[error("Invalid override: {0}")]
+    Override(String),
+    #[error("Unsupported configuration version: {0}")]
+    Version(String),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -18,30 +31,807 @@ pub struct Config {
     pub database: DatabaseConfig,
     pub active_usecases: ActiveUseCases,
     pub metrics_port: u16,
+    #[serde(default)]
+    pub mode: Mode,
+    /// Seed for all random generators, to make runs reproducible
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_metrics: Option<ServerMetricsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replication_lag: Option<ReplicationLagConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capacity_search: Option<CapacitySearchConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracing: Option<TracingConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub coordination: Option<CoordinationConfig>,
+    #[serde(default)]
+    pub error_policy: ErrorPolicy,
+    #[serde(default)]
+    pub supervision: SupervisionConfig,
+    #[serde(default)]
+    pub runtime: RuntimeConfig,
+    #[serde(default)]
+    pub seed_check: SeedCheckConfig,
     pub crud: CrudConfig,
     pub graph: GraphConfig,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queries: Option<QueriesConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transactions: Option<TransactionsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search: Option<SearchConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pregel: Option<PregelConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conflicts: Option<ConflictsConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<TtlConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeseries: Option<TimeseriesConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub banking: Option<BankingConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unique: Option<UniqueConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ddl: Option<DdlConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub users: Option<UsersConfig>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smartjoin: Option<SmartJoinConfig>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
 }
 
+/// What the loader does after setting up the use cases
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Produce load until the process is stopped
+    #[default]
+    Load,
+    /// Only create and seed the data, verify it and exit
+    Seed,
+}
+
+/// Scraping of the metrics of the database servers, which are re-exported
+/// with the metrics of the loader
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServerMetricsConfig {
+    #[serde(default = "default_server_metrics_interval_secs")]
+    pub interval_secs: u64,
+    /// Names of the server metrics to re-export, all others are dropped
+    #[serde(default = "default_server_metrics")]
+    pub metrics: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_server_metrics_interval_secs() -> u64 {
+    15
+}
+
+/// A probe which writes marker documents next to the load and measures how
+/// long it takes until they can be read on the followers
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplicationLagConfig {
+    /// Endpoints of the follower datacenter of a DC2DC setup, in which the
+    /// markers are looked for; without them they are looked for on the
+    /// follower shards with dirty reads
+    #[serde(default)]
+    pub follower_endpoints: Vec<String>,
+    /// Replication factor of the collection of the markers
+    #[serde(default = "default_replication_lag_replication_factor")]
+    pub replication_factor: u32,
+    /// Time between two markers
+    #[serde(default = "default_replication_lag_interval_ms")]
+    pub interval_ms: u64,
+    /// Time between two reads looking for a marker
+    #[serde(default = "default_replication_lag_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// Number of consecutive reads which have to find a marker. Every dirty
+    /// read goes to a replica the coordinator picks, so a single one may
+    /// well be answered by the leader.
+    #[serde(default = "default_replication_lag_confirmations")]
+    pub confirmations: u32,
+    /// Time after which a marker which is still not visible counts as a
+    /// timeout
+    #[serde(default = "default_replication_lag_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_replication_lag_replication_factor() -> u32 {
+    2
+}
+
+fn default_replication_lag_interval_ms() -> u64 {
+    1000
+}
+
+fn default_replication_lag_poll_interval_ms() -> u64 {
+    5
+}
+
+fn default_replication_lag_confirmations() -> u32 {
+    3
+}
+
+fn default_replication_lag_timeout_secs() -> u64 {
+    60
+}
+
+/// Searches the highest rate of a use case at which its p99 latency stays
+/// below a target
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CapacitySearchConfig {
+    /// The use case whose rate is searched
+    pub usecase: String,
+    pub target_p99_ms: f64,
+    /// The first rate tried, which is doubled until the target is missed
+    #[serde(default = "default_initial_ops_per_sec")]
+    pub initial_ops_per_sec: f64,
+    /// How long each rate is measured
+    #[serde(default = "default_step_secs")]
+    pub step_secs: u64,
+    /// The search stops once the highest good and the lowest bad rate are
+    /// this close, in percent of the bad rate
+    #[serde(default = "default_precision_pct")]
+    pub precision_pct: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_initial_ops_per_sec() -> f64 {
+    100.0
+}
+
+fn default_step_secs() -> u64 {
+    30
+}
+
+fn default_precision_pct() -> f64 {
+    5.0
+}
+
+/// Several loaders, usually on different machines, which share a run
+/// with the same `database.run_id`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CoordinationConfig {
+    /// The number of loaders which share the run
+    pub instances: u32,
+    /// The number of this loader from 1 to `instances`, by default the
+    /// loaders number themselves in the order they join
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<u32>,
+    /// How long a loader waits for the others at a barrier before it gives
+    /// up
+    #[serde(default = "default_barrier_timeout_secs")]
+    pub barrier_timeout_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_barrier_timeout_secs() -> u64 {
+    600
+}
+
+/// What happens to the run when operations fail
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ErrorPolicy {
+    /// Failed operations and seeding batches are logged and counted, the
+    /// load goes on
+    #[default]
+    Continue,
+    /// The first failed operation stops the whole run
+    AbortRun,
+    /// The run stops once more than `max_error_pct` percent of the
+    /// operations of the last `window_secs` seconds failed, windows with
+    /// fewer than `min_requests` operations are not judged
+    AbortAfterThreshold {
+        max_error_pct: f64,
+        #[serde(default = "default_error_window_secs")]
+        window_secs: u64,
+        #[serde(default = "default_error_min_requests")]
+        min_requests: u64,
+    },
+}
+
+fn default_error_window_secs() -> u64 {
+    60
+}
+
+fn default_error_min_requests() -> u64 {
+    100
+}
+
+/// How the use cases get the tokio runtimes they run on
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct RuntimeConfig {
+    #[serde(default)]
+    pub strategy: RuntimeStrategy,
+    /// Worker threads of the shared runtime, the number of CPUs if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_threads: Option<usize>,
+    /// The first CPU of the pinned core sets, the CPUs before it are left
+    /// to other processes
+    #[serde(default)]
+    pub first_core: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Where the worker threads of the use cases run
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum RuntimeStrategy {
+    /// Every use case has its own runtime with `threads` workers
+    #[default]
+    PerUsecase,
+    /// All use cases share one runtime with `runtime.shared_threads`
+    /// workers, `threads` of the use cases is ignored
+    Shared,
+    /// Every use case has its own runtime, whose `threads` workers are
+    /// pinned to as many CPUs of their own
+    Pinned,
+}
+
+/// Restarting of use cases which failed or panicked
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SupervisionConfig {
+    /// How often a use case is restarted after failing in a row, 0 turns
+    /// restarts off
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
+    /// The delay before the first restart, which doubles with every
+    /// further restart in a row
+    #[serde(default = "default_restart_backoff_secs")]
+    pub initial_backoff_secs: u64,
+    #[serde(default = "default_max_restart_backoff_secs")]
+    pub max_backoff_secs: u64,
+    /// A use case which ran this long before failing counts as healthy,
+    /// its restarts in a row start again from zero
+    #[serde(default = "default_healthy_after_secs")]
+    pub healthy_after_secs: u64,
+    /// Stop the whole process once a use case failed more often than
+    /// `max_restarts` in a row
+    #[serde(default)]
+    pub terminate_on_failure: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl Default for SupervisionConfig {
+    fn default() -> Self {
+        SupervisionConfig {
+            max_restarts: default_max_restarts(),
+            initial_backoff_secs: default_restart_backoff_secs(),
+            max_backoff_secs: default_max_restart_backoff_secs(),
+            healthy_after_secs: default_healthy_after_secs(),
+            terminate_on_failure: false,
+            comment: None,
+        }
+    }
+}
+
+/// Checks of the document counts and the shard distribution of the
+/// seeded collections, before the load starts
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SeedCheckConfig {
+    #[serde(default)]
+    pub on_mismatch: MismatchAction,
+    /// The largest difference between the fullest and the emptiest shard
+    /// of a collection, in percent of the average shard
+    #[serde(default = "default_max_shard_imbalance_pct")]
+    pub max_shard_imbalance_pct: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl Default for SeedCheckConfig {
+    fn default() -> Self {
+        SeedCheckConfig {
+            on_mismatch: MismatchAction::default(),
+            max_shard_imbalance_pct: default_max_shard_imbalance_pct(),
+            comment: None,
+        }
+    }
+}
+
+fn default_max_shard_imbalance_pct() -> f64 {
+    50.0
+}
+
+/// What a failed check of the seeded data does to the run
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MismatchAction {
+    /// Log a warning and start the load anyway
+    #[default]
+    Warn,
+    /// Fail the setup of the use case
+    Fail,
+}
+
+fn default_max_restarts() -> u32 {
+    3
+}
+
+fn default_restart_backoff_secs() -> u64 {
+    1
+}
+
+fn default_max_restart_backoff_secs() -> u64 {
+    60
+}
+
+fn default_healthy_after_secs() -> u64 {
+    300
+}
+
+/// Export of the operations as traces to an OpenTelemetry collector
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TracingConfig {
+    /// The OTLP/HTTP endpoint of the collector, e.g. `http://localhost:4318`
+    pub otlp_endpoint: String,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// The percentage of the operations which are traced
+    #[serde(default = "default_sample_pct")]
+    pub sample_pct: f64,
+    #[serde(default = "default_export_interval_secs")]
+    pub export_interval_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_service_name() -> String {
+    "adb_loader".to_string()
+}
+
+fn default_sample_pct() -> f64 {
+    100.0
+}
+
+fn default_export_interval_secs() -> u64 {
+    5
+}
+
+fn default_server_metrics() -> Vec<String> {
+    [
+        "arangodb_scheduler_queue_length",
+        "arangodb_scheduler_ongoing_low_prio",
+        "arangodb_scheduler_num_working_threads",
+        "rocksdb_block_cache_usage",
+        "rocksdb_estimate_pending_compaction_bytes",
+        "rocksdb_actual_delayed_write_rate",
+    ]
+    .iter()
+    .map(|name| name.to_string())
+    .collect()
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DatabaseConfig {
     pub endpoints: Vec<String>,
     pub username: String,
     pub password: String,
     pub prefix: String,
+    /// Template of the database names, with the placeholders `{prefix}`,
+    /// `{usecase}`, `{run_id}` and `{i}` (the number of the database)
+    #[serde(default = "default_database_name")]
+    pub database_name: String,
+    /// Identifies the run in the names, generated at start if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// Drop the databases with the prefix which other runs created more
+    /// than `stale_after_secs` ago before the use cases start
+    #[serde(default)]
+    pub cleanup_stale: bool,
+    #[serde(default = "default_stale_after_secs")]
+    pub stale_after_secs: u64,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default = "default_health_check_interval_secs")]
+    pub health_check_interval_secs: u64,
+    /// Maximal number of idle connections kept open per endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_max_idle_per_host: Option<usize>,
+    /// Interval of TCP keepalive probes on idle connections
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Timeout for establishing a connection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_ms: Option<u64>,
+    /// Sends all HTTP requests through this proxy instead of the one of
+    /// `https_proxy`/`http_proxy`, like `socks5://localhost:1080`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Timeout for every attempt to send a request, from connecting to the
+    /// end of the response body; a timed out attempt is retried elsewhere
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_timeout_ms: Option<u64>,
+    /// Use HTTP/2 instead of HTTP/1.1, multiplexing concurrent requests
+    /// over few connections
+    #[serde(default)]
+    pub http2: bool,
+    /// The protocol used to talk to the endpoints
+    #[serde(default)]
+    pub protocol: Protocol,
+    /// Compresses request bodies of at least `compress_min_bytes` bytes
+    /// and asks for compressed responses, only over HTTP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compress_requests: Option<Compression>,
+    #[serde(default = "default_compress_min_bytes")]
+    pub compress_min_bytes: usize,
+    /// Number of connections opened to every endpoint before a use case
+    /// starts, so that connecting does not show in its first latencies
+    #[serde(default)]
+    pub warmup_connections: u32,
+    /// Replace the endpoints with the coordinators of the cluster, asked
+    /// for every `discovery_interval_secs`
+    #[serde(default)]
+    pub auto_discover: bool,
+    #[serde(default = "default_discovery_interval_secs")]
+    pub discovery_interval_secs: u64,
+    /// The endpoints are the leader and followers of an active failover
+    /// deployment: requests go to the leader, which the followers name
+    #[serde(default)]
+    pub active_failover: bool,
+    /// Adapts the number of requests in flight per endpoint to the
+    /// pushback of the servers, without it only the use cases limit it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub adaptive_concurrency: Option<AdaptiveConcurrencyConfig>,
+    /// Takes an endpoint out of the rotation after consecutive failures or
+    /// slow answers, until probe requests show that it has recovered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Creates the databases with `sharding: "single"`, so that all their
+    /// collections live on one DB server; set per use case by
+    /// `active_usecases.<name>.one_shard`
+    #[serde(default)]
+    pub one_shard: bool,
+    /// The server rejects requests which would wait longer than this in its
+    /// queue, instead of working on them late
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_queue_time_seconds: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_discovery_interval_secs() -> u64 {
+    30
+}
+
+fn default_compress_min_bytes() -> usize {
+    1024
+}
+
+fn default_health_check_interval_secs() -> u64 {
+    5
+}
+
+fn default_stale_after_secs() -> u64 {
+    86400
+}
+
+fn default_database_name() -> String {
+    "{prefix}{usecase}".to_string()
+}
+
+/// The placeholders of `database.database_name`
+pub const DATABASE_NAME_PLACEHOLDERS: [&str; 4] = ["prefix", "usecase", "run_id", "i"];
+
+impl DatabaseConfig {
+    /// Returns the name of the database of a use case
+    pub fn database_name(&self, usecase: &str) -> String {
+        self.numbered_database_name(usecase, None)
+    }
+
+    /// Returns the name of the `index`-th database of a use case with
+    /// several databases. Without `{i}` in the template, the use case is
+    /// named `<usecase>_<index>`, like `crud_2`.
+    pub fn numbered_database_name(&self, usecase: &str, index: Option<u32>) -> String {
+        let usecase = match index {
+            Some(index) if !crate::naming::uses(&self.database_name, "i") => {
+                format!("{}_{}", usecase, index)
+            }
+            _ => usecase.to_string(),
+        };
+        crate::naming::expand(
+            &self.database_name,
+            &[
+                ("prefix", &self.prefix),
+                ("usecase", &usecase),
+                ("run_id", self.run_id.as_deref().unwrap_or_default()),
+                ("i", &index.unwrap_or(1).to_string()),
+            ],
+        )
+    }
+}
+
+/// Parameters of the AIMD controller of the requests in flight per
+/// endpoint
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AdaptiveConcurrencyConfig {
+    #[serde(default = "default_initial_concurrency")]
+    pub initial: usize,
+    #[serde(default = "default_min_concurrency")]
+    pub min: usize,
+    #[serde(default = "default_max_concurrency")]
+    pub max: usize,
+    /// A queue time reported by the server above this counts as pushback
+    #[serde(default = "default_target_queue_time_secs")]
+    pub target_queue_time_secs: f64,
+    /// The limit is multiplied by this on pushback
+    #[serde(default = "default_decrease_factor")]
+    pub decrease_factor: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_initial_concurrency() -> usize {
+    32
+}
+
+fn default_min_concurrency() -> usize {
+    1
+}
+
+fn default_max_concurrency() -> usize {
+    1024
+}
+
+fn default_target_queue_time_secs() -> f64 {
+    0.1
+}
+
+fn default_decrease_factor() -> f64 {
+    0.5
+}
+
+/// Parameters of the circuit breaker of every endpoint
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CircuitBreakerConfig {
+    /// Number of failed requests in a row which open the breaker
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Successful requests which took longer than this count as failures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slow_request_ms: Option<u64>,
+    /// How long an open breaker keeps the endpoint out of the rotation
+    /// before it lets probe requests through
+    #[serde(default = "default_open_secs")]
+    pub open_secs: u64,
+    /// Number of successful probe requests which close the breaker again
+    #[serde(default = "default_probe_requests")]
+    pub probe_requests: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_failure_threshold() -> u32 {
+    5
+}
+
+fn default_open_secs() -> u64 {
+    10
+}
+
+fn default_probe_requests() -> u32 {
+    3
+}
+
+/// The wire protocol used to talk to ArangoDB
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    /// HTTP/1.1 or HTTP/2 with JSON bodies
+    #[default]
+    Http,
+    /// VelocyStream with VelocyPack bodies, plain TCP only
+    Vst,
+}
+
+/// The content encoding of compressed request bodies
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    Gzip,
+    /// The zlib format, which HTTP calls deflate
+    Deflate,
+}
+
+impl Compression {
+    /// The value of the `Content-Encoding` header
+    pub fn content_encoding(self) -> &'static str {
+        match self {
+            Compression::Gzip => "gzip",
+            Compression::Deflate => "deflate",
+        }
+    }
+}
+
+/// TLS settings for `https://` endpoints
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TlsConfig {
+    /// PEM file with an additional CA certificate to trust
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_file: Option<String>,
+    /// PEM file with the client certificate for mutual TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert: Option<String>,
+    /// PEM file with the PKCS#8 private key belonging to `client_cert`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    /// Accept any server certificate, only for testing
+    #[serde(default)]
+    pub insecure_skip_verify: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Controls how requests failing with transient errors are retried
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetryConfig {
+    /// Total number of attempts, including the first one
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further retry
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Upper bound for the delay between two attempts
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    /// Relative random deviation applied to each delay, between 0 and 1
+    #[serde(default = "default_jitter")]
+    pub jitter: f64,
+    /// HTTP status codes which are considered transient
+    #[serde(default = "default_retry_status_codes")]
+    pub retry_status_codes: Vec<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            jitter: default_jitter(),
+            retry_status_codes: default_retry_status_codes(),
+            comment: None,
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    5
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    100
+}
+
+fn default_max_backoff_ms() -> u64 {
+    5000
+}
+
+fn default_jitter() -> f64 {
+    0.2
+}
+
+fn default_retry_status_codes() -> Vec<u16> {
+    vec![429, 502, 503, 504]
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ActiveUseCases {
     pub crud: UseCaseConfig,
     pub graph: UseCaseConfig,
+    #[serde(default)]
+    pub queries: UseCaseConfig,
+    #[serde(default)]
+    pub transactions: UseCaseConfig,
+    #[serde(default)]
+    pub search: UseCaseConfig,
+    #[serde(default)]
+    pub pregel: UseCaseConfig,
+    #[serde(default)]
+    pub conflicts: UseCaseConfig,
+    #[serde(default)]
+    pub ttl: UseCaseConfig,
+    #[serde(default)]
+    pub timeseries: UseCaseConfig,
+    #[serde(default)]
+    pub banking: UseCaseConfig,
+    #[serde(default)]
+    pub unique: UseCaseConfig,
+    #[serde(default)]
+    pub ddl: UseCaseConfig,
+    #[serde(default)]
+    pub users: UseCaseConfig,
+    #[serde(default)]
+    pub smartjoin: UseCaseConfig,
+}
+
+impl ActiveUseCases {
+    /// Returns the name of every use case with its configuration
+    pub fn all(&self) -> [(&'static str, &UseCaseConfig); 14] {
+        [
+            ("crud", &self.crud),
+            ("graph", &self.graph),
+            ("queries", &self.queries),
+            ("transactions", &self.transactions),
+            ("search", &self.search),
+            ("pregel", &self.pregel),
+            ("conflicts", &self.conflicts),
+            ("ttl", &self.ttl),
+            ("timeseries", &self.timeseries),
+            ("banking", &self.banking),
+            ("unique", &self.unique),
+            ("ddl", &self.ddl),
+            ("users", &self.users),
+            ("smartjoin", &self.smartjoin),
+        ]
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UseCaseConfig {
     pub on: bool,
     pub threads: u32,
+    /// Stop producing load after this many seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<u64>,
+    /// Stop producing load after this many operations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_operations: Option<u64>,
+    /// Operations in the first seconds of the load are not counted in the
+    /// latencies and the final summary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warmup_secs: Option<u64>,
+    /// Shapes the load over time, the use case stops after the last phase
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phases: Vec<Phase>,
+    /// Creates the databases of the use case as OneShard databases
+    #[serde(default)]
+    pub one_shard: bool,
+}
+
+impl Default for UseCaseConfig {
+    fn default() -> Self {
+        UseCaseConfig {
+            on: false,
+            threads: 4,
+            duration_secs: None,
+            max_operations: None,
+            warmup_secs: None,
+            phases: Vec::new(),
+            one_shard: false,
+        }
+    }
+}
+
+/// A phase of the load of a use case, with a constant target rate or a
+/// linear ramp from `from_ops_per_sec` to `ops_per_sec`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Phase {
+    pub name: String,
+    pub duration_secs: u64,
+    pub ops_per_sec: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_ops_per_sec: Option<f64>,
+    /// The number of workers during the phase, without it the concurrency
+    /// of the use case stays as it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -50,35 +840,2725 @@ pub struct CrudConfig {
     pub number_of_shards: u32,
     pub replication_factor: u32,
     pub number_of_documents: u32,
-    pub document_size: u32,
+    pub document_size: DocumentSize,
     pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
     pub insert_concurrency: u32,
+    /// Number of documents per insert request during seeding, 1 sends
+    /// single document requests
+    #[serde(default = "default_insert_batch_size")]
+    pub insert_batch_size: u32,
+    /// Number of documents generated at a time for a seed batch with more
+    /// documents, which is then sent as a chunked body; 0 generates every
+    /// batch as a whole
+    #[serde(default)]
+    pub stream_chunk_size: u32,
+    /// Number of collections seeded at the same time, sharing the
+    /// `insert_concurrency` requests in flight
+    #[serde(default = "default_collection_concurrency")]
+    pub collection_concurrency: u32,
+    /// The API used to insert documents during seeding
+    #[serde(default)]
+    pub seed_api: SeedApi,
+    /// The format of the documents sent to and received from the
+    /// document API
+    #[serde(default)]
+    pub document_format: DocumentFormat,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    #[serde(default = "default_read_pct")]
+    pub read_pct: u32,
+    #[serde(default = "default_update_pct")]
+    pub update_pct: u32,
+    #[serde(default = "default_replace_pct")]
+    pub replace_pct: u32,
+    #[serde(default = "default_delete_insert_pct")]
+    pub delete_insert_pct: u32,
+    /// Number of documents per read, more than 1 reads them with a single
+    /// batch request
+    #[serde(default = "default_read_batch_size")]
+    pub read_batch_size: u32,
+    /// Allow reads to be answered by followers (dirty reads)
+    #[serde(default)]
+    pub read_from_followers: bool,
+    /// The API used by the steady-state write operations
+    #[serde(default)]
+    pub write_api: WriteApi,
+    /// Percentage of the keys of AQL updates and replaces which match an
+    /// existing document
+    #[serde(default = "default_aql_match_pct")]
+    pub aql_match_pct: u32,
+    /// The operation mix in percent, replaces the four percentages above
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operation_mix: Option<OperationMix>,
+    /// Query parameters of the steady-state document API writes
+    #[serde(default)]
+    pub write_options: WriteOptions,
+    /// Target rate of steady-state operations, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    /// The shape of the generated documents, replaces `document_size`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_schema: Option<DocumentSchema>,
+    /// Read back the seeded documents and compare them with the expected
+    /// content, which requires a global seed
+    #[serde(default)]
+    pub verify: bool,
+    /// Number of random documents per collection to verify, all if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify_sample: Option<u32>,
+    /// Number of databases, each with all collections
+    #[serde(default = "default_number_of_databases")]
+    pub number_of_databases: u32,
+    /// How steady-state operations are spread over the databases
+    #[serde(default)]
+    pub database_distribution: DatabaseDistribution,
+    /// How steady-state operations pick the keys of their documents
+    #[serde(default)]
+    pub key_distribution: KeyDistribution,
+    /// Further properties of the created collections
+    #[serde(default, flatten)]
+    pub collection_properties: CollectionProperties,
+    /// Indexes created on every collection before it is seeded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<IndexSpec>,
+    /// Builds and drops an index periodically while the steady-state
+    /// operations run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_churn: Option<IndexChurnConfig>,
+    /// Template of the names of the `number_of_collections` identical
+    /// collections, with the placeholder `{i}` (the number of the
+    /// collection)
+    #[serde(default = "default_collection_name")]
+    pub collection_name: String,
+    /// Collections with their own sizes, replace the
+    /// `number_of_collections` identical collections
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub collections: Vec<CrudCollectionConfig>,
+    /// A binary attribute added to every generated document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blob: Option<BlobConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct GraphConfig {
-    pub number_of_vertices: u32,
-    pub number_of_edges: u32,
-    pub number_of_shards: u32,
-    pub replication_factor: u32,
-    pub smart: bool,
-    pub vertex_size: u32,
-    pub edge_size: u32,
-    pub drop_first: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub comment: Option<String>,
+/// A base64 encoded attribute of random bytes, to test the handling of
+/// large documents
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct BlobConfig {
+    /// Number of random bytes before encoding, which makes the attribute
+    /// about a third larger
+    pub size: DocumentSize,
 }
 
-impl Config {
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
-        let mut file = File::open(path)?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+fn default_collection_name() -> String {
+    "c{i}".to_string()
+}
 
-        let config: Config = serde_yaml::from_str(&contents)?;
-        Ok(config)
+impl CrudConfig {
+    /// Returns the names of all collections of a database
+    pub fn collection_names(&self) -> Vec<String> {
+        if self.collections.is_empty() {
+            (1..=self.number_of_collections)
+                .map(|i| self.numbered_collection_name(i))
+                .collect()
+        } else {
+            self.collections.iter().map(|c| c.name.clone()).collect()
+        }
+    }
+
+    /// Returns the name of the `index`-th of the identical collections
+    pub fn numbered_collection_name(&self, index: u32) -> String {
+        crate::naming::expand(&self.collection_name, &[("i", &index.to_string())])
+    }
+}
+
+/// A collection of the CRUD use case, the settings which are not given are
+/// taken from the `crud` section
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct CrudCollectionConfig {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number_of_shards: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replication_factor: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number_of_documents: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_size: Option<DocumentSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_schema: Option<DocumentSchema>,
+    /// The first collection of the list, whose shards this collection
+    /// shares the servers with, so that transactions and joins over both
+    /// stay local; its number of shards and replication factor are used
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distribute_shards_like: Option<String>,
+}
+
+/// Measures the cost of building an index under load
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexChurnConfig {
+    /// The index which is built and dropped again, usually a persistent
+    /// or an inverted index
+    pub index: IndexSpec,
+    /// The collection in the first database to build the index on
+    #[serde(default = "default_index_churn_collection")]
+    pub collection: String,
+    /// Time between the end of one build and the start of the next, the
+    /// foreground latency in this time serves as the baseline
+    #[serde(default = "default_index_churn_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_index_churn_collection() -> String {
+    "c1".to_string()
+}
+
+fn default_index_churn_interval_secs() -> u64 {
+    60
+}
+
+/// An index of a collection
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct IndexSpec {
+    #[serde(rename = "type")]
+    pub index_type: IndexType,
+    pub fields: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Further attributes of the index definition, passed to the server as
+    /// they are, e.g. `unique`, `sparse` or `expireAfter`
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub options: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexType {
+    Persistent,
+    Ttl,
+    Geo,
+    Inverted,
+    Fulltext,
+}
+
+impl IndexType {
+    /// The name of the type in the index API
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IndexType::Persistent => "persistent",
+            IndexType::Ttl => "ttl",
+            IndexType::Geo => "geo",
+            IndexType::Inverted => "inverted",
+            IndexType::Fulltext => "fulltext",
+        }
+    }
+}
+
+impl IndexSpec {
+    /// The definition sent to `/_api/index`
+    pub fn definition(&self) -> serde_json::Value {
+        let mut definition = self.options.clone();
+        definition.insert("type".to_string(), self.index_type.as_str().into());
+        definition.insert("fields".to_string(), self.fields.clone().into());
+        if let Some(name) = &self.name {
+            definition.insert("name".to_string(), name.clone().into());
+        }
+        serde_json::Value::Object(definition)
+    }
+}
+
+/// Optional properties of a new collection, which are left to the server
+/// defaults if unset
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct CollectionProperties {
+    /// The attributes which determine the shard of a document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shard_keys: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_for_sync: Option<bool>,
+    /// Number of in-sync replicas a write needs to succeed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub write_concern: Option<u32>,
+    /// A schema validating the documents, passed to the server as it is,
+    /// e.g. `{ rule: {...}, level: moderate, message: "..." }`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<serde_json::Value>,
+}
+
+/// Query parameters of document API writes, left to the server defaults
+/// if unset
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct WriteOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait_for_sync: Option<bool>,
+    /// How inserts treat existing keys, plain inserts default to `ignore`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overwrite_mode: Option<OverwriteMode>,
+    /// Return an empty response instead of the document metadata
+    #[serde(default)]
+    pub silent: bool,
+    #[serde(default)]
+    pub return_new: bool,
+    #[serde(default)]
+    pub return_old: bool,
+}
+
+/// The `overwriteMode` of document inserts
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverwriteMode {
+    Ignore,
+    Replace,
+    Update,
+    Conflict,
+}
+
+impl OverwriteMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OverwriteMode::Ignore => "ignore",
+            OverwriteMode::Replace => "replace",
+            OverwriteMode::Update => "update",
+            OverwriteMode::Conflict => "conflict",
+        }
+    }
+}
+
+fn default_read_batch_size() -> u32 {
+    1
+}
+
+fn default_aql_match_pct() -> u32 {
+    100
+}
+
+/// Percentages of the steady-state CRUD operations, which must add up to
+/// 100
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OperationMix {
+    #[serde(default)]
+    pub insert: u32,
+    #[serde(default)]
+    pub read: u32,
+    #[serde(default)]
+    pub update: u32,
+    #[serde(default)]
+    pub replace: u32,
+    #[serde(default)]
+    pub delete: u32,
+    #[serde(default)]
+    pub delete_insert: u32,
+    /// Reads only the `blob` attribute of a batch of documents
+    #[serde(default)]
+    pub read_blob: u32,
+}
+
+impl OperationMix {
+    pub fn total(&self) -> u32 {
+        self.insert
+            + self.read
+            + self.update
+            + self.replace
+            + self.delete
+            + self.delete_insert
+            + self.read_blob
+    }
+}
+
+fn default_number_of_databases() -> u32 {
+    1
+}
+
+/// How the CRUD use case picks the database for an operation
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DatabaseDistribution {
+    /// Every database is equally likely
+    #[default]
+    Uniform,
+    /// Database `i` is picked with a probability proportional to the
+    /// `i`-th weight
+    Weighted { weights: Vec<u32> },
+}
+
+/// How keys are picked from `1..=n` for an operation
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum KeyDistribution {
+    /// Every key is equally likely
+    #[default]
+    Uniform,
+    /// Key `k` is picked with a probability proportional to `1 / k^theta`
+    Zipfian {
+        #[serde(default = "default_theta")]
+        theta: f64,
+    },
+    /// `hot_ops_pct` percent of the operations go to the first
+    /// `hot_keys_pct` percent of the keys, the rest to the other keys
+    Hotspot { hot_ops_pct: u32, hot_keys_pct: u32 },
+    /// Like `zipfian`, but the highest keys, which were inserted last, are
+    /// the most popular ones
+    Latest {
+        #[serde(default = "default_theta")]
+        theta: f64,
+    },
+}
+
+/// The size of generated documents in bytes, a number for a fixed size or
+/// a distribution
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum DocumentSize {
+    Fixed(u32),
+    Distribution(SizeDistribution),
+}
+
+/// How the sizes of generated documents are distributed
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SizeDistribution {
+    Fixed {
+        size: u32,
+    },
+    /// Every size from `min` to `max` is equally likely
+    Uniform {
+        min: u32,
+        max: u32,
+    },
+    /// Mostly sizes around the median with a long tail of large ones, the
+    /// sizes average to `mean`, `sigma` is the standard deviation of their
+    /// logarithm
+    Lognormal {
+        mean: f64,
+        sigma: f64,
+    },
+}
+
+impl DocumentSize {
+    /// The smallest size of the distribution, `None` if it has no lower
+    /// bound above 0
+    pub fn min_size(&self) -> Option<u32> {
+        match self {
+            DocumentSize::Fixed(size)
+            | DocumentSize::Distribution(SizeDistribution::Fixed { size }) => Some(*size),
+            DocumentSize::Distribution(SizeDistribution::Uniform { min, .. }) => Some(*min),
+            DocumentSize::Distribution(SizeDistribution::Lognormal { .. }) => None,
+        }
+    }
+
+    /// Checks the parameters of the distribution
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            DocumentSize::Distribution(SizeDistribution::Uniform { min, max }) if min > max => {
+                Err(format!("min {} is larger than max {}", min, max))
+            }
+            DocumentSize::Distribution(SizeDistribution::Lognormal { mean, sigma })
+                if !(*mean >= 1.0 && *sigma >= 0.0 && sigma.is_finite()) =>
+            {
+                Err(format!(
+                    "lognormal needs a mean of at least 1 and a sigma of at least 0, \
+                     not {} and {}",
+                    mean, sigma
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for DocumentSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DocumentSize::Fixed(size)
+            | DocumentSize::Distribution(SizeDistribution::Fixed { size }) => write!(f, "{}", size),
+            DocumentSize::Distribution(SizeDistribution::Uniform { min, max }) => {
+                write!(f, "uniform from {} to {}", min, max)
+            }
+            DocumentSize::Distribution(SizeDistribution::Lognormal { mean, sigma }) => {
+                write!(f, "lognormal with mean {} and sigma {}", mean, sigma)
+            }
+        }
     }
 }
 
+fn default_theta() -> f64 {
+    0.99
+}
+
+fn default_insert_batch_size() -> u32 {
+    1000
+}
+
+fn default_collection_concurrency() -> u32 {
+    1
+}
+
+fn default_load_concurrency() -> u32 {
+    10
+}
+
+fn default_read_pct() -> u32 {
+    50
+}
+
+fn default_update_pct() -> u32 {
+    20
+}
+
+fn default_replace_pct() -> u32 {
+    20
+}
+
+fn default_delete_insert_pct() -> u32 {
+    10
+}
+
+/// Describes the attributes of generated documents, in addition to `_key`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DocumentSchema {
+    pub attributes: Vec<AttributeSpec>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct AttributeSpec {
+    pub name: String,
+    /// Generates this many attributes `<name>1` to `<name><count>` of the
+    /// same type instead of a single one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    #[serde(flatten)]
+    pub value: ValueSpec,
+}
+
+/// The type of a generated value and the range it is drawn from. All
+/// ranges include both bounds.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ValueSpec {
+    /// Random alphanumeric characters
+    String {
+        min_length: usize,
+        max_length: usize,
+    },
+    Integer {
+        min: i64,
+        max: i64,
+    },
+    Float {
+        min: f64,
+        max: f64,
+    },
+    Bool,
+    /// An ISO 8601 timestamp in UTC
+    Date {
+        #[serde(default = "default_min_year")]
+        min_year: i32,
+        #[serde(default = "default_max_year")]
+        max_year: i32,
+    },
+    Array {
+        min_length: usize,
+        max_length: usize,
+        items: Box<ValueSpec>,
+    },
+    Object {
+        attributes: Vec<AttributeSpec>,
+    },
+    /// Random words separated by spaces
+    Text {
+        min_words: u32,
+        max_words: u32,
+        #[serde(default)]
+        languages: LanguageMix,
+    },
+    /// Random bytes, base64 encoded; the sizes are those before encoding
+    Binary {
+        min_size: usize,
+        max_size: usize,
+    },
+    /// A tree of objects `depth` levels deep with `fan_out` attributes
+    /// `n1` to `n<fan_out>` per object and `leaf` values at the bottom.
+    /// With a `max_array_length` above 0, the last attribute of every
+    /// object is an array of such subtrees instead of a single one.
+    Nested {
+        depth: u32,
+        fan_out: u32,
+        #[serde(default)]
+        min_array_length: usize,
+        #[serde(default)]
+        max_array_length: usize,
+        #[serde(default = "default_nested_leaf")]
+        leaf: Box<ValueSpec>,
+    },
+}
+
+/// Relative weights of the languages generated texts are made of, English
+/// only by default
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct LanguageMix {
+    #[serde(default)]
+    pub english: u32,
+    /// Russian
+    #[serde(default)]
+    pub cyrillic: u32,
+    /// Chinese, Japanese and a little Korean
+    #[serde(default)]
+    pub cjk: u32,
+    /// Emoji, including sequences of several code points
+    #[serde(default)]
+    pub emoji: u32,
+    /// Latin words with decomposed accents
+    #[serde(default)]
+    pub combining: u32,
+}
+
+impl Default for LanguageMix {
+    fn default() -> Self {
+        LanguageMix {
+            english: 100,
+            cyrillic: 0,
+            cjk: 0,
+            emoji: 0,
+            combining: 0,
+        }
+    }
+}
+
+impl LanguageMix {
+    /// The weights in the order english, cyrillic, cjk, emoji, combining
+    pub fn weights(&self) -> [u32; 5] {
+        [
+            self.english,
+            self.cyrillic,
+            self.cjk,
+            self.emoji,
+            self.combining,
+        ]
+    }
+}
+
+fn default_nested_leaf() -> Box<ValueSpec> {
+    Box::new(ValueSpec::String {
+        min_length: 8,
+        max_length: 16,
+    })
+}
+
+fn default_min_year() -> i32 {
+    2000
+}
+
+fn default_max_year() -> i32 {
+    2030
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GraphConfig {
+    pub number_of_vertices: u32,
+    pub number_of_edges: u32,
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    /// Creates an Enterprise Edition graph: a SmartGraph if
+    /// `smart_graph_attribute` is set, an EnterpriseGraph otherwise
+    pub smart: bool,
+    /// The attribute whose value determines the shard of a vertex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub smart_graph_attribute: Option<String>,
+    /// Number of distinct values of the smart graph attribute
+    #[serde(default = "default_smart_groups")]
+    pub smart_groups: u32,
+    /// Skew of the sizes of the smart groups, from 0 (all of the same
+    /// size) to below 1: the first `g` groups hold the share
+    /// `(g / smart_groups)^(1 - smart_group_skew)` of the vertices
+    #[serde(default)]
+    pub smart_group_skew: f64,
+    /// Percentage of the edges which connect vertices of the same smart
+    /// group, the others connect different groups; without it the
+    /// topology decides
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intra_group_edges_pct: Option<u32>,
+    /// Creates a Disjoint SmartGraph, in which edges only connect vertices
+    /// with the same value of the smart graph attribute
+    #[serde(default)]
+    pub disjoint: bool,
+    /// Number of vertices of the satellite collection `S`, which turns a
+    /// SmartGraph into a Hybrid SmartGraph, 0 means none
+    #[serde(default)]
+    pub satellite_vertices: u32,
+    /// Creates the edge collection of a general graph with
+    /// `distributeShardsLike` on the vertex collection, so that edges
+    /// share the servers with the vertices they start at
+    #[serde(default)]
+    pub co_locate_edges: bool,
+    pub vertex_size: u32,
+    pub edge_size: u32,
+    pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    /// How the edges connect the vertices
+    #[serde(default)]
+    pub topology: GraphTopology,
+    /// Percentage of the edges which start at one of the supernodes
+    /// instead of the vertex chosen by the topology
+    #[serde(default)]
+    pub supernode_pct: u32,
+    /// The supernodes are the vertices with the lowest keys
+    #[serde(default = "default_number_of_supernodes")]
+    pub number_of_supernodes: u32,
+    #[serde(default = "default_load_concurrency")]
+    pub insert_concurrency: u32,
+    /// Number of concurrent workers running traversals and writes
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of steady-state operations, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    /// The depth range of the traversals, `min_depth..max_depth` in AQL
+    #[serde(default = "default_min_depth")]
+    pub min_depth: u32,
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    /// The relative frequencies of the traversal directions
+    #[serde(default = "default_outbound_pct")]
+    pub outbound_pct: u32,
+    #[serde(default)]
+    pub inbound_pct: u32,
+    #[serde(default)]
+    pub any_pct: u32,
+    /// Percentage of the steady-state operations which update a vertex
+    /// instead of running a traversal
+    #[serde(default)]
+    pub write_pct: u32,
+    /// Percentage of the steady-state operations which search a shortest
+    /// path between two random vertices instead of running a traversal
+    #[serde(default)]
+    pub shortest_path_pct: u32,
+    /// Percentage of the steady-state operations which search the
+    /// `k_paths` shortest paths between two random vertices
+    #[serde(default)]
+    pub k_shortest_paths_pct: u32,
+    #[serde(default = "default_k_paths")]
+    pub k_paths: u32,
+    /// How the start vertices of traversals and the updated vertices are
+    /// picked
+    #[serde(default)]
+    pub start_vertex_distribution: KeyDistribution,
+    /// Rate at which edges are inserted and deleted while the traversals
+    /// run, 0 turns edge churn off
+    #[serde(default)]
+    pub edge_churn_ops_per_sec: f64,
+    /// Number of churned edges which exist at the same time, the oldest one
+    /// is deleted for every new one beyond this
+    #[serde(default = "default_edge_churn_window")]
+    pub edge_churn_window: u32,
+    /// Interval of the consistency check of the edges during edge churn,
+    /// 0 turns the check off
+    #[serde(default = "default_edge_check_interval_secs")]
+    pub edge_check_interval_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_smart_groups() -> u32 {
+    100
+}
+
+fn default_number_of_supernodes() -> u32 {
+    10
+}
+
+fn default_edge_churn_window() -> u32 {
+    1000
+}
+
+fn default_edge_check_interval_secs() -> u64 {
+    60
+}
+
+fn default_k_paths() -> u32 {
+    3
+}
+
+fn default_min_depth() -> u32 {
+    1
+}
+
+fn default_max_depth() -> u32 {
+    3
+}
+
+fn default_outbound_pct() -> u32 {
+    100
+}
+
+/// The model used to generate the edges of the graph
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GraphTopology {
+    /// Every edge connects two vertices chosen uniformly at random
+    #[default]
+    ErdosRenyi,
+    /// Both ends of an edge are chosen with a probability proportional to
+    /// `1 / k^theta`, which yields a power-law degree distribution
+    ScaleFree {
+        #[serde(default = "default_theta")]
+        theta: f64,
+    },
+    /// A square grid in which every vertex is connected to its right and
+    /// its lower neighbour, `number_of_edges` is ignored
+    Grid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueriesConfig {
+    /// Name of the database the queries run in, without the prefix. By
+    /// default the queries run against the data of the CRUD use case.
+    #[serde(default = "default_queries_database")]
+    pub database: String,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of queries, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    /// Number of results fetched per cursor round trip
+    #[serde(default = "default_batch_size")]
+    pub batch_size: u32,
+    pub queries: Vec<QueryTemplate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_queries_database() -> String {
+    "crud".to_string()
+}
+
+fn default_batch_size() -> u32 {
+    1000
+}
+
+/// An AQL query which is run with freshly generated bind parameters each
+/// time it is chosen
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QueryTemplate {
+    /// Used as the operation name in metrics
+    pub name: String,
+    pub query: String,
+    /// Relative frequency of this query among all queries
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    #[serde(default)]
+    pub bind_vars: BTreeMap<String, BindVarGenerator>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// Describes how the value of a bind parameter is generated
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BindVarGenerator {
+    /// A document key `<prefix><n>` with `min <= n < max`
+    Key {
+        #[serde(default = "default_key_prefix")]
+        prefix: String,
+        #[serde(default)]
+        min: u64,
+        max: u64,
+    },
+    /// An integer `n` with `min <= n < max`
+    Int { min: i64, max: i64 },
+    /// An array `[low, low + width]` with `min <= low < max`
+    Range { min: i64, max: i64, width: i64 },
+    /// A random alphanumeric string of the given length
+    String { length: usize },
+    /// A fixed value
+    Constant { value: serde_json::Value },
+}
+
+fn default_key_prefix() -> String {
+    "K".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TransactionsConfig {
+    pub number_of_collections: u32,
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    /// Size of the key space the transactions write to
+    pub number_of_keys: u32,
+    pub document_size: u32,
+    #[serde(default)]
+    pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of transactions, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    #[serde(default = "default_operations_per_transaction")]
+    pub operations_per_transaction: u32,
+    /// Percentage of the operations in a transaction which are writes, the
+    /// others are reads
+    #[serde(default = "default_write_pct")]
+    pub write_pct: u32,
+    /// Percentage of the transactions which are aborted instead of committed
+    #[serde(default)]
+    pub abort_pct: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intermediate_commit_count: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intermediate_commit_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_operations_per_transaction() -> u32 {
+    10
+}
+
+fn default_write_pct() -> u32 {
+    50
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchConfig {
+    pub number_of_collections: u32,
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    pub number_of_documents: u32,
+    /// Number of words in the text attribute of each document
+    #[serde(default = "default_words_per_document")]
+    pub words_per_document: u32,
+    /// The languages of the texts and the search terms
+    #[serde(default)]
+    pub languages: LanguageMix,
+    #[serde(default)]
+    pub view_type: ViewType,
+    #[serde(default)]
+    pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    #[serde(default = "default_load_concurrency")]
+    pub insert_concurrency: u32,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of search queries, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    #[serde(default = "default_phrase_pct")]
+    pub phrase_pct: u32,
+    #[serde(default = "default_tokens_pct")]
+    pub tokens_pct: u32,
+    #[serde(default = "default_bm25_pct")]
+    pub bm25_pct: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PregelConfig {
+    /// Name of the database with the graph, without the prefix. By default
+    /// the jobs run against the graph of the graph use case.
+    #[serde(default = "default_pregel_database")]
+    pub database: String,
+    #[serde(default = "default_pregel_graph")]
+    pub graph: String,
+    /// Number of jobs running at the same time
+    #[serde(default = "default_pregel_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of job starts, 0 means a new job starts as soon as one
+    /// has finished
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    /// Interval in which the state of a running job is polled
+    #[serde(default = "default_pregel_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// Jobs running longer than this are cancelled and count as failed
+    #[serde(default = "default_pregel_timeout_secs")]
+    pub timeout_secs: u64,
+    pub jobs: Vec<PregelJob>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_pregel_database() -> String {
+    "graph".to_string()
+}
+
+fn default_pregel_graph() -> String {
+    "G".to_string()
+}
+
+fn default_pregel_concurrency() -> u32 {
+    1
+}
+
+fn default_pregel_poll_interval_ms() -> u64 {
+    1000
+}
+
+fn default_pregel_timeout_secs() -> u64 {
+    3600
+}
+
+/// A Pregel algorithm with its parameters
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PregelJob {
+    /// The algorithm as named by the server, e.g. `pagerank`, `wcc` or
+    /// `labelpropagation`
+    pub algorithm: String,
+    /// Relative frequency of this job among all jobs
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+    /// Passed to the server as they are, e.g. `maxGSS` or `store`
+    #[serde(default, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub params: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConflictsConfig {
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    /// Number of counter documents all workers update, the smaller, the
+    /// more conflicts
+    #[serde(default = "default_conflict_keys")]
+    pub number_of_keys: u32,
+    #[serde(default)]
+    pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of increments, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    /// How often an increment is retried after a conflict before it fails
+    #[serde(default = "default_conflict_max_retries")]
+    pub max_retries: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_conflict_keys() -> u32 {
+    10
+}
+
+fn default_conflict_max_retries() -> u32 {
+    10
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TtlConfig {
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    pub document_size: u32,
+    #[serde(default)]
+    pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    /// Documents expire this many seconds after their insertion
+    #[serde(default = "default_expire_after_secs")]
+    pub expire_after_secs: u64,
+    /// Number of documents per insert request
+    #[serde(default = "default_ttl_batch_size")]
+    pub batch_size: u32,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of insert requests, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    /// Interval in which the collection count is checked
+    #[serde(default = "default_ttl_monitor_interval_secs")]
+    pub monitor_interval_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_expire_after_secs() -> u64 {
+    60
+}
+
+fn default_ttl_batch_size() -> u32 {
+    100
+}
+
+fn default_ttl_monitor_interval_secs() -> u64 {
+    10
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimeseriesConfig {
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    #[serde(default)]
+    pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    /// Number of devices, each of which produces one series
+    #[serde(default = "default_number_of_series")]
+    pub number_of_series: u32,
+    /// Number of metric values per data point
+    #[serde(default = "default_values_per_point")]
+    pub values_per_point: u32,
+    /// Number of data points per insert request
+    #[serde(default = "default_timeseries_batch_size")]
+    pub batch_size: u32,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of insert requests, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    /// Interval between two range queries, 0 disables them
+    #[serde(default = "default_query_interval_ms")]
+    pub query_interval_ms: u64,
+    /// The range queries cover the data points of this many last seconds
+    #[serde(default = "default_query_window_secs")]
+    pub query_window_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_number_of_series() -> u32 {
+    100
+}
+
+fn default_values_per_point() -> u32 {
+    4
+}
+
+fn default_timeseries_batch_size() -> u32 {
+    100
+}
+
+fn default_query_interval_ms() -> u64 {
+    1000
+}
+
+fn default_query_window_secs() -> u64 {
+    60
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BankingConfig {
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    #[serde(default = "default_number_of_accounts")]
+    pub number_of_accounts: u32,
+    /// The balance every account starts with
+    #[serde(default = "default_initial_balance")]
+    pub initial_balance: u64,
+    /// Transfers move a random amount between 1 and this
+    #[serde(default = "default_max_transfer")]
+    pub max_transfer: u64,
+    #[serde(default)]
+    pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of transfers, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    /// Interval in which the total balance is checked
+    #[serde(default = "default_check_interval_secs")]
+    pub check_interval_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_number_of_accounts() -> u32 {
+    1000
+}
+
+fn default_initial_balance() -> u64 {
+    1000
+}
+
+fn default_max_transfer() -> u64 {
+    100
+}
+
+fn default_check_interval_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UniqueConfig {
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    #[serde(default)]
+    pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    /// Number of distinct values the inserts choose from, the smaller, the
+    /// more inserts violate the unique constraint
+    #[serde(default = "default_value_space")]
+    pub value_space: u32,
+    /// Whether the index is unique, a non-unique index gives the baseline
+    /// latency without the unique checks
+    #[serde(default = "default_unique")]
+    pub unique: bool,
+    /// Percentage of the operations which remove the document with a random
+    /// value, the others are inserts
+    #[serde(default = "default_remove_pct")]
+    pub remove_pct: u32,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of operations, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_value_space() -> u32 {
+    100_000
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DdlConfig {
+    /// Shards and replication factor of the churned collections
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    /// Number of churned collections which exist at the same time, when a
+    /// new one exceeds it, the oldest is dropped
+    #[serde(default = "default_max_collections")]
+    pub max_collections: u32,
+    /// Percentage of the operations which create a database instead of a
+    /// collection
+    #[serde(default)]
+    pub database_pct: u32,
+    /// Number of churned databases which exist at the same time
+    #[serde(default = "default_max_databases")]
+    pub max_databases: u32,
+    #[serde(default = "default_ddl_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of creations, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_max_collections() -> u32 {
+    100
+}
+
+fn default_max_databases() -> u32 {
+    5
+}
+
+fn default_ddl_concurrency() -> u32 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsersConfig {
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    #[serde(default = "default_number_of_users")]
+    pub number_of_users: u32,
+    #[serde(default = "default_user_collections")]
+    pub number_of_collections: u32,
+    /// Percentage of the operations which drop a user and create it again
+    #[serde(default = "default_user_churn_pct")]
+    pub churn_pct: u32,
+    /// Percentage of the operations which change a collection permission
+    #[serde(default = "default_grant_pct")]
+    pub grant_pct: u32,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of operations, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_number_of_users() -> u32 {
+    100
+}
+
+fn default_user_collections() -> u32 {
+    3
+}
+
+fn default_user_churn_pct() -> u32 {
+    5
+}
+
+fn default_grant_pct() -> u32 {
+    10
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SmartJoinConfig {
+    pub number_of_shards: u32,
+    pub replication_factor: u32,
+    /// Number of documents of the sharded dimension collection `customers`
+    pub number_of_customers: u32,
+    /// Number of documents of each fact collection
+    pub number_of_facts: u32,
+    /// Number of documents of the satellite collection `products`
+    #[serde(default = "default_smartjoin_products")]
+    pub number_of_products: u32,
+    /// Number of documents of the satellite collection `regions`
+    #[serde(default = "default_smartjoin_regions")]
+    pub number_of_regions: u32,
+    /// Number of customer segments, an aggregation covers one of them
+    #[serde(default = "default_smartjoin_segments")]
+    pub number_of_segments: u32,
+    /// Size of the payload of every fact in bytes
+    #[serde(default = "default_fact_size")]
+    pub fact_size: u32,
+    /// Also seeds the facts into a collection without
+    /// `distributeShardsLike` and runs every query against both
+    #[serde(default = "default_baseline")]
+    pub baseline: bool,
+    /// Percentage of the operations which aggregate over a segment instead
+    /// of joining the facts of one customer
+    #[serde(default = "default_aggregate_pct")]
+    pub aggregate_pct: u32,
+    #[serde(default)]
+    pub drop_first: bool,
+    #[serde(default)]
+    pub drop_on_exit: bool,
+    #[serde(default = "default_load_concurrency")]
+    pub insert_concurrency: u32,
+    #[serde(default = "default_load_concurrency")]
+    pub concurrency: u32,
+    /// Target rate of queries, 0 means as fast as possible
+    #[serde(default)]
+    pub target_ops_per_sec: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn default_smartjoin_products() -> u32 {
+    1000
+}
+
+fn default_smartjoin_regions() -> u32 {
+    20
+}
+
+fn default_smartjoin_segments() -> u32 {
+    100
+}
+
+fn default_fact_size() -> u32 {
+    100
+}
+
+fn default_baseline() -> bool {
+    true
+}
+
+fn default_aggregate_pct() -> u32 {
+    20
+}
+
+fn default_unique() -> bool {
+    true
+}
+
+fn default_remove_pct() -> u32 {
+    20
+}
+
+fn default_words_per_document() -> u32 {
+    50
+}
+
+fn default_phrase_pct() -> u32 {
+    30
+}
+
+fn default_tokens_pct() -> u32 {
+    40
+}
+
+fn default_bm25_pct() -> u32 {
+    30
+}
+
+/// The API used by the CRUD use case to seed its collections
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SeedApi {
+    /// `/_api/import` with a JSONL body, one document per line
+    #[default]
+    Import,
+    /// `/_api/document` with a JSON array or a single document
+    Document,
+}
+
+/// The format of request and response bodies on the document API
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentFormat {
+    /// `application/json`
+    #[default]
+    Json,
+    /// `application/x-velocypack`, the binary format the server stores
+    /// documents in
+    Velocypack,
+}
+
+/// The API used by the CRUD use case for writes in the steady state
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteApi {
+    /// The batch operations of `/_api/document`
+    #[default]
+    Document,
+    /// AQL `INSERT`, `UPDATE`, `UPSERT` and `REMOVE` statements
+    Aql,
+}
+
+/// The kind of view used by the search use case
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ViewType {
+    /// An `arangosearch` view with links to the collections
+    #[default]
+    Arangosearch,
+    /// A `search-alias` view over inverted indexes on the collections
+    SearchAlias,
+}
+
+impl Config {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::load(path, &[])
+    }
+
+    /// Reads the configuration file and merges the environment variables
+    /// of `ENV_OVERRIDES` and then the `overrides` over it, each given as
+    /// `<path>=<value>` like `crud.number_of_documents=1000000`
+    ///
+    /// # Errors
+    /// Fails if the file cannot be read, an override is malformed or the
+    /// resulting configuration is invalid
+    pub fn load<P: AsRef<Path>>(path: P, overrides: &[String]) -> Result<Self, ConfigError> {
+        let format = Format::of(path.as_ref());
+        let mut file = File::open(path)?;
+        let mut contents = String::new();
+        file.read_to_string(&mut contents)?;
+        let mut merged = Vec::new();
+        for (var, path) in ENV_OVERRIDES {
+            if let Ok(value) = std::env::var(var) {
+                let value = if *path == "database.endpoints" {
+                    serde_yaml::Value::Sequence(
+                        value.split(',').map(|url| url.trim().into()).collect(),
+                    )
+                } else {
+                    serde_yaml::Value::String(value)
+                };
+                merged.push((path.to_string(), value));
+            }
+        }
+        for assignment in overrides {
+            let (path, value) = assignment.split_once('=').ok_or_else(|| {
+                ConfigError::Override(format!("{} is not of the form <path>=<value>", assignment))
+            })?;
+            merged.push((path.to_string(), parse_override_value(value)));
+        }
+        Self::parse(&contents, format, merged)
+    }
+
+    /// Parses and validates a configuration, warnings are logged
+    ///
+    /// # Errors
+    /// Fails if the YAML does not match the configuration or the values
+    /// are invalid
+    pub fn from_yaml(contents: &str) -> Result<Self, ConfigError> {
+        Self::parse(contents, Format::Yaml, Vec::new())
+    }
+
+    /// Parses and validates a configuration in JSON, warnings are logged
+    ///
+    /// # Errors
+    /// Fails if the JSON does not match the configuration or the values
+    /// are invalid
+    pub fn from_json(contents: &str) -> Result<Self, ConfigError> {
+        Self::parse(contents, Format::Json, Vec::new())
+    }
+
+    /// Parses and validates a configuration in TOML, warnings are logged
+    ///
+    /// # Errors
+    /// Fails if the TOML is malformed or does not match the configuration
+    /// or the values are invalid
+    pub fn from_toml(contents: &str) -> Result<Self, ConfigError> {
+        Self::parse(contents, Format::Toml, Vec::new())
+    }
+
+    /// Parses `contents`, migrates it to `CURRENT_VERSION` and merges the
+    /// `overrides` over it
+    fn parse(
+        contents: &str,
+        format: Format,
+        overrides: Vec<(String, serde_yaml::Value)>,
+    ) -> Result<Self, ConfigError> {
+        let mut raw: serde_yaml::Value = match format {
+            Format::Yaml => serde_yaml::from_str(contents)?,
+            Format::Json => {
+                serde_yaml::to_value(serde_json::from_str::<serde_json::Value>(contents)?)?
+            }
+            Format::Toml => serde_yaml::to_value(toml::from_str::<serde_json::Value>(contents)?)?,
+        };
+        let migrated = migrate(&mut raw)?;
+        let config: Config = match format {
+            // Parsing the text keeps the line numbers in the errors
+            Format::Yaml if !migrated && overrides.is_empty() => serde_yaml::from_str(contents)?,
+            Format::Json if !migrated && overrides.is_empty() => serde_json::from_str(contents)?,
+            _ => {
+                for (path, value) in overrides {
+                    set_path(&mut raw, &path, value)?;
+                }
+                serde_yaml::from_value(raw.clone())?
+            }
+        };
+        Self::checked(config, serde_json::to_value(&raw).ok())
+    }
+
+    /// Rejects the fields of `raw` which `config` does not know and
+    /// validates it, warnings are logged
+    fn checked(config: Config, raw: Option<serde_json::Value>) -> Result<Self, ConfigError> {
+        let mut errors = Vec::new();
+        if let Some(raw) = raw {
+            let parsed = serde_json::to_value(&config).unwrap_or_default();
+            unknown_fields(&raw, &parsed, "", &mut errors);
+        }
+        match config.validate() {
+            Ok(warnings) => warnings.iter().for_each(|warning| warn!("{}", warning)),
+            Err(ConfigError::Invalid(problems)) => errors.extend(problems),
+            Err(e) => return Err(e),
+        }
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+        Ok(config)
+    }
+
+    /// Checks the values which are fine on their own, but do not fit
+    /// together. Returns warnings about values which only work with some
+    /// clusters.
+    ///
+    /// # Errors
+    /// Returns all problems at once as `ConfigError::Invalid`
+    pub fn validate(&self) -> Result<Vec<String>, ConfigError> {
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+        if self.database.endpoints.is_empty() {
+            errors.push("database.endpoints is empty".to_string());
+        }
+        if self.database.compress_requests.is_some() && self.database.protocol == Protocol::Vst {
+            warnings.push(
+                "database.compress_requests has no effect with VST, only HTTP bodies are \
+                 compressed"
+                    .to_string(),
+            );
+        }
+        let unix_endpoints = self
+            .database
+            .endpoints
+            .iter()
+            .filter(|url| url.starts_with("unix://"))
+            .count();
+        if unix_endpoints > 0 {
+            if unix_endpoints < self.database.endpoints.len() {
+                errors
+                    .push("database.endpoints cannot mix unix:// with other endpoints".to_string());
+            }
+            if self.database.protocol == Protocol::Vst {
+                errors.push("database.protocol vst does not support unix:// endpoints".to_string());
+            }
+            if self.database.auto_discover {
+                errors.push(
+                    "database.auto_discover would replace the unix:// endpoints with TCP ones"
+                        .to_string(),
+                );
+            }
+            if self.database.http2 {
+                warnings.push(
+                    "database.http2 has no effect with unix:// endpoints, which use HTTP/1.1"
+                        .to_string(),
+                );
+            }
+        }
+        if let Some(proxy) = &self.database.proxy {
+            if reqwest::Proxy::all(proxy.as_str()).is_err() {
+                errors.push(format!("database.proxy {} is no valid proxy URL", proxy));
+            }
+            if self.database.protocol == Protocol::Vst || unix_endpoints > 0 {
+                warnings.push(
+                    "database.proxy has no effect with VST or unix:// endpoints, only HTTP \
+                     requests go through it"
+                        .to_string(),
+                );
+            }
+        }
+        if let Some(breaker) = &self.database.circuit_breaker {
+            if breaker.failure_threshold == 0 || breaker.probe_requests == 0 {
+                errors.push(
+                    "database.circuit_breaker needs a failure_threshold and probe_requests of \
+                     at least 1"
+                        .to_string(),
+                );
+            }
+        }
+        if let Some(max_queue_time) = self.database.max_queue_time_seconds {
+            if max_queue_time.is_nan() || max_queue_time <= 0.0 {
+                errors.push(format!(
+                    "database.max_queue_time_seconds {} is not a positive number of seconds",
+                    max_queue_time
+                ));
+            }
+        }
+        let warmup_connections = self.database.warmup_connections as usize;
+        if warmup_connections > 1 && self.database.http2 {
+            warnings.push(
+                "database.warmup_connections opens a single connection per endpoint with http2, \
+                 which multiplexes the requests"
+                    .to_string(),
+            );
+        } else if let Some(max_idle) = self
+            .database
+            .pool_max_idle_per_host
+            .filter(|max_idle| *max_idle < warmup_connections)
+        {
+            warnings.push(format!(
+                "database.warmup_connections {} is more than database.pool_max_idle_per_host {}, \
+                 the connections beyond it are closed again",
+                warmup_connections, max_idle
+            ));
+        }
+        if self.database.active_failover && self.database.auto_discover {
+            warnings.push(
+                "database.auto_discover needs a cluster, an active failover deployment keeps \
+                 its endpoints"
+                    .to_string(),
+            );
+        }
+        if let Some(replication_lag) = &self.replication_lag {
+            if replication_lag.interval_ms == 0
+                || replication_lag.poll_interval_ms == 0
+                || replication_lag.confirmations == 0
+            {
+                errors.push(
+                    "replication_lag needs an interval_ms, poll_interval_ms and confirmations of \
+                     at least 1"
+                        .to_string(),
+                );
+            }
+            if replication_lag.follower_endpoints.is_empty()
+                && replication_lag.replication_factor < 2
+            {
+                errors.push(
+                    "replication_lag.replication_factor must be at least 2 without \
+                     follower_endpoints, otherwise the markers have no followers"
+                        .to_string(),
+                );
+            }
+        }
+        let endpoints = self.database.endpoints.len();
+        let mut check_replication_factor = |field: String, replication_factor: u64| {
+            if replication_factor == 0 {
+                errors.push(format!("{} must be at least 1", field));
+            } else if replication_factor as usize > endpoints {
+                warnings.push(format!(
+                    "{} {} is larger than the number of endpoints ({}), creating \
+                     collections fails unless the cluster has more DB servers",
+                    field, replication_factor, endpoints
+                ));
+            }
+        };
+        let sections = serde_json::to_value(self).unwrap_or_default();
+        let mut idle_threads = Vec::new();
+        for (name, usecase) in self.active_usecases.all() {
+            if !usecase.on {
+                continue;
+            }
+            if usecase.threads == 0 {
+                idle_threads.push(name);
+            }
+            if let Some(replication_factor) = sections[name]["replication_factor"].as_u64() {
+                check_replication_factor(
+                    format!("{}.replication_factor", name),
+                    replication_factor,
+                );
+            }
+        }
+        if let Some(replication_lag) = &self.replication_lag {
+            check_replication_factor(
+                "replication_lag.replication_factor".to_string(),
+                u64::from(replication_lag.replication_factor),
+            );
+        }
+        let crud_on = self.active_usecases.crud.on;
+        if crud_on {
+            for collection in &self.crud.collections {
+                if let Some(replication_factor) = collection.replication_factor {
+                    check_replication_factor(
+                        format!("crud.collections.{}.replication_factor", collection.name),
+                        u64::from(replication_factor),
+                    );
+                }
+            }
+            let first = self.crud.collections.first().map(|c| c.name.as_str());
+            for collection in &self.crud.collections {
+                let Some(prototype) = &collection.distribute_shards_like else {
+                    continue;
+                };
+                if Some(collection.name.as_str()) == first || Some(prototype.as_str()) != first {
+                    errors.push(format!(
+                        "crud.collections.{}.distribute_shards_like must name the first \
+                         collection {}",
+                        collection.name,
+                        first.unwrap_or_default()
+                    ));
+                } else if collection.number_of_shards.is_some()
+                    || collection.replication_factor.is_some()
+                {
+                    warnings.push(format!(
+                        "crud.collections.{} takes number_of_shards and replication_factor \
+                         from {}, its own ones are ignored",
+                        collection.name, prototype
+                    ));
+                }
+            }
+        }
+        for name in idle_threads {
+            errors.push(format!(
+                "active_usecases.{}.threads is 0, but the use case is on",
+                name
+            ));
+        }
+        let cpus = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+        let active: Vec<u32> = self
+            .active_usecases
+            .all()
+            .iter()
+            .filter(|(_, usecase)| usecase.on)
+            .map(|(_, usecase)| usecase.threads)
+            .collect();
+        let threads: usize = active.iter().map(|threads| *threads as usize).sum();
+        match self.runtime.strategy {
+            RuntimeStrategy::PerUsecase if active.len() > 1 && threads > cpus => {
+                warnings.push(format!(
+                    "the use cases have {} threads on {} CPUs, runtime.strategy shared avoids \
+                     over-subscribing them",
+                    threads, cpus
+                ))
+            }
+            RuntimeStrategy::PerUsecase => {}
+            RuntimeStrategy::Shared => match self.runtime.shared_threads {
+                Some(0) => errors.push("runtime.shared_threads must be at least 1".to_string()),
+                Some(shared) if shared > cpus => warnings.push(format!(
+                    "runtime.shared_threads {} is more than the {} CPUs",
+                    shared, cpus
+                )),
+                _ => {}
+            },
+            RuntimeStrategy::Pinned if self.runtime.first_core + threads > cpus => {
+                errors.push(format!(
+                    "runtime.strategy pinned needs {} CPUs from runtime.first_core {}, but \
+                     there are {}",
+                    threads, self.runtime.first_core, cpus
+                ))
+            }
+            RuntimeStrategy::Pinned => {}
+        }
+        let mut names: Vec<&str> = self
+            .crud
+            .collections
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        names.sort();
+        for duplicate in names.windows(2).filter(|pair| pair[0] == pair[1]) {
+            errors.push(format!("crud.collections has {} twice", duplicate[0]));
+        }
+        if let Err(e) =
+            crate::naming::check(&self.database.database_name, &DATABASE_NAME_PLACEHOLDERS)
+        {
+            errors.push(format!("database.database_name: {}", e));
+        } else if !self.database.database_name.starts_with("{prefix}") {
+            errors.push(
+                "database.database_name must start with {prefix}, cleanup finds the \
+                 databases of the loader by their prefix"
+                    .to_string(),
+            );
+        }
+        if let Some(run_id) = &self.database.run_id {
+            if run_id.is_empty()
+                || !run_id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                errors.push(format!(
+                    "database.run_id {:?} must only have letters, digits, _ and -",
+                    run_id
+                ));
+            }
+        }
+        if let Some(coordination) = &self.coordination {
+            if coordination.instances == 0 {
+                errors.push("coordination.instances must be at least 1".to_string());
+            }
+            if let Some(instance) = coordination.instance {
+                if instance == 0 || instance > coordination.instances {
+                    errors.push(format!(
+                        "coordination.instance {} is not between 1 and coordination.instances ({})",
+                        instance, coordination.instances
+                    ));
+                }
+            }
+            if self.database.run_id.is_none() {
+                errors.push(
+                    "coordination needs database.run_id, the loaders of a run share it".to_string(),
+                );
+            }
+        }
+        if crud_on && self.crud.write_api == WriteApi::Aql {
+            // Missing keys of AQL updates and replaces lie beyond the
+            // seeded ones, which must leave room for them
+            let too_many = std::iter::once(("crud".to_string(), self.crud.number_of_documents))
+                .chain(self.crud.collections.iter().filter_map(|collection| {
+                    collection
+                        .number_of_documents
+                        .map(|number| (format!("crud.collections.{}", collection.name), number))
+                }))
+                .filter(|(_, number)| *number > u32::MAX / 2);
+            for (section, number) in too_many {
+                errors.push(format!(
+                    "{}.number_of_documents {} is too large for crud.write_api aql, at most {}",
+                    section,
+                    number,
+                    u32::MAX / 2
+                ));
+            }
+        }
+        let reads_blobs = self.crud.operation_mix.is_some_and(|mix| mix.read_blob > 0);
+        if crud_on && reads_blobs && self.crud.blob.is_none() {
+            errors.push("crud.operation_mix.read_blob needs crud.blob".to_string());
+        }
+        if crud_on
+            && self.crud.document_format == DocumentFormat::Velocypack
+            && self.crud.seed_api == SeedApi::Import
+        {
+            errors.push(
+                "crud.document_format velocypack needs crud.seed_api document, the import API \
+                 only takes JSON"
+                    .to_string(),
+            );
+        }
+        if let Some(search) = self
+            .search
+            .as_ref()
+            .filter(|_| self.active_usecases.search.on)
+        {
+            if search.languages.weights().iter().all(|&weight| weight == 0) {
+                errors.push("search.languages needs at least one positive weight".to_string());
+            }
+        }
+        if let Err(e) = crate::naming::check(&self.crud.collection_name, &["i"]) {
+            errors.push(format!("crud.collection_name: {}", e));
+        } else if self.crud.collections.is_empty()
+            && self.crud.number_of_collections > 1
+            && !crate::naming::uses(&self.crud.collection_name, "i")
+        {
+            errors.push("crud.collection_name needs {i} for several collections".to_string());
+        }
+        if let Some(churn) = &self.crud.index_churn {
+            if !self.crud.collection_names().contains(&churn.collection) {
+                errors.push(format!(
+                    "crud.index_churn.collection {} is not a collection of the CRUD use case",
+                    churn.collection
+                ));
+            }
+        }
+        let mut document_sizes = vec![("crud.document_size".to_string(), self.crud.document_size)];
+        for collection in &self.crud.collections {
+            if let Some(size) = collection.document_size {
+                document_sizes.push((
+                    format!("crud.collections.{}.document_size", collection.name),
+                    size,
+                ));
+            }
+        }
+        if let Some(blob) = &self.crud.blob {
+            document_sizes.push(("crud.blob.size".to_string(), blob.size));
+        }
+        for (field, size) in &document_sizes {
+            if let Err(e) = size.check() {
+                errors.push(format!("{}: {}", field, e));
+            }
+        }
+        let mut sizes = Vec::new();
+        if crud_on && self.crud.collections.is_empty() && self.crud.document_schema.is_none() {
+            if let Some(size) = self.crud.document_size.min_size() {
+                sizes.push(("crud.document_size".to_string(), size));
+            }
+        }
+        if crud_on {
+            for collection in &self.crud.collections {
+                let size = match (&collection.document_schema, collection.document_size) {
+                    (Some(_), _) => None,
+                    (None, Some(size)) => size.min_size(),
+                    (None, None) if self.crud.document_schema.is_none() => {
+                        self.crud.document_size.min_size()
+                    }
+                    (None, None) => None,
+                };
+                if let Some(size) = size {
+                    sizes.push((
+                        format!("crud.collections.{}.document_size", collection.name),
+                        size,
+                    ));
+                }
+            }
+        }
+        if self.active_usecases.graph.on {
+            sizes.push(("graph.vertex_size".to_string(), self.graph.vertex_size));
+            sizes.push(("graph.edge_size".to_string(), self.graph.edge_size));
+        }
+        if let Some(transactions) = &self.transactions {
+            if self.active_usecases.transactions.on {
+                sizes.push((
+                    "transactions.document_size".to_string(),
+                    transactions.document_size,
+                ));
+            }
+        }
+        for (field, size) in sizes {
+            if size < MIN_DOCUMENT_SIZE {
+                warnings.push(format!(
+                    "{} is {}, the generated documents need at least {} bytes to fill \
+                     their attributes",
+                    field, size, MIN_DOCUMENT_SIZE
+                ));
+            }
+        }
+        if errors.is_empty() {
+            Ok(warnings)
+        } else {
+            Err(ConfigError::Invalid(errors))
+        }
+    }
+
+    /// Returns the sections in which `other` differs from this
+    /// configuration in more than the parameters which can be changed while
+    /// the use cases run (see `DYNAMIC_FIELDS`). Comments and the password
+    /// are ignored.
+    pub fn restart_required(&self, other: &Config) -> Vec<String> {
+        let normalize = |config: &Config| {
+            let mut value = serde_json::to_value(config).unwrap_or_default();
+            strip_comments(&mut value);
+            strip_password(&mut value);
+            if let Some(sections) = value.as_object_mut() {
+                for (section, fields) in DYNAMIC_FIELDS {
+                    if let Some(section) =
+                        sections.get_mut(*section).and_then(|s| s.as_object_mut())
+                    {
+                        for field in *fields {
+                            section.remove(*field);
+                        }
+                    }
+                }
+            }
+            value
+        };
+        let (old, new) = (normalize(self), normalize(other));
+        let (Some(old), Some(new)) = (old.as_object(), new.as_object()) else {
+            return Vec::new();
+        };
+        let mut sections: Vec<String> = old
+            .keys()
+            .chain(new.keys())
+            .filter(|key| old.get(*key) != new.get(*key))
+            .cloned()
+            .collect();
+        sections.sort();
+        sections.dedup();
+        sections
+    }
+
+    /// Returns a hash of the configuration without its comments and the
+    /// password, as 16 hex digits, to tell runs with different
+    /// configurations apart. The hash (64 bit FNV-1a) is stable across
+    /// builds.
+    pub fn fingerprint(&self) -> String {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        strip_comments(&mut value);
+        strip_password(&mut value);
+        let hash = value
+            .to_string()
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            });
+        format!("{:016x}", hash)
+    }
+
+    /// Returns the configuration with the password masked, to show it
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        if !config.database.password.is_empty() {
+            config.database.password = "********".to_string();
+        }
+        config
+    }
+}
+
+/// The parameters of each use case section which can be changed while the
+/// use case runs: its concurrency, target rate and operation mix
+pub const DYNAMIC_FIELDS: &[(&str, &[&str])] = &[
+    (
+        "crud",
+        &[
+            "concurrency",
+            "target_ops_per_sec",
+            "read_pct",
+            "update_pct",
+            "replace_pct",
+            "delete_insert_pct",
+            "operation_mix",
+            "read_batch_size",
+            "read_from_followers",
+            "write_api",
+            "aql_match_pct",
+            "write_options",
+        ],
+    ),
+    ("queries", &["concurrency", "target_ops_per_sec", "queries"]),
+    (
+        "transactions",
+        &[
+            "concurrency",
+            "target_ops_per_sec",
+            "operations_per_transaction",
+            "write_pct",
+            "abort_pct",
+        ],
+    ),
+    (
+        "search",
+        &[
+            "concurrency",
+            "target_ops_per_sec",
+            "phrase_pct",
+            "tokens_pct",
+            "bm25_pct",
+        ],
+    ),
+    (
+        "graph",
+        &[
+            "concurrency",
+            "target_ops_per_sec",
+            "min_depth",
+            "max_depth",
+            "outbound_pct",
+            "inbound_pct",
+            "any_pct",
+            "write_pct",
+            "shortest_path_pct",
+            "k_shortest_paths_pct",
+            "k_paths",
+            "start_vertex_distribution",
+        ],
+    ),
+    ("pregel", &["concurrency", "target_ops_per_sec", "jobs"]),
+    (
+        "conflicts",
+        &["concurrency", "target_ops_per_sec", "max_retries"],
+    ),
+    ("ttl", &["concurrency", "target_ops_per_sec", "batch_size"]),
+    (
+        "timeseries",
+        &["concurrency", "target_ops_per_sec", "batch_size"],
+    ),
+    (
+        "banking",
+        &["concurrency", "target_ops_per_sec", "max_transfer"],
+    ),
+    (
+        "unique",
+        &[
+            "concurrency",
+            "target_ops_per_sec",
+            "value_space",
+            "remove_pct",
+        ],
+    ),
+    (
+        "ddl",
+        &[
+            "concurrency",
+            "target_ops_per_sec",
+            "max_collections",
+            "database_pct",
+            "max_databases",
+        ],
+    ),
+    (
+        "users",
+        &[
+            "concurrency",
+            "target_ops_per_sec",
+            "churn_pct",
+            "grant_pct",
+        ],
+    ),
+    (
+        "smartjoin",
+        &["concurrency", "target_ops_per_sec", "aggregate_pct"],
+    ),
+];
+
+/// The formats of configuration files, told apart by their extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// `.json` and `.toml` files are read as JSON and TOML, all others as
+    /// YAML
+    fn of(path: &Path) -> Format {
+        match path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("json") => Format::Json,
+            Some("toml") => Format::Toml,
+            _ => Format::Yaml,
+        }
+    }
+}
+
+/// The version of the configuration format, older versions are migrated
+/// when they are read
+pub const CURRENT_VERSION: u32 = 2;
+
+/// The fields and sections which were renamed or moved from each version to
+/// the next, starting with version 1 to 2
+const MIGRATIONS: &[&[(&str, &str)]] = &[&[("crud.load_concurrency", "crud.concurrency")]];
+
+/// Migrates a configuration of an older version to `CURRENT_VERSION`,
+/// step by step, and returns whether anything had to be changed
+///
+/// # Errors
+/// Fails if the version is missing, newer than `CURRENT_VERSION` or unknown
+fn migrate(raw: &mut serde_yaml::Value) -> Result<bool, ConfigError> {
+    let version = match raw.get("version") {
+        Some(serde_yaml::Value::String(version)) => version.trim().parse::<u32>().ok(),
+        Some(serde_yaml::Value::Number(version)) => {
+            version.as_u64().and_then(|v| u32::try_from(v).ok())
+        }
+        None => {
+            return Err(ConfigError::Version(
+                "the field version is missing".to_string(),
+            ))
+        }
+        _ => None,
+    };
+    let version = match version {
+        Some(version) if (1..=CURRENT_VERSION).contains(&version) => version,
+        Some(version) if version > CURRENT_VERSION => {
+            return Err(ConfigError::Version(format!(
+                "version {} is newer than {}, the newest one this adb_loader understands",
+                version, CURRENT_VERSION
+            )))
+        }
+        _ => {
+            return Err(ConfigError::Version(format!(
+                "version {} is unknown, the versions are 1 to {}",
+                serde_json::to_string(&raw["version"]).unwrap_or_default(),
+                CURRENT_VERSION
+            )))
+        }
+    };
+    let current = serde_yaml::Value::String(CURRENT_VERSION.to_string());
+    if raw["version"] == current {
+        return Ok(false);
+    }
+    for (from, renames) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        for (old, new) in *renames {
+            if let Some(value) = remove_path(raw, old) {
+                set_path(raw, new, value)?;
+                warn!("Configuration version {}: {} is now {}", from + 1, old, new);
+            }
+        }
+    }
+    if version < CURRENT_VERSION {
+        warn!(
+            "Migrated the configuration from version {} to {}, please update the file",
+            version, CURRENT_VERSION
+        );
+    }
+    raw["version"] = current;
+    Ok(true)
+}
+
+/// Removes and returns the value at a dotted path, if it exists
+fn remove_path(root: &mut serde_yaml::Value, path: &str) -> Option<serde_yaml::Value> {
+    let (parent, last) = match path.rsplit_once('.') {
+        Some((parent, last)) => (Some(parent), last),
+        None => (None, path),
+    };
+    let mut node = root;
+    for segment in parent.into_iter().flat_map(|parent| parent.split('.')) {
+        node = node.get_mut(segment)?;
+    }
+    node.as_mapping_mut()?.remove(last)
+}
+
+/// Environment variables which replace values of the configuration file,
+/// so that checked-in configurations need not contain secrets.
+/// `ADB_ENDPOINTS` is a comma-separated list.
+const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("ADB_USERNAME", "database.username"),
+    ("ADB_PASSWORD", "database.password"),
+    ("ADB_ENDPOINTS", "database.endpoints"),
+];
+
+/// Parses the value of an override as YAML, so that numbers, booleans and
+/// lists like `[a, b]` keep their type. Anything else is a string.
+fn parse_override_value(value: &str) -> serde_yaml::Value {
+    if value.is_empty() {
+        return serde_yaml::Value::String(String::new());
+    }
+    match serde_yaml::from_str(value) {
+        Ok(serde_yaml::Value::Mapping(_)) | Err(_) => serde_yaml::Value::String(value.to_string()),
+        Ok(parsed) => parsed,
+    }
+}
+
+/// Replaces the value at a dotted path like `database.endpoints.0`,
+/// missing sections are created, numbers index into lists
+fn set_path(
+    root: &mut serde_yaml::Value,
+    path: &str,
+    value: serde_yaml::Value,
+) -> Result<(), ConfigError> {
+    use serde_yaml::Value;
+    let mut node = root;
+    for segment in path.split('.') {
+        if segment.is_empty() {
+            return Err(ConfigError::Override(format!(
+                "{} has an empty segment",
+                path
+            )));
+        }
+        if node.is_null() {
+            *node = Value::Mapping(Default::default());
+        }
+        node = match node {
+            Value::Mapping(map) => map
+                .entry(Value::String(segment.to_string()))
+                .or_insert(Value::Null),
+            Value::Sequence(items) => {
+                let len = items.len();
+                segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| items.get_mut(i))
+                    .ok_or_else(|| {
+                        ConfigError::Override(format!(
+                            "{}: {} is not an index of a list of {} entries",
+                            path, segment, len
+                        ))
+                    })?
+            }
+            _ => {
+                return Err(ConfigError::Override(format!(
+                    "{}: {} is not within a section",
+                    path, segment
+                )))
+            }
+        };
+    }
+    *node = value;
+    Ok(())
+}
+
+/// Collects the fields of `raw`, the configuration as written, which are
+/// lost when it is deserialized and serialized again, so that typos do not
+/// silently fall back to defaults. `deny_unknown_fields` cannot be used,
+/// because it does not work with flattened structs. Empty values are not
+/// reported, because they are skipped when serializing.
+fn unknown_fields(
+    raw: &serde_json::Value,
+    parsed: &serde_json::Value,
+    path: &str,
+    unknown: &mut Vec<String>,
+) {
+    use serde_json::Value;
+    match (raw, parsed) {
+        (Value::Object(raw), Value::Object(parsed)) => {
+            for (key, value) in raw {
+                let field = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match parsed.get(key) {
+                    Some(parsed) => unknown_fields(value, parsed, &field, unknown),
+                    None => {
+                        let empty = match value {
+                            Value::Null => true,
+                            Value::Array(items) => items.is_empty(),
+                            Value::Object(map) => map.is_empty(),
+                            _ => false,
+                        };
+                        if !empty {
+                            unknown.push(format!("unknown field {}", field));
+                        }
+                    }
+                }
+            }
+        }
+        (Value::Array(raw), Value::Array(parsed)) => {
+            for (i, (value, parsed)) in raw.iter().zip(parsed).enumerate() {
+                unknown_fields(value, parsed, &format!("{}[{}]", path, i), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Removes the password, which is a secret and no part of the setup
+fn strip_password(value: &mut serde_json::Value) {
+    if let Some(database) = value
+        .get_mut("database")
+        .and_then(serde_json::Value::as_object_mut)
+    {
+        database.remove("password");
+    }
+}
+
+/// Removes all `comment` fields, which adb_loader ignores
+fn strip_comments(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.remove("comment");
+            map.values_mut().for_each(strip_comments);
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_comments),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_ignores_comments() {
+        let config = Config::from_file("config.yaml").unwrap();
+        let mut commented = config.clone();
+        commented.comment = Some("another comment".to_string());
+        assert_eq!(config.fingerprint(), commented.fingerprint());
+        assert_eq!(config.fingerprint().len(), 16);
+
+        let mut changed = config.clone();
+        changed.crud.read_pct += 1;
+        assert_ne!(config.fingerprint(), changed.fingerprint());
+    }
+
+    #[test]
+    fn test_restart_required_ignores_dynamic_fields() {
+        let config = Config::from_file("config.yaml").unwrap();
+        let mut changed = config.clone();
+        changed.crud.read_pct += 10;
+        changed.crud.target_ops_per_sec = 100.0;
+        changed.crud.comment = None;
+        assert!(config.restart_required(&changed).is_empty());
+
+        changed.crud.number_of_shards += 1;
+        changed.metrics_port += 1;
+        assert_eq!(
+            config.restart_required(&changed),
+            vec!["crud".to_string(), "metrics_port".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let contents = std::fs::read_to_string("config.yaml").unwrap();
+        let contents = contents.replacen(
+            "  number_of_documents:",
+            "  number_of_document: 5\n  number_of_documents:",
+            1,
+        );
+        let raw: serde_json::Value = serde_yaml::from_str(&contents).unwrap();
+        let config: Config = serde_yaml::from_str(&contents).unwrap();
+        let parsed = serde_json::to_value(&config).unwrap();
+        let mut unknown = Vec::new();
+        unknown_fields(&raw, &parsed, "", &mut unknown);
+        assert_eq!(unknown, vec!["unknown field crud.number_of_document"]);
+    }
+
+    #[test]
+    fn test_validate_cross_field_checks() {
+        let config = Config::from_file("config.yaml").unwrap();
+        let mut invalid = config.clone();
+        invalid.active_usecases.crud.on = true;
+        invalid.active_usecases.crud.threads = 0;
+        invalid.crud.document_schema = None;
+        invalid.crud.document_size = DocumentSize::Fixed(10);
+        invalid.crud.replication_factor = config.database.endpoints.len() as u32 + 1;
+        let Err(ConfigError::Invalid(errors)) = invalid.validate() else {
+            panic!("invalid configuration accepted");
+        };
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].contains("active_usecases.crud.threads"));
+
+        invalid.active_usecases.crud.threads = 1;
+        let warnings = invalid.validate().unwrap();
+        assert!(warnings
+            .iter()
+            .any(|warning| warning.contains("crud.replication_factor")));
+        assert!(warnings
+            .iter()
+            .any(|warning| warning.contains("crud.document_size")));
+
+        let mut pinned = invalid.clone();
+        pinned.runtime.strategy = RuntimeStrategy::Pinned;
+        pinned.runtime.first_core = usize::MAX / 2;
+        let Err(ConfigError::Invalid(errors)) = pinned.validate() else {
+            panic!("pinning beyond the CPUs accepted");
+        };
+        assert!(errors[0].contains("runtime.strategy pinned"));
+
+        let mut probe = invalid.clone();
+        probe.replication_lag =
+            Some(serde_yaml::from_str("{replication_factor: 1, confirmations: 0}").unwrap());
+        let Err(ConfigError::Invalid(errors)) = probe.validate() else {
+            panic!("replication lag probe without followers accepted");
+        };
+        assert_eq!(errors.len(), 2);
+        assert!(errors
+            .iter()
+            .any(|error| error.contains("replication_lag.replication_factor")));
+        probe.replication_lag =
+            Some(serde_yaml::from_str("{follower_endpoints: [\"http://dc2:8529\"]}").unwrap());
+        assert!(probe.validate().is_ok());
+
+        invalid.database.warmup_connections = 8;
+        invalid.database.active_failover = true;
+        invalid.database.auto_discover = true;
+        invalid.database.pool_max_idle_per_host = Some(4);
+        let warnings = invalid.validate().unwrap();
+        assert!(warnings
+            .iter()
+            .any(|warning| warning.contains("database.warmup_connections 8")));
+        assert!(warnings
+            .iter()
+            .any(|warning| warning.contains("database.auto_discover needs a cluster")));
+
+        let mut proxied = config.clone();
+        proxied.database.proxy = Some("socks5://localhost:1080".to_string());
+        proxied.database.protocol = Protocol::Vst;
+        let warnings = proxied.validate().unwrap();
+        assert!(warnings
+            .iter()
+            .any(|warning| warning.contains("database.proxy has no effect")));
+        proxied.database.proxy = Some("http://[proxy".to_string());
+        let Err(ConfigError::Invalid(errors)) = proxied.validate() else {
+            panic!("invalid proxy accepted");
+        };
+        assert!(errors[0].contains("database.proxy"));
+
+        let mut aql = config.clone();
+        aql.active_usecases.crud.on = true;
+        aql.crud.write_api = WriteApi::Aql;
+        aql.crud.number_of_documents = u32::MAX / 2 + 1;
+        let Err(ConfigError::Invalid(errors)) = aql.validate() else {
+            panic!("keys beyond u32 for AQL writes accepted");
+        };
+        assert!(errors
+            .iter()
+            .any(|error| error.contains("crud.number_of_documents")));
+
+        for max_queue_time in [0.0, -1.0, f64::NAN] {
+            let mut queued = config.clone();
+            queued.database.max_queue_time_seconds = Some(max_queue_time);
+            let Err(ConfigError::Invalid(errors)) = queued.validate() else {
+                panic!("max_queue_time_seconds {} accepted", max_queue_time);
+            };
+            assert!(errors[0].contains("database.max_queue_time_seconds"));
+        }
+
+        invalid.database.endpoints[0] = "unix:///tmp/arangod.sock".to_string();
+        let Err(ConfigError::Invalid(errors)) = invalid.validate() else {
+            panic!("mixed unix:// endpoints accepted");
+        };
+        assert!(errors
+            .iter()
+            .any(|error| error.contains("cannot mix unix://")));
+        assert!(errors.iter().any(|error| error.contains("auto_discover")));
+    }
+
+    #[test]
+    fn test_validate_collections() {
+        let mut config = Config::from_file("config.yaml").unwrap();
+        let collection: CrudCollectionConfig =
+            serde_yaml::from_str("{name: users, number_of_documents: 10}").unwrap();
+        config.crud.collections = vec![collection.clone(), collection];
+        config.crud.index_churn =
+            Some(serde_yaml::from_str("{index: {type: persistent, fields: [a1]}}").unwrap());
+        let Err(ConfigError::Invalid(errors)) = config.validate() else {
+            panic!("duplicate collections accepted");
+        };
+        assert!(errors.iter().any(|error| error.contains("users twice")));
+        assert!(errors
+            .iter()
+            .any(|error| error.contains("crud.index_churn.collection")));
+
+        let mut config = Config::from_file("config.yaml").unwrap();
+        config.active_usecases.crud.on = true;
+        config.crud.collections = serde_yaml::from_str(
+            "[{name: customers}, {name: orders, distribute_shards_like: customers}, \
+             {name: items, distribute_shards_like: orders}]",
+        )
+        .unwrap();
+        let Err(ConfigError::Invalid(errors)) = config.validate() else {
+            panic!("distribute_shards_like on a later collection accepted");
+        };
+        assert!(errors
+            .iter()
+            .any(|error| error.contains("items.distribute_shards_like")));
+        assert!(!errors
+            .iter()
+            .any(|error| error.contains("orders.distribute_shards_like")));
+    }
+
+    #[test]
+    fn test_document_size_distributions() {
+        let mut config = Config::from_file("config.yaml").unwrap();
+        config.crud.document_size = serde_yaml::from_str("500").unwrap();
+        assert_eq!(config.crud.document_size, DocumentSize::Fixed(500));
+        config.crud.document_size =
+            serde_yaml::from_str("{type: lognormal, mean: 2000, sigma: 1.0}").unwrap();
+        assert!(config.crud.document_size.min_size().is_none());
+        assert!(config.validate().is_ok());
+
+        config.crud.document_size =
+            serde_yaml::from_str("{type: uniform, min: 500, max: 100}").unwrap();
+        let Err(ConfigError::Invalid(errors)) = config.validate() else {
+            panic!("invalid document size accepted");
+        };
+        assert!(errors
+            .iter()
+            .any(|error| error.contains("crud.document_size")));
+    }
+
+    #[test]
+    fn test_database_names() {
+        let mut config = Config::from_file("config.yaml").unwrap();
+        config.database.run_id = Some("r1".to_string());
+        assert_eq!(config.database.database_name("crud"), "adb_loader_crud");
+        config.database.database_name = "{prefix}{usecase}_{run_id}".to_string();
+        assert_eq!(
+            config.database.numbered_database_name("crud", Some(2)),
+            "adb_loader_crud_2_r1"
+        );
+        config.database.database_name = "{prefix}db{i:03}".to_string();
+        assert_eq!(
+            config.database.numbered_database_name("crud", Some(2)),
+            "adb_loader_db002"
+        );
+        assert!(config.validate().is_ok());
+
+        config.database.database_name = "{usecase}".to_string();
+        config.crud.collection_name = "orders".to_string();
+        let Err(ConfigError::Invalid(errors)) = config.validate() else {
+            panic!("invalid templates accepted");
+        };
+        assert!(errors.iter().any(|e| e.contains("database.database_name")));
+        assert!(errors.iter().any(|e| e.contains("crud.collection_name")));
+    }
+
+    #[test]
+    fn test_password_is_not_shown() {
+        std::env::set_var("ADB_PASSWORD", "s3cr3t-from-env");
+        let config = Config::load("config.yaml", &[]);
+        std::env::remove_var("ADB_PASSWORD");
+        let config = config.unwrap();
+        assert_eq!(config.database.password, "s3cr3t-from-env");
+        let dumped = serde_yaml::to_string(&config.redacted()).unwrap();
+        assert!(!dumped.contains("s3cr3t-from-env"));
+
+        let mut other = config.clone();
+        other.database.password = "changed".to_string();
+        assert_eq!(other.fingerprint(), config.fingerprint());
+        assert!(config.restart_required(&other).is_empty());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let config = Config::load(
+            "config.yaml",
+            &[
+                "crud.number_of_documents=5".to_string(),
+                "database.endpoints.1=http://other:8529".to_string(),
+                "database.password=".to_string(),
+                "tracing.otlp_endpoint=http://localhost:4318".to_string(),
+                "active_usecases.graph.on=false".to_string(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.crud.number_of_documents, 5);
+        assert_eq!(config.database.endpoints[1], "http://other:8529");
+        assert_eq!(config.database.password, "");
+        assert_eq!(
+            config.tracing.unwrap().otlp_endpoint,
+            "http://localhost:4318"
+        );
+        assert!(!config.active_usecases.graph.on);
+
+        for invalid in [
+            "crud",
+            "crud.number_of_documents.x=1",
+            "database.endpoints.9=x",
+        ] {
+            assert!(matches!(
+                Config::load("config.yaml", &[invalid.to_string()]),
+                Err(ConfigError::Override(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_json_and_toml_formats() {
+        let yaml = Config::from_file("config.yaml").unwrap();
+        let dir = std::env::temp_dir().join(format!("adb_loader_formats_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let json = dir.join("config.JSON");
+        std::fs::write(&json, serde_json::to_string_pretty(&yaml).unwrap()).unwrap();
+        assert_eq!(
+            Config::load(&json, &["seed=1".to_string()]).unwrap().seed,
+            Some(1)
+        );
+        assert_eq!(
+            Config::from_file(&json).unwrap().fingerprint(),
+            yaml.fingerprint()
+        );
+
+        let toml = dir.join("config.toml");
+        std::fs::write(
+            &toml,
+            r#"
+version = "1"
+metrics_port = 7777
+comment = '''
+A configuration in TOML
+'''
+
+[database]
+endpoints = ["http://localhost:8529"]
+username = "root"
+password = ""
+prefix = "adb_loader_"
+
+[active_usecases]
+crud = { on = true, threads = 4 }
+graph = { on = false, threads = 4 }
+
+[crud]
+number_of_collections = 3
+number_of_shards = 3
+replication_factor = 1
+number_of_documents = 100_000
+document_size = 1000
+drop_first = false
+insert_concurrency = 10
+target_ops_per_sec = 50
+
+[[crud.indexes]]
+type = "persistent"
+fields = ["a1"]
+
+[graph]
+number_of_vertices = 1000
+number_of_edges = 1500
+number_of_shards = 3
+replication_factor = 1
+smart = false
+vertex_size = 200
+edge_size = 100
+drop_first = false
+"#,
+        )
+        .unwrap();
+        let config = Config::from_file(&toml).unwrap();
+        assert_eq!(config.crud.number_of_documents, 100_000);
+        assert_eq!(config.crud.target_ops_per_sec, 50.0);
+        assert_eq!(config.crud.indexes[0].fields, vec!["a1".to_string()]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_versions() {
+        let mut raw: serde_yaml::Value =
+            serde_yaml::from_str("{version: \"1\", crud: {load_concurrency: 7}}").unwrap();
+        assert!(migrate(&mut raw).unwrap());
+        assert_eq!(
+            raw,
+            serde_yaml::from_str::<serde_yaml::Value>("{version: \"2\", crud: {concurrency: 7}}")
+                .unwrap()
+        );
+        assert!(!migrate(&mut raw).unwrap());
+
+        let mut numeric: serde_yaml::Value = serde_yaml::from_str("{version: 2}").unwrap();
+        assert!(migrate(&mut numeric).unwrap());
+        assert_eq!(numeric["version"], serde_yaml::Value::from("2"));
+
+        for invalid in [
+            "{version: \"3\"}",
+            "{version: x}",
+            "{version: 0}",
+            "{crud: {}}",
+        ] {
+            let mut raw: serde_yaml::Value = serde_yaml::from_str(invalid).unwrap();
+            assert!(matches!(migrate(&mut raw), Err(ConfigError::Version(_))));
+        }
+
+        let contents = std::fs::read_to_string("config.yaml")
+            .unwrap()
+            .replacen("version: \"2\"", "version: \"1\"", 1)
+            .replacen("  concurrency: 10\n", "  load_concurrency: 12\n", 1);
+        assert_eq!(Config::from_yaml(&contents).unwrap().crud.concurrency, 12);
+    }
+
+    #[test]
+    fn test_index_definition() {
+        let index: IndexSpec = serde_yaml::from_str(
+            "{ type: ttl, fields: [created], name: expiry, options: { expireAfter: 60 } }",
+        )
+        .unwrap();
+        assert_eq!(
+            index.definition(),
+            serde_json::json!({
+                "type": "ttl",
+                "fields": ["created"],
+                "name": "expiry",
+                "expireAfter": 60
+            })
+        );
+    }
+}
@@ -0,0 +1,335 @@
+use crate::arangodb::{
+    collection_exists, create_collection, create_database, database_exists, drop_database,
+    run_query, ApiRequest, Connection,
+};
+use crate::config::{CollectionProperties, ConflictsConfig, DatabaseConfig, Phase, UseCaseConfig};
+use crate::control;
+use crate::metrics::METRICS;
+use crate::pacer::Pacer;
+use crate::usecase::{run_workers, send_observed, CollectionCount, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{error, info, warn};
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::header::{HeaderValue, IF_MATCH};
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "conflicts";
+
+/// The collection with the counter documents
+const COLLECTION: &str = "counters";
+
+/// The conflicts use case: all workers increment a counter attribute in a
+/// small set of documents with optimistic locking. Every increment reads
+/// the document and writes it back with `If-Match` on the revision read,
+/// a conflicting write fails with 412 and is retried. After the load, the
+/// sum of the counters must have grown by at least the number of
+/// acknowledged increments, otherwise updates were lost.
+pub struct ConflictsUseCase {
+    conflicts_config: ConflictsConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+/// Counts the outcome of the increments of a run
+#[derive(Default)]
+struct IncrementStats {
+    /// Acknowledged increments
+    increments: AtomicU64,
+    /// Writes rejected with 412
+    conflicts: AtomicU64,
+    /// Attempts after a conflict
+    retries: AtomicU64,
+    /// Increments given up after `max_retries` retries
+    failed: AtomicU64,
+}
+
+impl ConflictsUseCase {
+    pub fn new(
+        conflicts_config: ConflictsConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        ConflictsUseCase {
+            conflicts_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Conflicts use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let conflicts_config = &self.conflicts_config;
+        info!("Starting conflicts use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!("Number of shards: {}", conflicts_config.number_of_shards);
+        info!(
+            "Replication factor: {}",
+            conflicts_config.replication_factor
+        );
+        info!("Number of keys: {}", conflicts_config.number_of_keys);
+        info!("Maximal retries: {}", conflicts_config.max_retries);
+        info!("Concurrency: {}", conflicts_config.concurrency);
+        if conflicts_config.target_ops_per_sec > 0.0 {
+            info!(
+                "Target rate: {} increments/s",
+                conflicts_config.target_ops_per_sec
+            );
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the conflicts use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("conflicts")
+}
+
+/// Creates the database and the counter collection if needed and inserts
+/// the counter documents which do not exist yet, existing counters keep
+/// their value
+async fn initialize_database(conn: &Connection, conflicts_config: &ConflictsConfig) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    if database_exists(conn, &db_name).await? && conflicts_config.drop_first {
+        info!("Dropping database {}", db_name);
+        drop_database(conn, &db_name).await?;
+    }
+    if !database_exists(conn, &db_name).await? {
+        info!("Creating database {}", db_name);
+        create_database(conn, &db_name).await?;
+    }
+    if !collection_exists(conn, &db_name, COLLECTION).await? {
+        create_collection(
+            conn,
+            &db_name,
+            COLLECTION,
+            conflicts_config.number_of_shards,
+            conflicts_config.replication_factor,
+            &CollectionProperties::default(),
+        )
+        .await?;
+    }
+
+    let counters: Vec<Value> = (1..=conflicts_config.number_of_keys)
+        .map(|key| json!({"_key": format!("K{}", key), "counter": 0}))
+        .collect();
+    let request = ApiRequest::in_database(
+        Method::POST,
+        &db_name,
+        &format!("/_api/document/{}", COLLECTION),
+    )
+    .query("overwriteMode", "ignore")
+    .json(&counters);
+    send_observed(conn, USECASE, "seed", request, &[]).await?;
+    Ok(())
+}
+
+/// Returns the sum of all counters
+async fn counter_sum(conn: &Connection, db_name: &str) -> Result<u64> {
+    let mut bind_vars = Map::new();
+    bind_vars.insert("@collection".to_string(), json!(COLLECTION));
+    let mut sum = 0;
+    run_query(
+        conn,
+        db_name,
+        "FOR d IN @@collection COLLECT AGGREGATE total = SUM(d.counter) RETURN total",
+        &bind_vars,
+        1,
+        |batch| sum += batch.first().and_then(Value::as_u64).unwrap_or(0),
+    )
+    .await?;
+    Ok(sum)
+}
+
+/// Increments the counter of a random document: reads it and writes the
+/// incremented counter back on the condition that the revision has not
+/// changed. On a conflict, the increment starts over with a new read.
+async fn perform_increment(
+    conn: &Connection,
+    conflicts_config: &ConflictsConfig,
+    stats: &IncrementStats,
+    mut rng: StdRng,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+    let key = rng.random_range(1..=conflicts_config.number_of_keys);
+    let path = format!("/_api/document/{}/K{}", COLLECTION, key);
+
+    for attempt in 0..=conflicts_config.max_retries {
+        if attempt > 0 {
+            stats.retries.fetch_add(1, Ordering::Relaxed);
+        }
+        let request = ApiRequest::in_database(Method::GET, &db_name, &path);
+        let document: Value = send_observed(conn, USECASE, "read", request, &[])
+            .await?
+            .json()?;
+        let revision = document["_rev"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Counter K{} has no revision", key))?;
+        let counter = document["counter"].as_u64().unwrap_or(0);
+
+        let request = ApiRequest::in_database(Method::PATCH, &db_name, &path)
+            .header(
+                IF_MATCH,
+                HeaderValue::from_str(&format!("\"{}\"", revision))?,
+            )
+            .json(&json!({"counter": counter + 1}));
+        let response = send_observed(conn, USECASE, "update", request, &[412]).await?;
+        if response.status.as_u16() == 412 {
+            stats.conflicts.fetch_add(1, Ordering::Relaxed);
+            METRICS.conflicts.with_label_values(&[USECASE]).inc();
+            continue;
+        }
+        stats.increments.fetch_add(1, Ordering::Relaxed);
+        return Ok(());
+    }
+    stats.failed.fetch_add(1, Ordering::Relaxed);
+    Err(anyhow::anyhow!(
+        "Increment of counter K{} gave up after {} conflicts",
+        key,
+        conflicts_config.max_retries + 1
+    ))
+}
+
+#[async_trait]
+impl UseCase for ConflictsUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        if self.conflicts_config.number_of_keys == 0 {
+            return Err(anyhow::anyhow!(
+                "The conflicts use case needs at least one key"
+            ));
+        }
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize_database(&connection, &self.conflicts_config).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let db_name = database_name(&self.db_config);
+        let initial_sum = counter_sum(&connection, &db_name).await?;
+        let stats = Arc::new(IncrementStats::default());
+
+        let pacer = Arc::new(Pacer::new(self.conflicts_config.target_ops_per_sec));
+        let conflicts_config = Arc::new(RwLock::new(Arc::new(self.conflicts_config.clone())));
+        let reloaded = Arc::clone(&conflicts_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(conflicts) = &config.conflicts {
+                control::apply_load(USECASE, conflicts.concurrency, conflicts.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(conflicts.clone());
+            }
+        });
+        let worker_connection = connection.clone();
+        let worker_stats = Arc::clone(&stats);
+        run_workers(
+            USECASE,
+            self.conflicts_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = worker_connection.clone();
+                let conflicts_config = Arc::clone(&conflicts_config.read().unwrap());
+                let stats = Arc::clone(&worker_stats);
+                async move { perform_increment(&connection, &conflicts_config, &stats, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+
+        // An increment whose response got lost may still have been applied,
+        // so the counters may have grown by more than the acknowledged
+        // increments, but never by less
+        let increments = stats.increments.load(Ordering::Relaxed);
+        let applied = counter_sum(&connection, &db_name)
+            .await?
+            .saturating_sub(initial_sum);
+        info!(
+            "Conflicts: {} increments, {} conflicts, {} retries, {} given up, counters grew by {}",
+            increments,
+            stats.conflicts.load(Ordering::Relaxed),
+            stats.retries.load(Ordering::Relaxed),
+            stats.failed.load(Ordering::Relaxed),
+            applied
+        );
+        if applied < increments {
+            error!(
+                "Lost updates: {} acknowledged increments are missing",
+                increments - applied
+            );
+            return Err(anyhow::anyhow!(
+                "{} of {} acknowledged increments were lost",
+                increments - applied,
+                increments
+            ));
+        }
+        if applied > increments {
+            warn!(
+                "{} increments were applied without being acknowledged",
+                applied - increments
+            );
+        }
+        Ok(())
+    }
+
+    async fn verify_seed(&self) -> Result<Vec<CollectionCount>> {
+        let db_name = database_name(&self.db_config);
+        let expected = self.conflicts_config.number_of_keys as u64;
+        Ok(vec![
+            CollectionCount::fetch(&self.connection()?, &db_name, COLLECTION, expected).await?,
+        ])
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.conflicts_config.drop_on_exit {
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&self.connection()?, &db_name).await?;
+        }
+        Ok(())
+    }
+}
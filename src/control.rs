@@ -0,0 +1,269 @@
+use crate::config::Config;
+use crate::metrics::operation_stats;
+use crate::pacer::Pacer;
+use log::info;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use tokio::sync::watch;
+
+/// The adjustable state of the workers of a use case
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlState {
+    pub paused: bool,
+    pub concurrency: u32,
+}
+
+/// Controls the load of a running use case: its workers can be paused and
+/// resumed, their number and the target rate can be changed while they run.
+/// Workers watch the state and react before their next operation.
+pub struct UseCaseControl {
+    pacer: Arc<Pacer>,
+    state: watch::Sender<ControlState>,
+    completed: AtomicU64,
+}
+
+impl UseCaseControl {
+    pub fn new(concurrency: u32, pacer: Arc<Pacer>) -> Self {
+        UseCaseControl {
+            pacer,
+            state: watch::Sender::new(ControlState {
+                paused: false,
+                concurrency,
+            }),
+            completed: AtomicU64::new(0),
+        }
+    }
+
+    pub fn state(&self) -> ControlState {
+        *self.state.borrow()
+    }
+
+    /// Returns a receiver which is notified about every change
+    pub fn subscribe(&self) -> watch::Receiver<ControlState> {
+        self.state.subscribe()
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.state.send_modify(|state| state.paused = paused);
+    }
+
+    pub fn set_concurrency(&self, concurrency: u32) {
+        self.state
+            .send_modify(|state| state.concurrency = concurrency);
+    }
+
+    /// Changes the target rate in operations per second, 0 means unlimited
+    pub fn set_rate(&self, ops_per_sec: f64) {
+        self.pacer.set_rate(ops_per_sec);
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.pacer.rate()
+    }
+
+    /// Counts an operation which the workers completed successfully
+    pub fn record_completed(&self) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of operations the workers completed successfully, however
+    /// many requests each of them sent
+    pub fn completed(&self) -> u64 {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    /// The pacer shared by the workers
+    pub fn pacer(&self) -> &Pacer {
+        &self.pacer
+    }
+}
+
+/// The controls of all use cases whose workers are running
+static CONTROLS: LazyLock<Mutex<BTreeMap<String, Arc<UseCaseControl>>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Makes the control of a use case available through the control API
+pub fn register(usecase: &str, control: Arc<UseCaseControl>) {
+    CONTROLS
+        .lock()
+        .unwrap()
+        .insert(usecase.to_string(), control);
+}
+
+/// Removes the control of a use case whose workers have stopped
+pub fn unregister(usecase: &str) {
+    CONTROLS.lock().unwrap().remove(usecase);
+}
+
+/// Returns the control of a running use case
+pub fn get(usecase: &str) -> Option<Arc<UseCaseControl>> {
+    CONTROLS.lock().unwrap().get(usecase).cloned()
+}
+
+/// Returns the names and controls of all running use cases
+pub fn running() -> Vec<(String, Arc<UseCaseControl>)> {
+    CONTROLS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, control)| (name.clone(), Arc::clone(control)))
+        .collect()
+}
+
+/// Applies the dynamic parameters of a changed configuration to a running
+/// use case
+pub type Reloader = Arc<dyn Fn(&Config) + Send + Sync>;
+
+/// The reloaders of all use cases whose workers are running
+static RELOADERS: LazyLock<Mutex<BTreeMap<String, Reloader>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Registers the function which applies a changed configuration to a
+/// running use case, replacing an earlier one of the same use case
+pub fn on_reload<F>(usecase: &str, reloader: F)
+where
+    F: Fn(&Config) + Send + Sync + 'static,
+{
+    RELOADERS
+        .lock()
+        .unwrap()
+        .insert(usecase.to_string(), Arc::new(reloader));
+}
+
+/// Removes the reloader of a use case which has stopped
+pub fn remove_reloader(usecase: &str) {
+    RELOADERS.lock().unwrap().remove(usecase);
+}
+
+/// Applies a changed configuration to all running use cases. The caller
+/// is responsible for rejecting changes which need a restart, see
+/// `Config::restart_required`.
+pub fn reload(config: &Config) {
+    let reloaders = RELOADERS.lock().unwrap().clone();
+    for (usecase, reloader) in reloaders {
+        info!("Applying changed configuration to use case {}", usecase);
+        reloader(config);
+    }
+}
+
+/// Sets the target rate and concurrency of a running use case, used by
+/// the reloaders
+pub fn apply_load(usecase: &str, concurrency: u32, ops_per_sec: f64) {
+    if let Some(control) = get(usecase) {
+        control.set_concurrency(concurrency);
+        control.set_rate(ops_per_sec);
+    }
+}
+
+/// Describes the state and request statistics of a use case as JSON
+fn describe(usecase: &str, control: &UseCaseControl) -> Value {
+    let state = control.state();
+    let operations: Vec<Value> = operation_stats()
+        .into_iter()
+        .filter(|stats| stats.usecase == usecase)
+        .map(|stats| {
+            json!({
+                "operation": stats.operation,
+                "requests": stats.requests,
+                "errors": stats.errors,
+                "avg_latency_ms": stats.avg_latency_ms(),
+            })
+        })
+        .collect();
+    json!({
+        "name": usecase,
+        "paused": state.paused,
+        "concurrency": state.concurrency,
+        "target_ops_per_sec": control.rate(),
+        "operations": operations,
+    })
+}
+
+/// Returns the value of a query parameter
+fn parameter<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+fn error(status: &'static str, message: &str) -> (&'static str, Value) {
+    (status, json!({ "error": message }))
+}
+
+/// Answers a request to the control API, which is served next to the
+/// metrics. Returns the HTTP status line and a JSON body.
+///
+/// * `GET /usecases` - state and statistics of all running use cases
+/// * `GET /usecases/<name>` - the same for one use case
+/// * `POST /usecases/<name>/pause` and `POST /usecases/<name>/resume`
+/// * `POST /usecases/<name>/rate?ops_per_sec=<rate>` - 0 means unlimited
+/// * `POST /usecases/<name>/concurrency?workers=<n>`
+pub fn handle_request(method: &str, path: &str, query: &str) -> (&'static str, Value) {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    if segments == ["usecases"] && method == "GET" {
+        let controls = CONTROLS.lock().unwrap().clone();
+        let usecases: Vec<Value> = controls
+            .iter()
+            .map(|(name, control)| describe(name, control))
+            .collect();
+        return ("200 OK", json!(usecases));
+    }
+
+    let (name, action) = match segments.as_slice() {
+        ["usecases", name] => (*name, None),
+        ["usecases", name, action] => (*name, Some(*action)),
+        _ => return error("404 Not Found", "Unknown path"),
+    };
+    let Some(control) = get(name) else {
+        return error("404 Not Found", "Use case is not running");
+    };
+    match (method, action) {
+        ("GET", None) => {}
+        ("POST", Some("pause")) => control.set_paused(true),
+        ("POST", Some("resume")) => control.set_paused(false),
+        ("POST", Some("rate")) => {
+            match parameter(query, "ops_per_sec").and_then(|value| value.parse::<f64>().ok()) {
+                Some(rate) if rate >= 0.0 => control.set_rate(rate),
+                _ => return error("400 Bad Request", "Expected ops_per_sec=<rate>"),
+            }
+        }
+        ("POST", Some("concurrency")) => {
+            match parameter(query, "workers").and_then(|value| value.parse::<u32>().ok()) {
+                Some(workers) => control.set_concurrency(workers),
+                None => return error("400 Bad Request", "Expected workers=<n>"),
+            }
+        }
+        _ => return error("405 Method Not Allowed", "Unknown action"),
+    }
+    ("200 OK", describe(name, &control))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_requests() {
+        let control = Arc::new(UseCaseControl::new(4, Arc::new(Pacer::new(0.0))));
+        register("control_test", Arc::clone(&control));
+
+        let (status, _) = handle_request("POST", "/usecases/control_test/pause", "");
+        assert_eq!(status, "200 OK");
+        assert!(control.state().paused);
+
+        let (_, body) = handle_request("POST", "/usecases/control_test/rate", "ops_per_sec=4");
+        assert_eq!(body["target_ops_per_sec"], json!(4.0));
+        let (_, body) = handle_request("POST", "/usecases/control_test/concurrency", "workers=8");
+        assert_eq!(body["concurrency"], json!(8));
+
+        let (status, _) = handle_request("POST", "/usecases/control_test/rate", "ops_per_sec=x");
+        assert_eq!(status, "400 Bad Request");
+        unregister("control_test");
+        let (status, _) = handle_request("GET", "/usecases/control_test", "");
+        assert_eq!(status, "404 Not Found");
+    }
+}
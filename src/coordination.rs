@@ -0,0 +1,382 @@
+use crate::arangodb::{
+    create_collection, create_database, run_query, ApiRequest, ArangoError, Connection,
+};
+use crate::config::{CollectionProperties, Config, CoordinationConfig, DatabaseConfig};
+use crate::report::Report;
+use anyhow::Result;
+use log::info;
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name of the coordination database in the name template
+const DATABASE: &str = "coordination";
+
+/// The collections of the coordination database: the joined loaders, the
+/// arrivals at the barriers and the reports of the loaders
+const INSTANCES: &str = "instances";
+const BARRIERS: &str = "barriers";
+const REPORTS: &str = "reports";
+
+/// How often a waiting loader looks whether the others have arrived
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The coordinator of this loader, if it shares the run with others
+static COORDINATOR: OnceLock<Coordinator> = OnceLock::new();
+
+/// Makes the coordinator known to the use cases. Must be called before any
+/// use case starts, only the first call counts.
+pub fn set(coordinator: Coordinator) {
+    let _ = COORDINATOR.set(coordinator);
+}
+
+/// Returns the coordinator, if this loader shares the run with others
+pub fn current() -> Option<&'static Coordinator> {
+    COORDINATOR.get()
+}
+
+/// The place of this loader among the loaders of a run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slot {
+    /// Counting from 1
+    pub instance: u32,
+    pub instances: u32,
+}
+
+impl Slot {
+    /// The first loader sets up the data the others share
+    pub fn is_first(&self) -> bool {
+        self.instance == 1
+    }
+
+    /// Returns the part of the keys `1..=number_of_keys` which this loader
+    /// works on. The parts are contiguous and differ by at most one key in
+    /// size; with more loaders than keys, some parts are empty.
+    pub fn key_range(&self, number_of_keys: u32) -> RangeInclusive<u32> {
+        let instances = u64::from(self.instances.max(1));
+        let boundary = |instance: u64| (u64::from(number_of_keys) * instance / instances) as u32;
+        let index = u64::from(self.instance.clamp(1, self.instances.max(1))) - 1;
+        boundary(index) + 1..=boundary(index + 1)
+    }
+}
+
+/// Returns the key range of this loader, all keys without coordination
+pub fn key_range(number_of_keys: u32) -> RangeInclusive<u32> {
+    match current() {
+        Some(coordinator) => coordinator.slot.key_range(number_of_keys),
+        None => 1..=number_of_keys,
+    }
+}
+
+/// Returns the configuration of a loader which is not the first one: it
+/// must neither drop the data the first loader set up nor drop it on exit
+/// while the others still work with it
+///
+/// # Errors
+/// Fails if the changed configuration cannot be read back
+pub fn follower_config(config: &Config) -> Result<Config> {
+    let mut value = serde_json::to_value(config)?;
+    if let Some(sections) = value.as_object_mut() {
+        for section in sections.values_mut().filter_map(Value::as_object_mut) {
+            for field in ["drop_first", "drop_on_exit"] {
+                if let Some(flag) = section.get_mut(field) {
+                    *flag = Value::Bool(false);
+                }
+            }
+        }
+    }
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Synchronizes the loaders of a run through a database of the cluster
+/// under load. Every call opens its own connection, so that it can be
+/// used from the runtime of any use case.
+#[derive(Debug)]
+pub struct Coordinator {
+    db_config: DatabaseConfig,
+    config: CoordinationConfig,
+    db_name: String,
+    run_id: String,
+    slot: Slot,
+}
+
+impl Coordinator {
+    /// Joins the run of `database.run_id`: creates the coordination
+    /// database if this is the first loader and claims the configured
+    /// instance number or the lowest free one
+    ///
+    /// # Errors
+    /// Fails if the run has no ID, the database cannot be prepared, or all
+    /// instance numbers (or the configured one) are taken
+    pub async fn join(db_config: &DatabaseConfig, config: &CoordinationConfig) -> Result<Self> {
+        let run_id = db_config
+            .run_id
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Coordination needs a database.run_id"))?;
+        let db_name = db_config.database_name(DATABASE);
+        let conn = Connection::new(db_config).await?;
+        // All loaders start at about the same time, whoever comes first
+        // creates the database and the collections
+        match create_database(&conn, &db_name).await {
+            Ok(()) | Err(ArangoError::DatabaseExists(_)) => {}
+            Err(e) => return Err(e.into()),
+        }
+        for collection in [INSTANCES, BARRIERS, REPORTS] {
+            match create_collection(
+                &conn,
+                &db_name,
+                collection,
+                1,
+                1,
+                &CollectionProperties::default(),
+            )
+            .await
+            {
+                Ok(()) | Err(ArangoError::CollectionExists(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let candidates = match config.instance {
+            Some(instance) => instance..=instance,
+            None => 1..=config.instances,
+        };
+        // Tells our own claims from those of the other loaders: a POST
+        // which is retried after its response got lost conflicts with the
+        // document it already wrote
+        let claim = format!("{:016x}", rand::random::<u64>());
+        for instance in candidates {
+            let key = format!("{}_{}", run_id, instance);
+            let document = json!({
+                "_key": key,
+                "run_id": run_id,
+                "instance": instance,
+                "pid": std::process::id(),
+                "claim": claim,
+            });
+            if insert(&conn, &db_name, INSTANCES, &document).await?
+                || claimed_by(&conn, &db_name, &key, &claim).await?
+            {
+                let slot = Slot {
+                    instance,
+                    instances: config.instances,
+                };
+                info!(
+                    "Joined run {} as loader {} of {}",
+                    run_id, slot.instance, slot.instances
+                );
+                return Ok(Coordinator {
+                    db_config: db_config.clone(),
+                    config: config.clone(),
+                    db_name,
+                    run_id,
+                    slot,
+                });
+            }
+        }
+        Err(anyhow::anyhow!(
+            "Cannot join run {}, {} taken in database {}",
+            run_id,
+            match config.instance {
+                Some(instance) => format!("loader {} is", instance),
+                None => format!("all {} loaders are", config.instances),
+            },
+            db_name
+        ))
+    }
+
+    pub fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    /// Waits until all loaders have arrived at the barrier `name`, e.g.
+    /// `crud.seeded`. Returns early without error on shutdown.
+    ///
+    /// # Errors
+    /// Fails if the others do not arrive within `barrier_timeout_secs`
+    pub async fn barrier(&self, name: &str, shutdown: &CancellationToken) -> Result<()> {
+        let conn = Connection::new(&self.db_config).await?;
+        let document = json!({
+            "_key": format!("{}_{}_{}", self.run_id, name, self.slot.instance),
+            "run_id": self.run_id,
+            "barrier": name,
+            "instance": self.slot.instance,
+        });
+        // Arriving twice, e.g. after a restart of a use case, is fine
+        insert(&conn, &self.db_name, BARRIERS, &document).await?;
+        info!("Waiting for all loaders at barrier {}", name);
+        let start = Instant::now();
+        let timeout = Duration::from_secs(self.config.barrier_timeout_secs);
+        loop {
+            let arrived = self.count(&conn, BARRIERS, Some(name)).await?;
+            if arrived >= u64::from(self.slot.instances) {
+                info!("All loaders arrived at barrier {}", name);
+                return Ok(());
+            }
+            if start.elapsed() > timeout {
+                return Err(anyhow::anyhow!(
+                    "Only {} of {} loaders arrived at barrier {} within {:?}",
+                    arrived,
+                    self.slot.instances,
+                    name,
+                    timeout
+                ));
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = shutdown.cancelled() => return Ok(()),
+            }
+        }
+    }
+
+    /// Stores the report of this loader for the first one
+    ///
+    /// # Errors
+    /// Fails if the report cannot be written
+    pub async fn publish_report(&self, report: &Report) -> Result<()> {
+        let conn = Connection::new(&self.db_config).await?;
+        let document = json!({
+            "_key": format!("{}_{}", self.run_id, self.slot.instance),
+            "run_id": self.run_id,
+            "report": report,
+        });
+        insert(&conn, &self.db_name, REPORTS, &document).await?;
+        Ok(())
+    }
+
+    /// Waits for the reports of all loaders and returns them, with the
+    /// ones which arrived if the others do not within the barrier timeout
+    ///
+    /// # Errors
+    /// Fails if the reports cannot be read
+    pub async fn collect_reports(&self) -> Result<Vec<Report>> {
+        let conn = Connection::new(&self.db_config).await?;
+        let start = Instant::now();
+        let timeout = Duration::from_secs(self.config.barrier_timeout_secs);
+        while self.count(&conn, REPORTS, None).await? < u64::from(self.slot.instances)
+            && start.elapsed() < timeout
+        {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        let mut bind_vars = Map::new();
+        bind_vars.insert("run_id".to_string(), json!(self.run_id));
+        let mut reports = Vec::new();
+        let mut failed = None;
+        run_query(
+            &conn,
+            &self.db_name,
+            "FOR r IN reports FILTER r.run_id == @run_id SORT r._key RETURN r.report",
+            &bind_vars,
+            100,
+            |batch| {
+                for report in batch {
+                    match serde_json::from_value(report) {
+                        Ok(report) => reports.push(report),
+                        Err(e) => failed = Some(e),
+                    }
+                }
+            },
+        )
+        .await?;
+        if let Some(e) = failed {
+            return Err(anyhow::anyhow!("Invalid report of a loader: {}", e));
+        }
+        Ok(reports)
+    }
+
+    /// Counts the documents of this run in a collection, only those of
+    /// the given barrier for the barriers
+    async fn count(
+        &self,
+        conn: &Connection,
+        collection: &str,
+        barrier: Option<&str>,
+    ) -> Result<u64> {
+        let mut bind_vars = Map::new();
+        bind_vars.insert("@collection".to_string(), json!(collection));
+        bind_vars.insert("run_id".to_string(), json!(self.run_id));
+        bind_vars.insert("barrier".to_string(), json!(barrier));
+        let mut count = 0;
+        run_query(
+            conn,
+            &self.db_name,
+            "RETURN LENGTH(FOR d IN @@collection FILTER d.run_id == @run_id \
+             AND (@barrier == null OR d.barrier == @barrier) RETURN 1)",
+            &bind_vars,
+            1,
+            |batch| count = batch.first().and_then(Value::as_u64).unwrap_or_default(),
+        )
+        .await?;
+        Ok(count)
+    }
+}
+
+/// Inserts a document, returns false if one with its key exists already
+async fn insert(
+    conn: &Connection,
+    db_name: &str,
+    collection: &str,
+    document: &Value,
+) -> Result<bool, ArangoError> {
+    let request = ApiRequest::in_database(
+        Method::POST,
+        db_name,
+        &format!("/_api/document/{}", collection),
+    )
+    .json(document);
+    let response = conn.send(request).await?;
+    if response.status.as_u16() == 409 {
+        return Ok(false);
+    }
+    response.error_for_status("insert coordination document")?;
+    Ok(true)
+}
+
+/// Whether the instance document `key` carries our `claim`
+async fn claimed_by(
+    conn: &Connection,
+    db_name: &str,
+    key: &str,
+    claim: &str,
+) -> Result<bool, ArangoError> {
+    let request = ApiRequest::in_database(
+        Method::GET,
+        db_name,
+        &format!("/_api/document/{}/{}", INSTANCES, key),
+    );
+    let document: Value = conn
+        .send(request)
+        .await?
+        .error_for_status("read coordination document")?
+        .json()?;
+    Ok(document.get("claim").and_then(Value::as_str) == Some(claim))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_ranges_cover_all_keys_once() {
+        for (number_of_keys, instances) in [(1000, 3), (10, 4), (2, 3), (0, 2)] {
+            let mut keys = Vec::new();
+            for instance in 1..=instances {
+                let range = Slot {
+                    instance,
+                    instances,
+                }
+                .key_range(number_of_keys);
+                keys.extend(range);
+            }
+            assert_eq!(keys, (1..=number_of_keys).collect::<Vec<_>>());
+        }
+        let slot = Slot {
+            instance: 2,
+            instances: 3,
+        };
+        assert_eq!(slot.key_range(1000), 334..=666);
+    }
+}
@@ -1,322 +1,1913 @@
 use crate::arangodb::{
-    collection_exists, create_client, create_collection, create_database, database_exists,
-    drop_database,
+    collection_count, collection_exists, create_collection, create_collection_like,
+    create_database, create_index, database_exists, drop_database, drop_index, run_query,
+    ApiRequest, BodyChunks, Connection, VELOCYPACK,
 };
-use crate::config::{CrudConfig, DatabaseConfig, UseCaseConfig};
+use crate::config::{
+    BlobConfig, CrudConfig, DatabaseConfig, DatabaseDistribution, DocumentFormat, DocumentSchema,
+    DocumentSize, IndexChurnConfig, IndexSpec, OverwriteMode, Phase, SeedApi, UseCaseConfig,
+    WriteApi, WriteOptions,
+};
+use crate::control;
+use crate::coordination::{self, Coordinator};
+use crate::distribution::{sample_key, sample_size};
+use crate::documents::{generate_binary, generate_from_schema};
+use crate::error_policy;
+use crate::metrics::{observe_request, operation_stats, METRICS};
+use crate::pacer::Pacer;
+use crate::random::{self, stream_rng};
+use crate::usecase::{run_workers, send_observed, CollectionCount, UseCase};
+use crate::velocypack;
 use anyhow::Result;
-use futures::stream::{self, StreamExt};
-use log::info;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use log::{debug, error, info, log, warn, Level};
 use rand::distr::{Alphanumeric, SampleString};
-use rand::{rng, Rng};
-use serde_json::{json, Value};
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::Method;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::runtime::Builder;
+use std::io::{self, Write};
+use std::ops::{Range, RangeInclusive};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "crud";
+
+/// The content type of the request bodies with documents
+const JSON: &str = "application/json";
+
+/// The attribute holding the binary data of a document
+const BLOB_ATTRIBUTE: &str = "blob";
+
+/// Number of string attributes used to reach the desired document size
+pub(crate) const NUM_ATTRIBUTES: u32 = 5;
+
+/// Rough size of `_key`, `number` and `bool` of a generated document
+const FIXED_ATTRIBUTES_SIZE: u32 = 50;
 
-fn generate_random_ascii(length: usize) -> String {
-    // Uses thread_rng as the random number generator
-    // and Alphanumeric as the distribution of characters
-    Alphanumeric.sample_string(&mut rng(), length)
+/// The smallest document size for which every string attribute gets at
+/// least one character
+pub(crate) const MIN_DOCUMENT_SIZE: u32 = FIXED_ATTRIBUTES_SIZE + NUM_ATTRIBUTES;
+
+/// Size of the pool of random characters the string attributes of
+/// generated documents are cut from
+const CHARACTER_POOL_SIZE: usize = 1 << 20;
+
+/// Copying a random slice of this pool is much cheaper than drawing every
+/// character. With a global seed, the pool is the same in every run.
+static CHARACTER_POOL: LazyLock<String> =
+    LazyLock::new(|| generate_random_ascii(&mut stream_rng("characters", 0), CHARACTER_POOL_SIZE));
+
+pub(crate) fn generate_random_ascii<R: Rng>(rng: &mut R, length: usize) -> String {
+    // Uses Alphanumeric as the distribution of characters
+    Alphanumeric.sample_string(rng, length)
 }
 
-/// Runs the CRUD use case with the given configuration.
-/// Sets up a tokio runtime with the configured number of threads and executes the async code.
-pub fn run(
+/// The CRUD use case: seeds a number of collections with documents and then
+/// performs a configurable mix of document operations on them.
+pub struct CrudUseCase {
     crud_config: CrudConfig,
     db_config: DatabaseConfig,
     usecase_config: UseCaseConfig,
-) -> Result<()> {
-    info!("Starting CRUD use case with configuration:");
-    info!("Database endpoints: {:?}", db_config.endpoints);
-    info!("Database prefix: {}", db_config.prefix);
-    info!(
-        "Number of collections: {}",
-        crud_config.number_of_collections
-    );
-    info!("Number of shards: {}", crud_config.number_of_shards);
-    info!("Replication factor: {}", crud_config.replication_factor);
-    info!("Number of documents: {}", crud_config.number_of_documents);
-    info!("Document size: {}", crud_config.document_size);
-    info!("Drop first: {}", crud_config.drop_first);
-    info!("Number of threads: {}", usecase_config.threads);
+    connection: Option<Connection>,
+}
+
+impl CrudUseCase {
+    pub fn new(
+        crud_config: CrudConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        CrudUseCase {
+            crud_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("CRUD use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let crud_config = &self.crud_config;
+        let db_config = &self.db_config;
+        info!("Starting CRUD use case with configuration:");
+        info!("Database endpoints: {:?}", db_config.endpoints);
+        info!("Database prefix: {}", db_config.prefix);
+        info!(
+            "Number of databases: {} ({:?})",
+            crud_config.number_of_databases, crud_config.database_distribution
+        );
+        info!("Key distribution: {:?}", crud_config.key_distribution);
+        info!(
+            "Number of collections: {}",
+            crud_config.number_of_collections
+        );
+        info!("Number of shards: {}", crud_config.number_of_shards);
+        info!("Replication factor: {}", crud_config.replication_factor);
+        info!(
+            "Collection properties: {:?}",
+            crud_config.collection_properties
+        );
+        for index in &crud_config.indexes {
+            info!("Index: {:?}", index);
+        }
+        info!("Number of documents: {}", crud_config.number_of_documents);
+        match &crud_config.document_schema {
+            Some(schema) => info!("Document schema: {:?}", schema),
+            None => info!("Document size: {}", crud_config.document_size),
+        }
+        for collection in &crud_config.collections {
+            info!("Collection: {:?}", collection);
+        }
+        if let Some(blob) = &crud_config.blob {
+            info!("Blob size: {}", blob.size);
+        }
+        info!("Insert batch size: {}", crud_config.insert_batch_size);
+        info!("Seed API: {:?}", crud_config.seed_api);
+        info!("Document format: {:?}", crud_config.document_format);
+        if crud_config.stream_chunk_size > 0 {
+            info!("Stream chunk size: {}", crud_config.stream_chunk_size);
+        }
+        info!(
+            "Collection concurrency: {}",
+            crud_config.collection_concurrency
+        );
+        match (crud_config.verify, crud_config.verify_sample) {
+            (false, _) => info!("Verify: no"),
+            (true, None) => info!("Verify: all documents"),
+            (true, Some(sample)) => info!("Verify: {} documents per collection", sample),
+        }
+        info!("Drop first: {}", crud_config.drop_first);
+        info!("Drop on exit: {}", crud_config.drop_on_exit);
+        let mix: Vec<String> = Operation::ALL
+            .iter()
+            .filter(|op| op.weight(crud_config) > 0)
+            .map(|op| format!("{} {}%", op.name(), op.weight(crud_config)))
+            .collect();
+        info!("Operation mix: {}", mix.join(", "));
+        info!("Read batch size: {}", crud_config.read_batch_size);
+        info!("Read from followers: {}", crud_config.read_from_followers);
+        info!("Write options: {:?}", crud_config.write_options);
+        info!(
+            "Write API: {:?}, AQL match: {}%",
+            crud_config.write_api, crud_config.aql_match_pct
+        );
+        info!("Load concurrency: {}", crud_config.concurrency);
+        if crud_config.target_ops_per_sec > 0.0 {
+            info!("Target rate: {} ops/s", crud_config.target_ops_per_sec);
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
 
-    // Create a multi-threaded runtime with the configured number of threads
-    let runtime = Builder::new_multi_thread()
-        .worker_threads(usecase_config.threads as usize)
-        .enable_all()
-        .build()?;
+/// Calls `f` with random slices of the character pool which add up to
+/// `length` characters
+fn for_random_chars<R: Rng>(rng: &mut R, length: usize, mut f: impl FnMut(&'static str)) {
+    let mut remaining = length;
+    while remaining > 0 {
+        let take = remaining.min(CHARACTER_POOL_SIZE);
+        let start = rng.random_range(0..=CHARACTER_POOL_SIZE - take);
+        f(&CHARACTER_POOL[start..start + take]);
+        remaining -= take;
+    }
+}
 
-    // Run the async code
-    runtime.block_on(run_async(crud_config, db_config))
+/// Returns the size of each string attribute of a flat document
+fn attribute_size(target_size: u32, num_attributes: u32) -> usize {
+    // Subtract size of _key, number, and bool fields (rough estimate)
+    (target_size.saturating_sub(FIXED_ATTRIBUTES_SIZE) / num_attributes.max(1)) as usize
 }
 
 /// Generates a random document with the specified approximate size and number of attributes.
 /// The document will have a _key field, a number field, a bool field, and additional
 /// string attributes to reach the desired size.
-fn generate_document(key: u32, target_size: u32, num_attributes: u32) -> HashMap<String, Value> {
+pub(crate) fn generate_document<R: Rng>(
+    rng: &mut R,
+    key: u32,
+    target_size: u32,
+    num_attributes: u32,
+) -> HashMap<String, Value> {
     let mut doc = HashMap::new();
 
     // Add the _key field
     doc.insert("_key".to_string(), json!(format!("K{}", key)));
 
     // Add random number and boolean
-    let mut rng = rng();
     doc.insert("number".to_string(), json!(rng.random::<i32>()));
     doc.insert("bool".to_string(), json!(rng.random::<bool>()));
 
-    // Calculate approximate size per attribute
-    // Subtract size of _key, number, and bool fields (rough estimate)
-    let remaining_size = target_size.saturating_sub(50);
-    let size_per_attr = remaining_size / num_attributes;
-
     // Add string attributes
+    let size_per_attr = attribute_size(target_size, num_attributes);
     for i in 1..=num_attributes {
-        let attr_name = format!("a{}", i);
-        let attr_value = generate_random_ascii(size_per_attr as usize);
-        doc.insert(attr_name, json!(attr_value));
+        let mut attr_value = String::with_capacity(size_per_attr);
+        for_random_chars(rng, size_per_attr, |chars| attr_value.push_str(chars));
+        doc.insert(format!("a{}", i), json!(attr_value));
     }
 
     doc
 }
 
-/// Inserts documents into a collection in batches using concurrent requests
+/// The attributes of a flat document after `_key`, `number` and `bool`,
+/// rendered once for an attribute size. Only the values are overwritten
+/// with random characters for every document.
+struct DocumentTemplate {
+    attributes: Vec<u8>,
+    values: Vec<Range<usize>>,
+}
+
+impl DocumentTemplate {
+    fn new(size_per_attr: usize, num_attributes: u32) -> Self {
+        let mut attributes = Vec::new();
+        let mut values = Vec::new();
+        for i in 1..=num_attributes {
+            attributes.extend_from_slice(format!(",\"a{}\":\"", i).as_bytes());
+            let start = attributes.len();
+            attributes.resize(start + size_per_attr, b'x');
+            values.push(start..attributes.len());
+            attributes.push(b'"');
+        }
+        attributes.push(b'}');
+        DocumentTemplate { attributes, values }
+    }
+
+    /// Appends a document with the given key to `buf`, with the random
+    /// draws of `generate_document`
+    fn write<R: Rng>(&self, buf: &mut Vec<u8>, rng: &mut R, key: u32) -> io::Result<()> {
+        let number = rng.random::<i32>();
+        let flag = rng.random::<bool>();
+        write!(
+            buf,
+            "{{\"_key\":\"K{}\",\"number\":{},\"bool\":{}",
+            key, number, flag
+        )?;
+        let start = buf.len();
+        buf.extend_from_slice(&self.attributes);
+        // Alphanumeric characters need no escaping
+        for value in &self.values {
+            let mut at = start + value.start;
+            for_random_chars(rng, value.len(), |chars| {
+                buf[at..at + chars.len()].copy_from_slice(chars.as_bytes());
+                at += chars.len();
+            });
+        }
+        Ok(())
+    }
+}
+
+/// How many document templates a thread keeps. Sizes drawn from a wide
+/// distribution start over with an empty cache once it is full.
+const MAX_DOCUMENT_TEMPLATES: usize = 64;
+
+thread_local! {
+    /// The document templates of a thread by attribute size and count
+    static DOCUMENT_TEMPLATES: RefCell<HashMap<(usize, u32), DocumentTemplate>> =
+        RefCell::new(HashMap::new());
+
+    /// The buffer the request bodies of a thread are written into, cleared
+    /// and reused for every batch. No await happens while it is in use, so
+    /// a worker has it to itself.
+    static BATCH_BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Appends the document `generate_document` returns for the same random
+/// state to `buf` as JSON, from the template for its size
+pub(crate) fn write_document<R: Rng>(
+    buf: &mut Vec<u8>,
+    rng: &mut R,
+    key: u32,
+    target_size: u32,
+    num_attributes: u32,
+) -> io::Result<()> {
+    let size_per_attr = attribute_size(target_size, num_attributes);
+    DOCUMENT_TEMPLATES.with_borrow_mut(|templates| {
+        if templates.len() >= MAX_DOCUMENT_TEMPLATES
+            && !templates.contains_key(&(size_per_attr, num_attributes))
+        {
+            templates.clear();
+        }
+        templates
+            .entry((size_per_attr, num_attributes))
+            .or_insert_with(|| DocumentTemplate::new(size_per_attr, num_attributes))
+            .write(buf, rng, key)
+    })
+}
+
+/// Adds the attributes of a document like `write_document` does, with the
+/// same random draws, to a VelocyPack object
+fn build_document<R: Rng>(
+    document: &mut velocypack::Builder,
+    rng: &mut R,
+    key: u32,
+    target_size: u32,
+    num_attributes: u32,
+) {
+    let number = rng.random::<i32>();
+    let flag = rng.random::<bool>();
+    document.add_str_attribute("_key", &format!("K{}", key));
+    document.add_attribute("number", &Value::from(number));
+    document.add_attribute("bool", &Value::Bool(flag));
+    let size_per_attr = attribute_size(target_size, num_attributes);
+    let mut value = String::with_capacity(size_per_attr);
+    for i in 1..=num_attributes {
+        value.clear();
+        for_random_chars(rng, size_per_attr, |chars| value.push_str(chars));
+        document.add_str_attribute(&format!("a{}", i), &value);
+    }
+}
+
+/// The settings of one collection, from its entry in `collections` or,
+/// without such a list, from the `crud` section for `c1` to `c<n>`
+#[derive(Debug, Clone, PartialEq)]
+struct CollectionSettings {
+    name: String,
+    number_of_shards: u32,
+    replication_factor: u32,
+    number_of_documents: u32,
+    document_size: DocumentSize,
+    document_schema: Option<DocumentSchema>,
+    blob: Option<BlobConfig>,
+    format: DocumentFormat,
+    distribute_shards_like: Option<String>,
+}
+
+/// Returns the number of collections in every database
+fn number_of_collections(crud_config: &CrudConfig) -> u32 {
+    if crud_config.collections.is_empty() {
+        crud_config.number_of_collections
+    } else {
+        crud_config.collections.len() as u32
+    }
+}
+
+/// Returns the settings of the `index`-th collection (counting from 1).
+/// A collection with its own `document_size` does not use the schema of
+/// the `crud` section.
+fn collection_settings(crud_config: &CrudConfig, index: u32) -> CollectionSettings {
+    let defaults = CollectionSettings {
+        name: crud_config.numbered_collection_name(index),
+        number_of_shards: crud_config.number_of_shards,
+        replication_factor: crud_config.replication_factor,
+        number_of_documents: crud_config.number_of_documents,
+        document_size: crud_config.document_size,
+        document_schema: crud_config.document_schema.clone(),
+        blob: crud_config.blob,
+        format: crud_config.document_format,
+        distribute_shards_like: None,
+    };
+    let Some(collection) = crud_config.collections.get(index as usize - 1) else {
+        return defaults;
+    };
+    let (document_size, document_schema) =
+        match (&collection.document_schema, collection.document_size) {
+            (Some(schema), _) => (defaults.document_size, Some(schema.clone())),
+            (None, Some(size)) => (size, None),
+            (None, None) => (defaults.document_size, defaults.document_schema),
+        };
+    CollectionSettings {
+        name: collection.name.clone(),
+        number_of_shards: collection
+            .number_of_shards
+            .unwrap_or(defaults.number_of_shards),
+        replication_factor: collection
+            .replication_factor
+            .unwrap_or(defaults.replication_factor),
+        number_of_documents: collection
+            .number_of_documents
+            .unwrap_or(defaults.number_of_documents),
+        document_size,
+        document_schema,
+        blob: defaults.blob,
+        format: defaults.format,
+        distribute_shards_like: collection.distribute_shards_like.clone(),
+    }
+}
+
+/// Returns the settings of all collections of a database
+fn all_collections(crud_config: &CrudConfig) -> Vec<CollectionSettings> {
+    (1..=number_of_collections(crud_config))
+        .map(|index| collection_settings(crud_config, index))
+        .collect()
+}
+
+/// Appends the document with the given key to `buf` in the given format,
+/// according to the document schema if one is configured and with a size
+/// drawn from `document_size` otherwise, plus the `blob` attribute if
+/// configured, without recording its size. Both formats make the same
+/// random draws, so they yield the same document.
+fn render_document<R: Rng>(
+    buf: &mut Vec<u8>,
+    rng: &mut R,
+    key: u32,
+    collection: &CollectionSettings,
+    format: DocumentFormat,
+) -> io::Result<()> {
+    if format == DocumentFormat::Velocypack {
+        let mut document = velocypack::Builder::default();
+        match &collection.document_schema {
+            Some(schema) => {
+                if let Value::Object(attributes) = generate_from_schema(rng, key, schema) {
+                    for (name, value) in &attributes {
+                        document.add_attribute(name, value);
+                    }
+                }
+            }
+            None => {
+                let size = sample_size(&collection.document_size, rng);
+                build_document(&mut document, rng, key, size, NUM_ATTRIBUTES);
+            }
+        }
+        if let Some(blob) = collection.blob {
+            let size = sample_size(&blob.size, rng);
+            document.add_str_attribute(BLOB_ATTRIBUTE, &generate_binary(rng, size as usize));
+        }
+        buf.extend_from_slice(&document.finish_object());
+        return Ok(());
+    }
+    match &collection.document_schema {
+        Some(schema) => serde_json::to_writer(&mut *buf, &generate_from_schema(rng, key, schema))?,
+        None => {
+            let size = sample_size(&collection.document_size, rng);
+            write_document(buf, rng, key, size, NUM_ATTRIBUTES)?;
+        }
+    }
+    if let Some(blob) = collection.blob {
+        // Reopen the object to add the blob
+        buf.pop();
+        let size = sample_size(&blob.size, rng);
+        write!(
+            buf,
+            ",\"{}\":\"{}\"}}",
+            BLOB_ATTRIBUTE,
+            generate_binary(rng, size as usize)
+        )?;
+    }
+    Ok(())
+}
+
+/// Appends a document to send to the server to `buf` in the format of the
+/// collection and records its size
+fn append_document<R: Rng>(
+    buf: &mut Vec<u8>,
+    rng: &mut R,
+    key: u32,
+    collection: &CollectionSettings,
+) -> io::Result<()> {
+    let start = buf.len();
+    render_document(buf, rng, key, collection, collection.format)?;
+    METRICS
+        .document_size
+        .with_label_values(&[USECASE])
+        .observe((buf.len() - start) as f64);
+    Ok(())
+}
+
+/// Generates a document to send to the server as a JSON value, for the
+/// AQL writes which pass the documents as bind parameters
+fn make_document<R: Rng>(rng: &mut R, key: u32, collection: &CollectionSettings) -> Result<Value> {
+    let mut buf = Vec::new();
+    render_document(&mut buf, rng, key, collection, DocumentFormat::Json)?;
+    METRICS
+        .document_size
+        .with_label_values(&[USECASE])
+        .observe(buf.len() as f64);
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+/// How a batch of documents is laid out in a request body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BatchFormat {
+    /// A JSON array, for the document API
+    Array,
+    /// One document per line, for the import API
+    Lines,
+    /// A VelocyPack array, for the document API
+    Velocypack,
+}
+
+/// Returns the layout and content type of a batch of documents for the
+/// document API
+fn document_batch(format: DocumentFormat) -> (BatchFormat, &'static str) {
+    match format {
+        DocumentFormat::Json => (BatchFormat::Array, JSON),
+        DocumentFormat::Velocypack => (BatchFormat::Velocypack, VELOCYPACK),
+    }
+}
+
+/// Sets a body of keys or patches for the document API in the given
+/// format, which the response comes in as well
+fn document_body<T: Serialize + ?Sized>(
+    request: ApiRequest,
+    format: DocumentFormat,
+    body: &T,
+) -> ApiRequest {
+    match format {
+        DocumentFormat::Json => request.json(body),
+        DocumentFormat::Velocypack => request.velocypack(body),
+    }
+}
+
+/// Runs `f` with the cleared batch buffer of the thread
+fn with_batch_buffer<T>(f: impl FnOnce(&mut Vec<u8>) -> io::Result<T>) -> io::Result<T> {
+    BATCH_BUFFER.with_borrow_mut(|buf| {
+        buf.clear();
+        f(buf)
+    })
+}
+
+/// Writes the documents with the given keys into one request body, each
+/// appended by `append`. The documents are written into the batch buffer
+/// of the thread, which keeps its capacity from one batch to the next.
+fn write_batch(
+    keys: &[u32],
+    format: BatchFormat,
+    mut append: impl FnMut(&mut Vec<u8>, u32) -> io::Result<()>,
+) -> io::Result<Bytes> {
+    with_batch_buffer(|buf| {
+        if format == BatchFormat::Velocypack {
+            // The array header depends on the size of its items
+            let mut batch = velocypack::Builder::default();
+            for key in keys {
+                append(buf, *key)?;
+                batch.add_encoded_item(buf);
+                buf.clear();
+            }
+            return Ok(Bytes::from(batch.finish_array()));
+        }
+        write_text_batch(buf, keys, format, append)?;
+        Ok(Bytes::copy_from_slice(buf))
+    })
+}
+
+/// Appends a JSON array or lines of documents to `buf`
+fn write_text_batch(
+    buf: &mut Vec<u8>,
+    keys: &[u32],
+    format: BatchFormat,
+    mut append: impl FnMut(&mut Vec<u8>, u32) -> io::Result<()>,
+) -> io::Result<()> {
+    if format == BatchFormat::Array {
+        buf.push(b'[');
+    }
+    for (i, key) in keys.iter().enumerate() {
+        if i > 0 && format == BatchFormat::Array {
+            buf.push(b',');
+        }
+        append(buf, *key)?;
+        if format == BatchFormat::Lines {
+            buf.push(b'\n');
+        }
+    }
+    if format == BatchFormat::Array {
+        buf.push(b']');
+    }
+    Ok(())
+}
+
+/// The body of a seed batch, generated `chunk_size` documents at a time
+/// while it is sent
+struct SeedChunks {
+    collection: CollectionSettings,
+    keys: RangeInclusive<u32>,
+    chunk_size: u32,
+    format: BatchFormat,
+}
+
+impl BodyChunks for SeedChunks {
+    fn chunk(&self, index: usize) -> Option<io::Result<Bytes>> {
+        let first = u64::from(*self.keys.start()) + index as u64 * u64::from(self.chunk_size);
+        let last_key = *self.keys.end();
+        if first > u64::from(last_key) {
+            return None;
+        }
+        let first = first as u32;
+        let last = first.saturating_add(self.chunk_size - 1).min(last_key);
+        let keys: Vec<u32> = (first..=last).collect();
+        Some(with_batch_buffer(|buf| {
+            write_text_batch(buf, &keys, self.format, |buf, key| {
+                append_seed_document(buf, key, &self.collection)
+            })?;
+            // The chunks of an array continue the one before
+            if self.format == BatchFormat::Array {
+                if index > 0 {
+                    buf[0] = b',';
+                }
+                if last < last_key {
+                    buf.pop();
+                }
+            }
+            Ok(Bytes::copy_from_slice(buf))
+        }))
+    }
+}
+
+/// Returns the random generator of the document a collection is seeded
+/// with for the given key. With a global seed, this is the same document
+/// in every run.
+fn seed_rng(key: u32, collection: &CollectionSettings) -> StdRng {
+    stream_rng(&format!("{}/{}", USECASE, collection.name), u64::from(key))
+}
+
+/// Appends the document a collection is seeded with for the given key
+fn append_seed_document(
+    buf: &mut Vec<u8>,
+    key: u32,
+    collection: &CollectionSettings,
+) -> io::Result<()> {
+    append_document(buf, &mut seed_rng(key, collection), key, collection)
+}
+
+/// Returns the document a collection is seeded with for the given key, to
+/// compare it with the stored one
+fn expected_document(key: u32, collection: &CollectionSettings) -> Result<Value> {
+    let mut buf = Vec::new();
+    render_document(
+        &mut buf,
+        &mut seed_rng(key, collection),
+        key,
+        collection,
+        DocumentFormat::Json,
+    )?;
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+/// Returns the first key to insert into a collection which already holds
+/// `count` seeded documents. Batches are inserted concurrently and may
+/// finish out of order, so the last `insert_concurrency` batches before the
+/// count may be incomplete and are sent again. Returns a key beyond
+/// `number_of_documents` if the collection is complete.
+fn resume_key(count: u64, number_of_documents: u32, crud_config: &CrudConfig) -> u32 {
+    let num_documents = u64::from(number_of_documents);
+    if count >= num_documents {
+        return number_of_documents.saturating_add(1);
+    }
+    let batch_size = u64::from(crud_config.insert_batch_size.max(1));
+    let window = u64::from(crud_config.insert_concurrency.max(1)) * batch_size;
+    (count.saturating_sub(window) / batch_size * batch_size + 1) as u32
+}
+
+/// Inserts the documents with the given keys into a collection in batches
+/// using concurrent requests to the configured seed API. Every request
+/// holds one of the `permits`, which are shared by all collections seeded
+/// at the same time. When shutdown is requested, no new batches are
+/// started, so that only the last batches can be missing when seeding is
+/// resumed. Failed batches are skipped unless the error policy stops at the
+/// first error.
 async fn insert_documents(
-    client: &reqwest::Client,
-    db_config: &DatabaseConfig,
-    insert_concurrency: u32,
+    conn: &Connection,
+    crud_config: &CrudConfig,
     db_name: &str,
-    collection_name: &str,
-    num_documents: u32,
-    document_size: u32,
-    num_attributes: u32,
+    collection: &CollectionSettings,
+    keys: RangeInclusive<u32>,
+    permits: &Semaphore,
+    shutdown: &CancellationToken,
 ) -> anyhow::Result<()> {
-    const BATCH_SIZE: u32 = 1000;
-
-    // Create a shared client reference
-    let client = Arc::new(client.clone());
-    let endpoints: Arc<Vec<String>> = Arc::new(
-        db_config
-            .endpoints
-            .iter()
-            .map(|ep| format!("{}/_db/{}/_api/document/{}", ep, db_name, collection_name))
-            .collect(),
-    );
+    let batch_size = crud_config.insert_batch_size.max(1);
+    let last_key = *keys.end();
+    let collection_name = collection.name.as_str();
+    let path = format!("/_api/document/{}", collection_name);
+    let path = path.as_str();
 
     // Create a stream of batch ranges
-    let batches = (1..=num_documents)
-        .step_by(BATCH_SIZE as usize)
-        .map(|start| {
-            let end = (start + BATCH_SIZE - 1).min(num_documents);
-            (start, end)
-        });
+    let batches = keys.step_by(batch_size as usize).map(|start| {
+        let end = (start + batch_size - 1).min(last_key);
+        (start, end)
+    });
 
     // Process batches concurrently with a buffer of insert_concurrency:
     stream::iter(batches)
-        .map(|(batch_start, batch_end)| {
-            let client = Arc::clone(&client);
-            let endpoints = Arc::clone(&endpoints);
-
-            async move {
-                let mut batch = Vec::new();
-                for i in batch_start..=batch_end {
-                    let doc = generate_document(i, document_size, num_attributes);
-                    batch.push(doc);
+        .take_until(shutdown.cancelled())
+        .map(|(batch_start, batch_end)| async move {
+            let _permit = permits.acquire().await?;
+
+            // Batches larger than a chunk are generated while they are sent,
+            // except VelocyPack arrays, whose header needs all items
+            let with_documents = |request: ApiRequest,
+                                  format: BatchFormat,
+                                  content_type: &'static str|
+             -> io::Result<ApiRequest> {
+                let chunk_size = crud_config.stream_chunk_size;
+                if chunk_size > 0
+                    && batch_end - batch_start >= chunk_size
+                    && format != BatchFormat::Velocypack
+                {
+                    let chunks = SeedChunks {
+                        collection: collection.clone(),
+                        keys: batch_start..=batch_end,
+                        chunk_size,
+                        format,
+                    };
+                    return Ok(request.chunked_body(Arc::new(chunks), content_type));
                 }
+                let keys: Vec<u32> = (batch_start..=batch_end).collect();
+                let body = write_batch(&keys, format, |buf, key| {
+                    append_seed_document(buf, key, collection)
+                })?;
+                Ok(request.body(body, content_type))
+            };
 
-                let mut rng = rng();
-                let index = rng.random_range(0..endpoints.len());
-                let endpoint = &endpoints[index];
-                let response = client.post(endpoint).json(&batch).send().await?;
-
-                if !response.status().is_success() {
-                    let error_status = response.status();
-                    let error_text = response.text().await?;
-                    return Err(anyhow::anyhow!(
-                        "Failed to insert documents: {} - {}",
-                        error_status,
-                        error_text
-                    ));
+            // Documents which exist from an interrupted run are kept
+            let (operation, request) = match crud_config.seed_api {
+                SeedApi::Import => {
+                    // One document per line, which spares the coordinator
+                    // parsing one large JSON array
+                    let request = with_documents(
+                        ApiRequest::in_database(Method::POST, db_name, "/_api/import")
+                            .query("collection", collection_name)
+                            .query("type", "documents")
+                            .query("onDuplicate", "ignore"),
+                        BatchFormat::Lines,
+                        "application/x-ndjson",
+                    )?;
+                    ("import", request)
                 }
+                // A batch size of 1 sends single documents instead of
+                // arrays, which the coordinator handles differently
+                SeedApi::Document if batch_size == 1 => {
+                    let mut document = Vec::new();
+                    append_seed_document(&mut document, batch_start, collection)?;
+                    let (_, content_type) = document_batch(collection.format);
+                    let request = ApiRequest::in_database(Method::POST, db_name, path)
+                        .query("overwriteMode", "ignore")
+                        .body(document, content_type);
+                    ("insert", request)
+                }
+                SeedApi::Document => {
+                    let (format, content_type) = document_batch(collection.format);
+                    let request = with_documents(
+                        ApiRequest::in_database(Method::POST, db_name, path)
+                            .query("overwriteMode", "ignore"),
+                        format,
+                        content_type,
+                    )?;
+                    ("insert_batch", request)
+                }
+            };
+            let start = Instant::now();
+            if let Err(e) = send_observed(conn, USECASE, operation, request, &[]).await {
+                return error_policy::tolerate(e);
+            }
+            let latency = start.elapsed();
 
-                info!(
-                    "Inserted documents {} to {} into collection {}",
-                    batch_start, batch_end, collection_name
-                );
+            METRICS
+                .documents_inserted
+                .with_label_values(&[USECASE])
+                .inc_by(u64::from(batch_end - batch_start + 1));
+            // Logging every single document would flood the log
+            let level = if batch_size == 1 {
+                Level::Debug
+            } else {
+                Level::Info
+            };
+            log!(
+                level,
+                usecase = USECASE,
+                collection = collection_name,
+                batch_start = batch_start,
+                batch_end = batch_end,
+                latency_ms = latency.as_secs_f64() * 1000.0;
+                "Inserted documents {} to {} into collection {}",
+                batch_start,
+                batch_end,
+                collection_name
+            );
 
-                Ok::<_, anyhow::Error>(())
-            }
+            Ok::<_, anyhow::Error>(())
         })
-        .buffer_unordered(insert_concurrency as usize)
-        .collect::<Vec<anyhow::Result<()>>>()
-        .await
-        .into_iter()
-        .collect::<anyhow::Result<Vec<()>>>()?;
+        .buffer_unordered(crud_config.insert_concurrency as usize)
+        .try_collect::<Vec<()>>()
+        .await?;
 
     Ok(())
 }
 
-/// Initializes the database and collections according to the configuration.
-/// Returns true if the database already existed and had all collections.
-async fn initialize_database_and_collections(
-    client: &reqwest::Client,
-    db_config: &DatabaseConfig,
+/// Creates the database, or drops and recreates it with `drop_first`.
+/// Returns true if the database already existed and was kept.
+async fn prepare_database(
+    conn: &Connection,
     crud_config: &CrudConfig,
+    db_name: &str,
 ) -> anyhow::Result<bool> {
-    let db_name = format!("{}{}", db_config.prefix, "crud");
-
-    // First check if database exists
-    if database_exists(client, db_config, &db_name).await? {
-        // If not dropping first, check if all collections exist
-        if !crud_config.drop_first {
-            let mut all_collections_exist = true;
-            for i in 1..=crud_config.number_of_collections {
-                let coll_name = format!("c{}", i);
-                if !collection_exists(client, db_config, &db_name, &coll_name).await? {
-                    all_collections_exist = false;
-                    break;
-                }
-            }
+    let mut database_existed = database_exists(conn, db_name).await?;
+    if database_existed && crud_config.drop_first {
+        info!("Dropping database {}", db_name);
+        drop_database(conn, db_name).await?;
+        database_existed = false;
+    }
+    if !database_existed {
+        info!("Creating database {}", db_name);
+        create_database(conn, db_name).await?;
+    }
+    Ok(database_existed)
+}
 
-            if all_collections_exist {
-                // Everything exists, we're done
-                return Ok(true);
+/// Creates a collection and its indexes unless it exists. Returns the key
+/// at which its seeding starts or resumes, `None` if it is complete.
+async fn prepare_collection(
+    conn: &Connection,
+    crud_config: &CrudConfig,
+    db_name: &str,
+    collection: &CollectionSettings,
+    database_existed: bool,
+) -> anyhow::Result<Option<u32>> {
+    let coll_name = &collection.name;
+    let first_key = if database_existed && collection_exists(conn, db_name, coll_name).await? {
+        let count = collection_count(conn, db_name, coll_name).await?;
+        let first_key = resume_key(count, collection.number_of_documents, crud_config);
+        if first_key > collection.number_of_documents {
+            return Ok(None);
+        }
+        info!(
+            "Collection {} has {} documents, resuming seeding at key {}",
+            coll_name, count, first_key
+        );
+        first_key
+    } else {
+        let properties = &crud_config.collection_properties;
+        match &collection.distribute_shards_like {
+            Some(prototype) => {
+                create_collection_like(conn, db_name, coll_name, prototype, properties).await?
+            }
+            None => {
+                create_collection(
+                    conn,
+                    db_name,
+                    coll_name,
+                    collection.number_of_shards,
+                    collection.replication_factor,
+                    properties,
+                )
+                .await?
             }
         }
-
-        // Drop the database if we're either dropping first or collections don't exist
-        info!("Dropping database {}", db_name);
-        drop_database(client, db_config, &db_name).await?;
+        1
+    };
+    // Creating an index which exists already does nothing, so this also
+    // completes the indexes of a collection whose seeding was interrupted
+    for index in &crud_config.indexes {
+        create_timed_index(conn, db_name, coll_name, index).await?;
     }
+    Ok(Some(first_key))
+}
 
-    // At this point, either the database didn't exist or we just dropped it
-    // Create the database
-    info!("Creating database {}", db_name);
-    create_database(client, db_config, &db_name).await?;
+/// Initializes a database and its collections according to the configuration.
+/// Without `drop_first`, an existing database is kept, missing collections
+/// are created and collections with fewer documents than configured are
+/// filled up, so that an interrupted seeding resumes where it stopped.
+/// Returns true if the database already existed.
+async fn initialize_database_and_collections(
+    conn: &Connection,
+    crud_config: &CrudConfig,
+    db_name: &str,
+    shutdown: &CancellationToken,
+) -> anyhow::Result<bool> {
+    let database_existed = prepare_database(conn, crud_config, db_name).await?;
 
-    // Create all collections and insert documents
-    for i in 1..=crud_config.number_of_collections {
-        let coll_name = format!("c{}", i);
-        create_collection(
-            client,
-            db_config,
-            &db_name,
-            &coll_name,
-            crud_config.number_of_shards,
-            crud_config.replication_factor,
-        )
-        .await?;
+    // Collections with distribute_shards_like follow the first one, which
+    // therefore has to exist before the others are created
+    let collections = all_collections(crud_config);
+    let first_prepared = match collections.first() {
+        Some(first) => {
+            prepare_collection(conn, crud_config, db_name, first, database_existed).await?
+        }
+        None => None,
+    };
 
-        // Insert documents into the collection
-        // Use 5 attributes by default to reach the desired document size
-        insert_documents(
-            client,
-            db_config,
-            crud_config.insert_concurrency,
-            &db_name,
-            &coll_name,
-            crud_config.number_of_documents,
-            crud_config.document_size,
-            5,
-        )
+    // Create and fill up to collection_concurrency collections at the same
+    // time, with at most insert_concurrency requests in flight overall
+    let permits = Semaphore::new(crud_config.insert_concurrency.max(1) as usize);
+    let permits = &permits;
+    stream::iter(collections.into_iter().enumerate())
+        .take_until(shutdown.cancelled())
+        .map(|(i, collection)| async move {
+            let prepared = if i == 0 {
+                first_prepared
+            } else {
+                prepare_collection(conn, crud_config, db_name, &collection, database_existed)
+                    .await?
+            };
+            let Some(first_key) = prepared else {
+                return Ok::<_, anyhow::Error>(());
+            };
+            insert_documents(
+                conn,
+                crud_config,
+                db_name,
+                &collection,
+                first_key..=collection.number_of_documents,
+                permits,
+                shutdown,
+            )
+            .await
+        })
+        .buffer_unordered(crud_config.collection_concurrency.max(1) as usize)
+        .try_collect::<Vec<()>>()
         .await?;
+    if shutdown.is_cancelled() {
+        info!("Seeding of database {} interrupted by shutdown", db_name);
     }
 
-    Ok(false)
+    Ok(database_existed)
 }
 
-/// Performs a single load testing operation by replacing a random batch of documents
-async fn perform_load_operation(
-    client: &reqwest::Client,
+/// Sets up the databases together with the other loaders of a coordinated
+/// run: the first one creates the databases and collections, then every
+/// loader seeds its key range of every collection. Seeding does not
+/// resume, but documents which exist already are kept.
+async fn initialize_coordinated(
+    conn: &Connection,
     crud_config: &CrudConfig,
-    db_config: &DatabaseConfig,
+    db_names: &[String],
+    coordinator: &Coordinator,
+    shutdown: &CancellationToken,
 ) -> anyhow::Result<()> {
-    let mut batch = Vec::new();
-    let endpoint_nr: usize;
-    let batch_size;
-    let collection_name;
-    {
-        let mut rng = rng();
+    if coordinator.slot().is_first() {
+        for db_name in db_names {
+            let database_existed = prepare_database(conn, crud_config, db_name).await?;
+            for collection in all_collections(crud_config) {
+                prepare_collection(conn, crud_config, db_name, &collection, database_existed)
+                    .await?;
+            }
+        }
+    }
+    coordinator.barrier("crud.created", shutdown).await?;
+
+    let permits = Semaphore::new(crud_config.insert_concurrency.max(1) as usize);
+    let permits = &permits;
+    for db_name in db_names {
+        stream::iter(1..=number_of_collections(crud_config))
+            .take_until(shutdown.cancelled())
+            .map(|i| async move {
+                let collection = collection_settings(crud_config, i);
+                let keys = coordinator.slot().key_range(collection.number_of_documents);
+                insert_documents(
+                    conn,
+                    crud_config,
+                    db_name,
+                    &collection,
+                    keys,
+                    permits,
+                    shutdown,
+                )
+                .await
+            })
+            .buffer_unordered(crud_config.collection_concurrency.max(1) as usize)
+            .try_collect::<Vec<()>>()
+            .await?;
+    }
+    // The data is complete once every loader has seeded its part
+    coordinator.barrier("crud.seeded", shutdown).await
+}
+
+/// Creates an index and records how long it took to build, returns the id
+/// of the index
+async fn create_timed_index(
+    conn: &Connection,
+    db_name: &str,
+    coll_name: &str,
+    index: &IndexSpec,
+) -> Result<String> {
+    let start = Instant::now();
+    let id = create_index(conn, db_name, coll_name, &index.definition()).await?;
+    let duration = start.elapsed();
+    METRICS
+        .index_build_duration
+        .with_label_values(&[USECASE, index.index_type.as_str()])
+        .observe(duration.as_secs_f64());
+    info!(
+        "Created {} index on {} in database {} in {:.3}s",
+        index.index_type.as_str(),
+        coll_name,
+        db_name,
+        duration.as_secs_f64()
+    );
+    Ok(id)
+}
 
-        // Select a random collection
-        let collection_num = rng.random_range(1..=crud_config.number_of_collections);
-        collection_name = format!("c{}", collection_num);
+/// Number of requests and their total latency in seconds of all
+/// steady-state operations so far
+fn foreground_latency() -> (u64, f64) {
+    operation_stats()
+        .iter()
+        .filter(|stats| stats.usecase == USECASE)
+        .fold((0, 0.0), |(requests, total), stats| {
+            (requests + stats.requests, total + stats.total_latency_secs)
+        })
+}
 
-        // Generate random batch size between 10 and 50
-        batch_size = rng.random_range(10..=50);
+/// The average latency in milliseconds between two snapshots
+fn average_latency_ms(before: (u64, f64), after: (u64, f64)) -> Option<f64> {
+    let requests = after.0.saturating_sub(before.0);
+    (requests > 0).then(|| (after.1 - before.1) / requests as f64 * 1000.0)
+}
 
-        // Generate random document keys and create new documents
-        for _ in 0..batch_size {
-            let key_num = rng.random_range(1..=crud_config.number_of_documents);
-            let doc = generate_document(key_num, 100, 5); // Using fixed size for simplicity
-            batch.push(doc);
+/// Builds and drops an index periodically while the steady-state
+/// operations run, and compares the latency of the operations during the
+/// build with the one in the interval before
+async fn churn_index(
+    conn: Connection,
+    db_name: String,
+    churn: IndexChurnConfig,
+    shutdown: CancellationToken,
+) {
+    let index_type = churn.index.index_type.as_str();
+    loop {
+        let baseline_start = foreground_latency();
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(churn.interval_secs)) => {}
+            _ = shutdown.cancelled() => return,
+        }
+        let build_start = foreground_latency();
+        let id = match create_timed_index(&conn, &db_name, &churn.collection, &churn.index).await {
+            Ok(id) => id,
+            Err(e) => {
+                error!("Failed to build index on {}: {}", churn.collection, e);
+                continue;
+            }
+        };
+        let build_end = foreground_latency();
+
+        match (
+            average_latency_ms(baseline_start, build_start),
+            average_latency_ms(build_start, build_end),
+        ) {
+            (Some(before), Some(during)) if before > 0.0 => {
+                METRICS
+                    .index_build_latency_ratio
+                    .with_label_values(&[USECASE, index_type])
+                    .set(during / before);
+                info!(
+                    "Average CRUD latency during the {} index build: {:.2}ms, before: {:.2}ms ({:+.0}%)",
+                    index_type,
+                    during,
+                    before,
+                    (during / before - 1.0) * 100.0
+                );
+            }
+            _ => info!("Not enough CRUD operations to compare latencies around the index build"),
         }
 
-        // Select random endpoint
-        endpoint_nr = rng.random_range(0..db_config.endpoints.len());
+        if let Err(e) = drop_index(&conn, &db_name, &id).await {
+            error!("Failed to drop index {}: {}", id, e);
+        }
     }
-    let endpoint = format!(
-        "{}/_db/crud/_api/document/{}",
-        db_config.endpoints[endpoint_nr], collection_name
-    );
+}
+
+/// Maximal number of mismatching documents logged individually per collection
+const MAX_REPORTED_MISMATCHES: usize = 10;
+
+/// The outcome of verifying the documents of one collection
+#[derive(Debug, Default)]
+struct VerifyResult {
+    checked: u64,
+    missing: u64,
+    corrupted: u64,
+}
+
+/// Reads back documents of a collection and compares them with the
+/// documents regenerated from the global seed. Either all documents or a
+/// random sample of `verify_sample` documents are checked.
+async fn verify_collection(
+    conn: &Connection,
+    crud_config: &CrudConfig,
+    db_name: &str,
+    collection: &CollectionSettings,
+) -> Result<VerifyResult> {
+    let num_documents = collection.number_of_documents;
+    let collection_name = collection.name.as_str();
+    let keys: Vec<u32> = match crud_config.verify_sample {
+        Some(sample) => {
+            let mut rng = stream_rng(&format!("{}/verify/{}", USECASE, collection_name), 0);
+            (0..sample.min(num_documents))
+                .map(|_| rng.random_range(1..=num_documents))
+                .collect()
+        }
+        None => (1..=num_documents).collect(),
+    };
+    let path = format!("/_api/document/{}", collection_name);
 
-    // Send PUT request
-    let response = client.put(&endpoint).json(&batch).send().await?;
+    let mut result = VerifyResult::default();
+    let mut reported = 0;
+    for chunk in keys.chunks(crud_config.insert_batch_size.max(1) as usize) {
+        let doc_keys: Vec<String> = chunk.iter().map(|key| format!("K{}", key)).collect();
+        let request = document_body(
+            ApiRequest::in_database(Method::PUT, db_name, &path).query("onlyget", true),
+            collection.format,
+            &doc_keys,
+        );
+        let response = send_observed(conn, USECASE, "verify", request, &[]).await?;
+        let actual: Vec<Value> = response.json()?;
 
-    if !response.status().is_success() {
-        let error_status = response.status();
-        let error_text = response.text().await?;
+        for (key, actual) in chunk.iter().zip(actual.iter()) {
+            result.checked += 1;
+            let expected = expected_document(*key, collection)?;
+            let problem = if actual.get("error").and_then(Value::as_bool) == Some(true) {
+                result.missing += 1;
+                "missing"
+            } else if expected
+                .as_object()
+                .is_some_and(|expected| expected.iter().any(|(k, v)| actual.get(k) != Some(v)))
+            {
+                result.corrupted += 1;
+                "corrupted"
+            } else {
+                continue;
+            };
+            if reported < MAX_REPORTED_MISMATCHES {
+                error!(
+                    "Document K{} in collection {} is {}",
+                    key, collection_name, problem
+                );
+                reported += 1;
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Verifies the seeded documents of all collections, fails if any document
+/// is missing or differs from the expected content
+async fn verify_documents(
+    conn: &Connection,
+    crud_config: &CrudConfig,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    if random::seed().is_none() {
+        warn!("Skipping verification, it needs a global seed to regenerate the documents");
+        return Ok(());
+    }
+    let mut failed = 0;
+    for db_name in database_names(conn.config(), crud_config) {
+        for collection in all_collections(crud_config) {
+            if shutdown.is_cancelled() {
+                break;
+            }
+            let result = verify_collection(conn, crud_config, &db_name, &collection).await?;
+            info!(
+                "Verified {} documents in collection {}/{}: {} missing, {} corrupted",
+                result.checked, db_name, collection.name, result.missing, result.corrupted
+            );
+            failed += result.missing + result.corrupted;
+        }
+    }
+    if failed > 0 {
         return Err(anyhow::anyhow!(
-            "Failed to replace documents: {} - {}",
-            error_status,
-            error_text
+            "Verification found {} missing or corrupted documents",
+            failed
         ));
     }
+    Ok(())
+}
 
-    info!(
-        "Replaced {} documents in collection {}",
-        batch_size, collection_name
-    );
+/// The operations the steady-state phase chooses from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operation {
+    Insert,
+    Read,
+    Update,
+    Replace,
+    Delete,
+    DeleteInsert,
+    ReadBlob,
+}
+
+impl Operation {
+    const ALL: [Operation; 7] = [
+        Operation::Insert,
+        Operation::Read,
+        Operation::Update,
+        Operation::Replace,
+        Operation::Delete,
+        Operation::DeleteInsert,
+        Operation::ReadBlob,
+    ];
+
+    /// The name used in logs and as the `operation` metrics label
+    fn name(self) -> &'static str {
+        match self {
+            Operation::Insert => "insert",
+            Operation::Read => "read",
+            Operation::Update => "update",
+            Operation::Replace => "replace",
+            Operation::Delete => "delete",
+            Operation::DeleteInsert => "delete_insert",
+            Operation::ReadBlob => "read_blob",
+        }
+    }
 
+    fn is_read(self) -> bool {
+        matches!(self, Operation::Read | Operation::ReadBlob)
+    }
+
+    /// The relative weight of this operation according to the configuration.
+    /// Plain inserts, deletes and blob reads are only available in
+    /// `operation_mix`.
+    fn weight(self, crud_config: &CrudConfig) -> u32 {
+        match (&crud_config.operation_mix, self) {
+            (Some(mix), Operation::Insert) => mix.insert,
+            (Some(mix), Operation::Read) => mix.read,
+            (Some(mix), Operation::Update) => mix.update,
+            (Some(mix), Operation::Replace) => mix.replace,
+            (Some(mix), Operation::Delete) => mix.delete,
+            (Some(mix), Operation::DeleteInsert) => mix.delete_insert,
+            (Some(mix), Operation::ReadBlob) => mix.read_blob,
+            (None, Operation::Read) => crud_config.read_pct,
+            (None, Operation::Update) => crud_config.update_pct,
+            (None, Operation::Replace) => crud_config.replace_pct,
+            (None, Operation::DeleteInsert) => crud_config.delete_insert_pct,
+            (None, Operation::Insert | Operation::Delete | Operation::ReadBlob) => 0,
+        }
+    }
+}
+
+/// Checks that the operation mix is usable: `operation_mix` must add up
+/// to 100, the individual percentages must not all be 0
+fn check_operation_mix(crud_config: &CrudConfig) -> Result<()> {
+    if let Some(mix) = &crud_config.operation_mix {
+        if mix.total() != 100 {
+            return Err(anyhow::anyhow!(
+                "The CRUD operation_mix adds up to {} instead of 100",
+                mix.total()
+            ));
+        }
+    }
+    if Operation::ALL.iter().all(|op| op.weight(crud_config) == 0) {
+        return Err(anyhow::anyhow!(
+            "The CRUD operation mix is empty, at least one percentage must be positive"
+        ));
+    }
     Ok(())
 }
 
-/// The actual async implementation of the CRUD use case.
-async fn run_async(crud_config: CrudConfig, db_config: DatabaseConfig) -> anyhow::Result<()> {
-    let client = create_client().await;
+/// Picks a random operation according to the configured operation mix.
+/// The percentages are used as weights, so they need not add up to 100.
+fn choose_operation<R: Rng>(crud_config: &CrudConfig, rng: &mut R) -> Operation {
+    let total: u32 = Operation::ALL.iter().map(|op| op.weight(crud_config)).sum();
+    let mut pick = rng.random_range(0..total);
+    for op in Operation::ALL {
+        let weight = op.weight(crud_config);
+        if pick < weight {
+            return op;
+        }
+        pick -= weight;
+    }
+    Operation::Read
+}
 
-    let database_existed =
-        initialize_database_and_collections(&client, &db_config, &crud_config).await?;
-    info!(
-        "Database initialization complete. Database existed: {}",
-        database_existed
+/// Returns the name of the `index`-th database (counting from 1) used by
+/// the CRUD use case. With a single database, it has no number.
+fn database_name(db_config: &DatabaseConfig, crud_config: &CrudConfig, index: u32) -> String {
+    if crud_config.number_of_databases <= 1 {
+        db_config.database_name(USECASE)
+    } else {
+        db_config.numbered_database_name(USECASE, Some(index))
+    }
+}
+
+/// Returns the names of all databases used by the CRUD use case
+fn database_names(db_config: &DatabaseConfig, crud_config: &CrudConfig) -> Vec<String> {
+    (1..=crud_config.number_of_databases.max(1))
+        .map(|index| database_name(db_config, crud_config, index))
+        .collect()
+}
+
+/// Picks the database for an operation according to the configured
+/// distribution, returns its index counting from 1
+fn choose_database<R: Rng>(crud_config: &CrudConfig, rng: &mut R) -> u32 {
+    let number_of_databases = crud_config.number_of_databases.max(1);
+    match &crud_config.database_distribution {
+        DatabaseDistribution::Uniform => rng.random_range(1..=number_of_databases),
+        DatabaseDistribution::Weighted { weights } => {
+            let total: u32 = weights.iter().sum();
+            let mut pick = rng.random_range(0..total.max(1));
+            for (index, weight) in weights.iter().enumerate() {
+                if pick < *weight {
+                    return index as u32 + 1;
+                }
+                pick -= weight;
+            }
+            1
+        }
+    }
+}
+
+/// Adds the configured write options to a document API write. Only inserts
+/// pass an `overwrite_mode`.
+fn with_write_options(
+    request: ApiRequest,
+    options: &WriteOptions,
+    overwrite_mode: Option<OverwriteMode>,
+) -> ApiRequest {
+    let mut request = request;
+    if let Some(wait_for_sync) = options.wait_for_sync {
+        request = request.query("waitForSync", wait_for_sync);
+    }
+    if let Some(mode) = overwrite_mode {
+        request = request.query("overwriteMode", mode.as_str());
+    }
+    for (name, set) in [
+        ("silent", options.silent),
+        ("returnNew", options.return_new),
+        ("returnOld", options.return_old),
+    ] {
+        if set {
+            request = request.query(name, true);
+        }
+    }
+    request
+}
+
+/// Performs a write operation on a batch of documents with AQL modification
+/// statements instead of the document API. Updates and replaces only match
+/// an existing document for `aql_match_pct` percent of the keys, the other
+/// keys are moved beyond the seeded key range: updates of those are ignored,
+/// replaces insert them with `UPSERT`.
+async fn perform_aql_write(
+    conn: &Connection,
+    crud_config: &CrudConfig,
+    db_name: &str,
+    collection: &CollectionSettings,
+    operation: Operation,
+    keys: &[u32],
+    rng: &mut StdRng,
+) -> Result<()> {
+    let keys: Vec<u32> = match operation {
+        Operation::Update | Operation::Replace => keys
+            .iter()
+            .map(|key| {
+                if rng.random_range(0..100) < crud_config.aql_match_pct {
+                    *key
+                } else {
+                    key.saturating_add(collection.number_of_documents)
+                }
+            })
+            .collect(),
+        _ => keys.to_vec(),
+    };
+    let documents: Vec<Value> = match operation {
+        Operation::Update => keys
+            .iter()
+            .map(|key| json!({"_key": format!("K{}", key), "number": rng.random::<i32>()}))
+            .collect(),
+        Operation::Delete => keys.iter().map(|key| json!(format!("K{}", key))).collect(),
+        _ => keys
+            .iter()
+            .map(|key| make_document(rng, *key, collection))
+            .collect::<Result<_>>()?,
+    };
+    let queries: &[&str] = match operation {
+        Operation::Insert => {
+            &["FOR doc IN @docs INSERT doc INTO @@collection OPTIONS { ignoreErrors: true }"]
+        }
+        Operation::Update => {
+            &["FOR doc IN @docs UPDATE doc IN @@collection OPTIONS { ignoreErrors: true }"]
+        }
+        Operation::Replace => {
+            &["FOR doc IN @docs UPSERT { _key: doc._key } INSERT doc REPLACE doc IN @@collection"]
+        }
+        Operation::Delete => {
+            &["FOR key IN @docs REMOVE key IN @@collection OPTIONS { ignoreErrors: true }"]
+        }
+        Operation::DeleteInsert => &[
+            "FOR doc IN @docs REMOVE doc._key IN @@collection OPTIONS { ignoreErrors: true }",
+            "FOR doc IN @docs INSERT doc INTO @@collection OPTIONS { ignoreErrors: true }",
+        ],
+        Operation::Read | Operation::ReadBlob => unreachable!("reads are not writes"),
+    };
+
+    let mut bind_vars = Map::new();
+    bind_vars.insert("@collection".to_string(), json!(collection.name));
+    bind_vars.insert("docs".to_string(), Value::Array(documents));
+    for query in queries {
+        let start = Instant::now();
+        let result = run_query(conn, db_name, query, &bind_vars, 1000, |_| {}).await;
+        observe_request(USECASE, operation.name(), start.elapsed(), result.is_ok());
+        result.map_err(|e| anyhow::anyhow!("AQL {} failed: {}", operation.name(), e))?;
+    }
+    Ok(())
+}
+
+/// Performs a single load testing operation on a random batch of documents.
+/// Which operation is performed is chosen according to the operation mix.
+async fn perform_load_operation(
+    conn: &Connection,
+    crud_config: &CrudConfig,
+    mut rng: StdRng,
+) -> anyhow::Result<()> {
+    let operation = choose_operation(crud_config, &mut rng);
+
+    // Select a random database and collection
+    let db_name = database_name(
+        conn.config(),
+        crud_config,
+        choose_database(crud_config, &mut rng),
+    );
+    let collection = collection_settings(
+        crud_config,
+        rng.random_range(1..=number_of_collections(crud_config)),
+    );
+    let collection_name = &collection.name;
+
+    // Reads fetch `read_batch_size` documents, all other operations work on
+    // a random batch of between 10 and 50 documents
+    let batch_size = match operation {
+        Operation::Read | Operation::ReadBlob => crud_config.read_batch_size.max(1),
+        _ => rng.random_range(10..=50),
+    };
+    // In a coordinated run every loader works on its own part of the keys
+    let key_range = coordination::key_range(collection.number_of_documents);
+    let first_key = *key_range.start();
+    let number_of_keys = (key_range.end() + 1).saturating_sub(first_key);
+    let keys: Vec<u32> = (0..batch_size)
+        .map(|_| {
+            first_key - 1 + sample_key(&crud_config.key_distribution, number_of_keys, &mut rng)
+        })
+        .collect();
+    let path = format!("/_api/document/{}", collection_name);
+    let options = &crud_config.write_options;
+    let (batch_format, content_type) = document_batch(collection.format);
+
+    match operation {
+        _ if !operation.is_read() && crud_config.write_api == WriteApi::Aql => {
+            perform_aql_write(
+                conn,
+                crud_config,
+                &db_name,
+                &collection,
+                operation,
+                &keys,
+                &mut rng,
+            )
+            .await?;
+        }
+        Operation::Insert => {
+            // Keys which exist already are skipped, so inserts refill the
+            // key space emptied by deletes
+            let batch = write_batch(&keys, batch_format, |buf, key| {
+                append_document(buf, &mut rng, key, &collection)
+            })?;
+            let request = with_write_options(
+                ApiRequest::in_database(Method::POST, &db_name, &path).body(batch, content_type),
+                options,
+                Some(options.overwrite_mode.unwrap_or(OverwriteMode::Ignore)),
+            );
+            send_observed(conn, USECASE, operation.name(), request, &[]).await?;
+        }
+        Operation::Delete => {
+            // Keys which were deleted already are reported per document in
+            // the response, which is not an error
+            let doc_keys: Vec<String> = keys.iter().map(|key| format!("K{}", key)).collect();
+            let request = with_write_options(
+                document_body(
+                    ApiRequest::in_database(Method::DELETE, &db_name, &path),
+                    collection.format,
+                    &doc_keys,
+                ),
+                options,
+                None,
+            );
+            send_observed(conn, USECASE, operation.name(), request, &[]).await?;
+        }
+        Operation::Read => {
+            let request = if keys.len() == 1 {
+                let request = ApiRequest::in_database(
+                    Method::GET,
+                    &db_name,
+                    &format!("{}/K{}", path, keys[0]),
+                );
+                match collection.format {
+                    DocumentFormat::Json => request,
+                    DocumentFormat::Velocypack => request.accept_velocypack(),
+                }
+            } else {
+                let doc_keys: Vec<String> = keys.iter().map(|key| format!("K{}", key)).collect();
+                document_body(
+                    ApiRequest::in_database(Method::PUT, &db_name, &path).query("onlyget", "true"),
+                    collection.format,
+                    &doc_keys,
+                )
+            };
+            let request = if crud_config.read_from_followers {
+                request.allow_dirty_read()
+            } else {
+                request
+            };
+            // A read may race with a delete/insert of the same key, so a
+            // missing document is not an error. Batch reads report missing
+            // documents per document in the response.
+            let response = send_observed(conn, USECASE, operation.name(), request, &[404]).await?;
+            if response.is_dirty_read() {
+                METRICS
+                    .follower_reads
+                    .with_label_values(&[USECASE, operation.name()])
+                    .inc();
+            }
+        }
+        Operation::ReadBlob => {
+            // The blobs come back one per cursor batch, so that large ones
+            // are transferred in several responses
+            let doc_keys: Vec<String> = keys.iter().map(|key| format!("K{}", key)).collect();
+            let mut bind_vars = Map::new();
+            bind_vars.insert("@collection".to_string(), json!(collection_name));
+            bind_vars.insert("keys".to_string(), json!(doc_keys));
+            bind_vars.insert("attribute".to_string(), json!(BLOB_ATTRIBUTE));
+            let start = Instant::now();
+            let result = run_query(
+                conn,
+                &db_name,
+                "FOR doc IN @@collection FILTER doc._key IN @keys RETURN doc.@attribute",
+                &bind_vars,
+                1,
+                |_| {},
+            )
+            .await;
+            observe_request(USECASE, operation.name(), start.elapsed(), result.is_ok());
+            result.map_err(|e| anyhow::anyhow!("Reading blobs failed: {}", e))?;
+        }
+        Operation::Update => {
+            let patches: Vec<Value> = keys
+                .iter()
+                .map(|key| json!({"_key": format!("K{}", key), "number": rng.random::<i32>()}))
+                .collect();
+            let request = with_write_options(
+                document_body(
+                    ApiRequest::in_database(Method::PATCH, &db_name, &path),
+                    collection.format,
+                    &patches,
+                ),
+                options,
+                None,
+            );
+            send_observed(conn, USECASE, operation.name(), request, &[]).await?;
+        }
+        Operation::Replace => {
+            let batch = write_batch(&keys, batch_format, |buf, key| {
+                append_document(buf, &mut rng, key, &collection)
+            })?;
+            let request = with_write_options(
+                ApiRequest::in_database(Method::PUT, &db_name, &path).body(batch, content_type),
+                options,
+                None,
+            );
+            send_observed(conn, USECASE, operation.name(), request, &[]).await?;
+        }
+        Operation::DeleteInsert => {
+            // Delete the documents and insert them again right away, so that
+            // the number of documents stays approximately constant:
+            let doc_keys: Vec<String> = keys.iter().map(|key| format!("K{}", key)).collect();
+            let batch = write_batch(&keys, batch_format, |buf, key| {
+                append_document(buf, &mut rng, key, &collection)
+            })?;
+            let request = with_write_options(
+                document_body(
+                    ApiRequest::in_database(Method::DELETE, &db_name, &path),
+                    collection.format,
+                    &doc_keys,
+                ),
+                options,
+                None,
+            );
+            send_observed(conn, USECASE, operation.name(), request, &[]).await?;
+            let request = with_write_options(
+                ApiRequest::in_database(Method::POST, &db_name, &path).body(batch, content_type),
+                options,
+                options.overwrite_mode,
+            );
+            send_observed(conn, USECASE, operation.name(), request, &[]).await?;
+        }
+    }
+
+    debug!(
+        "Performed {} on {} documents in collection {}/{}",
+        operation.name(),
+        keys.len(),
+        db_name,
+        collection_name
     );
 
-    // Create a shared client reference for the load testing tasks
-    let client = Arc::new(client);
+    Ok(())
+}
+
+/// Periodically logs the throughput of each operation type, computed from
+/// the request counters in the metrics.
+async fn report_throughput(interval: tokio::time::Duration, shutdown: CancellationToken) {
+    let counts = || -> Vec<u64> {
+        Operation::ALL
+            .iter()
+            .map(|op| {
+                METRICS
+                    .requests
+                    .with_label_values(&[USECASE, op.name()])
+                    .get()
+            })
+            .collect()
+    };
+    let mut last = counts();
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => return,
+        }
+        let current = counts();
+        let rates: Vec<String> = Operation::ALL
+            .iter()
+            .zip(current.iter().zip(last.iter()))
+            .map(|(op, (now, before))| {
+                format!(
+                    "{}: {:.1}/s",
+                    op.name(),
+                    (now - before) as f64 / interval.as_secs_f64()
+                )
+            })
+            .collect();
+        info!("CRUD throughput: {}", rates.join(", "));
+        last = current;
+    }
+}
+
+#[async_trait]
+impl UseCase for CrudUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    fn coordinated(&self) -> bool {
+        true
+    }
 
-    // Spawn 10 concurrent load testing tasks
-    let mut handles = Vec::new();
-    for _ in 0..10 {
-        let client = Arc::clone(&client);
-        let crud_config = crud_config.clone();
-        let db_config = db_config.clone();
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
 
-        let handle = tokio::spawn(async move {
-            loop {
-                let sleep_ms = 500;
-                tokio::time::sleep(tokio::time::Duration::from_millis(sleep_ms)).await;
+        check_operation_mix(&self.crud_config)?;
 
-                if let Err(e) = perform_load_operation(&client, &crud_config, &db_config).await {
-                    log::error!("Load operation failed: {}", e);
+        if let DatabaseDistribution::Weighted { weights } = &self.crud_config.database_distribution
+        {
+            if weights.len() != self.crud_config.number_of_databases.max(1) as usize
+                || weights.iter().all(|weight| *weight == 0)
+            {
+                return Err(anyhow::anyhow!(
+                    "The database distribution needs one weight per database, at least one positive"
+                ));
+            }
+        }
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        let db_names = database_names(&self.db_config, &self.crud_config);
+        match coordination::current() {
+            Some(coordinator) => {
+                initialize_coordinated(
+                    &connection,
+                    &self.crud_config,
+                    &db_names,
+                    coordinator,
+                    shutdown,
+                )
+                .await?
+            }
+            None => {
+                for db_name in &db_names {
+                    if shutdown.is_cancelled() {
+                        break;
+                    }
+                    let database_existed = initialize_database_and_collections(
+                        &connection,
+                        &self.crud_config,
+                        db_name,
+                        shutdown,
+                    )
+                    .await?;
+                    info!(
+                        "Initialization of database {} complete. Database existed: {}",
+                        db_name, database_existed
+                    );
                 }
             }
+        }
+        if self.crud_config.verify && !shutdown.is_cancelled() {
+            verify_documents(&connection, &self.crud_config, shutdown).await?;
+        }
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+
+        let reporter = tokio::spawn(report_throughput(
+            tokio::time::Duration::from_secs(10),
+            shutdown.clone(),
+        ));
+
+        let churn = self.crud_config.index_churn.clone().map(|churn| {
+            tokio::spawn(churn_index(
+                connection.clone(),
+                database_name(&self.db_config, &self.crud_config, 1),
+                churn,
+                shutdown.clone(),
+            ))
+        });
+
+        let pacer = Arc::new(Pacer::new(self.crud_config.target_ops_per_sec));
+        // The operation mix can be changed by reloading the configuration
+        let crud_config = Arc::new(RwLock::new(Arc::new(self.crud_config.clone())));
+        let reloaded = Arc::clone(&crud_config);
+        control::on_reload(USECASE, move |config| {
+            if let Err(e) = check_operation_mix(&config.crud) {
+                warn!("Ignoring reloaded CRUD configuration: {}", e);
+                return;
+            }
+            control::apply_load(
+                USECASE,
+                config.crud.concurrency,
+                config.crud.target_ops_per_sec,
+            );
+            *reloaded.write().unwrap() = Arc::new(config.crud.clone());
         });
+        run_workers(
+            USECASE,
+            self.crud_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let crud_config = Arc::clone(&crud_config.read().unwrap());
+                async move { perform_load_operation(&connection, &crud_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        let _ = reporter.await;
+        if let Some(churn) = churn {
+            let _ = churn.await;
+        }
+        Ok(())
+    }
 
-        handles.push(handle);
+    async fn verify_seed(&self) -> Result<Vec<CollectionCount>> {
+        let connection = self.connection()?;
+        let mut counts = Vec::new();
+        for db_name in database_names(&self.db_config, &self.crud_config) {
+            for collection in all_collections(&self.crud_config) {
+                let expected = u64::from(collection.number_of_documents);
+                counts.push(
+                    CollectionCount::fetch(&connection, &db_name, &collection.name, expected)
+                        .await?,
+                );
+            }
+        }
+        Ok(counts)
     }
 
-    // Wait for all tasks to complete (they won't, as they run in an infinite loop)
-    futures::future::join_all(handles).await;
+    async fn teardown(&self) -> Result<()> {
+        if self.crud_config.drop_on_exit {
+            let connection = self.connection()?;
+            for db_name in database_names(&self.db_config, &self.crud_config) {
+                info!("Dropping database {}", db_name);
+                drop_database(&connection, &db_name).await?;
+            }
+        }
+        Ok(())
+    }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_written_documents_match_generated_ones() {
+        for target_size in [0, 60, 1000, 3 * CHARACTER_POOL_SIZE as u32] {
+            let generated = json!(generate_document(
+                &mut StdRng::seed_from_u64(3),
+                42,
+                target_size,
+                NUM_ATTRIBUTES
+            ));
+            let mut buf = Vec::new();
+            write_document(
+                &mut buf,
+                &mut StdRng::seed_from_u64(3),
+                42,
+                target_size,
+                NUM_ATTRIBUTES,
+            )
+            .unwrap();
+            let written: Value = serde_json::from_slice(&buf).unwrap();
+            assert_eq!(written, generated);
+        }
+
+        let body = write_batch(&[1, 2, 3], BatchFormat::Array, |buf, key| {
+            write_document(buf, &mut StdRng::seed_from_u64(1), key, 100, NUM_ATTRIBUTES)
+        })
+        .unwrap();
+        let batch: Vec<Value> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch[2]["_key"], "K3");
+        let body = write_batch(&[1, 2], BatchFormat::Lines, |buf, key| {
+            write_document(buf, &mut StdRng::seed_from_u64(1), key, 100, NUM_ATTRIBUTES)
+        })
+        .unwrap();
+        assert_eq!(body.iter().filter(|&&b| b == b'\n').count(), 2);
+    }
+
+    #[test]
+    fn test_seed_chunks_add_up_to_the_batch() {
+        let config = crate::config::Config::from_file("config.yaml").unwrap();
+        for format in [BatchFormat::Array, BatchFormat::Lines] {
+            let chunks = SeedChunks {
+                collection: collection_settings(&config.crud, 1),
+                keys: 11..=20,
+                chunk_size: 3,
+                format,
+            };
+            let body: Vec<u8> = (0..)
+                .map_while(|index| chunks.chunk(index))
+                .flat_map(|chunk| chunk.unwrap())
+                .collect();
+            let documents: Vec<Value> = match format {
+                BatchFormat::Array => serde_json::from_slice(&body).unwrap(),
+                BatchFormat::Lines => body
+                    .split(|&b| b == b'\n')
+                    .filter(|line| !line.is_empty())
+                    .map(|line| serde_json::from_slice(line).unwrap())
+                    .collect(),
+                BatchFormat::Velocypack => unreachable!("not streamed"),
+            };
+            let keys: Vec<&str> = documents
+                .iter()
+                .map(|document| document["_key"].as_str().unwrap())
+                .collect();
+            assert_eq!(
+                keys,
+                (11..=20).map(|key| format!("K{}", key)).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_velocypack_documents_match_json_ones() {
+        let config = crate::config::Config::from_file("config.yaml").unwrap();
+        let mut collection = collection_settings(&config.crud, 1);
+        collection.blob = Some(BlobConfig {
+            size: DocumentSize::Fixed(100),
+        });
+        let render = |format, key| {
+            let mut buf = Vec::new();
+            let mut rng = StdRng::seed_from_u64(u64::from(key));
+            render_document(&mut buf, &mut rng, key, &collection, format).unwrap();
+            buf
+        };
+        let json: Value = serde_json::from_slice(&render(DocumentFormat::Json, 7)).unwrap();
+        let vpack = render(DocumentFormat::Velocypack, 7);
+        assert_eq!(velocypack::decode(&vpack).unwrap(), json);
+        assert_eq!(json[BLOB_ATTRIBUTE].as_str().unwrap().len(), 136);
+
+        let body = write_batch(&[1, 2, 3], BatchFormat::Velocypack, |buf, key| {
+            buf.extend_from_slice(&render(DocumentFormat::Velocypack, key));
+            Ok(())
+        })
+        .unwrap();
+        let batch = velocypack::decode(&body).unwrap();
+        let json: Value = serde_json::from_slice(&render(DocumentFormat::Json, 3)).unwrap();
+        assert_eq!(batch.as_array().unwrap().len(), 3);
+        assert_eq!(batch[2], json);
+    }
 }
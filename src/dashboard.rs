@@ -0,0 +1,317 @@
+use crate::arangodb::Connection;
+use crate::config::DatabaseConfig;
+use crate::control;
+use crate::latency::{LatencySnapshot, LatencySummary, LATENCIES};
+use crate::metrics::operation_stats;
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{Event, EventStream};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use futures::StreamExt;
+use log::error;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::panic;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// Number of intervals shown in the throughput sparklines
+const HISTORY: usize = 60;
+
+/// Number of log lines shown below the statistics
+const LOG_LINES: usize = 10;
+
+/// Log lines are cut to this many characters
+const LOG_WIDTH: usize = 120;
+
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Keeps the most recent log lines, so that the log can be shown inside
+/// the dashboard instead of scrolling over it. Used as the target of the
+/// logger in dashboard mode.
+#[derive(Clone, Default)]
+pub struct LogBuffer {
+    inner: Arc<Mutex<LogLines>>,
+}
+
+#[derive(Default)]
+struct LogLines {
+    partial: String,
+    lines: VecDeque<String>,
+}
+
+impl LogBuffer {
+    /// Returns the most recent complete log lines, oldest first
+    pub fn recent(&self, count: usize) -> Vec<String> {
+        let inner = self.inner.lock().unwrap();
+        let skip = inner.lines.len().saturating_sub(count);
+        inner.lines.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.partial.push_str(&String::from_utf8_lossy(buf));
+        while let Some(end) = inner.partial.find('\n') {
+            let line: String = inner.partial.drain(..=end).collect();
+            inner.lines.push_back(line.trim_end().to_string());
+            if inner.lines.len() > LOG_LINES * 10 {
+                inner.lines.pop_front();
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Renders a series of values as a line of block characters, scaled to
+/// the largest value
+fn sparkline(values: &VecDeque<f64>) -> String {
+    let max = values.iter().copied().fold(0.0, f64::max);
+    values
+        .iter()
+        .map(|value| {
+            if max <= 0.0 {
+                SPARKS[0]
+            } else {
+                let level = (value / max * (SPARKS.len() - 1) as f64).round() as usize;
+                SPARKS[level.min(SPARKS.len() - 1)]
+            }
+        })
+        .collect()
+}
+
+/// The throughput history of the use cases and the state needed to
+/// compute the next interval
+#[derive(Default)]
+struct Dashboard {
+    throughput: BTreeMap<String, VecDeque<f64>>,
+    errors_per_sec: BTreeMap<String, f64>,
+    counts: BTreeMap<String, (u64, u64)>,
+    latencies: LatencySnapshot,
+    last_latencies: Vec<LatencySummary>,
+}
+
+impl Dashboard {
+    /// Takes the statistics of the interval which just ended
+    fn update(&mut self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64().max(0.001);
+        let mut totals: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+        for stats in operation_stats() {
+            let total = totals.entry(stats.usecase).or_default();
+            total.0 += stats.requests;
+            total.1 += stats.errors;
+        }
+        for (usecase, (requests, errors)) in totals {
+            let (last_requests, last_errors) = self
+                .counts
+                .insert(usecase.clone(), (requests, errors))
+                .unwrap_or_default();
+            let history = self.throughput.entry(usecase.clone()).or_default();
+            history.push_back(requests.saturating_sub(last_requests) as f64 / secs);
+            if history.len() > HISTORY {
+                history.pop_front();
+            }
+            self.errors_per_sec
+                .insert(usecase, errors.saturating_sub(last_errors) as f64 / secs);
+        }
+        self.last_latencies = LATENCIES.summaries_since(&mut self.latencies);
+    }
+
+    /// Renders the whole screen
+    fn render(&self, uptime: Duration, endpoints: &[(String, bool)], logs: &[String]) -> String {
+        let ms = |us: u64| us as f64 / 1000.0;
+        let mut out = String::new();
+        let _ = writeln!(out, "adb_loader - running for {}s", uptime.as_secs());
+        let _ = writeln!(out);
+        let _ = writeln!(out, "Endpoints:");
+        for (url, healthy) in endpoints {
+            let state = if *healthy { "healthy" } else { "QUARANTINED" };
+            let _ = writeln!(out, "  {:<40} {}", url, state);
+        }
+        let _ = writeln!(out);
+
+        let controls: BTreeMap<String, _> = control::running().into_iter().collect();
+        let _ = writeln!(
+            out,
+            "{:<14} {:>10} {:>9} {:>8} {:>8}  throughput",
+            "Use case", "ops/s", "errors/s", "workers", "target"
+        );
+        for (usecase, history) in &self.throughput {
+            let (workers, target) = match controls.get(usecase) {
+                Some(control) if control.state().paused => ("paused".to_string(), control.rate()),
+                Some(control) => (control.state().concurrency.to_string(), control.rate()),
+                None => ("stopped".to_string(), 0.0),
+            };
+            let target = if target > 0.0 {
+                format!("{:.0}", target)
+            } else {
+                "-".to_string()
+            };
+            let _ = writeln!(
+                out,
+                "{:<14} {:>10.1} {:>9.1} {:>8} {:>8}  {}",
+                usecase,
+                history.back().copied().unwrap_or_default(),
+                self.errors_per_sec
+                    .get(usecase)
+                    .copied()
+                    .unwrap_or_default(),
+                workers,
+                target,
+                sparkline(history)
+            );
+        }
+        let _ = writeln!(out);
+
+        let _ = writeln!(
+            out,
+            "{:<14} {:<18} {:>10} {:>10} {:>10} {:>10}",
+            "Use case", "Operation", "p50 ms", "p99 ms", "p99.9 ms", "max ms"
+        );
+        for summary in &self.last_latencies {
+            let _ = writeln!(
+                out,
+                "{:<14} {:<18} {:>10.2} {:>10.2} {:>10.2} {:>10.2}",
+                summary.usecase,
+                summary.operation,
+                ms(summary.p50),
+                ms(summary.p99),
+                ms(summary.p999),
+                ms(summary.max)
+            );
+        }
+        let _ = writeln!(out);
+
+        let _ = writeln!(out, "Log:");
+        for line in logs {
+            let line: String = line.chars().take(LOG_WIDTH).collect();
+            let _ = writeln!(out, "  {}", line);
+        }
+        out
+    }
+}
+
+/// Switches the terminal to the alternate screen with a hidden cursor and
+/// back when dropped, also when the dashboard task unwinds
+struct Screen;
+
+impl Screen {
+    fn enter() -> Self {
+        // A panic elsewhere leaves the alternate screen as well, so that
+        // its message stays readable
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            previous(info);
+        }));
+        let _ = execute!(io::stdout(), EnterAlternateScreen, Hide);
+        Screen
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+fn restore_terminal() {
+    let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+}
+
+/// Draws a screen cut to the size of the terminal, so that long lines do
+/// not wrap and push the top out of view
+fn draw(stdout: &mut io::Stdout, screen: &str) -> io::Result<()> {
+    let (columns, rows) = terminal::size().unwrap_or((u16::MAX, u16::MAX));
+    queue!(stdout, Clear(ClearType::All))?;
+    for (row, line) in screen.lines().take(rows as usize).enumerate() {
+        let line: String = line.chars().take(columns as usize).collect();
+        queue!(stdout, MoveTo(0, row as u16), Print(line))?;
+    }
+    stdout.flush()
+}
+
+/// Shows a live dashboard of the running use cases on the terminal,
+/// refreshed every `interval` and whenever the terminal is resized, until
+/// `shutdown` is cancelled: throughput sparklines, error rates, latency
+/// percentiles of the last interval, the health of the endpoints and the
+/// most recent log lines. The dashboard uses the alternate screen, which is
+/// left again at the end or on a panic, so that the final statistics stay
+/// visible.
+pub async fn run_dashboard(
+    db_config: DatabaseConfig,
+    logs: LogBuffer,
+    interval: Duration,
+    shutdown: CancellationToken,
+) {
+    // An own connection, whose health checks show the endpoint health
+    let connection = match Connection::new(&db_config).await {
+        Ok(connection) => connection,
+        Err(e) => {
+            error!("Cannot start the dashboard: {}", e);
+            return;
+        }
+    };
+    connection.spawn_health_checks(shutdown.clone());
+
+    let started = Instant::now();
+    let mut dashboard = Dashboard::default();
+    let mut last = Instant::now();
+    let mut stdout = io::stdout();
+    let _screen = Screen::enter();
+    let mut events = EventStream::new();
+    // Without a terminal to read from, only the ticks redraw
+    let mut terminal_events = true;
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                dashboard.update(last.elapsed());
+                last = Instant::now();
+            }
+            event = events.next(), if terminal_events => match event {
+                Some(Ok(Event::Resize(..))) => {}
+                Some(Ok(_)) => continue,
+                Some(Err(_)) | None => {
+                    terminal_events = false;
+                    continue;
+                }
+            },
+            _ = shutdown.cancelled() => break,
+        }
+        let endpoints: Vec<(String, bool)> = connection
+            .endpoints()
+            .all()
+            .iter()
+            .map(|endpoint| (endpoint.url().to_string(), endpoint.is_healthy()))
+            .collect();
+        let screen = dashboard.render(started.elapsed(), &endpoints, &logs.recent(LOG_LINES));
+        let _ = draw(&mut stdout, &screen);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparkline_and_log_buffer() {
+        let values: VecDeque<f64> = [0.0, 50.0, 100.0].into_iter().collect();
+        assert_eq!(sparkline(&values), "▁▅█");
+        assert_eq!(sparkline(&VecDeque::from(vec![0.0, 0.0])), "▁▁");
+
+        let mut logs = LogBuffer::default();
+        write!(logs, "first\nsec").unwrap();
+        write!(logs, "ond\nthird").unwrap();
+        assert_eq!(logs.recent(5), vec!["first", "second"]);
+        assert_eq!(logs.recent(1), vec!["second"]);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::arangodb::{
+    create_collection, create_database, database_exists, drop_collection, drop_database,
+    ArangoError, Connection,
+};
+use crate::config::{CollectionProperties, DatabaseConfig, DdlConfig, Phase, UseCaseConfig};
+use crate::control;
+use crate::metrics::observe_request;
+use crate::pacer::Pacer;
+use crate::usecase::{run_workers, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{info, warn};
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "ddl";
+
+/// The DDL use case: continuously creates and drops collections and
+/// optionally databases, which stresses the propagation of the Plan in the
+/// agency while other use cases run. Every DDL operation is recorded
+/// separately.
+pub struct DdlUseCase {
+    ddl_config: DdlConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+/// The collections and databases created by the churn which still exist,
+/// oldest first
+struct ChurnState {
+    /// Distinguishes the names of different runs, so that leftovers of an
+    /// aborted run do not collide
+    run_id: u64,
+    next: AtomicU64,
+    collections: Mutex<VecDeque<String>>,
+    databases: Mutex<VecDeque<String>>,
+}
+
+impl DdlUseCase {
+    pub fn new(
+        ddl_config: DdlConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        DdlUseCase {
+            ddl_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("DDL use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let ddl_config = &self.ddl_config;
+        info!("Starting DDL use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!("Number of shards: {}", ddl_config.number_of_shards);
+        info!("Replication factor: {}", ddl_config.replication_factor);
+        info!("Maximal collections: {}", ddl_config.max_collections);
+        info!(
+            "Databases: {}%, at most {}",
+            ddl_config.database_pct, ddl_config.max_databases
+        );
+        info!("Concurrency: {}", ddl_config.concurrency);
+        if ddl_config.target_ops_per_sec > 0.0 {
+            info!("Target rate: {} creations/s", ddl_config.target_ops_per_sec);
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database in which collections are churned
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("ddl")
+}
+
+/// Runs a DDL operation and records its latency under `operation`
+async fn timed<F>(operation: &str, ddl: F) -> Result<()>
+where
+    F: Future<Output = Result<(), ArangoError>>,
+{
+    let start = Instant::now();
+    let result = ddl.await;
+    observe_request(USECASE, operation, start.elapsed(), result.is_ok());
+    result.map_err(|e| anyhow::anyhow!("DDL operation {} failed: {}", operation, e))
+}
+
+/// Creates a new collection, or a new database, and drops the oldest one
+/// if there are more than configured
+async fn perform_operation(
+    conn: &Connection,
+    ddl_config: &DdlConfig,
+    state: &ChurnState,
+    mut rng: StdRng,
+) -> Result<()> {
+    let number = state.next.fetch_add(1, Ordering::Relaxed);
+    if rng.random_range(0..100) < ddl_config.database_pct {
+        let name = format!(
+            "{}_{}_{}",
+            database_name(conn.config()),
+            state.run_id,
+            number
+        );
+        timed("create_database", create_database(conn, &name)).await?;
+        let oldest = {
+            let mut databases = state.databases.lock().unwrap();
+            databases.push_back(name);
+            if databases.len() > ddl_config.max_databases as usize {
+                databases.pop_front()
+            } else {
+                None
+            }
+        };
+        if let Some(oldest) = oldest {
+            timed("drop_database", drop_database(conn, &oldest)).await?;
+        }
+    } else {
+        let db_name = database_name(conn.config());
+        let name = format!("c_{}_{}", state.run_id, number);
+        timed(
+            "create_collection",
+            create_collection(
+                conn,
+                &db_name,
+                &name,
+                ddl_config.number_of_shards,
+                ddl_config.replication_factor,
+                &CollectionProperties::default(),
+            ),
+        )
+        .await?;
+        let oldest = {
+            let mut collections = state.collections.lock().unwrap();
+            collections.push_back(name);
+            if collections.len() > ddl_config.max_collections as usize {
+                collections.pop_front()
+            } else {
+                None
+            }
+        };
+        if let Some(oldest) = oldest {
+            timed("drop_collection", drop_collection(conn, &db_name, &oldest)).await?;
+        }
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl UseCase for DdlUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+
+        // The database only holds churned collections, so leftovers of an
+        // earlier run are dropped with it
+        let db_name = database_name(&self.db_config);
+        if database_exists(&connection, &db_name).await? {
+            info!("Dropping database {}", db_name);
+            drop_database(&connection, &db_name).await?;
+        }
+        info!("Creating database {}", db_name);
+        create_database(&connection, &db_name).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let state = Arc::new(ChurnState {
+            run_id: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            next: AtomicU64::new(1),
+            collections: Mutex::new(VecDeque::new()),
+            databases: Mutex::new(VecDeque::new()),
+        });
+
+        let pacer = Arc::new(Pacer::new(self.ddl_config.target_ops_per_sec));
+        let ddl_config = Arc::new(RwLock::new(Arc::new(self.ddl_config.clone())));
+        let reloaded = Arc::clone(&ddl_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(ddl) = &config.ddl {
+                control::apply_load(USECASE, ddl.concurrency, ddl.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(ddl.clone());
+            }
+        });
+        let worker_connection = connection.clone();
+        let worker_state = Arc::clone(&state);
+        run_workers(
+            USECASE,
+            self.ddl_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = worker_connection.clone();
+                let ddl_config = Arc::clone(&ddl_config.read().unwrap());
+                let state = Arc::clone(&worker_state);
+                async move { perform_operation(&connection, &ddl_config, &state, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+
+        // Drop the databases created by the churn, the collections go with
+        // the database in teardown
+        let databases: Vec<String> = state.databases.lock().unwrap().drain(..).collect();
+        for name in databases {
+            if let Err(e) = drop_database(&connection, &name).await {
+                warn!("Cannot drop database {}: {}", name, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        let db_name = database_name(&self.db_config);
+        info!("Dropping database {}", db_name);
+        drop_database(&self.connection()?, &db_name).await?;
+        Ok(())
+    }
+}
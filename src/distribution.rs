@@ -0,0 +1,116 @@
+use crate::config::{DocumentSize, KeyDistribution, SizeDistribution};
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal, Zipf};
+
+/// Picks a key from `1..=number_of_keys` according to the distribution
+pub fn sample_key<R: Rng>(distribution: &KeyDistribution, number_of_keys: u32, rng: &mut R) -> u32 {
+    let n = number_of_keys.max(1);
+    match distribution {
+        KeyDistribution::Uniform => rng.random_range(1..=n),
+        KeyDistribution::Zipfian { theta } => zipf_rank(n, *theta, rng),
+        KeyDistribution::Latest { theta } => n + 1 - zipf_rank(n, *theta, rng),
+        KeyDistribution::Hotspot {
+            hot_ops_pct,
+            hot_keys_pct,
+        } => {
+            let hot_keys = ((u64::from(n) * u64::from((*hot_keys_pct).min(100))).div_ceil(100)
+                as u32)
+                .clamp(1, n);
+            if hot_keys == n || rng.random_range(0..100) < *hot_ops_pct {
+                rng.random_range(1..=hot_keys)
+            } else {
+                rng.random_range(hot_keys + 1..=n)
+            }
+        }
+    }
+}
+
+/// Picks the size of a generated document in bytes
+pub fn sample_size<R: Rng>(size: &DocumentSize, rng: &mut R) -> u32 {
+    match size {
+        DocumentSize::Fixed(size)
+        | DocumentSize::Distribution(SizeDistribution::Fixed { size }) => *size,
+        DocumentSize::Distribution(SizeDistribution::Uniform { min, max }) => {
+            rng.random_range(*min..=(*max).max(*min))
+        }
+        DocumentSize::Distribution(SizeDistribution::Lognormal { mean, sigma }) => {
+            // The mean of a log-normal distribution is exp(mu + sigma^2 / 2)
+            let mu = mean.max(1.0).ln() - sigma * sigma / 2.0;
+            match LogNormal::new(mu, sigma.max(0.0)) {
+                Ok(lognormal) => lognormal
+                    .sample(rng)
+                    .round()
+                    .clamp(1.0, f64::from(u32::MAX)) as u32,
+                Err(_) => mean.round() as u32,
+            }
+        }
+    }
+}
+
+/// Returns a rank from `1..=n`, where rank `k` has a probability
+/// proportional to `1 / k^theta`
+fn zipf_rank<R: Rng>(n: u32, theta: f64, rng: &mut R) -> u32 {
+    match Zipf::new(f64::from(n), theta.max(0.0)) {
+        Ok(zipf) => (zipf.sample(rng) as u32).clamp(1, n),
+        Err(_) => rng.random_range(1..=n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_skewed_distributions_prefer_their_hot_keys() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let count = |distribution: KeyDistribution, rng: &mut StdRng, hot: fn(u32) -> bool| {
+            (0..10_000)
+                .map(|_| sample_key(&distribution, 1000, rng))
+                .inspect(|key| assert!((1..=1000).contains(key)))
+                .filter(|key| hot(*key))
+                .count()
+        };
+
+        let uniform = count(KeyDistribution::Uniform, &mut rng, |key| key <= 10);
+        let zipfian = count(KeyDistribution::Zipfian { theta: 0.99 }, &mut rng, |key| {
+            key <= 10
+        });
+        let latest = count(KeyDistribution::Latest { theta: 0.99 }, &mut rng, |key| {
+            key > 990
+        });
+        let hotspot = count(
+            KeyDistribution::Hotspot {
+                hot_ops_pct: 90,
+                hot_keys_pct: 10,
+            },
+            &mut rng,
+            |key| key <= 100,
+        );
+        assert!(uniform < 300);
+        assert!(zipfian > 3000);
+        assert!(latest > 3000);
+        assert!((8500..9500).contains(&hotspot));
+    }
+
+    #[test]
+    fn test_sample_size() {
+        let mut rng = StdRng::seed_from_u64(7);
+        assert_eq!(sample_size(&DocumentSize::Fixed(500), &mut rng), 500);
+        let uniform = DocumentSize::Distribution(SizeDistribution::Uniform { min: 100, max: 200 });
+        for _ in 0..1000 {
+            assert!((100..=200).contains(&sample_size(&uniform, &mut rng)));
+        }
+        let lognormal = DocumentSize::Distribution(SizeDistribution::Lognormal {
+            mean: 1000.0,
+            sigma: 1.0,
+        });
+        let sizes: Vec<u32> = (0..10000)
+            .map(|_| sample_size(&lognormal, &mut rng))
+            .collect();
+        let mean = sizes.iter().map(|&size| f64::from(size)).sum::<f64>() / sizes.len() as f64;
+        assert!((800.0..1200.0).contains(&mean));
+        assert!(sizes.iter().any(|&size| size > 5000));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::config::{AttributeSpec, DocumentSchema, ValueSpec};
+use crate::text::random_text;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::distr::{Alphanumeric, SampleString};
+use rand::Rng;
+use serde_json::{json, Map, Value};
+use std::ops::RangeInclusive;
+
+/// Generates a document with the given key according to a schema
+pub fn generate_from_schema<R: Rng>(rng: &mut R, key: u32, schema: &DocumentSchema) -> Value {
+    let mut doc = Map::new();
+    doc.insert("_key".to_string(), json!(format!("K{}", key)));
+    add_attributes(rng, &mut doc, &schema.attributes);
+    Value::Object(doc)
+}
+
+fn add_attributes<R: Rng>(rng: &mut R, object: &mut Map<String, Value>, specs: &[AttributeSpec]) {
+    for spec in specs {
+        match spec.count {
+            Some(count) => {
+                for i in 1..=count {
+                    object.insert(
+                        format!("{}{}", spec.name, i),
+                        generate_value(rng, &spec.value),
+                    );
+                }
+            }
+            None => {
+                object.insert(spec.name.clone(), generate_value(rng, &spec.value));
+            }
+        }
+    }
+}
+
+/// Generates a single random value of the given type
+pub fn generate_value<R: Rng>(rng: &mut R, spec: &ValueSpec) -> Value {
+    match spec {
+        ValueSpec::String {
+            min_length,
+            max_length,
+        } => {
+            let length = rng.random_range(*min_length..=(*max_length).max(*min_length));
+            json!(Alphanumeric.sample_string(rng, length))
+        }
+        ValueSpec::Integer { min, max } => json!(rng.random_range(*min..=(*max).max(*min))),
+        ValueSpec::Float { min, max } => json!(min + rng.random::<f64>() * (max - min)),
+        ValueSpec::Bool => json!(rng.random::<bool>()),
+        ValueSpec::Text {
+            min_words,
+            max_words,
+            languages,
+        } => {
+            let words = rng.random_range(*min_words..=(*max_words).max(*min_words));
+            json!(random_text(rng, words, languages))
+        }
+        ValueSpec::Binary { min_size, max_size } => {
+            let size = rng.random_range(*min_size..=(*max_size).max(*min_size));
+            json!(generate_binary(rng, size))
+        }
+        ValueSpec::Date { min_year, max_year } => {
+            let from = days_from_civil(*min_year, 1, 1) * 86400;
+            let to = days_from_civil((*max_year).max(*min_year) + 1, 1, 1) * 86400;
+            json!(format_timestamp(rng.random_range(from..to)))
+        }
+        ValueSpec::Array {
+            min_length,
+            max_length,
+            items,
+        } => {
+            let length = rng.random_range(*min_length..=(*max_length).max(*min_length));
+            Value::Array((0..length).map(|_| generate_value(rng, items)).collect())
+        }
+        ValueSpec::Object { attributes } => {
+            let mut object = Map::new();
+            add_attributes(rng, &mut object, attributes);
+            Value::Object(object)
+        }
+        ValueSpec::Nested {
+            depth,
+            fan_out,
+            min_array_length,
+            max_array_length,
+            leaf,
+        } => generate_nested(
+            rng,
+            *depth,
+            *fan_out,
+            *min_array_length..=*max_array_length,
+            leaf,
+        ),
+    }
+}
+
+/// Generates an object with `fan_out` subtrees one level less deep, or a
+/// leaf value at depth 0. An empty `array_lengths` range means no arrays.
+fn generate_nested<R: Rng>(
+    rng: &mut R,
+    depth: u32,
+    fan_out: u32,
+    array_lengths: RangeInclusive<usize>,
+    leaf: &ValueSpec,
+) -> Value {
+    if depth == 0 {
+        return generate_value(rng, leaf);
+    }
+    let mut object = Map::new();
+    for i in 1..=fan_out {
+        let value = if i == fan_out && *array_lengths.end() > 0 {
+            let length = rng.random_range(
+                *array_lengths.start().min(array_lengths.end())..=*array_lengths.end(),
+            );
+            Value::Array(
+                (0..length)
+                    .map(|_| generate_nested(rng, depth - 1, fan_out, array_lengths.clone(), leaf))
+                    .collect(),
+            )
+        } else {
+            generate_nested(rng, depth - 1, fan_out, array_lengths.clone(), leaf)
+        };
+        object.insert(format!("n{}", i), value);
+    }
+    Value::Object(object)
+}
+
+/// Returns `size` random bytes, base64 encoded
+pub fn generate_binary<R: Rng>(rng: &mut R, size: usize) -> String {
+    let mut bytes = vec![0u8; size];
+    rng.fill(&mut bytes[..]);
+    BASE64.encode(bytes)
+}
+
+/// Returns the number of days since 1970-01-01 of a date in the proleptic
+/// Gregorian calendar
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = i64::from(year) - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The inverse of `days_from_civil`
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Formats seconds since the epoch as ISO 8601 timestamp in UTC
+fn format_timestamp(secs: i64) -> String {
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let secs_of_day = secs.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rng;
+
+    #[test]
+    fn test_timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_827_696), "2000-02-29T12:34:56Z");
+        let secs = days_from_civil(2024, 12, 31) * 86400 + 86399;
+        assert_eq!(format_timestamp(secs), "2024-12-31T23:59:59Z");
+    }
+
+    #[test]
+    fn test_generate_from_schema() {
+        let schema: DocumentSchema = serde_yaml::from_str(
+            r#"
+attributes:
+  - name: s
+    count: 3
+    type: string
+    min_length: 4
+    max_length: 8
+  - name: tags
+    type: array
+    min_length: 2
+    max_length: 2
+    items:
+      type: integer
+      min: 1
+      max: 6
+  - name: address
+    type: object
+    attributes:
+      - name: created
+        type: date
+      - name: active
+        type: bool
+"#,
+        )
+        .unwrap();
+        let doc = generate_from_schema(&mut rng(), 7, &schema);
+        assert_eq!(doc["_key"], "K7");
+        for name in ["s1", "s2", "s3"] {
+            let len = doc[name].as_str().unwrap().len();
+            assert!((4..=8).contains(&len));
+        }
+        let tags = doc["tags"].as_array().unwrap();
+        assert_eq!(tags.len(), 2);
+        assert!(tags.iter().all(|t| (1..=6).contains(&t.as_i64().unwrap())));
+        let created = doc["address"]["created"].as_str().unwrap();
+        assert!(created.starts_with("20") && created.ends_with('Z'));
+        assert!(doc["address"]["active"].is_boolean());
+    }
+
+    #[test]
+    fn test_generate_nested() {
+        let spec: ValueSpec = serde_yaml::from_str(
+            "{type: nested, depth: 3, fan_out: 2, min_array_length: 2, max_array_length: 2, \
+             leaf: {type: integer, min: 1, max: 1}}",
+        )
+        .unwrap();
+        let value = generate_value(&mut rng(), &spec);
+        assert_eq!(value["n1"]["n1"]["n1"], 1);
+        assert_eq!(value["n1"]["n2"][1]["n1"], 1);
+        let array = value["n2"].as_array().unwrap();
+        assert_eq!(array.len(), 2);
+        assert_eq!(array[0]["n2"][0]["n2"][1], 1);
+
+        let binary = ValueSpec::Binary {
+            min_size: 30,
+            max_size: 30,
+        };
+        let encoded = generate_value(&mut rng(), &binary);
+        assert_eq!(BASE64.decode(encoded.as_str().unwrap()).unwrap().len(), 30);
+
+        let flat: ValueSpec = serde_yaml::from_str("{type: nested, depth: 2, fan_out: 3}").unwrap();
+        let value = generate_value(&mut rng(), &flat);
+        assert!(value["n3"]["n3"].is_string());
+    }
+}
@@ -0,0 +1,326 @@
+use crate::adaptive::AdaptiveLimiter;
+use crate::breaker::CircuitBreaker;
+use crate::config::{AdaptiveConcurrencyConfig, CircuitBreakerConfig, DatabaseConfig};
+use log::{info, warn};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// A single coordinator endpoint, its health state and, if configured, its
+/// adaptive concurrency limiter and circuit breaker
+#[derive(Debug)]
+pub struct Endpoint {
+    url: String,
+    healthy: AtomicBool,
+    limiter: Option<AdaptiveLimiter>,
+    breaker: Option<CircuitBreaker>,
+}
+
+impl Endpoint {
+    /// The base URL of the endpoint, without trailing slash
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    pub fn limiter(&self) -> Option<&AdaptiveLimiter> {
+        self.limiter.as_ref()
+    }
+
+    pub fn breaker(&self) -> Option<&CircuitBreaker> {
+        self.breaker.as_ref()
+    }
+}
+
+/// Distributes requests round-robin over the healthy endpoints. Endpoints
+/// which fail are quarantined until a health check or a successful request
+/// shows that they have recovered. If no endpoint is healthy, all of them
+/// are used, so that the run can continue as soon as the cluster is back.
+/// The endpoints can be replaced while requests are in flight, e.g. by the
+/// ones discovered in the cluster. In an active failover deployment the
+/// manager also knows which endpoint is the leader, once one told it.
+#[derive(Debug)]
+pub struct EndpointManager {
+    endpoints: RwLock<Vec<Arc<Endpoint>>>,
+    leader: RwLock<Option<Arc<Endpoint>>>,
+    next: AtomicUsize,
+    adaptive_concurrency: Option<AdaptiveConcurrencyConfig>,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+impl EndpointManager {
+    /// Creates a manager for the given endpoints, without limiters and
+    /// circuit breakers
+    pub fn new(urls: &[String]) -> Self {
+        EndpointManager::with_settings(urls, None, None)
+    }
+
+    /// Creates a manager for the configured endpoints
+    pub fn from_config(config: &DatabaseConfig) -> Self {
+        EndpointManager::with_settings(
+            &config.endpoints,
+            config.adaptive_concurrency.clone(),
+            config.circuit_breaker.clone(),
+        )
+    }
+
+    fn with_settings(
+        urls: &[String],
+        adaptive_concurrency: Option<AdaptiveConcurrencyConfig>,
+        circuit_breaker: Option<CircuitBreakerConfig>,
+    ) -> Self {
+        let manager = EndpointManager {
+            endpoints: RwLock::new(Vec::new()),
+            leader: RwLock::new(None),
+            next: AtomicUsize::new(0),
+            adaptive_concurrency,
+            circuit_breaker,
+        };
+        *manager.endpoints.write().unwrap() =
+            urls.iter().map(|url| manager.endpoint(url)).collect();
+        manager
+    }
+
+    fn endpoint(&self, url: &str) -> Arc<Endpoint> {
+        let url = url.trim_end_matches('/');
+        Arc::new(Endpoint {
+            url: url.to_string(),
+            healthy: AtomicBool::new(true),
+            limiter: self
+                .adaptive_concurrency
+                .as_ref()
+                .map(|config| AdaptiveLimiter::new(url, config)),
+            breaker: self
+                .circuit_breaker
+                .as_ref()
+                .map(|config| CircuitBreaker::new(url, config)),
+        })
+    }
+
+    /// Returns all endpoints, healthy or not
+    pub fn all(&self) -> Vec<Arc<Endpoint>> {
+        self.endpoints.read().unwrap().clone()
+    }
+
+    /// Returns the number of currently healthy endpoints
+    pub fn healthy_count(&self) -> usize {
+        self.endpoints
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|e| e.is_healthy())
+            .count()
+    }
+
+    /// Replaces the endpoints with the given ones. The endpoints which stay
+    /// keep their state, an empty list changes nothing. Returns whether the
+    /// endpoints changed.
+    pub fn update(&self, urls: &[String]) -> bool {
+        if urls.is_empty() {
+            return false;
+        }
+        let mut endpoints = self.endpoints.write().unwrap();
+        let updated: Vec<Arc<Endpoint>> = urls
+            .iter()
+            .map(|url| {
+                let url = url.trim_end_matches('/');
+                endpoints
+                    .iter()
+                    .find(|endpoint| endpoint.url == url)
+                    .cloned()
+                    .unwrap_or_else(|| self.endpoint(url))
+            })
+            .collect();
+        let changed = updated.len() != endpoints.len()
+            || updated
+                .iter()
+                .zip(endpoints.iter())
+                .any(|(new, old)| !Arc::ptr_eq(new, old));
+        *endpoints = updated;
+        let mut leader = self.leader.write().unwrap();
+        if leader
+            .as_ref()
+            .is_some_and(|leader| !endpoints.iter().any(|e| Arc::ptr_eq(e, leader)))
+        {
+            *leader = None;
+        }
+        changed
+    }
+
+    /// Returns the leader of an active failover deployment, if known
+    pub fn leader(&self) -> Option<Arc<Endpoint>> {
+        self.leader.read().unwrap().clone()
+    }
+
+    /// Makes the endpoint with the given URL the leader and returns it, an
+    /// unknown URL leaves the leader as it is and returns `None`
+    pub fn set_leader(&self, url: &str) -> Option<Arc<Endpoint>> {
+        let url = url.trim_end_matches('/');
+        let endpoint = self
+            .endpoints
+            .read()
+            .unwrap()
+            .iter()
+            .find(|endpoint| endpoint.url == url)
+            .cloned()?;
+        let mut leader = self.leader.write().unwrap();
+        if leader
+            .as_ref()
+            .is_none_or(|leader| !Arc::ptr_eq(leader, &endpoint))
+        {
+            info!("Endpoint {} is the leader", url);
+            *leader = Some(endpoint.clone());
+        }
+        Some(endpoint)
+    }
+
+    /// Forgets the leader if it is the given endpoint, e.g. after it
+    /// failed, so that the next answer of a follower tells the new one
+    pub fn forget_leader(&self, endpoint: &Endpoint) {
+        let mut leader = self.leader.write().unwrap();
+        if leader
+            .as_ref()
+            .is_some_and(|leader| std::ptr::eq(Arc::as_ptr(leader), endpoint))
+        {
+            warn!("Leader {} lost, waiting for the next one", endpoint.url);
+            *leader = None;
+        }
+    }
+
+    /// Picks the endpoint for the next request
+    pub fn pick(&self) -> Arc<Endpoint> {
+        self.pick_where(|_| true)
+    }
+
+    /// Picks the next healthy endpoint which `accept`s the request, e.g.
+    /// one whose circuit breaker is closed. `accept` is only asked about
+    /// healthy endpoints and stops being asked once one accepts.
+    pub fn pick_where(&self, accept: impl Fn(&Endpoint) -> bool) -> Arc<Endpoint> {
+        let endpoints = self.endpoints.read().unwrap();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let n = endpoints.len();
+        (0..n)
+            .map(|offset| &endpoints[(start + offset) % n])
+            .find(|endpoint| endpoint.is_healthy() && accept(endpoint))
+            .unwrap_or(&endpoints[start % n])
+            .clone()
+    }
+
+    /// Quarantines an endpoint after a failed request or health check
+    pub fn mark_failed(&self, endpoint: &Endpoint) {
+        if endpoint.healthy.swap(false, Ordering::Relaxed) {
+            warn!(
+                "Endpoint {} quarantined, {} of {} endpoints healthy",
+                endpoint.url,
+                self.healthy_count(),
+                self.all().len()
+            );
+        }
+    }
+
+    /// Brings an endpoint back after it answered successfully
+    pub fn mark_healthy(&self, endpoint: &Endpoint) {
+        if !endpoint.healthy.swap(true, Ordering::Relaxed) {
+            info!(
+                "Endpoint {} recovered, {} of {} endpoints healthy",
+                endpoint.url,
+                self.healthy_count(),
+                self.all().len()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager() -> EndpointManager {
+        EndpointManager::new(&[
+            "http://a:8529/".to_string(),
+            "http://b:8529".to_string(),
+            "http://c:8529".to_string(),
+        ])
+    }
+
+    #[test]
+    fn test_round_robin_skips_quarantined_endpoints() {
+        let manager = manager();
+        assert_eq!(manager.all()[0].url(), "http://a:8529");
+
+        let b = manager.all()[1].clone();
+        manager.mark_failed(&b);
+        assert_eq!(manager.healthy_count(), 2);
+        for _ in 0..10 {
+            assert_ne!(manager.pick().url(), "http://b:8529");
+        }
+
+        manager.mark_healthy(&b);
+        let picked: Vec<String> = (0..3).map(|_| manager.pick().url().to_string()).collect();
+        assert!(picked.contains(&"http://b:8529".to_string()));
+    }
+
+    #[test]
+    fn test_pick_where_skips_rejecting_endpoints() {
+        let manager = manager();
+        for _ in 0..10 {
+            let picked = manager.pick_where(|endpoint| endpoint.url() != "http://a:8529");
+            assert_ne!(picked.url(), "http://a:8529");
+        }
+    }
+
+    #[test]
+    fn test_all_endpoints_used_when_none_healthy() {
+        let manager = manager();
+        for endpoint in manager.all() {
+            manager.mark_failed(&endpoint);
+        }
+        assert_eq!(manager.healthy_count(), 0);
+        let picked: Vec<String> = (0..3).map(|_| manager.pick().url().to_string()).collect();
+        assert_eq!(picked.len(), 3);
+        assert_ne!(picked[0], picked[1]);
+    }
+
+    #[test]
+    fn test_update_keeps_the_state_of_remaining_endpoints() {
+        let manager = manager();
+        let b = manager.all()[1].clone();
+        manager.mark_failed(&b);
+        assert!(!manager.update(&[
+            "http://a:8529".to_string(),
+            "http://b:8529/".to_string(),
+            "http://c:8529".to_string(),
+        ]));
+        assert!(manager.update(&["http://b:8529".to_string(), "http://d:8529".to_string()]));
+        let urls: Vec<String> = manager.all().iter().map(|e| e.url().to_string()).collect();
+        assert_eq!(urls, ["http://b:8529", "http://d:8529"]);
+        assert!(!manager.all()[0].is_healthy());
+        assert!(manager.all()[1].is_healthy());
+        assert!(!manager.update(&[]));
+        assert_eq!(manager.all().len(), 2);
+    }
+
+    #[test]
+    fn test_leader_follows_failovers() {
+        let manager = manager();
+        assert!(manager.leader().is_none());
+        assert!(manager.set_leader("http://x:8529").is_none());
+        assert!(manager.leader().is_none());
+
+        let b = manager.set_leader("http://b:8529/").unwrap();
+        assert_eq!(manager.leader().unwrap().url(), "http://b:8529");
+
+        // Only the failure of the leader itself makes it forget the leader
+        manager.forget_leader(&manager.all()[0]);
+        assert!(manager.leader().is_some());
+        manager.forget_leader(&b);
+        assert!(manager.leader().is_none());
+
+        manager.set_leader("http://c:8529");
+        assert_eq!(manager.leader().unwrap().url(), "http://c:8529");
+        manager.update(&["http://a:8529".to_string(), "http://b:8529".to_string()]);
+        assert!(manager.leader().is_none());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::config::ErrorPolicy;
+use crate::metrics::operation_stats;
+use anyhow::Result;
+use log::error;
+use std::collections::VecDeque;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+static POLICY: RwLock<ErrorPolicy> = RwLock::new(ErrorPolicy::Continue);
+
+/// The reason why the error policy stopped the run, if it did
+static ABORTED: Mutex<Option<String>> = Mutex::new(None);
+
+/// The ways a run can fail, they end the process with different exit
+/// codes, so that scripts can tell a broken cluster from a broken setup
+#[derive(Debug, Error)]
+pub enum RunError {
+    /// The load ran, but operations failed beyond the error policy or a
+    /// use case failed while producing load
+    #[error("Load failed: {0}")]
+    LoadFailed(String),
+    /// The load could not start, because the configuration is invalid or
+    /// a use case could not set up its data
+    #[error("Load impossible: {0}")]
+    LoadImpossible(String),
+}
+
+impl RunError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            RunError::LoadFailed(_) => 2,
+            RunError::LoadImpossible(_) => 3,
+        }
+    }
+}
+
+/// Marks the failure of a use case during its setup, as opposed to a
+/// failure while it produces load
+#[derive(Debug, Error)]
+#[error("Setup of use case {0} failed")]
+pub struct SetupFailed(pub String);
+
+/// Sets the policy for the whole process
+pub fn set_policy(policy: &ErrorPolicy) {
+    *POLICY.write().unwrap() = policy.clone();
+}
+
+/// Returns the reason why the error policy stopped the run, if it did
+pub fn aborted() -> Option<String> {
+    ABORTED.lock().unwrap().clone()
+}
+
+/// Decides about a failed batch of the seeding: unless the policy stops
+/// the run at the first error, the failure is logged and seeding goes on
+/// with the next batch. The error budget still counts the failure.
+///
+/// # Errors
+/// Returns `e` with the `abort_run` policy
+pub fn tolerate(e: anyhow::Error) -> Result<()> {
+    if *POLICY.read().unwrap() == ErrorPolicy::AbortRun {
+        return Err(e);
+    }
+    error!("{:#}, continuing according to the error policy", e);
+    Ok(())
+}
+
+/// The operations and failures seen in the sliding window of the
+/// `abort_after_threshold` policy
+struct ErrorWindow {
+    window: Duration,
+    samples: VecDeque<(Instant, u64, u64)>,
+}
+
+impl ErrorWindow {
+    fn new(window: Duration) -> Self {
+        ErrorWindow {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Adds the current totals and returns the operations and failures
+    /// since the oldest sample within the window
+    fn add(&mut self, now: Instant, requests: u64, errors: u64) -> (u64, u64) {
+        self.samples.push_back((now, requests, errors));
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|(time, _, _)| now.duration_since(*time) >= self.window)
+        {
+            self.samples.pop_front();
+        }
+        let (_, first_requests, first_errors) = self.samples[0];
+        (requests - first_requests, errors - first_errors)
+    }
+}
+
+/// Checks the failed operations of all use cases every `interval` and
+/// cancels `shutdown` once the policy says so, until `shutdown` is
+/// cancelled for other reasons. The reason is kept for `aborted`.
+pub async fn watch(policy: ErrorPolicy, interval: Duration, shutdown: CancellationToken) {
+    if policy == ErrorPolicy::Continue {
+        return;
+    }
+    let mut window = match &policy {
+        ErrorPolicy::AbortAfterThreshold { window_secs, .. } => {
+            ErrorWindow::new(Duration::from_secs(*window_secs))
+        }
+        _ => ErrorWindow::new(Duration::MAX),
+    };
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => return,
+        }
+        let (requests, errors) = operation_stats()
+            .iter()
+            .fold((0, 0), |(requests, errors), stats| {
+                (requests + stats.requests, errors + stats.errors)
+            });
+        let (requests, errors) = window.add(Instant::now(), requests, errors);
+        let reason = match &policy {
+            ErrorPolicy::AbortRun if errors > 0 => Some(format!("{} operations failed", errors)),
+            ErrorPolicy::AbortAfterThreshold {
+                max_error_pct,
+                window_secs,
+                min_requests,
+            } if requests >= *min_requests
+                && errors as f64 * 100.0 > *max_error_pct * requests as f64 =>
+            {
+                Some(format!(
+                    "{} of {} operations failed in the last {}s, more than {}%",
+                    errors, requests, window_secs, max_error_pct
+                ))
+            }
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            error!("Stopping the run according to the error policy: {}", reason);
+            *ABORTED.lock().unwrap() = Some(reason);
+            shutdown.cancel();
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_window_forgets_old_samples() {
+        let start = Instant::now();
+        let mut window = ErrorWindow::new(Duration::from_secs(60));
+        assert_eq!(window.add(start, 0, 0), (0, 0));
+        assert_eq!(
+            window.add(start + Duration::from_secs(30), 1000, 50),
+            (1000, 50)
+        );
+        assert_eq!(
+            window.add(start + Duration::from_secs(60), 2000, 60),
+            (2000, 60)
+        );
+        // The sample at 0s is older than the window now
+        assert_eq!(
+            window.add(start + Duration::from_secs(90), 3000, 60),
+            (2000, 10)
+        );
+    }
+}
@@ -0,0 +1,110 @@
+use crate::latency::{LatencySnapshot, LATENCIES};
+use crate::metrics::operation_stats;
+use anyhow::Result;
+use log::error;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+/// The columns of the CSV file, the latencies are in milliseconds
+const HEADER: &str =
+    "timestamp,usecase,operation,requests,ops_per_sec,errors,p50_ms,p90_ms,p99_ms,p999_ms,max_ms";
+
+/// Periodically appends the throughput, the errors and the latency
+/// percentiles of every operation in the last interval to a CSV file, for
+/// offline analysis where no Prometheus is available. The header is
+/// written if the file is new. The last interval is written on shutdown.
+pub async fn export_stats(path: PathBuf, interval: Duration, shutdown: CancellationToken) {
+    let mut exporter = match StatsExporter::open(&path) {
+        Ok(exporter) => exporter,
+        Err(e) => {
+            error!("Cannot open statistics file {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        let stopped = tokio::select! {
+            _ = ticker.tick() => false,
+            _ = shutdown.cancelled() => true,
+        };
+        if let Err(e) = exporter.write_interval() {
+            error!("Cannot write statistics to {}: {}", path.display(), e);
+        }
+        if stopped {
+            return;
+        }
+    }
+}
+
+/// The CSV file and the state at the end of the last interval
+struct StatsExporter {
+    file: File,
+    last: Instant,
+    latencies: LatencySnapshot,
+    counts: BTreeMap<(String, String), (u64, u64)>,
+}
+
+impl StatsExporter {
+    fn open(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            writeln!(file, "{}", HEADER)?;
+        }
+        Ok(StatsExporter {
+            file,
+            last: Instant::now(),
+            latencies: LatencySnapshot::default(),
+            counts: BTreeMap::new(),
+        })
+    }
+
+    /// Appends one line per operation which was performed since the last
+    /// interval
+    fn write_interval(&mut self) -> Result<()> {
+        let elapsed = self.last.elapsed().as_secs_f64().max(0.001);
+        self.last = Instant::now();
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs_f64();
+
+        let mut deltas = BTreeMap::new();
+        for stats in operation_stats() {
+            let key = (stats.usecase, stats.operation);
+            let (requests, errors) = self.counts.get(&key).copied().unwrap_or_default();
+            deltas.insert(
+                key.clone(),
+                (
+                    stats.requests.saturating_sub(requests),
+                    stats.errors.saturating_sub(errors),
+                ),
+            );
+            self.counts.insert(key, (stats.requests, stats.errors));
+        }
+
+        let ms = |us: u64| us as f64 / 1000.0;
+        for summary in LATENCIES.summaries_since(&mut self.latencies) {
+            let key = (summary.usecase.clone(), summary.operation.clone());
+            let (requests, errors) = deltas.get(&key).copied().unwrap_or_default();
+            writeln!(
+                self.file,
+                "{:.3},{},{},{},{:.2},{},{:.3},{:.3},{:.3},{:.3},{:.3}",
+                timestamp,
+                summary.usecase,
+                summary.operation,
+                requests,
+                requests as f64 / elapsed,
+                errors,
+                ms(summary.p50),
+                ms(summary.p90),
+                ms(summary.p99),
+                ms(summary.p999),
+                ms(summary.max)
+            )?;
+        }
+        self.file.flush()?;
+        Ok(())
+    }
+}
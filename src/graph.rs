@@ -0,0 +1,1191 @@
+use crate::arangodb::{
+    collection_count, collection_exists, create_collection, create_collection_from,
+    create_database, create_graph, database_exists, drop_database, run_query, ApiRequest,
+    Connection,
+};
+use crate::config::{
+    CollectionProperties, DatabaseConfig, GraphConfig, GraphTopology, KeyDistribution, Phase,
+    UseCaseConfig,
+};
+use crate::control;
+use crate::crud::{generate_document, NUM_ATTRIBUTES};
+use crate::distribution::sample_key;
+use crate::error_policy;
+use crate::metrics::{observe_request, METRICS};
+use crate::pacer::Pacer;
+use crate::random::stream_rng;
+use crate::usecase::{run_workers, send_observed, CollectionCount, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use log::{debug, error, info, warn};
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "graph";
+
+/// The names of the graph and its collections
+const GRAPH_NAME: &str = "G";
+const VERTEX_COLLECTION: &str = "V";
+const EDGE_COLLECTION: &str = "E";
+
+/// The satellite vertices of a Hybrid SmartGraph and the edges which
+/// connect every vertex to one of them
+const SATELLITE_COLLECTION: &str = "S";
+const SATELLITE_EDGE_COLLECTION: &str = "ES";
+
+/// Number of vertices or edges per insert request
+const BATCH_SIZE: u64 = 1000;
+
+/// The graph use case: seeds a graph whose edges follow a configurable
+/// topology, so that traversals see a realistic degree distribution, and
+/// then runs traversals from random start vertices, mixed with vertex
+/// updates.
+pub struct GraphUseCase {
+    graph_config: GraphConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl GraphUseCase {
+    pub fn new(
+        graph_config: GraphConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        GraphUseCase {
+            graph_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Graph use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let graph_config = &self.graph_config;
+        info!("Starting graph use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!("Number of vertices: {}", graph_config.number_of_vertices);
+        info!("Number of edges: {}", expected_edges(graph_config));
+        info!("Topology: {:?}", graph_config.topology);
+        match (graph_config.smart, smart_graph_attribute(graph_config)) {
+            (false, _) if graph_config.co_locate_edges => {
+                info!("Graph kind: general graph with co-located edges")
+            }
+            (false, _) => info!("Graph kind: general graph"),
+            (true, None) => info!("Graph kind: EnterpriseGraph"),
+            (true, Some(attribute)) => info!(
+                "Graph kind: {}SmartGraph on attribute {} with {} groups{}",
+                if graph_config.disjoint {
+                    "Disjoint "
+                } else {
+                    ""
+                },
+                attribute,
+                graph_config.smart_groups,
+                if graph_config.satellite_vertices > 0 {
+                    format!(", {} satellite vertices", graph_config.satellite_vertices)
+                } else {
+                    String::new()
+                }
+            ),
+        }
+        if smart_graph_attribute(graph_config).is_some() {
+            info!("Smart group skew: {}", graph_config.smart_group_skew);
+            if let Some(pct) = graph_config.intra_group_edges_pct {
+                info!("Edges within a smart group: {}%", pct);
+            }
+        }
+        if graph_config.supernode_pct > 0 {
+            info!(
+                "Supernodes: {}% of the edges start at one of {} vertices",
+                graph_config.supernode_pct, graph_config.number_of_supernodes
+            );
+        }
+        info!("Number of shards: {}", graph_config.number_of_shards);
+        info!("Replication factor: {}", graph_config.replication_factor);
+        info!(
+            "Vertex size: {}, edge size: {}",
+            graph_config.vertex_size, graph_config.edge_size
+        );
+        info!("Insert concurrency: {}", graph_config.insert_concurrency);
+        info!(
+            "Traversal depth: {}..{}, outbound {}%, inbound {}%, any {}%",
+            graph_config.min_depth,
+            graph_config.max_depth,
+            graph_config.outbound_pct,
+            graph_config.inbound_pct,
+            graph_config.any_pct
+        );
+        info!(
+            "Start vertex distribution: {:?}",
+            graph_config.start_vertex_distribution
+        );
+        info!("Vertex updates: {}%", graph_config.write_pct);
+        info!(
+            "Shortest paths: {}%, {} shortest paths: {}%",
+            graph_config.shortest_path_pct, graph_config.k_paths, graph_config.k_shortest_paths_pct
+        );
+        if graph_config.edge_churn_ops_per_sec > 0.0 {
+            info!(
+                "Edge churn: {} edges/s, window {}, check every {}s",
+                graph_config.edge_churn_ops_per_sec,
+                graph_config.edge_churn_window,
+                graph_config.edge_check_interval_secs
+            );
+        }
+        info!("Concurrency: {}", graph_config.concurrency);
+        if graph_config.target_ops_per_sec > 0.0 {
+            info!("Target rate: {} ops/s", graph_config.target_ops_per_sec);
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the graph use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("graph")
+}
+
+/// The number of columns of a grid with `n` vertices
+fn grid_width(n: u32) -> u32 {
+    (f64::from(n).sqrt().ceil() as u32).max(1)
+}
+
+/// The number of edge indexes to generate edges for. Only the grid has
+/// indexes without an edge.
+fn edge_slots(graph_config: &GraphConfig) -> u64 {
+    match graph_config.topology {
+        GraphTopology::Grid => 2 * u64::from(graph_config.number_of_vertices),
+        _ => u64::from(graph_config.number_of_edges),
+    }
+}
+
+/// The number of edges the graph has after seeding
+fn expected_edges(graph_config: &GraphConfig) -> u64 {
+    let n = u64::from(graph_config.number_of_vertices);
+    match graph_config.topology {
+        GraphTopology::Grid if n > 0 => {
+            let width = u64::from(grid_width(graph_config.number_of_vertices));
+            // Every vertex but the last one of each row has a right
+            // neighbour, all but the last row have a lower one
+            let horizontal = (n - 1) - (n - 1) / width;
+            let vertical = n.saturating_sub(width);
+            horizontal + vertical
+        }
+        GraphTopology::Grid => 0,
+        _ => u64::from(graph_config.number_of_edges),
+    }
+}
+
+/// Returns the keys of the vertices connected by the edge with the given
+/// index, or `None` if the topology has no edge with this index
+fn edge_ends<R: Rng>(graph_config: &GraphConfig, index: u64, rng: &mut R) -> Option<(u32, u32)> {
+    let n = graph_config.number_of_vertices.max(1);
+    let (from, to) = match &graph_config.topology {
+        GraphTopology::ErdosRenyi => (rng.random_range(1..=n), rng.random_range(1..=n)),
+        GraphTopology::ScaleFree { theta } => {
+            let distribution = KeyDistribution::Zipfian { theta: *theta };
+            (
+                sample_key(&distribution, n, rng),
+                sample_key(&distribution, n, rng),
+            )
+        }
+        GraphTopology::Grid => {
+            let width = grid_width(n);
+            let vertex = (index / 2) as u32 + 1;
+            let neighbour = if index.is_multiple_of(2) {
+                (!vertex.is_multiple_of(width)).then_some(vertex + 1)
+            } else {
+                Some(vertex + width)
+            };
+            (vertex, neighbour.filter(|neighbour| *neighbour <= n)?)
+        }
+    };
+    if rng.random_range(0..100) < graph_config.supernode_pct {
+        let supernodes = graph_config.number_of_supernodes.clamp(1, n);
+        return Some((rng.random_range(1..=supernodes), to));
+    }
+    Some((from, to))
+}
+
+/// Returns the smart graph attribute, if a SmartGraph is created
+fn smart_graph_attribute(graph_config: &GraphConfig) -> Option<&str> {
+    graph_config
+        .smart_graph_attribute
+        .as_deref()
+        .filter(|_| graph_config.smart)
+}
+
+/// Number of positions per smart group in the cycle of a skewed group
+/// assignment, which bounds how small a group can get
+const SKEW_RESOLUTION: u32 = 100;
+
+/// The number of keys after which the assignment of vertices to smart
+/// groups repeats
+fn group_cycle(graph_config: &GraphConfig) -> u32 {
+    let groups = graph_config.smart_groups.max(1);
+    if graph_config.smart_group_skew > 0.0 {
+        groups.saturating_mul(SKEW_RESOLUTION)
+    } else {
+        groups
+    }
+}
+
+/// The smart group of the vertex with the given key. Without skew the
+/// vertices take turns, otherwise the position of the key in the cycle
+/// is mapped to the groups with a power law, so that the first groups
+/// are the largest.
+fn group_of(graph_config: &GraphConfig, key: u32) -> u32 {
+    let groups = graph_config.smart_groups.max(1);
+    let cycle = group_cycle(graph_config);
+    let position = key % cycle;
+    if graph_config.smart_group_skew <= 0.0 {
+        return position;
+    }
+    let x = (f64::from(position) + 0.5) / f64::from(cycle);
+    let exponent = 1.0 / (1.0 - graph_config.smart_group_skew.min(0.999));
+    ((f64::from(groups) * x.powf(exponent)) as u32).min(groups - 1)
+}
+
+/// The positions in the cycle whose vertices belong to a group, empty
+/// for groups too small to get one
+fn group_positions(graph_config: &GraphConfig, group: u32) -> Range<u32> {
+    let cycle = group_cycle(graph_config);
+    // The groups grow with the position, so the first position of a
+    // group is found by bisection
+    let first = |group: u32| {
+        let (mut low, mut high) = (0, cycle);
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if group_of(graph_config, middle) < group {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    };
+    first(group)..first(group + 1)
+}
+
+/// The value of the smart graph attribute of a vertex
+fn smart_group(graph_config: &GraphConfig, key: u32) -> String {
+    format!("g{}", group_of(graph_config, key))
+}
+
+/// Returns a vertex of the given smart group close to the vertex with
+/// the given key, in the same cycle of the group assignment if the graph
+/// is large enough
+fn move_to_group(graph_config: &GraphConfig, key: u32, group: u32) -> Option<u32> {
+    let positions = group_positions(graph_config, group);
+    if positions.is_empty() {
+        return None;
+    }
+    let cycle = group_cycle(graph_config);
+    let n = graph_config.number_of_vertices;
+    let position = positions.start + key % positions.len() as u32;
+    let mut moved = key - key % cycle + position;
+    if moved == 0 {
+        moved += cycle;
+    }
+    if moved > n {
+        moved = moved.saturating_sub(cycle);
+    }
+    (moved != 0).then_some(moved)
+}
+
+/// Moves the end of an edge into the smart group of its start or out of
+/// it: Disjoint SmartGraphs only have edges within a group, otherwise
+/// `intra_group_edges_pct` percent of the edges stay within one
+fn place_edge_end<R: Rng>(graph_config: &GraphConfig, from: u32, to: u32, rng: &mut R) -> u32 {
+    let intra = match graph_config.intra_group_edges_pct {
+        _ if graph_config.disjoint => true,
+        Some(pct) => rng.random_range(0..100) < pct,
+        None => return to,
+    };
+    let group = group_of(graph_config, from);
+    if intra {
+        return move_to_group(graph_config, to, group).unwrap_or(from);
+    }
+    let groups = graph_config.smart_groups.max(1);
+    if groups == 1 || group_of(graph_config, to) != group {
+        return to;
+    }
+    // Tries the other groups in turn, starting at a random one
+    let offset = rng.random_range(0..groups - 1);
+    (0..groups - 1)
+        .map(|i| (group + (offset + i) % (groups - 1) + 1) % groups)
+        .find_map(|other| move_to_group(graph_config, to, other))
+        .unwrap_or(to)
+}
+
+/// The `_key` of a vertex, which is prefixed with its smart group in a
+/// SmartGraph
+fn vertex_key(graph_config: &GraphConfig, key: u32) -> String {
+    match smart_graph_attribute(graph_config) {
+        Some(_) => format!("{}:K{}", smart_group(graph_config, key), key),
+        None => format!("K{}", key),
+    }
+}
+
+fn vertex_id(graph_config: &GraphConfig, key: u32) -> String {
+    format!("{}/{}", VERTEX_COLLECTION, vertex_key(graph_config, key))
+}
+
+/// Generates the vertex with the given key
+fn generate_vertex(graph_config: &GraphConfig, key: u32) -> Value {
+    let mut rng = stream_rng(
+        &format!("{}/{}", USECASE, VERTEX_COLLECTION),
+        u64::from(key),
+    );
+    let mut vertex = generate_document(&mut rng, key, graph_config.vertex_size, NUM_ATTRIBUTES);
+    vertex.insert("_key".to_string(), json!(vertex_key(graph_config, key)));
+    if let Some(attribute) = smart_graph_attribute(graph_config) {
+        vertex.insert(attribute.to_string(), json!(smart_group(graph_config, key)));
+    }
+    json!(vertex)
+}
+
+/// Generates an edge between two vertex ids. Edges of Enterprise Edition
+/// graphs get their keys from the server, which encodes the shards of
+/// both ends in them.
+fn generate_edge_between<R: Rng>(
+    graph_config: &GraphConfig,
+    rng: &mut R,
+    key: u32,
+    from: String,
+    to: String,
+) -> Value {
+    let mut edge = generate_document(rng, key, graph_config.edge_size, NUM_ATTRIBUTES);
+    if graph_config.smart {
+        edge.remove("_key");
+    }
+    edge.insert("_from".to_string(), json!(from));
+    edge.insert("_to".to_string(), json!(to));
+    json!(edge)
+}
+
+/// Generates the edge with the given index, if the topology has one
+fn generate_edge(graph_config: &GraphConfig, index: u64) -> Option<Value> {
+    let mut rng = stream_rng(&format!("{}/{}", USECASE, EDGE_COLLECTION), index);
+    let (from, to) = edge_ends(graph_config, index, &mut rng)?;
+    let to = place_edge_end(graph_config, from, to, &mut rng);
+    Some(generate_edge_between(
+        graph_config,
+        &mut rng,
+        (index + 1) as u32,
+        vertex_id(graph_config, from),
+        vertex_id(graph_config, to),
+    ))
+}
+
+/// Generates the edge from the vertex with the given key to a satellite
+/// vertex
+fn generate_satellite_edge(graph_config: &GraphConfig, key: u32) -> Value {
+    let mut rng = stream_rng(
+        &format!("{}/{}", USECASE, SATELLITE_EDGE_COLLECTION),
+        u64::from(key),
+    );
+    let satellite = (key - 1) % graph_config.satellite_vertices.max(1) + 1;
+    generate_edge_between(
+        graph_config,
+        &mut rng,
+        key,
+        vertex_id(graph_config, key),
+        format!("{}/K{}", SATELLITE_COLLECTION, satellite),
+    )
+}
+
+/// Generates the satellite vertex with the given key
+fn generate_satellite(graph_config: &GraphConfig, key: u32) -> Value {
+    let mut rng = stream_rng(
+        &format!("{}/{}", USECASE, SATELLITE_COLLECTION),
+        u64::from(key),
+    );
+    json!(generate_document(
+        &mut rng,
+        key,
+        graph_config.vertex_size,
+        NUM_ATTRIBUTES
+    ))
+}
+
+/// The definition of the graph for the general graph API
+fn graph_definition(graph_config: &GraphConfig) -> Value {
+    let mut edge_definitions = vec![json!({
+        "collection": EDGE_COLLECTION,
+        "from": [VERTEX_COLLECTION],
+        "to": [VERTEX_COLLECTION]
+    })];
+    let mut options = json!({
+        "numberOfShards": graph_config.number_of_shards,
+        "replicationFactor": graph_config.replication_factor
+    });
+    if graph_config.satellite_vertices > 0 {
+        edge_definitions.push(json!({
+            "collection": SATELLITE_EDGE_COLLECTION,
+            "from": [VERTEX_COLLECTION],
+            "to": [SATELLITE_COLLECTION]
+        }));
+        options["satellites"] = json!([SATELLITE_COLLECTION]);
+    }
+    let mut definition = json!({
+        "name": GRAPH_NAME,
+        "edgeDefinitions": edge_definitions,
+    });
+    if graph_config.smart {
+        definition["isSmart"] = json!(true);
+        if let Some(attribute) = smart_graph_attribute(graph_config) {
+            options["smartGraphAttribute"] = json!(attribute);
+        }
+        if graph_config.disjoint {
+            definition["isDisjoint"] = json!(true);
+        }
+    }
+    definition["options"] = options;
+    definition
+}
+
+/// Checks that the Enterprise Edition options fit together
+fn check_graph_kind(graph_config: &GraphConfig) -> Result<()> {
+    let attribute = smart_graph_attribute(graph_config);
+    if graph_config.disjoint && attribute.is_none() {
+        return Err(anyhow::anyhow!(
+            "A disjoint graph needs smart: true and a smart_graph_attribute"
+        ));
+    }
+    if graph_config.satellite_vertices > 0 && attribute.is_none() {
+        return Err(anyhow::anyhow!(
+            "Satellite vertices need smart: true and a smart_graph_attribute"
+        ));
+    }
+    if graph_config.co_locate_edges && graph_config.smart {
+        return Err(anyhow::anyhow!(
+            "The co_locate_edges option is for general graphs, Enterprise Edition graphs \
+             co-locate their edges already"
+        ));
+    }
+    if !(0.0..1.0).contains(&graph_config.smart_group_skew) {
+        return Err(anyhow::anyhow!(
+            "The smart_group_skew must be at least 0 and below 1"
+        ));
+    }
+    if let Some(pct) = graph_config.intra_group_edges_pct {
+        if attribute.is_none() {
+            return Err(anyhow::anyhow!(
+                "The intra_group_edges_pct needs smart: true and a smart_graph_attribute"
+            ));
+        }
+        if pct > 100 || (graph_config.disjoint && pct < 100) {
+            return Err(anyhow::anyhow!(
+                "The intra_group_edges_pct must be at most 100, and 100 for a disjoint graph"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Inserts the documents generated for the indexes `0..slots` into a
+/// collection in batches, recorded as `operation`, with up to
+/// `insert_concurrency` batches in flight. When shutdown is requested, no
+/// new batches are started.
+#[allow(clippy::too_many_arguments)]
+async fn insert_documents<F>(
+    conn: &Connection,
+    graph_config: &GraphConfig,
+    db_name: &str,
+    collection_name: &str,
+    operation: &str,
+    slots: u64,
+    generate: F,
+    shutdown: &CancellationToken,
+) -> Result<()>
+where
+    F: Fn(u64) -> Option<Value> + Sync,
+{
+    let path = format!("/_api/document/{}", collection_name);
+    let path = path.as_str();
+    let generate = &generate;
+
+    let batches = (0..slots)
+        .step_by(BATCH_SIZE as usize)
+        .map(|start| (start, (start + BATCH_SIZE).min(slots)));
+
+    stream::iter(batches)
+        .take_until(shutdown.cancelled())
+        .map(|(batch_start, batch_end)| async move {
+            let batch: Vec<Value> = (batch_start..batch_end).filter_map(generate).collect();
+            if batch.is_empty() {
+                return Ok(());
+            }
+            let request = ApiRequest::in_database(Method::POST, db_name, path).json(&batch);
+            if let Err(e) = send_observed(conn, USECASE, operation, request, &[]).await {
+                return error_policy::tolerate(e);
+            }
+
+            METRICS
+                .documents_inserted
+                .with_label_values(&[USECASE])
+                .inc_by(batch.len() as u64);
+            info!(
+                "Inserted {} documents into collection {}",
+                batch.len(),
+                collection_name
+            );
+            Ok::<_, anyhow::Error>(())
+        })
+        .buffer_unordered(graph_config.insert_concurrency.max(1) as usize)
+        .try_collect::<Vec<()>>()
+        .await?;
+    Ok(())
+}
+
+/// Creates the database and the graph and seeds the vertices and edges.
+/// An existing database with both collections is used as it is, unless
+/// `drop_first` is set.
+async fn initialize_database(
+    conn: &Connection,
+    graph_config: &GraphConfig,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    if database_exists(conn, &db_name).await? {
+        if !graph_config.drop_first
+            && collection_exists(conn, &db_name, VERTEX_COLLECTION).await?
+            && collection_exists(conn, &db_name, EDGE_COLLECTION).await?
+        {
+            info!("Using existing graph in database {}", db_name);
+            return Ok(());
+        }
+        info!("Dropping database {}", db_name);
+        drop_database(conn, &db_name).await?;
+    }
+
+    info!("Creating database {}", db_name);
+    create_database(conn, &db_name).await?;
+    if graph_config.co_locate_edges {
+        // The graph takes over existing collections as they are
+        create_collection(
+            conn,
+            &db_name,
+            VERTEX_COLLECTION,
+            graph_config.number_of_shards,
+            graph_config.replication_factor,
+            &CollectionProperties::default(),
+        )
+        .await?;
+        let edges = json!({
+            "name": EDGE_COLLECTION,
+            "type": 3,
+            "distributeShardsLike": VERTEX_COLLECTION
+        });
+        create_collection_from(conn, &db_name, &edges).await?;
+    }
+    info!("Creating graph {} in database {}", GRAPH_NAME, db_name);
+    create_graph(conn, &db_name, &graph_definition(graph_config)).await?;
+
+    insert_documents(
+        conn,
+        graph_config,
+        &db_name,
+        VERTEX_COLLECTION,
+        "insert_vertices",
+        u64::from(graph_config.number_of_vertices),
+        |index| Some(generate_vertex(graph_config, index as u32 + 1)),
+        shutdown,
+    )
+    .await?;
+    insert_documents(
+        conn,
+        graph_config,
+        &db_name,
+        EDGE_COLLECTION,
+        "insert_edges",
+        edge_slots(graph_config),
+        |index| generate_edge(graph_config, index),
+        shutdown,
+    )
+    .await?;
+    if graph_config.satellite_vertices > 0 {
+        insert_documents(
+            conn,
+            graph_config,
+            &db_name,
+            SATELLITE_COLLECTION,
+            "insert_satellites",
+            u64::from(graph_config.satellite_vertices),
+            |index| Some(generate_satellite(graph_config, index as u32 + 1)),
+            shutdown,
+        )
+        .await?;
+        insert_documents(
+            conn,
+            graph_config,
+            &db_name,
+            SATELLITE_EDGE_COLLECTION,
+            "insert_satellite_edges",
+            u64::from(graph_config.number_of_vertices),
+            |index| Some(generate_satellite_edge(graph_config, index as u32 + 1)),
+            shutdown,
+        )
+        .await?;
+    }
+    if shutdown.is_cancelled() {
+        info!("Seeding of database {} interrupted by shutdown", db_name);
+    }
+    Ok(())
+}
+
+/// The direction in which a traversal follows the edges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Outbound,
+    Inbound,
+    Any,
+}
+
+impl Direction {
+    const ALL: [Direction; 3] = [Direction::Outbound, Direction::Inbound, Direction::Any];
+
+    /// The name used as the `operation` metrics label
+    fn operation(self) -> &'static str {
+        match self {
+            Direction::Outbound => "traversal_outbound",
+            Direction::Inbound => "traversal_inbound",
+            Direction::Any => "traversal_any",
+        }
+    }
+
+    /// The AQL keyword of the direction
+    fn keyword(self) -> &'static str {
+        match self {
+            Direction::Outbound => "OUTBOUND",
+            Direction::Inbound => "INBOUND",
+            Direction::Any => "ANY",
+        }
+    }
+
+    /// The relative weight of this direction according to the configuration
+    fn weight(self, graph_config: &GraphConfig) -> u32 {
+        match self {
+            Direction::Outbound => graph_config.outbound_pct,
+            Direction::Inbound => graph_config.inbound_pct,
+            Direction::Any => graph_config.any_pct,
+        }
+    }
+}
+
+/// Picks a random direction according to the configured direction mix
+fn choose_direction<R: Rng>(graph_config: &GraphConfig, rng: &mut R) -> Direction {
+    let total: u32 = Direction::ALL
+        .iter()
+        .map(|direction| direction.weight(graph_config))
+        .sum();
+    let mut pick = rng.random_range(0..total.max(1));
+    for direction in Direction::ALL {
+        let weight = direction.weight(graph_config);
+        if pick < weight {
+            return direction;
+        }
+        pick -= weight;
+    }
+    Direction::Outbound
+}
+
+/// Checks the parameters of the steady-state operations
+fn check_workload(graph_config: &GraphConfig) -> Result<()> {
+    if graph_config.min_depth > graph_config.max_depth {
+        return Err(anyhow::anyhow!(
+            "The graph use case needs min_depth <= max_depth"
+        ));
+    }
+    let special_pct =
+        graph_config.write_pct + graph_config.shortest_path_pct + graph_config.k_shortest_paths_pct;
+    if special_pct > 100 {
+        return Err(anyhow::anyhow!(
+            "The graph use case needs write_pct + shortest_path_pct + k_shortest_paths_pct <= 100"
+        ));
+    }
+    if graph_config.write_pct < 100
+        && Direction::ALL
+            .iter()
+            .all(|direction| direction.weight(graph_config) == 0)
+    {
+        return Err(anyhow::anyhow!(
+            "The graph use case needs a positive weight for at least one direction"
+        ));
+    }
+    Ok(())
+}
+
+/// Runs one traversal from a random start vertex, searches shortest paths
+/// between two random vertices or updates a random vertex. Traversals are
+/// recorded per direction and path searches per kind, so that their
+/// latency percentiles are reported separately from the writes.
+async fn perform_operation(
+    conn: &Connection,
+    graph_config: &GraphConfig,
+    mut rng: StdRng,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+    let key = sample_key(
+        &graph_config.start_vertex_distribution,
+        graph_config.number_of_vertices,
+        &mut rng,
+    );
+
+    let roll = rng.random_range(0..100);
+    if roll < graph_config.write_pct {
+        let mut update = generate_document(&mut rng, key, graph_config.vertex_size, NUM_ATTRIBUTES);
+        update.remove("_key");
+        let request = ApiRequest::in_database(
+            Method::PATCH,
+            &db_name,
+            &format!("/_api/document/{}", vertex_id(graph_config, key)),
+        )
+        .json(&update);
+        send_observed(conn, USECASE, "update_vertex", request, &[]).await?;
+        return Ok(());
+    }
+
+    let direction = choose_direction(graph_config, &mut rng);
+    let mut bind_vars = Map::new();
+    bind_vars.insert("start".to_string(), json!(vertex_id(graph_config, key)));
+    bind_vars.insert("graph".to_string(), json!(GRAPH_NAME));
+    let roll = roll - graph_config.write_pct;
+    let (operation, query) =
+        if roll < graph_config.shortest_path_pct + graph_config.k_shortest_paths_pct {
+            let target = sample_key(
+                &graph_config.start_vertex_distribution,
+                graph_config.number_of_vertices,
+                &mut rng,
+            );
+            bind_vars.insert("target".to_string(), json!(vertex_id(graph_config, target)));
+            if roll < graph_config.shortest_path_pct {
+                let query = format!(
+                    "FOR v IN {} SHORTEST_PATH @start TO @target GRAPH @graph \
+                 COLLECT WITH COUNT INTO length RETURN length",
+                    direction.keyword()
+                );
+                ("shortest_path", query)
+            } else {
+                bind_vars.insert("k".to_string(), json!(graph_config.k_paths));
+                let query = format!(
+                    "FOR p IN {} K_SHORTEST_PATHS @start TO @target GRAPH @graph \
+                 LIMIT @k RETURN LENGTH(p.edges)",
+                    direction.keyword()
+                );
+                ("k_shortest_paths", query)
+            }
+        } else {
+            bind_vars.insert("min".to_string(), json!(graph_config.min_depth));
+            bind_vars.insert("max".to_string(), json!(graph_config.max_depth));
+            let query = format!(
+            "FOR v IN @min..@max {} @start GRAPH @graph COLLECT WITH COUNT INTO paths RETURN paths",
+            direction.keyword()
+        );
+            (direction.operation(), query)
+        };
+
+    // All queries return a single count or at most `k_paths` lengths, so
+    // that one round trip fetches all results
+    let start = Instant::now();
+    let result = run_query(
+        conn,
+        &db_name,
+        &query,
+        &bind_vars,
+        graph_config.k_paths.max(1),
+        |_| {},
+    )
+    .await;
+    observe_request(USECASE, operation, start.elapsed(), result.is_ok());
+    result.map_err(|e| {
+        anyhow::anyhow!(
+            "Operation {} from {} failed: {}",
+            operation,
+            vertex_id(graph_config, key),
+            e
+        )
+    })?;
+    Ok(())
+}
+
+/// Inserts a random edge and returns the key the server chose for it
+async fn insert_churn_edge<R: Rng>(
+    conn: &Connection,
+    db_name: &str,
+    graph_config: &GraphConfig,
+    rng: &mut R,
+) -> Result<String> {
+    let n = graph_config.number_of_vertices;
+    let slot = rng.random_range(0..edge_slots(graph_config).max(1));
+    // A grid has slots without an edge, these get a random one instead
+    let (from, to) = edge_ends(graph_config, slot, rng)
+        .unwrap_or_else(|| (rng.random_range(1..=n), rng.random_range(1..=n)));
+    let to = place_edge_end(graph_config, from, to, rng);
+    let mut edge = generate_edge_between(
+        graph_config,
+        rng,
+        0,
+        vertex_id(graph_config, from),
+        vertex_id(graph_config, to),
+    );
+    if let Some(edge) = edge.as_object_mut() {
+        edge.remove("_key");
+    }
+    let request = ApiRequest::in_database(
+        Method::POST,
+        db_name,
+        &format!("/_api/document/{}", EDGE_COLLECTION),
+    )
+    .json(&edge);
+    let response = send_observed(conn, USECASE, "insert_edge", request, &[]).await?;
+    response.json::<Value>()?["_key"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("Edge insert returned no key"))
+}
+
+async fn delete_churn_edge(conn: &Connection, db_name: &str, key: &str) -> Result<()> {
+    let request = ApiRequest::in_database(
+        Method::DELETE,
+        db_name,
+        &format!("/_api/document/{}/{}", EDGE_COLLECTION, key),
+    );
+    send_observed(conn, USECASE, "delete_edge", request, &[]).await?;
+    Ok(())
+}
+
+/// Counts the edges and the edges whose start or end vertex is missing,
+/// and compares them with the expectation. A failed check is recorded as
+/// an error of the operation `check_edges`.
+async fn check_edges(
+    conn: &Connection,
+    db_name: &str,
+    expected: u64,
+    churned: &VecDeque<String>,
+) -> Result<()> {
+    let start = Instant::now();
+    let result = async {
+        let actual = collection_count(conn, db_name, EDGE_COLLECTION).await?;
+        let mut dangling = 0;
+        run_query(
+            conn,
+            db_name,
+            &format!(
+                "FOR e IN {} FILTER DOCUMENT(e._from) == null OR DOCUMENT(e._to) == null \
+                 COLLECT WITH COUNT INTO dangling RETURN dangling",
+                EDGE_COLLECTION
+            ),
+            &Map::new(),
+            1,
+            |batch| dangling += batch.first().and_then(Value::as_u64).unwrap_or(0),
+        )
+        .await?;
+        let mut missing = 0;
+        run_query(
+            conn,
+            db_name,
+            &format!(
+                "FOR key IN @keys FILTER DOCUMENT({}, key) == null \
+                 COLLECT WITH COUNT INTO missing RETURN missing",
+                EDGE_COLLECTION
+            ),
+            &Map::from_iter([("keys".to_string(), json!(churned))]),
+            1,
+            |batch| missing += batch.first().and_then(Value::as_u64).unwrap_or(0),
+        )
+        .await?;
+        Ok::<_, anyhow::Error>((actual, dangling, missing))
+    }
+    .await;
+
+    let consistent = matches!(result, Ok((actual, 0, 0)) if actual == expected);
+    observe_request(USECASE, "check_edges", start.elapsed(), consistent);
+    let (actual, dangling, missing) = result?;
+    if consistent {
+        info!("Edge check: {} edges as expected", actual);
+    } else {
+        error!(
+            "Edge check failed: {} edges instead of {}, {} dangling edges, {} of {} churned edges missing",
+            actual,
+            expected,
+            dangling,
+            missing,
+            churned.len()
+        );
+    }
+    Ok(())
+}
+
+/// Inserts and deletes edges at the configured rate while the traversals
+/// run, keeping at most `edge_churn_window` churned edges, and
+/// periodically checks that the number of edges matches the number of
+/// inserts and deletes and that no edge is dangling
+async fn churn_edges(conn: Connection, graph_config: GraphConfig, shutdown: CancellationToken) {
+    let db_name = database_name(conn.config());
+    let mut rng = stream_rng(&format!("{}/churn", USECASE), 0);
+    let pacer = Pacer::new(graph_config.edge_churn_ops_per_sec);
+    // The edges are counted at the start, so that edges left over from an
+    // earlier run do not disturb the check
+    let mut expected = match collection_count(&conn, &db_name, EDGE_COLLECTION).await {
+        Ok(count) => count,
+        Err(e) => {
+            error!("Failed to count edges, edge churn disabled: {}", e);
+            return;
+        }
+    };
+    let mut churned = VecDeque::new();
+    let check_interval = Duration::from_secs(graph_config.edge_check_interval_secs);
+    let mut last_check = Instant::now();
+
+    loop {
+        tokio::select! {
+            _ = pacer.wait() => {}
+            _ = shutdown.cancelled() => break,
+        }
+        match insert_churn_edge(&conn, &db_name, &graph_config, &mut rng).await {
+            Ok(key) => {
+                churned.push_back(key);
+                expected += 1;
+            }
+            Err(e) => debug!("Edge churn insert failed: {}", e),
+        }
+        if churned.len() > graph_config.edge_churn_window as usize {
+            if let Some(key) = churned.pop_front() {
+                match delete_churn_edge(&conn, &db_name, &key).await {
+                    Ok(()) => expected -= 1,
+                    Err(e) => debug!("Edge churn delete of {} failed: {}", key, e),
+                }
+            }
+        }
+
+        if !check_interval.is_zero() && last_check.elapsed() >= check_interval {
+            if let Err(e) = check_edges(&conn, &db_name, expected, &churned).await {
+                error!("Edge check could not run: {}", e);
+            }
+            last_check = Instant::now();
+        }
+    }
+}
+
+#[async_trait]
+impl UseCase for GraphUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        if self.graph_config.number_of_vertices == 0 {
+            return Err(anyhow::anyhow!(
+                "The graph use case needs at least one vertex"
+            ));
+        }
+        check_workload(&self.graph_config)?;
+        check_graph_kind(&self.graph_config)?;
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize_database(&connection, &self.graph_config, shutdown).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let churn = (self.graph_config.edge_churn_ops_per_sec > 0.0).then(|| {
+            tokio::spawn(churn_edges(
+                connection.clone(),
+                self.graph_config.clone(),
+                shutdown.clone(),
+            ))
+        });
+
+        let pacer = Arc::new(Pacer::new(self.graph_config.target_ops_per_sec));
+        // The traversal mix can be changed by reloading the configuration
+        let graph_config = Arc::new(RwLock::new(Arc::new(self.graph_config.clone())));
+        let reloaded = Arc::clone(&graph_config);
+        control::on_reload(USECASE, move |config| {
+            if let Err(e) = check_workload(&config.graph) {
+                warn!("Ignoring reloaded graph configuration: {}", e);
+                return;
+            }
+            control::apply_load(
+                USECASE,
+                config.graph.concurrency,
+                config.graph.target_ops_per_sec,
+            );
+            *reloaded.write().unwrap() = Arc::new(config.graph.clone());
+        });
+        run_workers(
+            USECASE,
+            self.graph_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let graph_config = Arc::clone(&graph_config.read().unwrap());
+                async move { perform_operation(&connection, &graph_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        if let Some(churn) = churn {
+            let _ = churn.await;
+        }
+        Ok(())
+    }
+
+    async fn verify_seed(&self) -> Result<Vec<CollectionCount>> {
+        let connection = self.connection()?;
+        let db_name = database_name(&self.db_config);
+        let number_of_vertices = u64::from(self.graph_config.number_of_vertices);
+        let mut expected_counts = vec![
+            (VERTEX_COLLECTION, number_of_vertices),
+            (EDGE_COLLECTION, expected_edges(&self.graph_config)),
+        ];
+        if self.graph_config.satellite_vertices > 0 {
+            expected_counts.push((
+                SATELLITE_COLLECTION,
+                u64::from(self.graph_config.satellite_vertices),
+            ));
+            expected_counts.push((SATELLITE_EDGE_COLLECTION, number_of_vertices));
+        }
+        let mut counts = Vec::new();
+        for (collection, expected) in expected_counts {
+            counts.push(CollectionCount::fetch(&connection, &db_name, collection, expected).await?);
+        }
+        Ok(counts)
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.graph_config.drop_on_exit {
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&self.connection()?, &db_name).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_grid_edges_match_expected_count() {
+        let mut graph_config = Config::from_file("config.yaml").unwrap().graph;
+        graph_config.topology = GraphTopology::Grid;
+        let mut rng = StdRng::seed_from_u64(1);
+        for n in [1, 2, 7, 9, 10, 100] {
+            graph_config.number_of_vertices = n;
+            let edges: Vec<(u32, u32)> = (0..edge_slots(&graph_config))
+                .filter_map(|index| edge_ends(&graph_config, index, &mut rng))
+                .collect();
+            assert_eq!(edges.len() as u64, expected_edges(&graph_config));
+            assert!(edges.iter().all(|(from, to)| from < to && *to <= n));
+        }
+    }
+
+    #[test]
+    fn test_smart_groups_and_intra_group_edges() {
+        let mut graph_config = Config::from_file("config.yaml").unwrap().graph;
+        graph_config.smart = true;
+        graph_config.smart_graph_attribute = Some("community".to_string());
+        graph_config.smart_groups = 10;
+        graph_config.number_of_vertices = 10_000;
+        let n = graph_config.number_of_vertices;
+
+        // Without skew the vertices take turns, like before
+        assert!((1..=n).all(|key| group_of(&graph_config, key) == key % 10));
+        assert_eq!(vertex_key(&graph_config, 13), "g3:K13");
+        assert!(check_graph_kind(&graph_config).is_ok());
+        graph_config.co_locate_edges = true;
+        assert!(check_graph_kind(&graph_config).is_err());
+        graph_config.co_locate_edges = false;
+
+        graph_config.smart_group_skew = 0.5;
+        let mut sizes = [0u32; 10];
+        for key in 1..=n {
+            sizes[group_of(&graph_config, key) as usize] += 1;
+        }
+        assert!(sizes.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert!(sizes[0] > 3 * sizes[9]);
+        for group in 0..10 {
+            for key in [1, 4_321, n] {
+                let moved = move_to_group(&graph_config, key, group).unwrap();
+                assert!((1..=n).contains(&moved));
+                assert_eq!(group_of(&graph_config, moved), group);
+            }
+        }
+
+        graph_config.intra_group_edges_pct = Some(80);
+        let mut rng = StdRng::seed_from_u64(1);
+        let intra = (0..10_000)
+            .filter(|_| {
+                let from = rng.random_range(1..=n);
+                let to = rng.random_range(1..=n);
+                let to = place_edge_end(&graph_config, from, to, &mut rng);
+                group_of(&graph_config, from) == group_of(&graph_config, to)
+            })
+            .count();
+        assert!(
+            (7_500..8_500).contains(&intra),
+            "{} intra-group edges",
+            intra
+        );
+
+        graph_config.disjoint = true;
+        assert!(check_graph_kind(&graph_config).is_err());
+        graph_config.intra_group_edges_pct = None;
+        assert!((1..=n).all(|from| {
+            let to = place_edge_end(&graph_config, from, n + 1 - from, &mut rng);
+            group_of(&graph_config, from) == group_of(&graph_config, to)
+        }));
+    }
+}
@@ -0,0 +1,102 @@
+use anyhow::Result;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// The annotated configuration shipped with adb_loader, which explains
+/// every option in its `comment` fields
+pub const FULL: &str = include_str!("../config.yaml");
+
+/// The smallest useful configuration: the CRUD use case against a single
+/// server, everything else at its defaults
+pub const MINIMAL: &str = r#"---
+comment: |
+  A minimal configuration for adb_loader: it seeds three collections with
+  100000 documents each in the database `adb_loader_crud` and then runs
+  CRUD operations against them until the program is stopped. All options
+  which are left out have their defaults, `adb_loader init-config --full`
+  writes a configuration with every option and its explanation.
+  adb_loader reads, but ignores all fields called `comment`.
+version: "2"
+database:
+  comment: |
+    Requests are spread round-robin over all `endpoints`. All databases
+    of the loader get the `prefix`, so that `adb_loader cleanup` can find
+    them again.
+  endpoints:
+    - http://localhost:8529
+  username: root
+  password: ""
+  prefix: "adb_loader_"
+active_usecases:
+  crud:
+    on: true
+    threads: 4
+  graph:
+    on: false
+    threads: 4
+metrics_port: 7777
+crud:
+  comment: |
+    Creates `number_of_collections` collections with `number_of_documents`
+    documents of about `document_size` bytes each, then reads, updates,
+    replaces and re-inserts documents with `concurrency` workers.
+  number_of_collections: 3
+  number_of_shards: 3
+  replication_factor: 1
+  number_of_documents: 100000
+  document_size: 1000
+  drop_first: false
+  insert_concurrency: 10
+  concurrency: 10
+graph:
+  comment: |
+    Only used if the graph use case is on in `active_usecases`.
+  number_of_vertices: 100000
+  number_of_edges: 150000
+  number_of_shards: 3
+  replication_factor: 1
+  smart: false
+  vertex_size: 200
+  edge_size: 100
+  drop_first: false
+"#;
+
+/// Returns the example configuration, with every option if `full` is set
+pub fn example(full: bool) -> &'static str {
+    if full {
+        FULL
+    } else {
+        MINIMAL
+    }
+}
+
+/// Writes the example configuration to `path`, which must not exist yet,
+/// so that an edited configuration is never overwritten
+///
+/// # Errors
+/// Fails if the file exists or cannot be written
+pub fn write(path: &Path, full: bool) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("Cannot create {}: {}", path.display(), e))?;
+    file.write_all(example(full).as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_examples_are_valid() {
+        let minimal = Config::from_yaml(MINIMAL).unwrap();
+        assert!(minimal.active_usecases.crud.on);
+        assert!(minimal.validate().unwrap().is_empty());
+        let full = Config::from_yaml(FULL).unwrap();
+        assert!(full.transactions.is_some());
+    }
+}
@@ -0,0 +1,252 @@
+use hdrhistogram::Histogram;
+use log::info;
+use std::collections::BTreeMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Highest latency we can record, one hour in microseconds. Larger values
+/// are clamped to this.
+const MAX_LATENCY_US: u64 = 3_600_000_000;
+
+/// Latency percentiles of one operation of one use case, in microseconds
+#[derive(Debug, Clone, Default)]
+pub struct LatencySummary {
+    pub usecase: String,
+    pub operation: String,
+    pub count: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub max: u64,
+}
+
+impl LatencySummary {
+    fn from_histogram(usecase: &str, operation: &str, histogram: &Histogram<u64>) -> Self {
+        LatencySummary {
+            usecase: usecase.to_string(),
+            operation: operation.to_string(),
+            count: histogram.len(),
+            p50: histogram.value_at_quantile(0.5),
+            p90: histogram.value_at_quantile(0.9),
+            p99: histogram.value_at_quantile(0.99),
+            p999: histogram.value_at_quantile(0.999),
+            max: histogram.max(),
+        }
+    }
+
+    /// Formats the percentiles in milliseconds for log output
+    pub fn format_ms(&self) -> String {
+        let ms = |us: u64| us as f64 / 1000.0;
+        format!(
+            "{}/{}: n={} p50={:.2}ms p90={:.2}ms p99={:.2}ms p99.9={:.2}ms max={:.2}ms",
+            self.usecase,
+            self.operation,
+            self.count,
+            ms(self.p50),
+            ms(self.p90),
+            ms(self.p99),
+            ms(self.p999),
+            ms(self.max)
+        )
+    }
+}
+
+type Key = (String, String);
+
+/// Keeps HDR histograms of request latencies per use case and operation,
+/// both for the whole run and for the current reporting interval. Use
+/// cases can also be watched in named windows, which keep a histogram over
+/// all their operations, e.g. for the phases of the load.
+pub struct LatencyRecorder {
+    total: Mutex<BTreeMap<Key, Histogram<u64>>>,
+    /// The latencies measured from the intended send time, see
+    /// `record_corrected`
+    corrected: Mutex<BTreeMap<Key, Histogram<u64>>>,
+    interval: Mutex<BTreeMap<Key, Histogram<u64>>>,
+    windows: Mutex<BTreeMap<String, (String, Histogram<u64>)>>,
+}
+
+/// The total histograms at some point in time, to compute the percentiles
+/// since then without resetting the interval of the recorder
+#[derive(Default)]
+pub struct LatencySnapshot(BTreeMap<Key, Histogram<u64>>);
+
+/// The process wide latency recorder
+pub static LATENCIES: LazyLock<LatencyRecorder> = LazyLock::new(LatencyRecorder::new);
+
+fn new_histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, MAX_LATENCY_US, 3).expect("Invalid histogram bounds")
+}
+
+fn record_into(map: &Mutex<BTreeMap<Key, Histogram<u64>>>, key: &Key, value: u64) {
+    let mut map = map.lock().unwrap();
+    match map.get_mut(key) {
+        Some(histogram) => histogram.saturating_record(value),
+        None => {
+            let mut histogram = new_histogram();
+            histogram.saturating_record(value);
+            map.insert(key.clone(), histogram);
+        }
+    }
+}
+
+fn summarize(map: &Mutex<BTreeMap<Key, Histogram<u64>>>) -> Vec<LatencySummary> {
+    map.lock()
+        .unwrap()
+        .iter()
+        .map(|((usecase, operation), histogram)| {
+            LatencySummary::from_histogram(usecase, operation, histogram)
+        })
+        .collect()
+}
+
+impl LatencyRecorder {
+    pub fn new() -> Self {
+        LatencyRecorder {
+            total: Mutex::new(BTreeMap::new()),
+            corrected: Mutex::new(BTreeMap::new()),
+            interval: Mutex::new(BTreeMap::new()),
+            windows: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Records the latency of a single request
+    pub fn record(&self, usecase: &str, operation: &str, duration: Duration) {
+        let key = (usecase.to_string(), operation.to_string());
+        let value = (duration.as_micros() as u64).clamp(1, MAX_LATENCY_US);
+        record_into(&self.total, &key, value);
+        record_into(&self.interval, &key, value);
+        for (watched, histogram) in self.windows.lock().unwrap().values_mut() {
+            if watched == usecase {
+                histogram.saturating_record(value);
+            }
+        }
+    }
+
+    /// Returns the percentiles of every operation performed since the
+    /// snapshot was taken and advances the snapshot to now
+    pub fn summaries_since(&self, snapshot: &mut LatencySnapshot) -> Vec<LatencySummary> {
+        let total = self.total.lock().unwrap().clone();
+        let mut summaries = Vec::new();
+        for ((usecase, operation), histogram) in &total {
+            let mut delta = histogram.clone();
+            // The total only grows, so the previous one is contained in it
+            let subtracted = match snapshot.0.get(&(usecase.clone(), operation.clone())) {
+                Some(previous) => delta.subtract(previous).is_ok(),
+                None => true,
+            };
+            if subtracted && !delta.is_empty() {
+                summaries.push(LatencySummary::from_histogram(usecase, operation, &delta));
+            }
+        }
+        snapshot.0 = total;
+        summaries
+    }
+
+    /// Starts keeping a named window histogram over all operations of a
+    /// use case, replacing an earlier window of the same name
+    pub fn watch(&self, window: &str, usecase: &str) {
+        self.windows
+            .lock()
+            .unwrap()
+            .insert(window.to_string(), (usecase.to_string(), new_histogram()));
+    }
+
+    /// Returns the percentiles of a window since the last call and starts
+    /// a new window
+    pub fn take_window(&self, window: &str) -> Option<LatencySummary> {
+        let mut windows = self.windows.lock().unwrap();
+        let (usecase, histogram) = windows.get_mut(window)?;
+        let summary = LatencySummary::from_histogram(usecase, "all", histogram);
+        histogram.reset();
+        Some(summary)
+    }
+
+    /// Records the latency of a whole operation measured from the time at
+    /// which it was supposed to start, in addition to `record` for its
+    /// requests. With a target rate, operations which start late because
+    /// all workers are busy would otherwise hide the stall (coordinated
+    /// omission).
+    pub fn record_corrected(&self, usecase: &str, operation: &str, duration: Duration) {
+        let key = (usecase.to_string(), operation.to_string());
+        let value = (duration.as_micros() as u64).clamp(1, MAX_LATENCY_US);
+        record_into(&self.corrected, &key, value);
+    }
+
+    /// Returns the percentiles over the whole run so far
+    pub fn total(&self) -> Vec<LatencySummary> {
+        summarize(&self.total)
+    }
+
+    /// Returns the corrected percentiles over the whole run so far, only
+    /// operations of use cases with a target rate have them
+    pub fn total_corrected(&self) -> Vec<LatencySummary> {
+        summarize(&self.corrected)
+    }
+
+    /// Returns the percentiles since the last call and starts a new interval
+    pub fn take_interval(&self) -> Vec<LatencySummary> {
+        let mut interval = self.interval.lock().unwrap();
+        let summaries: Vec<LatencySummary> = interval
+            .iter()
+            .filter(|(_, histogram)| !histogram.is_empty())
+            .map(|((usecase, operation), histogram)| {
+                LatencySummary::from_histogram(usecase, operation, histogram)
+            })
+            .collect();
+        for histogram in interval.values_mut() {
+            histogram.reset();
+        }
+        summaries
+    }
+}
+
+impl Default for LatencyRecorder {
+    fn default() -> Self {
+        LatencyRecorder::new()
+    }
+}
+
+/// Periodically logs the latency percentiles of the last interval for
+/// every operation which was performed in it
+pub async fn report_latencies(interval: Duration, shutdown: CancellationToken) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => return,
+        }
+        for summary in LATENCIES.take_interval() {
+            info!("Latency {}", summary.format_ms());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_percentiles_and_interval_reset() {
+        let recorder = LatencyRecorder::new();
+        for ms in 1..=100 {
+            recorder.record("crud", "read", Duration::from_millis(ms));
+        }
+
+        let interval = recorder.take_interval();
+        assert_eq!(interval.len(), 1);
+        let summary = &interval[0];
+        assert_eq!(summary.count, 100);
+        // HDR histograms have a relative precision of 3 significant digits
+        assert!((49_900..=50_100).contains(&summary.p50));
+        assert!((98_900..=99_100).contains(&summary.p99));
+        assert!((99_900..=100_100).contains(&summary.max));
+
+        // The interval is reset, the total is not
+        assert!(recorder.take_interval().is_empty());
+        assert_eq!(recorder.total()[0].count, 100);
+    }
+}
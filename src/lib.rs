@@ -0,0 +1,86 @@
+//! `adb_loader` produces constant load on an ArangoDB cluster.
+//!
+//! The binary is a thin command line wrapper around this library, which
+//! can also be used to drive the load generator from another Rust program:
+//!
+//! ```no_run
+//! use adb_loader::{config::Config, run_usecase, CancellationToken};
+//!
+//! let config = Config::from_file("config.yaml").unwrap();
+//! let shutdown = CancellationToken::new();
+//! run_usecase("crud", &config, shutdown).unwrap();
+//! ```
+
+pub mod adaptive;
+pub mod admin;
+pub mod arangodb;
+pub mod banking;
+pub mod breaker;
+pub mod capacity;
+pub mod config;
+pub mod conflicts;
+pub mod control;
+pub mod coordination;
+pub mod crud;
+pub mod dashboard;
+pub mod ddl;
+pub mod distribution;
+pub mod documents;
+pub mod endpoints;
+pub mod error_policy;
+pub mod export;
+pub mod graph;
+pub mod init_config;
+pub mod latency;
+pub mod logging;
+pub mod metrics;
+pub mod naming;
+pub mod pacer;
+pub mod phases;
+pub mod pregel;
+pub mod queries;
+pub mod random;
+pub mod replication_lag;
+pub mod report;
+pub mod runtime;
+pub mod search;
+pub mod server_metrics;
+pub mod smartjoin;
+pub mod supervisor;
+pub mod text;
+pub mod timeseries;
+pub mod traces;
+pub mod transactions;
+pub mod ttl;
+pub mod unique;
+pub mod unix;
+pub mod usecase;
+pub mod users;
+pub mod velocypack;
+pub mod vst;
+
+use anyhow::Result;
+use config::Config;
+use usecase::Registry;
+
+pub use tokio_util::sync::CancellationToken;
+
+/// Runs the named built-in use case with the given configuration on the
+/// calling thread. This blocks until the use case finishes or `shutdown`
+/// is cancelled, use cases get their tokio runtime from `runtime`. In seed mode
+/// (see `Config::mode`) it returns as soon as the data is seeded and
+/// verified.
+///
+/// # Errors
+/// Fails if the use case is unknown or disabled in the configuration, or
+/// if the use case itself fails.
+pub fn run_usecase(name: &str, config: &Config, shutdown: CancellationToken) -> Result<()> {
+    if let Some(seed) = config.seed {
+        random::set_seed(seed);
+    }
+    runtime::configure(config)?;
+    let usecase = Registry::with_builtin()
+        .create(name, config)
+        .ok_or_else(|| anyhow::anyhow!("Unknown or disabled use case: {}", name))?;
+    usecase::run_blocking(usecase, shutdown, config.mode, &config.seed_check)
+}
@@ -0,0 +1,241 @@
+use clap::ValueEnum;
+use env_logger::fmt::Formatter;
+use log::kv::{self, Key, Value, VisitSource};
+use log::Record;
+use serde_json::{Map, Number};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The format of the log records
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// The plain lines of env_logger
+    #[default]
+    Text,
+    /// One JSON object per line, for ingestion into log stores like
+    /// Elasticsearch or Loki
+    Json,
+}
+
+/// Initializes the global logger from `RUST_LOG` with the given format.
+/// The log goes to `target` if given, to standard error otherwise.
+pub fn init(format: LogFormat, target: Option<Box<dyn Write + Send>>) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let timestamp = buf.timestamp_millis().to_string();
+            write_json(buf, &timestamp, record)
+        });
+    }
+    if let Some(target) = target {
+        builder.target(env_logger::Target::Pipe(target));
+    }
+    builder.init();
+}
+
+/// A log file which is rotated once it reaches a size or an age. The
+/// rotated files get the suffixes `.1` (the most recent) to `.<keep>`,
+/// older ones are deleted, so that long runs neither lose their log nor
+/// fill the disk.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    opened: Instant,
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    keep: usize,
+}
+
+impl RotatingFile {
+    /// Opens the log file, an existing file is appended to
+    pub fn open(
+        path: &Path,
+        max_bytes: Option<u64>,
+        max_age: Option<Duration>,
+        keep: usize,
+    ) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile {
+            path: path.to_path_buf(),
+            file,
+            size,
+            opened: Instant::now(),
+            max_bytes,
+            max_age,
+            keep,
+        })
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn due(&self) -> bool {
+        self.max_bytes.is_some_and(|max| self.size >= max)
+            || self.max_age.is_some_and(|max| self.opened.elapsed() >= max)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.keep).rev() {
+                let from = self.rotated(index);
+                if from.exists() {
+                    fs::rename(from, self.rotated(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The logger writes whole records, so records are not split
+        // between two files
+        if self.size > 0 && self.due() {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Writes the log to several targets, like the console and a file
+pub struct Tee(pub Vec<Box<dyn Write + Send>>);
+
+impl Write for Tee {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for target in &mut self.0 {
+            target.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for target in &mut self.0 {
+            target.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn write_json(buf: &mut Formatter, timestamp: &str, record: &Record) -> io::Result<()> {
+    let line = serde_json::to_string(&json_record(timestamp, record))?;
+    writeln!(buf, "{}", line)
+}
+
+/// Converts a log record into a JSON object with the timestamp, the level,
+/// the module, the message and all key-values of the record, like the use
+/// case, the collection or the latency of an operation
+pub fn json_record(timestamp: &str, record: &Record) -> Map<String, serde_json::Value> {
+    let mut object = Map::new();
+    object.insert("timestamp".into(), timestamp.into());
+    object.insert("level".into(), record.level().as_str().into());
+    object.insert("target".into(), record.target().into());
+    object.insert("message".into(), record.args().to_string().into());
+    let mut fields = JsonFields(&mut object);
+    // Collecting into a map cannot fail
+    let _ = record.key_values().visit(&mut fields);
+    object
+}
+
+struct JsonFields<'a>(&'a mut Map<String, serde_json::Value>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.insert(key.as_str().to_string(), json_value(&value));
+        Ok(())
+    }
+}
+
+/// Keeps numbers and booleans, everything else becomes a string
+fn json_value(value: &Value) -> serde_json::Value {
+    if let Some(n) = value.to_u64() {
+        n.into()
+    } else if let Some(n) = value.to_i64() {
+        n.into()
+    } else if let Some(n) = value.to_f64().and_then(Number::from_f64) {
+        serde_json::Value::Number(n)
+    } else if let Some(b) = value.to_bool() {
+        b.into()
+    } else {
+        value.to_string().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_record_has_message_and_fields() {
+        let fields: [(&str, Value); 5] = [
+            ("usecase", "crud".into()),
+            ("batch_start", 1u32.into()),
+            ("batch_end", 1000u32.into()),
+            ("latency_ms", 12.5.into()),
+            ("retried", false.into()),
+        ];
+        let object = json_record(
+            "2026-01-01T00:00:00.000Z",
+            &Record::builder()
+                .args(format_args!("Inserted documents"))
+                .level(log::Level::Info)
+                .target("adb_loader::crud")
+                .key_values(&fields)
+                .build(),
+        );
+        assert_eq!(object["timestamp"], "2026-01-01T00:00:00.000Z");
+        assert_eq!(object["level"], "INFO");
+        assert_eq!(object["target"], "adb_loader::crud");
+        assert_eq!(object["message"], "Inserted documents");
+        assert_eq!(object["usecase"], "crud");
+        assert_eq!(object["batch_start"], 1);
+        assert_eq!(object["batch_end"], 1000);
+        assert_eq!(object["latency_ms"], 12.5);
+        assert_eq!(object["retried"], false);
+    }
+
+    #[test]
+    fn test_rotating_file_keeps_the_newest_files() {
+        let dir = std::env::temp_dir().join(format!("adb_loader_log_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("loader.log");
+        let mut file = RotatingFile::open(&path, Some(10), None, 2).unwrap();
+        for line in [
+            "first line\n",
+            "second line\n",
+            "third line\n",
+            "fourth line\n",
+        ] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        let read = |path: &Path| fs::read_to_string(path).unwrap();
+        assert_eq!(read(&path), "fourth line\n");
+        assert_eq!(read(&file.rotated(1)), "third line\n");
+        assert_eq!(read(&file.rotated(2)), "second line\n");
+        assert!(!file.rotated(3).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,40 +1,247 @@
-mod arangodb;
-mod config;
-mod crud;
-
+use adb_loader::admin;
+use adb_loader::config::Mode;
+use adb_loader::coordination::{self, Coordinator};
+use adb_loader::dashboard::{run_dashboard, LogBuffer};
+use adb_loader::error_policy::{self, RunError, SetupFailed};
+use adb_loader::init_config;
+use adb_loader::latency::{report_latencies, LATENCIES};
+use adb_loader::logging::{self, LogFormat, RotatingFile, Tee};
+use adb_loader::report::{self, Report, Tolerances};
+use adb_loader::usecase::Registry;
+use adb_loader::{
+    capacity, config, control, export, metrics, naming, random, replication_lag, server_metrics,
+    traces, CancellationToken,
+};
 use anyhow::Result;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use log::{error, info};
-use serde_yaml;
 use std::path::PathBuf;
-use std::thread;
+use std::process::ExitCode;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, SignalKind};
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
-struct Args {
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Without a subcommand, the load is run
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Set up the enabled use cases and produce load (the default)
+    Run(RunArgs),
+    /// Check the configuration and the connectivity to all endpoints
+    /// without producing load
+    Validate(ConfigArgs),
+    /// Drop all databases whose name starts with the configured prefix
+    Cleanup(CleanupArgs),
+    /// Show the collections of all databases with the configured prefix
+    /// and their document counts
+    Status(ConfigArgs),
+    /// Write an annotated example configuration
+    InitConfig(InitConfigArgs),
+}
+
+#[derive(Args)]
+struct InitConfigArgs {
+    /// Write every available option with its explanation instead of a
+    /// minimal configuration
+    #[arg(long)]
+    full: bool,
+
+    /// Write the configuration to this file, which must not exist yet,
+    /// instead of to stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct CleanupArgs {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    /// Only drop the databases which other runs created more than
+    /// `database.stale_after_secs` ago
+    #[arg(long)]
+    stale: bool,
+}
+
+#[derive(Args)]
+struct ConfigArgs {
     /// Path to the configuration file
     #[arg(short, long, default_value = "config.yaml")]
     config: PathBuf,
+
+    /// Replace a value of the configuration, e.g.
+    /// `--set crud.number_of_documents=1000000`, can be repeated
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    set: Vec<String>,
 }
 
-fn main() -> Result<()> {
-    // Initialize the logger
-    env_logger::init();
+#[derive(Args)]
+struct RunArgs {
+    /// Path to the configuration file
+    #[arg(short, long, default_value = "config.yaml")]
+    config: PathBuf,
+
+    /// Replace a value of the configuration, e.g.
+    /// `--set crud.number_of_documents=1000000`, can be repeated
+    #[arg(long = "set", value_name = "PATH=VALUE")]
+    set: Vec<String>,
 
-    let args = Args::parse();
+    /// Only create and seed the data of the enabled use cases, verify it
+    /// and exit, regardless of the `mode` in the configuration
+    #[arg(long)]
+    seed_only: bool,
 
-    let config = config::Config::from_file(&args.config)?;
+    /// Seed for all random generators, overrides `seed` in the configuration
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Write a JSON report with the counts, errors and latencies of the
+    /// run to this file at the end
+    #[arg(long)]
+    report_file: Option<PathBuf>,
+
+    /// Compare the run with the report of an earlier run and fail if the
+    /// throughput or the p99 latency of an operation regressed
+    #[arg(long)]
+    compare_baseline: Option<PathBuf>,
+
+    /// Tolerated drop of the throughput of an operation compared with the
+    /// baseline, in percent
+    #[arg(long, default_value_t = 10.0)]
+    max_throughput_drop_pct: f64,
+
+    /// Tolerated increase of the p99 latency of an operation compared with
+    /// the baseline, in percent
+    #[arg(long, default_value_t = 20.0)]
+    max_p99_increase_pct: f64,
+
+    /// Append the throughput, errors and latency percentiles of every
+    /// operation to this CSV file periodically
+    #[arg(long)]
+    stats_file: Option<PathBuf>,
+
+    /// The interval of the lines in the statistics file
+    #[arg(long, default_value_t = 1)]
+    stats_interval_secs: u64,
+
+    /// Show a live dashboard in the terminal instead of the log lines
+    #[arg(long)]
+    tui: bool,
+
+    /// The format of the log, `json` writes one structured record per line
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Also write the log to this file, which is rotated by size or age
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+
+    /// Rotate the log file once it reaches this size in MB
+    #[arg(long)]
+    log_file_max_mb: Option<u64>,
+
+    /// Rotate the log file once it is older than this many seconds
+    #[arg(long)]
+    log_file_max_age_secs: Option<u64>,
+
+    /// Number of rotated log files which are kept, older ones are deleted
+    #[arg(long, default_value_t = 5)]
+    log_file_keep: usize,
+
+    /// Do not write the log to the console, only to the log file
+    #[arg(long)]
+    no_console_log: bool,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        None => run(cli.run),
+        Some(Command::Run(args)) => run(args),
+        Some(Command::Validate(args)) => validate(&args),
+        Some(Command::Cleanup(args)) => cleanup(&args),
+        Some(Command::Status(args)) => status(&args),
+        Some(Command::InitConfig(args)) => init_config(&args),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(e.downcast_ref::<RunError>().map_or(1, RunError::exit_code))
+        }
+    }
+}
 
-    // Dump the configuration in YAML format
+/// Runs the loader, failures which end the process with their own exit
+/// code are `RunError`s
+fn run(args: RunArgs) -> Result<()> {
+    // Initialize the logger, the dashboard shows the log itself
+    let logs = LogBuffer::default();
+    let mut targets: Vec<Box<dyn std::io::Write + Send>> = Vec::new();
+    if args.tui {
+        targets.push(Box::new(logs.clone()));
+    } else if !args.no_console_log && args.log_file.is_some() {
+        targets.push(Box::new(std::io::stderr()));
+    }
+    if let Some(path) = &args.log_file {
+        targets.push(Box::new(RotatingFile::open(
+            path,
+            args.log_file_max_mb.map(|mb| mb * 1024 * 1024),
+            args.log_file_max_age_secs.map(Duration::from_secs),
+            args.log_file_keep,
+        )?));
+    }
+    // Without a log file or dashboard the logger keeps writing to the
+    // console directly, with colors
+    let target = (args.tui || args.log_file.is_some())
+        .then(|| Box::new(Tee(targets)) as Box<dyn std::io::Write + Send>);
+    logging::init(args.log_format, target);
+    let started = SystemTime::now();
+
+    let mut config =
+        load_config(&args).map_err(|e| RunError::LoadImpossible(format!("{:#}", e)))?;
+    config
+        .database
+        .run_id
+        .get_or_insert_with(naming::generate_run_id);
+    if let Some(seed) = config.seed {
+        random::set_seed(seed);
+    }
+
+    // Dump the configuration in YAML format, without the password
     println!("Loaded configuration (YAML format):");
-    println!("{}", serde_yaml::to_string(&config)?);
+    println!("{}", serde_yaml::to_string(&config.redacted())?);
 
     info!("Configuration summary:");
     info!("Version: {}", config.version);
     info!("Endpoints: {:?}", config.database.endpoints);
     info!("Username: {}", config.database.username);
     info!("Prefix: {}", config.database.prefix);
+    info!(
+        "Run ID: {}",
+        config.database.run_id.as_deref().unwrap_or_default()
+    );
     info!("Metrics port: {}", config.metrics_port);
+    info!("Mode: {:?}", config.mode);
+    match config.seed {
+        Some(seed) => info!("Random seed: {}", seed),
+        None => info!("Random seed: none, runs are not reproducible"),
+    }
     info!("Active use cases:");
     info!(
         "CRUD: {} ({} threads)",
@@ -44,21 +251,432 @@ fn main() -> Result<()> {
         "Graph: {} ({} threads)",
         config.active_usecases.graph.on, config.active_usecases.graph.threads
     );
+    info!(
+        "Queries: {} ({} threads)",
+        config.active_usecases.queries.on, config.active_usecases.queries.threads
+    );
+    info!(
+        "Transactions: {} ({} threads)",
+        config.active_usecases.transactions.on, config.active_usecases.transactions.threads
+    );
+    info!(
+        "Search: {} ({} threads)",
+        config.active_usecases.search.on, config.active_usecases.search.threads
+    );
+    info!(
+        "Pregel: {} ({} threads)",
+        config.active_usecases.pregel.on, config.active_usecases.pregel.threads
+    );
+    info!(
+        "Conflicts: {} ({} threads)",
+        config.active_usecases.conflicts.on, config.active_usecases.conflicts.threads
+    );
+    info!(
+        "TTL: {} ({} threads)",
+        config.active_usecases.ttl.on, config.active_usecases.ttl.threads
+    );
+    info!(
+        "Time series: {} ({} threads)",
+        config.active_usecases.timeseries.on, config.active_usecases.timeseries.threads
+    );
+    info!(
+        "Banking: {} ({} threads)",
+        config.active_usecases.banking.on, config.active_usecases.banking.threads
+    );
+    info!(
+        "Unique: {} ({} threads)",
+        config.active_usecases.unique.on, config.active_usecases.unique.threads
+    );
+    info!(
+        "DDL: {} ({} threads)",
+        config.active_usecases.ddl.on, config.active_usecases.ddl.threads
+    );
+    info!(
+        "Users: {} ({} threads)",
+        config.active_usecases.users.on, config.active_usecases.users.threads
+    );
+    info!(
+        "SmartJoin: {} ({} threads)",
+        config.active_usecases.smartjoin.on, config.active_usecases.smartjoin.threads
+    );
+
+    // A small runtime for the metrics server and signal handling, the use
+    // cases each bring their own runtime
+    let control_runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
 
-    // Start CRUD use case if enabled
-    if config.active_usecases.crud.on {
-        let crud_config = config.crud.clone();
+    let metrics_port = config.metrics_port;
+    control_runtime.spawn(async move {
+        if let Err(e) = metrics::serve(metrics_port).await {
+            error!("Metrics server failed: {}", e);
+        }
+    });
+
+    let shutdown = CancellationToken::new();
+    error_policy::set_policy(&config.error_policy);
+    control_runtime.spawn(error_policy::watch(
+        config.error_policy.clone(),
+        Duration::from_secs(1),
+        shutdown.clone(),
+    ));
+    control_runtime.spawn(report_latencies(Duration::from_secs(10), shutdown.clone()));
+
+    if let Some(server_metrics) = config.server_metrics.clone() {
         let db_config = config.database.clone();
-        let usecase_config = config.active_usecases.crud.clone();
-        thread::spawn(move || {
-            if let Err(e) = crud::run(crud_config, db_config, usecase_config) {
-                error!("CRUD use case failed: {}", e);
+        let scrape_shutdown = shutdown.clone();
+        control_runtime.spawn(async move {
+            if let Err(e) = server_metrics::scrape(db_config, server_metrics, scrape_shutdown).await
+            {
+                error!("Scraping server metrics failed: {}", e);
             }
         });
     }
 
-    // Keep main thread alive
-    loop {
-        thread::sleep(std::time::Duration::from_secs(1));
+    if let Some(replication_lag) = config.replication_lag.clone() {
+        let db_config = config.database.clone();
+        let probe_shutdown = shutdown.clone();
+        control_runtime.spawn(async move {
+            if let Err(e) = replication_lag::probe(db_config, replication_lag, probe_shutdown).await
+            {
+                error!("Measuring the replication lag failed: {}", e);
+            }
+        });
+    }
+
+    // Tracing is on before the use cases start their first operation
+    let trace_export = config.tracing.clone().map(|tracing| {
+        traces::init(&tracing);
+        control_runtime.spawn(traces::export(tracing, shutdown.clone()))
+    });
+
+    let stats_export = args.stats_file.clone().map(|path| {
+        control_runtime.spawn(export::export_stats(
+            path,
+            Duration::from_secs(args.stats_interval_secs.max(1)),
+            shutdown.clone(),
+        ))
+    });
+
+    let dashboard = args.tui.then(|| {
+        control_runtime.spawn(run_dashboard(
+            config.database.clone(),
+            logs,
+            Duration::from_secs(1),
+            shutdown.clone(),
+        ))
+    });
+
+    let capacity_search = config
+        .capacity_search
+        .clone()
+        .map(|search| control_runtime.spawn(capacity::search(search, shutdown.clone())));
+
+    // Databases left behind by earlier runs are dropped before the use
+    // cases create their own, a failure does not stop the run
+    if config.database.cleanup_stale {
+        match control_runtime.block_on(admin::cleanup_stale(&config.database)) {
+            Ok(dropped) => info!("Dropped {} stale databases", dropped.len()),
+            Err(e) => error!("Failed to drop stale databases: {:#}", e),
+        }
+    }
+
+    // Loaders which share a run number themselves before the use cases
+    // start, all but the first keep the data as it is
+    if let Some(coordination) = &config.coordination {
+        let coordinator = control_runtime
+            .block_on(Coordinator::join(&config.database, coordination))
+            .map_err(|e| RunError::LoadImpossible(format!("{:#}", e)))?;
+        if !coordinator.slot().is_first() {
+            config = coordination::follower_config(&config)?;
+        }
+        coordination::set(coordinator);
+    }
+
+    // Start all enabled use cases, each on its own thread
+    let registry = Registry::with_builtin();
+    let handles = registry.start_enabled(&config, &shutdown);
+
+    // A signal stops the use cases. They also stop by themselves in seed
+    // mode once the data is seeded, or when they reach their limits.
+    let signal_shutdown = shutdown.clone();
+    control_runtime.spawn(async move {
+        match wait_for_signal().await {
+            Ok(()) => info!("Shutdown requested, waiting for in-flight operations"),
+            Err(e) => error!("Failed to wait for shutdown signal: {}", e),
+        }
+        signal_shutdown.cancel();
+    });
+
+    let report_file = args.report_file.clone();
+    let baseline = args.compare_baseline.clone();
+    let tolerances = Tolerances {
+        throughput_drop_pct: args.max_throughput_drop_pct,
+        p99_increase_pct: args.max_p99_increase_pct,
+    };
+
+    // SIGHUP reloads the configuration file and applies the parameters
+    // which can be changed while the use cases run
+    let mut running_config = config.clone();
+    control_runtime.spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(e) => {
+                error!("Failed to wait for reload signal: {}", e);
+                return;
+            }
+        };
+        while hangup.recv().await.is_some() {
+            reload_config(&args, &mut running_config);
+        }
+    });
+
+    // The control runtime keeps serving metrics while the use cases run
+    let (failed, setup_failed) =
+        control_runtime.block_on(control_runtime.spawn_blocking(move || join_usecases(handles)))?;
+    info!("All use cases finished");
+    shutdown.cancel();
+    if let Some(stats_export) = stats_export {
+        control_runtime.block_on(stats_export)?;
+    }
+    if let Some(trace_export) = trace_export {
+        control_runtime.block_on(trace_export)?;
+    }
+    if let Some(dashboard) = dashboard {
+        control_runtime.block_on(dashboard)?;
+    }
+
+    println!("Final statistics:");
+    println!("{}", metrics::summary());
+    println!("Latency percentiles:");
+    for summary in LATENCIES.total() {
+        println!("{}", summary.format_ms());
+    }
+    let corrected = LATENCIES.total_corrected();
+    if !corrected.is_empty() {
+        println!("Latency percentiles from the intended start (coordinated omission):");
+        for summary in corrected {
+            println!("{}", summary.format_ms());
+        }
+    }
+    let capacity = match capacity_search {
+        Some(capacity_search) => {
+            let result = control_runtime.block_on(capacity_search)?;
+            println!("Maximal rate with p99 latency below the target:");
+            println!("{}", result.format());
+            Some(result)
+        }
+        None => None,
+    };
+    let mut report = Report::collect(&config, started, failed, capacity);
+    // The first loader of a coordinated run reports for all of them
+    if let Some(coordinator) = coordination::current() {
+        if let Err(e) = control_runtime.block_on(coordinator.publish_report(&report)) {
+            error!("Failed to publish the report: {:#}", e);
+        }
+        if coordinator.slot().is_first() {
+            match control_runtime.block_on(coordinator.collect_reports()) {
+                Ok(reports) => {
+                    info!("Merged the reports of {} loaders", reports.len());
+                    report = Report::merge(reports).unwrap_or(report);
+                }
+                Err(e) => error!("Failed to collect the reports of the loaders: {:#}", e),
+            }
+        }
+    }
+    if let Some(path) = &report_file {
+        report.write(path)?;
+        info!("Report written to {}", path.display());
+    }
+    if let Some(path) = &baseline {
+        let comparison = report::compare(&Report::read(path)?, &report, tolerances);
+        println!("Comparison with baseline {}:", path.display());
+        for line in &comparison.lines {
+            println!("{}", line);
+        }
+        if comparison.regressions > 0 {
+            return Err(anyhow::anyhow!(
+                "{} operations regressed compared with the baseline",
+                comparison.regressions
+            ));
+        }
+    }
+
+    if let Some(reason) = error_policy::aborted() {
+        return Err(RunError::LoadFailed(reason).into());
+    }
+    if setup_failed > 0 {
+        return Err(RunError::LoadImpossible(format!(
+            "{} use cases could not be set up",
+            setup_failed
+        ))
+        .into());
+    }
+    if failed > 0 {
+        return Err(RunError::LoadFailed(format!("{} use cases failed", failed)).into());
+    }
+    if config.mode == Mode::Seed {
+        println!("Seeding complete");
+    }
+    Ok(())
+}
+
+/// Writes an example configuration to the given file or to stdout
+fn init_config(args: &InitConfigArgs) -> Result<()> {
+    match &args.output {
+        Some(path) => {
+            init_config::write(path, args.full)?;
+            eprintln!("Wrote example configuration to {}", path.display());
+        }
+        None => print!("{}", init_config::example(args.full)),
+    }
+    Ok(())
+}
+
+/// Reads the configuration file for the administrative subcommands, which
+/// log plainly to the console and talk to the database on a small runtime
+fn admin_setup(args: &ConfigArgs) -> Result<(config::Config, tokio::runtime::Runtime)> {
+    logging::init(LogFormat::Text, None);
+    let config = config::Config::load(&args.config, &args.set)
+        .map_err(|e| RunError::LoadImpossible(format!("{:#}", e)))?;
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    Ok((config, runtime))
+}
+
+/// Checks that the configuration can be read and that every endpoint
+/// answers with the configured credentials
+fn validate(args: &ConfigArgs) -> Result<()> {
+    let (config, runtime) = admin_setup(args)?;
+    println!("Configuration {} is valid", args.config.display());
+    let checks = runtime
+        .block_on(admin::check_endpoints(&config.database))
+        .map_err(|e| RunError::LoadImpossible(format!("{:#}", e)))?;
+    for check in &checks {
+        println!("{}", check.format());
+    }
+    let failed = checks.iter().filter(|check| check.result.is_err()).count();
+    if failed > 0 {
+        return Err(RunError::LoadImpossible(format!(
+            "{} of {} endpoints cannot be used",
+            failed,
+            checks.len()
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Drops the databases of the loader
+fn cleanup(args: &CleanupArgs) -> Result<()> {
+    let (config, runtime) = admin_setup(&args.config)?;
+    let dropped = if args.stale {
+        runtime.block_on(admin::cleanup_stale(&config.database))?
+    } else {
+        runtime.block_on(admin::cleanup(&config.database))?
+    };
+    println!(
+        "Dropped {} {}databases with prefix {}",
+        dropped.len(),
+        if args.stale { "stale " } else { "" },
+        config.database.prefix
+    );
+    for name in dropped {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+/// Shows the collections of the loader and their document counts
+fn status(args: &ConfigArgs) -> Result<()> {
+    let (config, runtime) = admin_setup(args)?;
+    let status = runtime.block_on(admin::status(&config.database))?;
+    println!("{}", admin::format_status(&status));
+    Ok(())
+}
+
+/// Reads the configuration file and applies the command line overrides
+fn load_config(args: &RunArgs) -> Result<config::Config> {
+    let mut config = config::Config::load(&args.config, &args.set)?;
+    if args.seed_only {
+        config.mode = Mode::Seed;
+    }
+    if args.seed.is_some() {
+        config.seed = args.seed;
+    }
+    Ok(config)
+}
+
+/// Re-reads the configuration file and applies its dynamic parameters to
+/// the running use cases. A configuration with changes which need a
+/// restart is rejected as a whole.
+fn reload_config(args: &RunArgs, running: &mut config::Config) {
+    info!("Reloading configuration from {}", args.config.display());
+    let mut config = match load_config(args) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to reload configuration: {}", e);
+            return;
+        }
+    };
+    // The generated run ID stays for the whole run
+    if config.database.run_id.is_none() {
+        config.database.run_id = running.database.run_id.clone();
+    }
+    // The other loaders of a coordinated run keep running the way they
+    // started, without the setup of the first one
+    if let Some(coordinator) = coordination::current() {
+        if !coordinator.slot().is_first() {
+            config = match coordination::follower_config(&config) {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("Failed to reload configuration: {}", e);
+                    return;
+                }
+            };
+        }
+    }
+    let sections = running.restart_required(&config);
+    if !sections.is_empty() {
+        error!(
+            "Configuration not reloaded, changes in {} need a restart",
+            sections.join(", ")
+        );
+        return;
+    }
+    control::reload(&config);
+    *running = config;
+}
+
+/// Waits for all use case threads and returns the number of failed ones
+/// and how many of them already failed during their setup
+fn join_usecases(handles: Vec<JoinHandle<Result<()>>>) -> (usize, usize) {
+    let mut failed = 0;
+    let mut setup_failed = 0;
+    for handle in handles {
+        match handle.join() {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                failed += 1;
+                if e.downcast_ref::<SetupFailed>().is_some() {
+                    setup_failed += 1;
+                }
+            }
+            Err(_) => {
+                error!("A use case thread panicked");
+                failed += 1;
+            }
+        }
+    }
+    (failed, setup_failed)
+}
+
+/// Waits until the process receives SIGINT or SIGTERM
+async fn wait_for_signal() -> std::io::Result<()> {
+    let mut sigterm = signal(SignalKind::terminate())?;
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result,
+        _ = sigterm.recv() => Ok(()),
     }
 }
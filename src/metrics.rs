@@ -0,0 +1,526 @@
+use crate::control;
+use crate::latency::LATENCIES;
+use log::{error, info};
+use prometheus::{
+    exponential_buckets, register_gauge_vec, register_histogram_vec, register_int_counter_vec,
+    Encoder, GaugeVec, HistogramVec, IntCounterVec, TextEncoder,
+};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::sync::{LazyLock, RwLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// All metrics exported by the loader. Every metric of the load carries a
+/// `usecase` label, so that several use cases running in the same process
+/// can be told apart in a single scrape.
+pub struct Metrics {
+    /// Number of requests sent to the database, by use case and operation
+    pub requests: IntCounterVec,
+    /// Number of failed requests, by use case and operation
+    pub errors: IntCounterVec,
+    /// Number of failed requests, by use case, operation and error class
+    /// (see `arangodb::error_class`)
+    pub error_classes: IntCounterVec,
+    /// Number of attempts to send a request to an endpoint, including
+    /// retries, by endpoint
+    pub endpoint_requests: IntCounterVec,
+    /// Number of failed attempts, by endpoint and error class
+    pub endpoint_errors: IntCounterVec,
+    /// Latency of the attempts in seconds, by endpoint
+    pub endpoint_request_duration: HistogramVec,
+    /// Number of requests sent during the warm-up of a use case, which are
+    /// not counted in `requests`, by use case and operation
+    pub warmup_requests: IntCounterVec,
+    /// Number of failed requests during the warm-up, by use case and
+    /// operation
+    pub warmup_errors: IntCounterVec,
+    /// Number of documents inserted, by use case
+    pub documents_inserted: IntCounterVec,
+    /// Number of requests the server rejected because they would have
+    /// waited too long in its queue, by use case and operation
+    pub queue_time_rejections: IntCounterVec,
+    /// Number of writes rejected because of a revision conflict, by use case
+    pub conflicts: IntCounterVec,
+    /// Number of inserts rejected because of a unique index, by use case
+    pub unique_violations: IntCounterVec,
+    /// Number of reads which may have been answered by a follower, by use
+    /// case and operation
+    pub follower_reads: IntCounterVec,
+    /// Request latency in seconds, by use case and operation
+    pub request_duration: HistogramVec,
+    /// Latency of whole operations in seconds measured from their intended
+    /// start, only for use cases with a target rate, by use case and the
+    /// operation of their first request
+    pub request_duration_corrected: HistogramVec,
+    /// Time it took to build an index in seconds, by use case and index type
+    pub index_build_duration: HistogramVec,
+    /// Average latency of the other requests of a use case during the last
+    /// index build divided by the one before, by use case and index type
+    pub index_build_latency_ratio: GaugeVec,
+    /// Number of expired documents the TTL thread has not removed yet, by
+    /// use case
+    pub ttl_backlog: GaugeVec,
+    /// Size of the generated documents in bytes as sent to the server, by
+    /// use case
+    pub document_size: HistogramVec,
+    /// The last scraped value of a server metric, by endpoint, metric name
+    /// and the labels the server attached to it
+    pub server_metrics: GaugeVec,
+    /// Time from writing a marker document until it could be read on the
+    /// followers in seconds, by target (`followers` or `datacenter`)
+    pub replication_lag: HistogramVec,
+    /// Number of marker documents which did not become visible within the
+    /// timeout, by target
+    pub replication_lag_timeouts: IntCounterVec,
+    /// 1 for the current phase of the load of a use case, 0 for the others,
+    /// by use case and phase
+    pub phase: GaugeVec,
+    /// The current limit of requests in flight of the adaptive concurrency
+    /// control, by endpoint
+    pub concurrency_limit: GaugeVec,
+    /// The state of the circuit breaker of an endpoint: 0 closed, 1 half
+    /// open, 2 open
+    pub circuit_breaker_state: GaugeVec,
+    /// 1 for the current state of a use case (see `supervisor`), 0 for the
+    /// others, by use case and state
+    pub usecase_state: GaugeVec,
+    /// Number of restarts of a use case after it failed, by use case
+    pub usecase_restarts: IntCounterVec,
+}
+
+/// The process wide metrics, registered with the default Prometheus registry
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(|| Metrics {
+    requests: register_int_counter_vec!(
+        "adb_loader_requests_total",
+        "Number of requests sent to the database",
+        &["usecase", "operation"]
+    )
+    .expect("Failed to register requests counter"),
+    errors: register_int_counter_vec!(
+        "adb_loader_errors_total",
+        "Number of failed requests",
+        &["usecase", "operation"]
+    )
+    .expect("Failed to register errors counter"),
+    error_classes: register_int_counter_vec!(
+        "adb_loader_error_classes_total",
+        "Number of failed requests by error class",
+        &["usecase", "operation", "class"]
+    )
+    .expect("Failed to register error classes counter"),
+    endpoint_requests: register_int_counter_vec!(
+        "adb_loader_endpoint_requests_total",
+        "Number of attempts to send a request to an endpoint",
+        &["endpoint"]
+    )
+    .expect("Failed to register endpoint requests counter"),
+    endpoint_errors: register_int_counter_vec!(
+        "adb_loader_endpoint_errors_total",
+        "Number of failed attempts to send a request to an endpoint",
+        &["endpoint", "class"]
+    )
+    .expect("Failed to register endpoint errors counter"),
+    endpoint_request_duration: register_histogram_vec!(
+        "adb_loader_endpoint_request_duration_seconds",
+        "Latency of the attempts to send a request to an endpoint in seconds",
+        &["endpoint"],
+        exponential_buckets(0.0005, 2.0, 16).expect("Invalid histogram buckets")
+    )
+    .expect("Failed to register endpoint latency histogram"),
+    warmup_requests: register_int_counter_vec!(
+        "adb_loader_warmup_requests_total",
+        "Number of requests sent during the warm-up",
+        &["usecase", "operation"]
+    )
+    .expect("Failed to register warm-up requests counter"),
+    warmup_errors: register_int_counter_vec!(
+        "adb_loader_warmup_errors_total",
+        "Number of failed requests during the warm-up",
+        &["usecase", "operation"]
+    )
+    .expect("Failed to register warm-up errors counter"),
+    documents_inserted: register_int_counter_vec!(
+        "adb_loader_documents_inserted_total",
+        "Number of documents inserted",
+        &["usecase"]
+    )
+    .expect("Failed to register documents counter"),
+    queue_time_rejections: register_int_counter_vec!(
+        "adb_loader_queue_time_rejections_total",
+        "Number of requests rejected because they would have waited too long in the queue",
+        &["usecase", "operation"]
+    )
+    .expect("Failed to register queue time rejections counter"),
+    conflicts: register_int_counter_vec!(
+        "adb_loader_conflicts_total",
+        "Number of writes rejected because of a revision conflict",
+        &["usecase"]
+    )
+    .expect("Failed to register conflicts counter"),
+    unique_violations: register_int_counter_vec!(
+        "adb_loader_unique_violations_total",
+        "Number of inserts rejected because of a unique index",
+        &["usecase"]
+    )
+    .expect("Failed to register unique violations counter"),
+    follower_reads: register_int_counter_vec!(
+        "adb_loader_follower_reads_total",
+        "Number of reads which may have been answered by a follower",
+        &["usecase", "operation"]
+    )
+    .expect("Failed to register follower reads counter"),
+    request_duration: register_histogram_vec!(
+        "adb_loader_request_duration_seconds",
+        "Request latency in seconds",
+        &["usecase", "operation"],
+        exponential_buckets(0.0005, 2.0, 16).expect("Invalid histogram buckets")
+    )
+    .expect("Failed to register latency histogram"),
+    request_duration_corrected: register_histogram_vec!(
+        "adb_loader_request_duration_corrected_seconds",
+        "Operation latency in seconds measured from the intended start of the operation",
+        &["usecase", "operation"],
+        exponential_buckets(0.0005, 2.0, 16).expect("Invalid histogram buckets")
+    )
+    .expect("Failed to register corrected latency histogram"),
+    index_build_duration: register_histogram_vec!(
+        "adb_loader_index_build_duration_seconds",
+        "Time it took to build an index in seconds",
+        &["usecase", "type"],
+        exponential_buckets(0.01, 2.0, 16).expect("Invalid histogram buckets")
+    )
+    .expect("Failed to register index build histogram"),
+    index_build_latency_ratio: register_gauge_vec!(
+        "adb_loader_index_build_latency_ratio",
+        "Average request latency during the last index build divided by the one before",
+        &["usecase", "type"]
+    )
+    .expect("Failed to register index build latency gauge"),
+    document_size: register_histogram_vec!(
+        "adb_loader_document_size_bytes",
+        "Size of the generated documents in bytes",
+        &["usecase"],
+        exponential_buckets(64.0, 2.0, 16).expect("Invalid histogram buckets")
+    )
+    .expect("Failed to register document size histogram"),
+    ttl_backlog: register_gauge_vec!(
+        "adb_loader_ttl_backlog",
+        "Number of expired documents not removed yet",
+        &["usecase"]
+    )
+    .expect("Failed to register TTL backlog gauge"),
+    server_metrics: register_gauge_vec!(
+        "adb_loader_server_metric",
+        "Last scraped value of a metric of a database server",
+        &["endpoint", "metric", "labels"]
+    )
+    .expect("Failed to register server metrics gauge"),
+    replication_lag: register_histogram_vec!(
+        "adb_loader_replication_lag_seconds",
+        "Time from writing a marker document until it was visible on the followers in seconds",
+        &["target"],
+        exponential_buckets(0.001, 2.0, 16).expect("Invalid histogram buckets")
+    )
+    .expect("Failed to register replication lag histogram"),
+    replication_lag_timeouts: register_int_counter_vec!(
+        "adb_loader_replication_lag_timeouts_total",
+        "Number of marker documents which did not become visible within the timeout",
+        &["target"]
+    )
+    .expect("Failed to register replication lag timeouts counter"),
+    phase: register_gauge_vec!(
+        "adb_loader_phase",
+        "1 for the current phase of the load of a use case, 0 for the others",
+        &["usecase", "phase"]
+    )
+    .expect("Failed to register phase gauge"),
+    concurrency_limit: register_gauge_vec!(
+        "adb_loader_concurrency_limit",
+        "Current limit of requests in flight to an endpoint",
+        &["endpoint"]
+    )
+    .expect("Failed to register concurrency limit gauge"),
+    circuit_breaker_state: register_gauge_vec!(
+        "adb_loader_circuit_breaker_state",
+        "State of the circuit breaker of an endpoint: 0 closed, 1 half open, 2 open",
+        &["endpoint"]
+    )
+    .expect("Failed to register circuit breaker gauge"),
+    usecase_state: register_gauge_vec!(
+        "adb_loader_usecase_state",
+        "1 for the current state of a use case, 0 for the others",
+        &["usecase", "state"]
+    )
+    .expect("Failed to register use case state gauge"),
+    usecase_restarts: register_int_counter_vec!(
+        "adb_loader_usecase_restarts_total",
+        "Number of restarts of a use case after it failed",
+        &["usecase"]
+    )
+    .expect("Failed to register use case restarts counter"),
+});
+
+tokio::task_local! {
+    /// The use case and operation of the first request of the current
+    /// paced operation, set by `observe_request`
+    static PACED_OPERATION: RefCell<Option<(String, String)>>;
+}
+
+/// Runs an operation which was supposed to start at `intended_start`. Its
+/// latency measured from then is recorded once for the whole operation,
+/// under the name of its first request.
+pub async fn with_intended_start<F: Future>(intended_start: Instant, operation: F) -> F::Output {
+    let (output, observed) = PACED_OPERATION
+        .scope(RefCell::new(None), async {
+            let output = operation.await;
+            (output, PACED_OPERATION.with(|observed| observed.take()))
+        })
+        .await;
+    if let Some((usecase, operation)) = observed {
+        let corrected = intended_start.elapsed();
+        METRICS
+            .request_duration_corrected
+            .with_label_values(&[&usecase, &operation])
+            .observe(corrected.as_secs_f64());
+        LATENCIES.record_corrected(&usecase, &operation, corrected);
+    }
+    output
+}
+
+/// The use cases which are warming up
+static WARMING_UP: LazyLock<RwLock<BTreeSet<String>>> =
+    LazyLock::new(|| RwLock::new(BTreeSet::new()));
+
+/// Marks a use case as warming up or as warmed up
+pub fn set_warming_up(usecase: &str, warming_up: bool) {
+    let mut usecases = WARMING_UP.write().unwrap();
+    if warming_up {
+        usecases.insert(usecase.to_string());
+    } else {
+        usecases.remove(usecase);
+    }
+}
+
+/// Records the outcome and latency of a single request, both in the
+/// Prometheus metrics and in the HDR latency histograms. Requests of a
+/// use case which is warming up are only counted in the warm-up counters,
+/// so that they do not show up in the latencies and the final summary.
+///
+/// # Arguments
+/// * `usecase` - The name of the use case which sent the request
+/// * `operation` - The kind of operation, e.g. `insert_batch` or `read`
+/// * `duration` - The time the request took
+/// * `success` - Whether the request succeeded
+pub fn observe_request(usecase: &str, operation: &str, duration: Duration, success: bool) {
+    let labels = [usecase, operation];
+    if WARMING_UP.read().unwrap().contains(usecase) {
+        METRICS.warmup_requests.with_label_values(&labels).inc();
+        if !success {
+            METRICS.warmup_errors.with_label_values(&labels).inc();
+        }
+        return;
+    }
+    METRICS.requests.with_label_values(&labels).inc();
+    if !success {
+        METRICS.errors.with_label_values(&labels).inc();
+    }
+    METRICS
+        .request_duration
+        .with_label_values(&labels)
+        .observe(duration.as_secs_f64());
+    LATENCIES.record(usecase, operation, duration);
+    let _ = PACED_OPERATION.try_with(|observed| {
+        observed
+            .borrow_mut()
+            .get_or_insert_with(|| (usecase.to_string(), operation.to_string()));
+    });
+}
+
+/// Request statistics of one operation of one use case
+#[derive(Debug, Clone, Default)]
+pub struct OperationStats {
+    pub usecase: String,
+    pub operation: String,
+    pub requests: u64,
+    pub errors: u64,
+    /// Sum of all request latencies in seconds
+    pub total_latency_secs: f64,
+}
+
+impl OperationStats {
+    /// The average request latency in milliseconds
+    pub fn avg_latency_ms(&self) -> f64 {
+        if self.requests > 0 {
+            self.total_latency_secs / self.requests as f64 * 1000.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Collects the statistics of all use cases and operations seen so far
+pub fn operation_stats() -> Vec<OperationStats> {
+    let mut stats = Vec::new();
+    for family in prometheus::gather() {
+        if family.get_name() != "adb_loader_request_duration_seconds" {
+            continue;
+        }
+        for metric in family.get_metric() {
+            let label = |name: &str| {
+                metric
+                    .get_label()
+                    .iter()
+                    .find(|pair| pair.get_name() == name)
+                    .map(|pair| pair.get_value().to_string())
+                    .unwrap_or_default()
+            };
+            let usecase = label("usecase");
+            let operation = label("operation");
+            let histogram = metric.get_histogram();
+            let errors = METRICS
+                .errors
+                .with_label_values(&[usecase.as_str(), operation.as_str()])
+                .get();
+            stats.push(OperationStats {
+                usecase,
+                operation,
+                requests: histogram.get_sample_count(),
+                errors,
+                total_latency_secs: histogram.get_sample_sum(),
+            });
+        }
+    }
+    stats
+}
+
+/// Returns a human readable table with the number of requests, errors and
+/// the average latency for each use case and operation seen so far
+pub fn summary() -> String {
+    let mut lines = vec![format!(
+        "{:<12} {:<16} {:>12} {:>10} {:>16}",
+        "Use case", "Operation", "Requests", "Errors", "Avg latency (ms)"
+    )];
+    for stats in operation_stats() {
+        lines.push(format!(
+            "{:<12} {:<16} {:>12} {:>10} {:>16.3}",
+            stats.usecase,
+            stats.operation,
+            stats.requests,
+            stats.errors,
+            stats.avg_latency_ms()
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Renders all registered metrics in the Prometheus text format
+pub fn render() -> (String, Vec<u8>) {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    if let Err(e) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        error!("Failed to encode metrics: {}", e);
+    }
+    (encoder.format_type().to_string(), buffer)
+}
+
+/// Serves the metrics on `/metrics` and the control API (see
+/// `control::handle_request`) on the given port. This runs until the
+/// listener fails, so it is usually spawned on its own runtime.
+pub async fn serve(port: u16) -> std::io::Result<()> {
+    // Make sure all metrics show up in the first scrape:
+    LazyLock::force(&METRICS);
+
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    info!("Serving metrics on port {}", port);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream).await {
+                error!("Failed to answer metrics request: {}", e);
+            }
+        });
+    }
+}
+
+/// Answers a single HTTP request. We only need `GET /metrics` and a few
+/// control requests without body, so there is no point in pulling in a
+/// full HTTP server.
+async fn handle_connection(mut stream: TcpStream) -> std::io::Result<()> {
+    let mut buffer = vec![0u8; 8192];
+    let mut read = 0;
+    loop {
+        let n = stream.read(&mut buffer[read..]).await?;
+        if n == 0 {
+            break;
+        }
+        read += n;
+        if read == buffer.len() || buffer[..read].windows(4).any(|w| w == b"\r\n\r\n") {
+            break;
+        }
+    }
+
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let mut request_line = request.lines().next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("");
+    let target = request_line.next().unwrap_or("");
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (status, content_type, body) = match path {
+        "/metrics" => {
+            let (content_type, body) = render();
+            ("200 OK", content_type, body)
+        }
+        _ if path.starts_with("/usecases") => {
+            let (status, body) = control::handle_request(method, path, query);
+            (
+                status,
+                "application/json".to_string(),
+                serde_json::to_vec(&body).unwrap_or_default(),
+            )
+        }
+        _ => (
+            "404 Not Found",
+            "text/plain".to_string(),
+            b"Not found\n".to_vec(),
+        ),
+    };
+
+    let header = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(header.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_corrected_latency_once_per_operation() {
+        let intended_start = Instant::now() - Duration::from_secs(2);
+        with_intended_start(intended_start, async {
+            observe_request("paced_test", "delete", Duration::from_millis(1), true);
+            observe_request("paced_test", "insert", Duration::from_millis(1), true);
+        })
+        .await;
+        let corrected: Vec<_> = LATENCIES
+            .total_corrected()
+            .into_iter()
+            .filter(|summary| summary.usecase == "paced_test")
+            .collect();
+        assert_eq!(corrected.len(), 1);
+        assert_eq!(corrected[0].operation, "delete");
+        assert_eq!(corrected[0].count, 1);
+        // The lag before the start is included, not only the requests
+        assert!(corrected[0].max >= 2_000_000);
+    }
+}
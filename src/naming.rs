@@ -0,0 +1,123 @@
+use rand::Rng;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Returns a new run ID, the start time in seconds and a random suffix in
+/// hex, so that loaders started at the same time on different machines
+/// get different IDs. It does not depend on the global seed.
+pub fn generate_run_id() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default();
+    format!("{:x}{:04x}", secs, rand::rng().random::<u16>())
+}
+
+/// A piece of a template
+enum Part<'a> {
+    Text(&'a str),
+    Placeholder(&'a str, usize),
+}
+
+/// Splits a template into its literal text and its placeholders with
+/// their zero padding, `{i:04}` is `("i", 4)`
+fn parse(template: &str) -> Result<Vec<Part<'_>>, String> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            parts.push(Part::Text(&rest[..start]));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed {{ in {:?}", template))?;
+        let placeholder = &rest[start + 1..start + end];
+        let (name, width) = match placeholder.split_once(':') {
+            None => (placeholder, 0),
+            Some((name, spec)) => match spec.strip_prefix('0').map(str::parse) {
+                Some(Ok(width)) => (name, width),
+                _ => return Err(format!("bad format {:?} in {:?}", spec, template)),
+            },
+        };
+        parts.push(Part::Placeholder(name, width));
+        rest = &rest[start + end + 1..];
+    }
+    if rest.contains('}') {
+        return Err(format!("unopened }} in {:?}", template));
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Text(rest));
+    }
+    Ok(parts)
+}
+
+/// Checks that a template is well-formed and only uses the given
+/// placeholders
+pub fn check(template: &str, known: &[&str]) -> Result<(), String> {
+    for part in parse(template)? {
+        if let Part::Placeholder(name, _) = part {
+            if !known.contains(&name) {
+                return Err(format!(
+                    "unknown placeholder {{{}}} in {:?}, known are {}",
+                    name,
+                    template,
+                    known.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns whether a template uses the given placeholder
+pub fn uses(template: &str, name: &str) -> bool {
+    parse(template).is_ok_and(|parts| {
+        parts
+            .iter()
+            .any(|part| matches!(part, Part::Placeholder(used, _) if *used == name))
+    })
+}
+
+/// Fills in the placeholders of a template. Templates are checked when the
+/// configuration is loaded, a broken one or an unknown placeholder is kept
+/// as it is.
+pub fn expand(template: &str, values: &[(&str, &str)]) -> String {
+    let Ok(parts) = parse(template) else {
+        return template.to_string();
+    };
+    let mut name = String::with_capacity(template.len());
+    for part in parts {
+        match part {
+            Part::Text(text) => name.push_str(text),
+            Part::Placeholder(placeholder, width) => {
+                match values.iter().find(|(known, _)| *known == placeholder) {
+                    Some((_, value)) => name.push_str(&format!("{:0>width$}", value)),
+                    None => name.push_str(&format!("{{{}}}", placeholder)),
+                }
+            }
+        }
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates() {
+        let values = [("prefix", "adb_"), ("run_id", "r1"), ("i", "7")];
+        assert_eq!(
+            expand("{prefix}crud_{run_id}", &values),
+            "adb_crud_r1".to_string()
+        );
+        assert_eq!(expand("orders_{i:04}", &values), "orders_0007".to_string());
+        assert!(uses("orders_{i:04}", "i"));
+        assert!(!uses("orders", "i"));
+
+        assert!(check("{prefix}crud_{i:03}", &["prefix", "i"]).is_ok());
+        assert!(check("{prefix}crud_{run}", &["prefix", "i"]).is_err());
+        assert!(check("crud_{i", &["i"]).is_err());
+        assert!(check("crud_{i:x}", &["i"]).is_err());
+        assert!(check("crud}", &["i"]).is_err());
+    }
+}
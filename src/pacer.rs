@@ -0,0 +1,138 @@
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// An open-loop pacer (token bucket) which hands out send slots at a fixed
+/// rate, shared by all workers of a use case. Slots are scheduled
+/// independently of how long requests take, so a slow server does not
+/// lower the offered load. If the workers fall behind, they may catch up
+/// with a burst of at most one second worth of slots. The slots skipped
+/// this way still count for the intended send times, so that a long stall
+/// shows in the latencies measured from them.
+///
+/// A rate of 0 means unlimited, `wait` then returns immediately.
+pub struct Pacer {
+    state: Mutex<PacerState>,
+}
+
+struct PacerState {
+    /// Time between two slots, `None` if unlimited
+    interval: Option<Duration>,
+    /// Maximal number of slots handed out at once to catch up
+    burst: u32,
+    /// The next free slot
+    next: Instant,
+    /// The intended send time of the next slot, which unlike `next` is not
+    /// moved forward to limit the burst
+    intended: Instant,
+}
+
+fn interval_for(ops_per_sec: f64) -> Option<Duration> {
+    (ops_per_sec > 0.0).then(|| Duration::from_secs_f64(1.0 / ops_per_sec))
+}
+
+impl Pacer {
+    /// Creates a pacer for the given number of operations per second
+    pub fn new(ops_per_sec: f64) -> Self {
+        Pacer {
+            state: Mutex::new(PacerState {
+                interval: interval_for(ops_per_sec),
+                burst: (ops_per_sec.ceil() as u32).max(1),
+                next: Instant::now(),
+                intended: Instant::now(),
+            }),
+        }
+    }
+
+    /// Changes the target rate, taking effect with the next slot
+    pub fn set_rate(&self, ops_per_sec: f64) {
+        let mut state = self.state.lock().unwrap();
+        state.interval = interval_for(ops_per_sec);
+        state.burst = (ops_per_sec.ceil() as u32).max(1);
+        state.next = state.next.max(Instant::now());
+        state.intended = state.next;
+    }
+
+    /// Returns the current target rate, 0 if unlimited
+    pub fn rate(&self) -> f64 {
+        match self.state.lock().unwrap().interval {
+            Some(interval) => 1.0 / interval.as_secs_f64(),
+            None => 0.0,
+        }
+    }
+
+    /// Waits for the next slot and returns the time at which the operation
+    /// was supposed to be sent, which is further in the past than the slot
+    /// if slots were skipped to limit the burst
+    pub async fn wait(&self) -> Instant {
+        let (slot, intended) = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let Some(interval) = state.interval else {
+                return now;
+            };
+            let earliest = now
+                .checked_sub(interval * state.burst)
+                .unwrap_or(state.next);
+            let slot = state.next.max(earliest);
+            state.next = slot + interval;
+            // Once the workers keep up again, the skipped slots are given up
+            let intended = if slot >= now { slot } else { state.intended };
+            state.intended = intended + interval;
+            (slot, intended)
+        };
+        tokio::time::sleep_until(slot).await;
+        intended
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pacer_limits_rate() {
+        let pacer = Pacer::new(200.0);
+        let start = Instant::now();
+        for _ in 0..21 {
+            pacer.wait().await;
+        }
+        // 21 slots at 200/s span 100ms
+        assert!(start.elapsed() >= Duration::from_millis(95));
+    }
+
+    #[tokio::test]
+    async fn test_stall_shows_in_intended_times() {
+        let pacer = Pacer::new(10.0);
+        let stalled = Instant::now() - Duration::from_secs(5);
+        {
+            let mut state = pacer.state.lock().unwrap();
+            state.next = stalled;
+            state.intended = stalled;
+        }
+        // The burst is limited to one second worth of slots, but the
+        // intended times go back to the start of the stall
+        let start = Instant::now();
+        assert_eq!(pacer.wait().await, stalled);
+        assert_eq!(pacer.wait().await, stalled + Duration::from_millis(100));
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        // When the workers keep up again, the intended times are the slots
+        for _ in 0..10 {
+            pacer.wait().await;
+        }
+        let intended = pacer.wait().await;
+        assert!(Instant::now().saturating_duration_since(intended) < Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_unlimited_pacer_does_not_wait() {
+        let pacer = Pacer::new(0.0);
+        let start = Instant::now();
+        for _ in 0..1000 {
+            pacer.wait().await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(100));
+        assert_eq!(pacer.rate(), 0.0);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::config::Phase;
+use crate::control::{self, UseCaseControl};
+use crate::latency::LATENCIES;
+use crate::metrics::METRICS;
+use log::info;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// How often the target rate is updated during a ramp
+const RAMP_STEP: Duration = Duration::from_secs(1);
+
+/// Returns the target rate `elapsed` into a phase. A ramp never targets
+/// less than one operation per second, since 0 means unlimited.
+fn rate_at(phase: &Phase, elapsed: Duration) -> f64 {
+    match phase.from_ops_per_sec {
+        Some(from) if phase.duration_secs > 0 => {
+            let progress = (elapsed.as_secs_f64() / phase.duration_secs as f64).min(1.0);
+            (from + (phase.ops_per_sec - from) * progress).max(1.0)
+        }
+        _ => phase.ops_per_sec,
+    }
+}
+
+/// Drives the target rate of a running use case through its phases and
+/// cancels `shutdown` after the last one. The current phase is exported in
+/// `adb_loader_phase` and the latency percentiles of every phase are
+/// logged when it ends, so that the results can be told apart by phase.
+pub async fn run_phases(usecase: String, phases: Vec<Phase>, shutdown: CancellationToken) {
+    // The workers of the use case start at the beginning of `run`
+    let control = loop {
+        if let Some(control) = control::get(&usecase) {
+            break control;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+            _ = shutdown.cancelled() => return,
+        }
+    };
+    for phase in &phases {
+        METRICS
+            .phase
+            .with_label_values(&[&usecase, &phase.name])
+            .set(0.0);
+    }
+
+    let window = format!("phases/{}", usecase);
+    LATENCIES.watch(&window, &usecase);
+    for phase in &phases {
+        info!(
+            "Use case {} enters phase {} for {}s",
+            usecase, phase.name, phase.duration_secs
+        );
+        let gauge = METRICS.phase.with_label_values(&[&usecase, &phase.name]);
+        gauge.set(1.0);
+        let completed = run_phase(&control, phase, &shutdown).await;
+        gauge.set(0.0);
+        if let Some(summary) = LATENCIES.take_window(&window) {
+            info!("Phase {} of {}", phase.name, summary.format_ms());
+        }
+        if !completed {
+            return;
+        }
+    }
+    info!("Use case {} completed its last phase", usecase);
+    shutdown.cancel();
+}
+
+/// Applies the load of one phase until it is over, returns false if the
+/// use case was stopped before
+async fn run_phase(control: &UseCaseControl, phase: &Phase, shutdown: &CancellationToken) -> bool {
+    if let Some(concurrency) = phase.concurrency {
+        control.set_concurrency(concurrency);
+    }
+    let duration = Duration::from_secs(phase.duration_secs);
+    let start = Instant::now();
+    loop {
+        let elapsed = start.elapsed();
+        if elapsed >= duration {
+            return true;
+        }
+        control.set_rate(rate_at(phase, elapsed));
+        let wait = if phase.from_ops_per_sec.is_some() {
+            RAMP_STEP.min(duration - elapsed)
+        } else {
+            duration - elapsed
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = shutdown.cancelled() => return false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_at_ramps_linearly() {
+        let ramp = Phase {
+            name: "ramp".to_string(),
+            duration_secs: 300,
+            ops_per_sec: 10_000.0,
+            from_ops_per_sec: Some(0.0),
+            concurrency: None,
+        };
+        assert_eq!(rate_at(&ramp, Duration::ZERO), 1.0);
+        assert_eq!(rate_at(&ramp, Duration::from_secs(150)), 5_000.0);
+        assert_eq!(rate_at(&ramp, Duration::from_secs(600)), 10_000.0);
+
+        let hold = Phase {
+            from_ops_per_sec: None,
+            ..ramp
+        };
+        assert_eq!(rate_at(&hold, Duration::from_secs(150)), 10_000.0);
+    }
+}
@@ -0,0 +1,296 @@
+use crate::arangodb::{database_exists, graph_exists, ApiRequest, Connection};
+use crate::config::{DatabaseConfig, Phase, PregelConfig, PregelJob, UseCaseConfig};
+use crate::control;
+use crate::metrics::observe_request;
+use crate::pacer::Pacer;
+use crate::usecase::{run_workers, send_observed, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{info, warn};
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "pregel";
+
+/// The Pregel use case: runs graph analytics jobs against an existing
+/// graph, by default the one seeded by the graph use case, and records
+/// the runtime of every job. Running it next to another use case shows
+/// how the jobs and the other load interfere.
+pub struct PregelUseCase {
+    pregel_config: PregelConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl PregelUseCase {
+    pub fn new(
+        pregel_config: PregelConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        PregelUseCase {
+            pregel_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Pregel use case was not set up"))
+    }
+
+    fn database_name(&self) -> String {
+        self.db_config.database_name(&self.pregel_config.database)
+    }
+
+    fn log_configuration(&self) {
+        let pregel_config = &self.pregel_config;
+        info!("Starting Pregel use case with configuration:");
+        info!("Database: {}", self.database_name());
+        info!("Graph: {}", pregel_config.graph);
+        for job in &pregel_config.jobs {
+            info!(
+                "Job {} (weight {}): {:?}",
+                job.algorithm, job.weight, job.params
+            );
+        }
+        info!("Concurrency: {}", pregel_config.concurrency);
+        if pregel_config.target_ops_per_sec > 0.0 {
+            info!("Target rate: {} jobs/s", pregel_config.target_ops_per_sec);
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!(
+            "Poll interval: {}ms, timeout: {}s",
+            pregel_config.poll_interval_ms, pregel_config.timeout_secs
+        );
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Picks a random job according to the configured weights
+fn choose_job<'a, R: Rng>(jobs: &'a [PregelJob], rng: &mut R) -> &'a PregelJob {
+    let total: u32 = jobs.iter().map(|job| job.weight).sum();
+    let mut pick = rng.random_range(0..total.max(1));
+    for job in jobs {
+        if pick < job.weight {
+            return job;
+        }
+        pick -= job.weight;
+    }
+    &jobs[0]
+}
+
+/// How a job ended
+enum JobOutcome {
+    Done,
+    Failed(String),
+    Cancelled,
+}
+
+/// Polls the state of a job until it has finished. On shutdown or when
+/// the job takes longer than the timeout, it is cancelled.
+async fn wait_for_job(
+    conn: &Connection,
+    db_name: &str,
+    pregel_config: &PregelConfig,
+    job_path: &str,
+    shutdown: &CancellationToken,
+) -> Result<JobOutcome> {
+    let deadline = Instant::now() + Duration::from_secs(pregel_config.timeout_secs);
+    let poll_interval = Duration::from_millis(pregel_config.poll_interval_ms.max(1));
+    loop {
+        let cancelled = tokio::select! {
+            _ = tokio::time::sleep(poll_interval) => false,
+            _ = shutdown.cancelled() => true,
+        };
+        if cancelled || Instant::now() >= deadline {
+            let request = ApiRequest::in_database(Method::DELETE, db_name, job_path);
+            send_observed(conn, USECASE, "cancel", request, &[]).await?;
+            return Ok(if cancelled {
+                JobOutcome::Cancelled
+            } else {
+                JobOutcome::Failed("timeout".to_string())
+            });
+        }
+
+        let request = ApiRequest::in_database(Method::GET, db_name, job_path);
+        let status: Value = send_observed(conn, USECASE, "status", request, &[])
+            .await?
+            .json()?;
+        match status["state"].as_str().unwrap_or_default() {
+            "done" => return Ok(JobOutcome::Done),
+            state @ ("canceled" | "fatal error") => {
+                return Ok(JobOutcome::Failed(state.to_string()))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Starts a random job and waits for it to finish. The runtime of the job
+/// from its start until it is done is recorded under the name of its
+/// algorithm.
+async fn perform_job(
+    conn: &Connection,
+    db_name: &str,
+    pregel_config: &PregelConfig,
+    shutdown: &CancellationToken,
+    mut rng: StdRng,
+) -> Result<()> {
+    let job = choose_job(&pregel_config.jobs, &mut rng);
+    let start = Instant::now();
+    let request =
+        ApiRequest::in_database(Method::POST, db_name, "/_api/control_pregel").json(&json!({
+            "algorithm": job.algorithm,
+            "graphName": pregel_config.graph,
+            "params": job.params,
+        }));
+    let response: Value = send_observed(conn, USECASE, "start", request, &[])
+        .await?
+        .json()?;
+    // Older versions return the id as a number, newer ones as a string
+    let id = match &response {
+        Value::String(id) => id.clone(),
+        Value::Number(id) => id.to_string(),
+        _ => return Err(anyhow::anyhow!("Pregel start returned no job id")),
+    };
+    let job_path = format!("/_api/control_pregel/{}", id);
+
+    let outcome = wait_for_job(conn, db_name, pregel_config, &job_path, shutdown).await?;
+    let runtime = start.elapsed();
+    match outcome {
+        JobOutcome::Done => {
+            observe_request(USECASE, &job.algorithm, runtime, true);
+            info!(
+                "Pregel job {} ({}) done after {:.1}s",
+                id,
+                job.algorithm,
+                runtime.as_secs_f64()
+            );
+            Ok(())
+        }
+        JobOutcome::Failed(reason) => {
+            observe_request(USECASE, &job.algorithm, runtime, false);
+            Err(anyhow::anyhow!(
+                "Pregel job {} ({}) failed: {}",
+                id,
+                job.algorithm,
+                reason
+            ))
+        }
+        JobOutcome::Cancelled => {
+            info!(
+                "Pregel job {} ({}) cancelled on shutdown",
+                id, job.algorithm
+            );
+            Ok(())
+        }
+    }
+}
+
+#[async_trait]
+impl UseCase for PregelUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        if self.pregel_config.jobs.iter().all(|job| job.weight == 0) {
+            return Err(anyhow::anyhow!(
+                "No Pregel jobs configured, at least one job must have a positive weight"
+            ));
+        }
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+
+        // The graph is usually created by the graph use case, which may
+        // still be seeding it, so wait for it to appear
+        let db_name = self.database_name();
+        while !(database_exists(&connection, &db_name).await?
+            && graph_exists(&connection, &db_name, &self.pregel_config.graph).await?)
+        {
+            info!(
+                "Waiting for graph {} in database {} to be created",
+                self.pregel_config.graph, db_name
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        }
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let db_name = Arc::new(self.database_name());
+        let pacer = Arc::new(Pacer::new(self.pregel_config.target_ops_per_sec));
+        // The job mix can be changed by reloading the configuration
+        let pregel_config = Arc::new(RwLock::new(Arc::new(self.pregel_config.clone())));
+        let reloaded = Arc::clone(&pregel_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(pregel) = &config.pregel {
+                if pregel.jobs.iter().all(|job| job.weight == 0) {
+                    warn!("Ignoring reloaded Pregel jobs, no job has a positive weight");
+                    return;
+                }
+                control::apply_load(USECASE, pregel.concurrency, pregel.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(pregel.clone());
+            }
+        });
+        let jobs_shutdown = shutdown.clone();
+        run_workers(
+            USECASE,
+            self.pregel_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let db_name = Arc::clone(&db_name);
+                let pregel_config = Arc::clone(&pregel_config.read().unwrap());
+                let shutdown = jobs_shutdown.clone();
+                async move {
+                    perform_job(&connection, &db_name, &pregel_config, &shutdown, rng).await
+                }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        Ok(())
+    }
+}
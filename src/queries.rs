@@ -0,0 +1,276 @@
+use crate::arangodb::{database_exists, run_query, Connection};
+use crate::config::{
+    BindVarGenerator, DatabaseConfig, Phase, QueriesConfig, QueryTemplate, UseCaseConfig,
+};
+use crate::control;
+use crate::metrics::observe_request;
+use crate::pacer::Pacer;
+use crate::usecase::{run_workers, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{info, warn};
+use rand::distr::{Alphanumeric, SampleString};
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "queries";
+
+/// The queries use case: runs a weighted mix of AQL query templates with
+/// randomly generated bind parameters against an existing database, for
+/// example the one seeded by the CRUD use case.
+pub struct QueriesUseCase {
+    queries_config: QueriesConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl QueriesUseCase {
+    pub fn new(
+        queries_config: QueriesConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        QueriesUseCase {
+            queries_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Queries use case was not set up"))
+    }
+
+    fn database_name(&self) -> String {
+        self.db_config.database_name(&self.queries_config.database)
+    }
+
+    fn log_configuration(&self) {
+        let queries_config = &self.queries_config;
+        info!("Starting queries use case with configuration:");
+        info!("Database: {}", self.database_name());
+        for template in &queries_config.queries {
+            info!(
+                "Query {} (weight {}): {}",
+                template.name, template.weight, template.query
+            );
+        }
+        info!("Batch size: {}", queries_config.batch_size);
+        info!("Concurrency: {}", queries_config.concurrency);
+        if queries_config.target_ops_per_sec > 0.0 {
+            info!(
+                "Target rate: {} queries/s",
+                queries_config.target_ops_per_sec
+            );
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Generates a value for a bind parameter
+fn generate_bind_var<R: Rng>(generator: &BindVarGenerator, rng: &mut R) -> Value {
+    match generator {
+        BindVarGenerator::Key { prefix, min, max } => {
+            json!(format!(
+                "{}{}",
+                prefix,
+                rng.random_range(*min..(*max).max(min + 1))
+            ))
+        }
+        BindVarGenerator::Int { min, max } => json!(rng.random_range(*min..(*max).max(min + 1))),
+        BindVarGenerator::Range { min, max, width } => {
+            let low = rng.random_range(*min..(*max).max(min + 1));
+            json!([low, low + width])
+        }
+        BindVarGenerator::String { length } => json!(Alphanumeric.sample_string(rng, *length)),
+        BindVarGenerator::Constant { value } => value.clone(),
+    }
+}
+
+/// Generates the bind parameters for one execution of a query template
+fn generate_bind_vars<R: Rng>(template: &QueryTemplate, rng: &mut R) -> Map<String, Value> {
+    template
+        .bind_vars
+        .iter()
+        .map(|(name, generator)| (name.clone(), generate_bind_var(generator, rng)))
+        .collect()
+}
+
+/// Picks a random query template according to the configured weights
+fn choose_template<'a, R: Rng>(templates: &'a [QueryTemplate], rng: &mut R) -> &'a QueryTemplate {
+    let total: u32 = templates.iter().map(|template| template.weight).sum();
+    let mut pick = rng.random_range(0..total);
+    for template in templates {
+        if pick < template.weight {
+            return template;
+        }
+        pick -= template.weight;
+    }
+    &templates[0]
+}
+
+/// Runs one randomly chosen query and drains its cursor. The latency is
+/// recorded under the name of the query template and covers all batches.
+async fn perform_query(
+    conn: &Connection,
+    db_name: &str,
+    queries_config: &QueriesConfig,
+    mut rng: StdRng,
+) -> Result<()> {
+    let template = choose_template(&queries_config.queries, &mut rng);
+    let bind_vars = generate_bind_vars(template, &mut rng);
+
+    let start = Instant::now();
+    let result = run_query(
+        conn,
+        db_name,
+        &template.query,
+        &bind_vars,
+        queries_config.batch_size,
+        |_| {},
+    )
+    .await;
+    observe_request(USECASE, &template.name, start.elapsed(), result.is_ok());
+    result.map_err(|e| anyhow::anyhow!("Query {} failed: {}", template.name, e))?;
+    Ok(())
+}
+
+#[async_trait]
+impl UseCase for QueriesUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        if self.queries_config.queries.iter().all(|q| q.weight == 0) {
+            return Err(anyhow::anyhow!(
+                "No queries configured, at least one query must have a positive weight"
+            ));
+        }
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+
+        // The data is usually created by another use case, which may still
+        // be setting up, so wait for the database to appear
+        let db_name = self.database_name();
+        while !database_exists(&connection, &db_name).await? {
+            info!("Waiting for database {} to be created", db_name);
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(1)) => {}
+                _ = shutdown.cancelled() => break,
+            }
+        }
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let db_name = Arc::new(self.database_name());
+        let pacer = Arc::new(Pacer::new(self.queries_config.target_ops_per_sec));
+        // The query mix can be changed by reloading the configuration
+        let queries_config = Arc::new(RwLock::new(Arc::new(self.queries_config.clone())));
+        let reloaded = Arc::clone(&queries_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(queries) = &config.queries {
+                if queries.queries.iter().all(|q| q.weight == 0) {
+                    warn!("Ignoring reloaded queries, no query has a positive weight");
+                    return;
+                }
+                control::apply_load(USECASE, queries.concurrency, queries.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(queries.clone());
+            }
+        });
+        run_workers(
+            USECASE,
+            self.queries_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let db_name = Arc::clone(&db_name);
+                let queries_config = Arc::clone(&queries_config.read().unwrap());
+                async move { perform_query(&connection, &db_name, &queries_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rng;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_generated_bind_vars_stay_in_bounds() {
+        let template = QueryTemplate {
+            name: "lookup".to_string(),
+            query: "FOR d IN c FILTER d._key == @key AND d.number IN @range RETURN d".to_string(),
+            weight: 1,
+            bind_vars: BTreeMap::from([
+                (
+                    "key".to_string(),
+                    BindVarGenerator::Key {
+                        prefix: "K".to_string(),
+                        min: 0,
+                        max: 10,
+                    },
+                ),
+                (
+                    "range".to_string(),
+                    BindVarGenerator::Range {
+                        min: 5,
+                        max: 6,
+                        width: 3,
+                    },
+                ),
+            ]),
+        };
+        let mut rng = rng();
+        for _ in 0..100 {
+            let bind_vars = generate_bind_vars(&template, &mut rng);
+            let key = bind_vars["key"].as_str().unwrap();
+            let n: u64 = key.strip_prefix('K').unwrap().parse().unwrap();
+            assert!(n < 10);
+            assert_eq!(bind_vars["range"], json!([5, 8]));
+        }
+    }
+}
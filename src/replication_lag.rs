@@ -0,0 +1,227 @@
+use crate::arangodb::{
+    collection_exists, create_collection, create_database, database_exists, ApiRequest, Connection,
+};
+use crate::config::{CollectionProperties, DatabaseConfig, ReplicationLagConfig};
+use crate::metrics::METRICS;
+use anyhow::Result;
+use log::{info, warn};
+use reqwest::{Method, StatusCode};
+use serde_json::json;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+
+const DATABASE: &str = "replication_lag";
+const COLLECTION: &str = "markers";
+
+/// Writes a marker document every `interval_ms` next to the load and
+/// measures how long it takes until the marker can be read on the
+/// followers: with dirty reads on the follower shards of the cluster, or
+/// with plain reads in the follower datacenter of a DC2DC setup. The lag
+/// is exported in `adb_loader_replication_lag_seconds`, markers which do
+/// not show up within `timeout_secs` in
+/// `adb_loader_replication_lag_timeouts_total`.
+pub async fn probe(
+    db_config: DatabaseConfig,
+    config: ReplicationLagConfig,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let connection = Connection::new(&db_config).await?;
+    let db_name = db_config.database_name(DATABASE);
+    prepare(&connection, &db_name, &config).await?;
+
+    let datacenter = if config.follower_endpoints.is_empty() {
+        None
+    } else {
+        let mut follower_config = db_config.clone();
+        follower_config.endpoints = config.follower_endpoints.clone();
+        follower_config.auto_discover = false;
+        Some(Connection::new(&follower_config).await?)
+    };
+    let (reader, target) = match &datacenter {
+        Some(follower) => (follower, "datacenter"),
+        None => (&connection, "followers"),
+    };
+    info!(
+        "Measuring the replication lag to the {} every {}ms",
+        target, config.interval_ms
+    );
+
+    // The keys start with the start of the run, so that they do not
+    // collide with markers an earlier run left behind
+    let run = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut ticker = tokio::time::interval(Duration::from_millis(config.interval_ms.max(1)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    for sequence in 1u64.. {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => break,
+        }
+        let key = format!("{}_{}", run, sequence);
+        let written = Instant::now();
+        if let Err(e) = write_marker(&connection, &db_name, &key).await {
+            warn!("Cannot write replication marker {}: {}", key, e);
+            continue;
+        }
+        let dirty = datacenter.is_none();
+        match wait_for_marker(reader, &db_name, &key, dirty, &config, written, &shutdown).await {
+            Ok(Some(lag)) => METRICS
+                .replication_lag
+                .with_label_values(&[target])
+                .observe(lag.as_secs_f64()),
+            Ok(None) if shutdown.is_cancelled() => break,
+            Ok(None) => {
+                warn!(
+                    "Replication marker {} not visible on the {} after {}s",
+                    key, target, config.timeout_secs
+                );
+                METRICS
+                    .replication_lag_timeouts
+                    .with_label_values(&[target])
+                    .inc();
+            }
+            Err(e) => warn!("Cannot read replication marker {}: {}", key, e),
+        }
+        // The collection only ever holds the current marker
+        let request = ApiRequest::in_database(
+            Method::DELETE,
+            &db_name,
+            &format!("/_api/document/{}/{}", COLLECTION, key),
+        );
+        if let Err(e) = connection.send(request).await {
+            warn!("Cannot remove replication marker {}: {}", key, e);
+        }
+    }
+    Ok(())
+}
+
+/// Creates the database and the collection of the markers unless they
+/// exist
+async fn prepare(conn: &Connection, db_name: &str, config: &ReplicationLagConfig) -> Result<()> {
+    if !database_exists(conn, db_name).await? {
+        info!("Creating database {}", db_name);
+        create_database(conn, db_name).await?;
+    }
+    if !collection_exists(conn, db_name, COLLECTION).await? {
+        create_collection(
+            conn,
+            db_name,
+            COLLECTION,
+            1,
+            config.replication_factor,
+            &CollectionProperties::default(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Writes a marker with the time it was written, for a look at the
+/// collection by hand
+async fn write_marker(conn: &Connection, db_name: &str, key: &str) -> Result<()> {
+    let written_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let request = ApiRequest::in_database(
+        Method::POST,
+        db_name,
+        &format!("/_api/document/{}", COLLECTION),
+    )
+    .json(&json!({ "_key": key, "written_at": written_at }));
+    conn.send(request).await?.error_for_status("write marker")?;
+    Ok(())
+}
+
+/// Reads a marker until `confirmations` consecutive reads found it and
+/// returns the time from `written` until the first of them, or `None` on
+/// timeout or shutdown
+async fn wait_for_marker(
+    reader: &Connection,
+    db_name: &str,
+    key: &str,
+    dirty: bool,
+    config: &ReplicationLagConfig,
+    written: Instant,
+    shutdown: &CancellationToken,
+) -> Result<Option<Duration>> {
+    let path = format!("/_api/document/{}/{}", COLLECTION, key);
+    let timeout = Duration::from_secs(config.timeout_secs);
+    let mut sightings = Sightings::new(config.confirmations);
+    while written.elapsed() < timeout {
+        let mut request = ApiRequest::in_database(Method::GET, db_name, &path);
+        if dirty {
+            request = request.allow_dirty_read();
+        }
+        let response = reader.send(request).await?;
+        // Until the follower datacenter has the database, it is not found
+        // either
+        let found = response.status != StatusCode::NOT_FOUND;
+        if found {
+            response.error_for_status("read marker")?;
+        }
+        if let Some(lag) = sightings.record(found, written.elapsed()) {
+            return Ok(Some(lag));
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(config.poll_interval_ms)) => {}
+            _ = shutdown.cancelled() => return Ok(None),
+        }
+    }
+    Ok(None)
+}
+
+/// Tracks the consecutive reads which found a marker
+struct Sightings {
+    confirmations: u32,
+    streak: u32,
+    first_seen: Duration,
+}
+
+impl Sightings {
+    fn new(confirmations: u32) -> Self {
+        Sightings {
+            confirmations: confirmations.max(1),
+            streak: 0,
+            first_seen: Duration::ZERO,
+        }
+    }
+
+    /// Records a read `elapsed` after the write and returns the lag once
+    /// enough consecutive reads found the marker
+    fn record(&mut self, found: bool, elapsed: Duration) -> Option<Duration> {
+        if !found {
+            self.streak = 0;
+            return None;
+        }
+        if self.streak == 0 {
+            self.first_seen = elapsed;
+        }
+        self.streak += 1;
+        (self.streak >= self.confirmations).then_some(self.first_seen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sightings_need_consecutive_reads() {
+        let ms = Duration::from_millis;
+        let mut sightings = Sightings::new(3);
+        assert_eq!(sightings.record(true, ms(1)), None);
+        assert_eq!(sightings.record(true, ms(2)), None);
+        // A read which misses the marker starts over
+        assert_eq!(sightings.record(false, ms(3)), None);
+        assert_eq!(sightings.record(true, ms(4)), None);
+        assert_eq!(sightings.record(true, ms(5)), None);
+        assert_eq!(sightings.record(true, ms(6)), Some(ms(4)));
+
+        let mut single = Sightings::new(0);
+        assert_eq!(single.record(true, ms(7)), Some(ms(7)));
+    }
+}
@@ -0,0 +1,403 @@
+use crate::capacity::CapacityResult;
+use crate::config::Config;
+use crate::latency::{LatencySummary, LATENCIES};
+use crate::metrics::operation_stats;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The counters which break down the errors and rejections of a run
+const ERROR_COUNTERS: &[&str] = &[
+    "adb_loader_errors_total",
+    "adb_loader_queue_time_rejections_total",
+    "adb_loader_conflicts_total",
+    "adb_loader_unique_violations_total",
+    "adb_loader_warmup_errors_total",
+];
+
+/// The machine-readable summary of a run, written at its end
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Report {
+    /// See `Config::fingerprint`
+    pub config_hash: String,
+    pub version: String,
+    /// Start of the run in seconds since the epoch
+    pub started_at: u64,
+    pub duration_secs: f64,
+    pub failed_usecases: usize,
+    pub operations: Vec<OperationReport>,
+    /// The non-zero values of the error counters, by counter name and the
+    /// labels of the value, e.g. `crud/insert`
+    pub errors: BTreeMap<String, BTreeMap<String, u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capacity: Option<CapacityResult>,
+    /// The number of loaders whose reports were merged into this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loaders: Option<usize>,
+}
+
+/// Counts and latency percentiles of one operation of one use case, the
+/// latencies are in milliseconds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OperationReport {
+    pub usecase: String,
+    pub operation: String,
+    pub requests: u64,
+    pub errors: u64,
+    /// Average throughput over the whole run
+    pub ops_per_sec: f64,
+    pub avg_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub p999_ms: f64,
+    pub max_ms: f64,
+    /// The percentiles measured from the intended start of the operations,
+    /// only for use cases with a target rate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corrected_p50_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corrected_p99_ms: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub corrected_max_ms: Option<f64>,
+}
+
+impl Report {
+    /// Collects the report from the metrics of the process
+    pub fn collect(
+        config: &Config,
+        started: SystemTime,
+        failed_usecases: usize,
+        capacity: Option<CapacityResult>,
+    ) -> Self {
+        let ms = |us: u64| us as f64 / 1000.0;
+        let by_operation = |summaries: Vec<LatencySummary>| -> BTreeMap<_, _> {
+            summaries
+                .into_iter()
+                .map(|summary| {
+                    (
+                        (summary.usecase.clone(), summary.operation.clone()),
+                        summary,
+                    )
+                })
+                .collect()
+        };
+        let latencies = by_operation(LATENCIES.total());
+        let corrected = by_operation(LATENCIES.total_corrected());
+        let duration_secs = started.elapsed().unwrap_or(Duration::ZERO).as_secs_f64();
+        let operations = operation_stats()
+            .into_iter()
+            .map(|stats| {
+                let key = (stats.usecase.clone(), stats.operation.clone());
+                let latency = latencies.get(&key).cloned().unwrap_or_default();
+                let corrected = corrected.get(&key);
+                OperationReport {
+                    avg_ms: stats.avg_latency_ms(),
+                    usecase: stats.usecase,
+                    operation: stats.operation,
+                    requests: stats.requests,
+                    errors: stats.errors,
+                    ops_per_sec: stats.requests as f64 / duration_secs.max(0.001),
+                    p50_ms: ms(latency.p50),
+                    p90_ms: ms(latency.p90),
+                    p99_ms: ms(latency.p99),
+                    p999_ms: ms(latency.p999),
+                    max_ms: ms(latency.max),
+                    corrected_p50_ms: corrected.map(|c| ms(c.p50)),
+                    corrected_p99_ms: corrected.map(|c| ms(c.p99)),
+                    corrected_max_ms: corrected.map(|c| ms(c.max)),
+                }
+            })
+            .collect();
+        Report {
+            config_hash: config.fingerprint(),
+            version: config.version.clone(),
+            started_at: started
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            duration_secs: started.elapsed().unwrap_or(Duration::ZERO).as_secs_f64(),
+            failed_usecases,
+            operations,
+            errors: error_counters(),
+            capacity,
+            loaders: None,
+        }
+    }
+
+    /// Merges the reports of the loaders of a coordinated run. Counts and
+    /// throughputs add up, the average latency is weighted by the requests.
+    /// Percentiles cannot be merged exactly, the merged ones are the
+    /// highest of any loader and thus an upper bound.
+    pub fn merge(reports: Vec<Report>) -> Option<Report> {
+        let loaders = reports.len();
+        let mut reports = reports.into_iter();
+        let mut merged = reports.next()?;
+        for report in reports {
+            merged.started_at = merged.started_at.min(report.started_at);
+            merged.duration_secs = merged.duration_secs.max(report.duration_secs);
+            merged.failed_usecases += report.failed_usecases;
+            merged.capacity = merged.capacity.or(report.capacity);
+            for (counter, values) in report.errors {
+                let merged_values = merged.errors.entry(counter).or_default();
+                for (labels, value) in values {
+                    *merged_values.entry(labels).or_default() += value;
+                }
+            }
+            for operation in report.operations {
+                let Some(existing) = merged.operations.iter_mut().find(|existing| {
+                    existing.usecase == operation.usecase
+                        && existing.operation == operation.operation
+                }) else {
+                    merged.operations.push(operation);
+                    continue;
+                };
+                let requests = existing.requests + operation.requests;
+                if requests > 0 {
+                    existing.avg_ms = (existing.avg_ms * existing.requests as f64
+                        + operation.avg_ms * operation.requests as f64)
+                        / requests as f64;
+                }
+                existing.requests = requests;
+                existing.errors += operation.errors;
+                existing.ops_per_sec += operation.ops_per_sec;
+                existing.p50_ms = existing.p50_ms.max(operation.p50_ms);
+                existing.p90_ms = existing.p90_ms.max(operation.p90_ms);
+                existing.p99_ms = existing.p99_ms.max(operation.p99_ms);
+                existing.p999_ms = existing.p999_ms.max(operation.p999_ms);
+                existing.max_ms = existing.max_ms.max(operation.max_ms);
+                let max = |a: Option<f64>, b: Option<f64>| match (a, b) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    (a, b) => a.or(b),
+                };
+                existing.corrected_p50_ms =
+                    max(existing.corrected_p50_ms, operation.corrected_p50_ms);
+                existing.corrected_p99_ms =
+                    max(existing.corrected_p99_ms, operation.corrected_p99_ms);
+                existing.corrected_max_ms =
+                    max(existing.corrected_max_ms, operation.corrected_max_ms);
+            }
+        }
+        merged.loaders = Some(loaders);
+        Some(merged)
+    }
+
+    /// Reads a report written by `write`
+    pub fn read(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Cannot read report {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Writes the report as pretty-printed JSON
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .map_err(|e| anyhow::anyhow!("Cannot write report {}: {}", path.display(), e))
+    }
+}
+
+/// Returns the non-zero values of the error counters
+fn error_counters() -> BTreeMap<String, BTreeMap<String, u64>> {
+    let mut counters = BTreeMap::new();
+    for family in prometheus::gather() {
+        if !ERROR_COUNTERS.contains(&family.get_name()) {
+            continue;
+        }
+        let values: BTreeMap<String, u64> = family
+            .get_metric()
+            .iter()
+            .filter(|metric| metric.get_counter().get_value() > 0.0)
+            .map(|metric| {
+                // Prometheus sorts the labels by name, the use case goes first
+                let mut labels = metric.get_label().to_vec();
+                labels.sort_by_key(|pair| pair.get_name() != "usecase");
+                let labels: Vec<&str> = labels.iter().map(|pair| pair.get_value()).collect();
+                (labels.join("/"), metric.get_counter().get_value() as u64)
+            })
+            .collect();
+        if !values.is_empty() {
+            counters.insert(family.get_name().to_string(), values);
+        }
+    }
+    counters
+}
+
+/// The regressions a run may have compared with a baseline before it fails,
+/// in percent
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerances {
+    pub throughput_drop_pct: f64,
+    pub p99_increase_pct: f64,
+}
+
+/// Operations with fewer requests in the baseline are not compared, their
+/// percentiles are too noisy
+const MIN_REQUESTS: u64 = 100;
+
+/// The result of comparing a run with a baseline
+#[derive(Debug, Default)]
+pub struct Comparison {
+    /// One human-readable line per operation
+    pub lines: Vec<String>,
+    pub regressions: usize,
+}
+
+/// Compares the throughput and the p99 latency of every operation of the
+/// baseline with the current run. An operation regresses if its throughput
+/// dropped or its p99 latency rose by more than the tolerance, or if it is
+/// missing in the current run.
+pub fn compare(baseline: &Report, current: &Report, tolerances: Tolerances) -> Comparison {
+    let change_pct = |before: f64, after: f64| {
+        if before > 0.0 {
+            (after - before) / before * 100.0
+        } else {
+            0.0
+        }
+    };
+    let mut comparison = Comparison::default();
+    if baseline.config_hash != current.config_hash {
+        comparison.lines.push(format!(
+            "Warning: the configuration changed ({} -> {})",
+            baseline.config_hash, current.config_hash
+        ));
+    }
+    for before in &baseline.operations {
+        let name = format!("{}/{}", before.usecase, before.operation);
+        if before.requests < MIN_REQUESTS {
+            comparison.lines.push(format!(
+                "{}: skipped, only {} requests",
+                name, before.requests
+            ));
+            continue;
+        }
+        let Some(after) = current
+            .operations
+            .iter()
+            .find(|op| op.usecase == before.usecase && op.operation == before.operation)
+        else {
+            comparison
+                .lines
+                .push(format!("{}: REGRESSION, missing", name));
+            comparison.regressions += 1;
+            continue;
+        };
+        let throughput = change_pct(before.ops_per_sec, after.ops_per_sec);
+        let p99 = change_pct(before.p99_ms, after.p99_ms);
+        let regressed =
+            -throughput > tolerances.throughput_drop_pct || p99 > tolerances.p99_increase_pct;
+        if regressed {
+            comparison.regressions += 1;
+        }
+        comparison.lines.push(format!(
+            "{}: {:.1} -> {:.1} ops/s ({:+.1}%), p99 {:.2} -> {:.2}ms ({:+.1}%){}",
+            name,
+            before.ops_per_sec,
+            after.ops_per_sec,
+            throughput,
+            before.p99_ms,
+            after.p99_ms,
+            p99,
+            if regressed { ", REGRESSION" } else { "" }
+        ));
+    }
+    comparison
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operation(name: &str, requests: u64, ops_per_sec: f64, p99_ms: f64) -> OperationReport {
+        OperationReport {
+            usecase: "crud".to_string(),
+            operation: name.to_string(),
+            requests,
+            errors: 0,
+            ops_per_sec,
+            avg_ms: 1.0,
+            p50_ms: 1.0,
+            p90_ms: 1.0,
+            p99_ms,
+            p999_ms: p99_ms,
+            max_ms: p99_ms,
+            corrected_p50_ms: None,
+            corrected_p99_ms: None,
+            corrected_max_ms: None,
+        }
+    }
+
+    fn report(operations: Vec<OperationReport>) -> Report {
+        Report {
+            config_hash: "0123456789abcdef".to_string(),
+            version: "1".to_string(),
+            started_at: 0,
+            duration_secs: 60.0,
+            failed_usecases: 0,
+            operations,
+            errors: BTreeMap::new(),
+            capacity: None,
+            loaders: None,
+        }
+    }
+
+    #[test]
+    fn test_merge_adds_up_loaders() {
+        let mut first = report(vec![operation("read", 6000, 100.0, 10.0)]);
+        first.errors.insert(
+            "adb_loader_errors_total".to_string(),
+            BTreeMap::from([("crud/read".to_string(), 2)]),
+        );
+        let mut second = report(vec![
+            operation("read", 2000, 50.0, 30.0),
+            operation("insert", 100, 2.0, 5.0),
+        ]);
+        second.operations[0].avg_ms = 5.0;
+        second.errors.insert(
+            "adb_loader_errors_total".to_string(),
+            BTreeMap::from([("crud/read".to_string(), 3)]),
+        );
+
+        let merged = Report::merge(vec![first, second]).unwrap();
+        assert_eq!(merged.loaders, Some(2));
+        assert_eq!(merged.operations.len(), 2);
+        let read = &merged.operations[0];
+        assert_eq!(read.requests, 8000);
+        assert_eq!(read.ops_per_sec, 150.0);
+        assert_eq!(read.avg_ms, 2.0);
+        assert_eq!(read.p99_ms, 30.0);
+        assert_eq!(merged.errors["adb_loader_errors_total"]["crud/read"], 5);
+        assert!(Report::merge(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn test_compare_finds_regressions() {
+        let baseline = report(vec![
+            operation("read", 6000, 100.0, 10.0),
+            operation("insert", 6000, 100.0, 10.0),
+            operation("delete", 6000, 100.0, 10.0),
+            operation("rare", 10, 0.1, 10.0),
+            operation("gone", 6000, 100.0, 10.0),
+        ]);
+        let current = report(vec![
+            // Within the tolerances
+            operation("read", 5700, 95.0, 11.0),
+            // Throughput dropped
+            operation("insert", 4800, 80.0, 10.0),
+            // Latency rose
+            operation("delete", 6000, 100.0, 15.0),
+            operation("rare", 1, 0.01, 100.0),
+        ]);
+        let tolerances = Tolerances {
+            throughput_drop_pct: 10.0,
+            p99_increase_pct: 20.0,
+        };
+        let comparison = compare(&baseline, &current, tolerances);
+        assert_eq!(comparison.regressions, 3);
+        assert_eq!(comparison.lines.len(), 5);
+        assert!(!comparison.lines[0].contains("REGRESSION"));
+        assert!(comparison.lines[3].contains("skipped"));
+    }
+}
@@ -0,0 +1,169 @@
+//! The tokio runtimes the use cases run on, according to `runtime` in the
+//! configuration: one runtime per use case, one shared by all of them or
+//! one per use case whose threads are pinned to CPUs of their own.
+
+use crate::config::{Config, RuntimeStrategy};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::ops::Range;
+use std::sync::OnceLock;
+use tokio::runtime::{Builder, Runtime};
+
+/// How the runtimes are built, without it every use case gets its own
+static PLAN: OnceLock<Plan> = OnceLock::new();
+
+#[derive(Debug)]
+enum Plan {
+    PerUsecase,
+    Shared(Runtime),
+    Pinned(HashMap<&'static str, Range<usize>>),
+}
+
+/// The runtime a use case runs on
+pub enum UseCaseRuntime {
+    Own(Runtime),
+    Shared(&'static Runtime),
+}
+
+impl UseCaseRuntime {
+    /// Runs a future to completion on the calling thread, the tasks it
+    /// spawns run on the worker threads of the runtime
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        match self {
+            UseCaseRuntime::Own(runtime) => runtime.block_on(future),
+            UseCaseRuntime::Shared(runtime) => runtime.block_on(future),
+        }
+    }
+}
+
+/// Returns the CPUs the threads of every active use case are pinned to,
+/// consecutive ranges in the order of `active_usecases` from
+/// `runtime.first_core` on
+pub fn core_sets(config: &Config) -> HashMap<&'static str, Range<usize>> {
+    let mut next = config.runtime.first_core;
+    config
+        .active_usecases
+        .all()
+        .into_iter()
+        .filter(|(_, usecase)| usecase.on)
+        .map(|(name, usecase)| {
+            let start = next;
+            next += usecase.threads.max(1) as usize;
+            (name, start..next)
+        })
+        .collect()
+}
+
+/// Sets up the runtimes according to the configuration, the shared one is
+/// built right away. Must be called before any use case starts, only the
+/// first call counts.
+///
+/// # Errors
+/// Fails if the shared runtime cannot be built
+pub fn configure(config: &Config) -> io::Result<()> {
+    if PLAN.get().is_some() {
+        return Ok(());
+    }
+    let plan = match config.runtime.strategy {
+        RuntimeStrategy::PerUsecase => Plan::PerUsecase,
+        RuntimeStrategy::Shared => {
+            let threads = config.runtime.shared_threads.unwrap_or_else(|| {
+                std::thread::available_parallelism().map_or(1, |cpus| cpus.get())
+            });
+            info!("All use cases share one runtime with {} threads", threads);
+            Plan::Shared(
+                Builder::new_multi_thread()
+                    .worker_threads(threads.max(1))
+                    .thread_name("shared-worker")
+                    .enable_all()
+                    .build()?,
+            )
+        }
+        RuntimeStrategy::Pinned => {
+            let core_sets = core_sets(config);
+            for (name, cores) in &core_sets {
+                info!("Use case {} runs on CPUs {:?}", name, cores);
+            }
+            Plan::Pinned(core_sets)
+        }
+    };
+    let _ = PLAN.set(plan);
+    Ok(())
+}
+
+/// Returns the runtime for a use case with the given number of threads
+///
+/// # Errors
+/// Fails if the runtime of the use case cannot be built
+pub fn for_usecase(name: &str, threads: usize) -> io::Result<UseCaseRuntime> {
+    let mut builder = Builder::new_multi_thread();
+    builder.worker_threads(threads.max(1)).enable_all();
+    match PLAN.get() {
+        Some(Plan::Shared(runtime)) => return Ok(UseCaseRuntime::Shared(runtime)),
+        Some(Plan::Pinned(core_sets)) => match core_sets.get(name) {
+            Some(cores) => {
+                let cores = cores.clone();
+                builder.on_thread_start(move || pin_current_thread(&cores));
+            }
+            None => warn!("Use case {} has no CPUs to be pinned to", name),
+        },
+        Some(Plan::PerUsecase) | None => {}
+    }
+    Ok(UseCaseRuntime::Own(builder.build()?))
+}
+
+/// Restricts the calling thread to the given CPUs
+#[cfg(target_os = "linux")]
+fn pin_current_thread(cores: &Range<usize>) {
+    // SAFETY: `cpu_set_t` is a plain bit set, for which all zeros is the
+    // empty set, and `CPU_SET` ignores CPUs beyond its size
+    let result = unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        for core in cores.clone() {
+            libc::CPU_SET(core, &mut set);
+        }
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set)
+    };
+    if result != 0 {
+        warn!(
+            "Pinning a thread to CPUs {:?} failed: {}",
+            cores,
+            io::Error::last_os_error()
+        );
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_current_thread(cores: &Range<usize>) {
+    warn!(
+        "Pinning threads to CPUs {:?} is only supported on Linux",
+        cores
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_core_sets_are_disjoint() {
+        let mut config = Config::from_file("config.yaml").unwrap();
+        config.runtime.first_core = 2;
+        config.active_usecases.crud.on = true;
+        config.active_usecases.crud.threads = 3;
+        config.active_usecases.graph.on = true;
+        config.active_usecases.graph.threads = 2;
+        config.active_usecases.queries.on = false;
+        let core_sets = core_sets(&config);
+        assert_eq!(core_sets["crud"], 2..5);
+        assert_eq!(core_sets["graph"], 5..7);
+        assert!(!core_sets.contains_key("queries"));
+        let mut cores: Vec<usize> = core_sets.values().flat_map(Range::clone).collect();
+        let total = cores.len();
+        cores.sort();
+        cores.dedup();
+        assert_eq!(cores.len(), total);
+    }
+}
@@ -0,0 +1,522 @@
+use crate::arangodb::{
+    collection_exists, create_collection, create_database, create_index, create_view,
+    database_exists, drop_database, run_query, view_exists, ApiRequest, Connection,
+};
+use crate::config::{
+    CollectionProperties, DatabaseConfig, LanguageMix, Phase, SearchConfig, UseCaseConfig, ViewType,
+};
+use crate::control;
+use crate::error_policy;
+use crate::metrics::{observe_request, METRICS};
+use crate::pacer::Pacer;
+use crate::random::stream_rng;
+use crate::text::random_text;
+use crate::usecase::{run_workers, send_observed, CollectionCount, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use log::{info, warn};
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "search";
+
+/// The name of the view all search queries run against
+const VIEW_NAME: &str = "v";
+
+/// The name of the inverted indexes backing a `search-alias` view
+const INDEX_NAME: &str = "text_inverted";
+
+/// The analyzer used for the text attribute, built into ArangoDB
+const ANALYZER: &str = "text_en";
+
+/// Maximal number of results a search query returns
+const RESULT_LIMIT: u32 = 10;
+
+/// The search use case: seeds collections with text-rich documents, makes
+/// them searchable through a view and runs full text queries against it.
+pub struct SearchUseCase {
+    search_config: SearchConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl SearchUseCase {
+    pub fn new(
+        search_config: SearchConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        SearchUseCase {
+            search_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Search use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let search_config = &self.search_config;
+        info!("Starting search use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!(
+            "Number of collections: {}",
+            search_config.number_of_collections
+        );
+        info!("Number of shards: {}", search_config.number_of_shards);
+        info!("Replication factor: {}", search_config.replication_factor);
+        info!("Number of documents: {}", search_config.number_of_documents);
+        info!("Words per document: {}", search_config.words_per_document);
+        info!("Languages: {:?}", search_config.languages);
+        info!("View type: {:?}", search_config.view_type);
+        info!(
+            "Query mix: phrase {}%, tokens {}%, bm25 {}%",
+            search_config.phrase_pct, search_config.tokens_pct, search_config.bm25_pct
+        );
+        info!("Concurrency: {}", search_config.concurrency);
+        if search_config.target_ops_per_sec > 0.0 {
+            info!(
+                "Target rate: {} queries/s",
+                search_config.target_ops_per_sec
+            );
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the search use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("search")
+}
+
+fn collection_names(search_config: &SearchConfig) -> Vec<String> {
+    (1..=search_config.number_of_collections)
+        .map(|i| format!("c{}", i))
+        .collect()
+}
+
+/// Generates a document with a title and a longer text to search in
+fn generate_search_document<R: Rng>(
+    rng: &mut R,
+    key: u32,
+    words: u32,
+    languages: &LanguageMix,
+) -> Value {
+    json!({
+        "_key": format!("K{}", key),
+        "title": random_text(rng, 5, languages),
+        "text": random_text(rng, words, languages),
+        "number": rng.random::<i32>(),
+    })
+}
+
+/// Inserts the configured number of documents into a collection in
+/// batches, with up to `insert_concurrency` batches in flight. When
+/// shutdown is requested, no new batches are started.
+async fn insert_documents(
+    conn: &Connection,
+    search_config: &SearchConfig,
+    db_name: &str,
+    collection_name: &str,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    const BATCH_SIZE: u32 = 1000;
+    let num_documents = search_config.number_of_documents;
+    let path = format!("/_api/document/{}", collection_name);
+    let path = path.as_str();
+    // With a global seed, every run seeds the same documents
+    let stream = format!("{}/{}", USECASE, collection_name);
+    let stream = stream.as_str();
+
+    let batches = (1..=num_documents)
+        .step_by(BATCH_SIZE as usize)
+        .map(|start| (start, (start + BATCH_SIZE - 1).min(num_documents)));
+
+    stream::iter(batches)
+        .take_until(shutdown.cancelled())
+        .map(|(batch_start, batch_end)| async move {
+            let batch: Vec<Value> = (batch_start..=batch_end)
+                .map(|i| {
+                    let mut rng = stream_rng(stream, u64::from(i));
+                    generate_search_document(
+                        &mut rng,
+                        i,
+                        search_config.words_per_document,
+                        &search_config.languages,
+                    )
+                })
+                .collect();
+
+            let request = ApiRequest::in_database(Method::POST, db_name, path).json(&batch);
+            if let Err(e) = send_observed(conn, USECASE, "insert_batch", request, &[]).await {
+                return error_policy::tolerate(e);
+            }
+
+            METRICS
+                .documents_inserted
+                .with_label_values(&[USECASE])
+                .inc_by(u64::from(batch_end - batch_start + 1));
+            info!(
+                "Inserted documents {} to {} into collection {}",
+                batch_start, batch_end, collection_name
+            );
+
+            Ok::<_, anyhow::Error>(())
+        })
+        .buffer_unordered(search_config.insert_concurrency.max(1) as usize)
+        .collect::<Vec<Result<()>>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<()>>>()?;
+
+    Ok(())
+}
+
+/// Creates the view over all collections, for `search-alias` views
+/// together with the inverted indexes it consists of
+async fn create_search_view(
+    conn: &Connection,
+    db_name: &str,
+    search_config: &SearchConfig,
+) -> Result<()> {
+    let collections = collection_names(search_config);
+    let definition = match search_config.view_type {
+        ViewType::Arangosearch => {
+            let links: Map<String, Value> = collections
+                .iter()
+                .map(|coll_name| {
+                    let link = json!({
+                        "fields": {
+                            "title": { "analyzers": [ANALYZER] },
+                            "text": { "analyzers": [ANALYZER] }
+                        }
+                    });
+                    (coll_name.clone(), link)
+                })
+                .collect();
+            json!({ "name": VIEW_NAME, "type": "arangosearch", "links": links })
+        }
+        ViewType::SearchAlias => {
+            for coll_name in &collections {
+                let index = json!({
+                    "type": "inverted",
+                    "name": INDEX_NAME,
+                    "fields": [
+                        { "name": "title", "analyzer": ANALYZER },
+                        { "name": "text", "analyzer": ANALYZER }
+                    ]
+                });
+                create_index(conn, db_name, coll_name, &index).await?;
+            }
+            let indexes: Vec<Value> = collections
+                .iter()
+                .map(|coll_name| json!({ "collection": coll_name, "index": INDEX_NAME }))
+                .collect();
+            json!({ "name": VIEW_NAME, "type": "search-alias", "indexes": indexes })
+        }
+    };
+    info!("Creating view {} in database {}", VIEW_NAME, db_name);
+    create_view(conn, db_name, &definition).await?;
+    Ok(())
+}
+
+/// Initializes the database, collections and view according to the
+/// configuration. An existing database is reused if it has all collections
+/// and the view, unless `drop_first` is set.
+async fn initialize_database(
+    conn: &Connection,
+    search_config: &SearchConfig,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    if database_exists(conn, &db_name).await? {
+        if !search_config.drop_first {
+            let mut complete = view_exists(conn, &db_name, VIEW_NAME).await?;
+            for coll_name in collection_names(search_config) {
+                if !complete {
+                    break;
+                }
+                complete = collection_exists(conn, &db_name, &coll_name).await?;
+            }
+            if complete {
+                return Ok(());
+            }
+        }
+        info!("Dropping database {}", db_name);
+        drop_database(conn, &db_name).await?;
+    }
+
+    info!("Creating database {}", db_name);
+    create_database(conn, &db_name).await?;
+    for coll_name in collection_names(search_config) {
+        create_collection(
+            conn,
+            &db_name,
+            &coll_name,
+            search_config.number_of_shards,
+            search_config.replication_factor,
+            &CollectionProperties::default(),
+        )
+        .await?;
+    }
+    create_search_view(conn, &db_name, search_config).await?;
+
+    for coll_name in collection_names(search_config) {
+        insert_documents(conn, search_config, &db_name, &coll_name, shutdown).await?;
+        if shutdown.is_cancelled() {
+            info!("Seeding of database {} interrupted by shutdown", db_name);
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The kinds of search queries the use case runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchQuery {
+    Phrase,
+    Tokens,
+    Bm25,
+}
+
+impl SearchQuery {
+    const ALL: [SearchQuery; 3] = [SearchQuery::Phrase, SearchQuery::Tokens, SearchQuery::Bm25];
+
+    /// The name used in logs and as the `operation` metrics label
+    fn name(self) -> &'static str {
+        match self {
+            SearchQuery::Phrase => "phrase",
+            SearchQuery::Tokens => "tokens",
+            SearchQuery::Bm25 => "bm25",
+        }
+    }
+
+    /// The relative weight of this query according to the configuration
+    fn weight(self, search_config: &SearchConfig) -> u32 {
+        match self {
+            SearchQuery::Phrase => search_config.phrase_pct,
+            SearchQuery::Tokens => search_config.tokens_pct,
+            SearchQuery::Bm25 => search_config.bm25_pct,
+        }
+    }
+
+    /// The AQL query string. The analyzer must be given explicitly for
+    /// `arangosearch` views, `search-alias` views take it from the index.
+    fn query(self, view_type: ViewType) -> String {
+        let condition = match (self, view_type) {
+            (SearchQuery::Phrase, ViewType::Arangosearch) => {
+                format!("PHRASE(d.text, @phrase, \"{}\")", ANALYZER)
+            }
+            (SearchQuery::Phrase, ViewType::SearchAlias) => "PHRASE(d.text, @phrase)".to_string(),
+            (_, ViewType::Arangosearch) => format!(
+                "ANALYZER(d.text IN TOKENS(@words, \"{0}\"), \"{0}\")",
+                ANALYZER
+            ),
+            (_, ViewType::SearchAlias) => format!("d.text IN TOKENS(@words, \"{}\")", ANALYZER),
+        };
+        let sort = match self {
+            SearchQuery::Bm25 => " SORT BM25(d) DESC",
+            _ => "",
+        };
+        format!(
+            "FOR d IN {} SEARCH {}{} LIMIT {} RETURN d._key",
+            VIEW_NAME, condition, sort, RESULT_LIMIT
+        )
+    }
+
+    /// Generates the bind parameters for one execution of the query
+    fn bind_vars<R: Rng>(self, rng: &mut R, languages: &LanguageMix) -> Map<String, Value> {
+        let mut bind_vars = Map::new();
+        match self {
+            SearchQuery::Phrase => {
+                bind_vars.insert("phrase".to_string(), json!(random_text(rng, 2, languages)));
+            }
+            SearchQuery::Tokens | SearchQuery::Bm25 => {
+                bind_vars.insert("words".to_string(), json!(random_text(rng, 3, languages)));
+            }
+        }
+        bind_vars
+    }
+}
+
+/// Picks a random query kind according to the configured query mix
+fn choose_query<R: Rng>(search_config: &SearchConfig, rng: &mut R) -> SearchQuery {
+    let total: u32 = SearchQuery::ALL
+        .iter()
+        .map(|query| query.weight(search_config))
+        .sum();
+    let mut pick = rng.random_range(0..total);
+    for query in SearchQuery::ALL {
+        let weight = query.weight(search_config);
+        if pick < weight {
+            return query;
+        }
+        pick -= weight;
+    }
+    SearchQuery::Phrase
+}
+
+/// Runs one random search query and fetches its results
+async fn perform_search(
+    conn: &Connection,
+    search_config: &SearchConfig,
+    mut rng: StdRng,
+) -> Result<()> {
+    let query = choose_query(search_config, &mut rng);
+    let bind_vars = query.bind_vars(&mut rng, &search_config.languages);
+    let db_name = database_name(conn.config());
+
+    let start = Instant::now();
+    let result = run_query(
+        conn,
+        &db_name,
+        &query.query(search_config.view_type),
+        &bind_vars,
+        RESULT_LIMIT,
+        |_| {},
+    )
+    .await;
+    observe_request(USECASE, query.name(), start.elapsed(), result.is_ok());
+    result.map_err(|e| anyhow::anyhow!("Search query {} failed: {}", query.name(), e))?;
+    Ok(())
+}
+
+#[async_trait]
+impl UseCase for SearchUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        if SearchQuery::ALL
+            .iter()
+            .all(|query| query.weight(&self.search_config) == 0)
+        {
+            return Err(anyhow::anyhow!(
+                "The search query mix is empty, at least one percentage must be positive"
+            ));
+        }
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize_database(&connection, &self.search_config, shutdown).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let pacer = Arc::new(Pacer::new(self.search_config.target_ops_per_sec));
+        // The operation mix can be changed by reloading the configuration
+        let search_config = Arc::new(RwLock::new(Arc::new(self.search_config.clone())));
+        let reloaded = Arc::clone(&search_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(search) = &config.search {
+                if SearchQuery::ALL
+                    .iter()
+                    .all(|query| query.weight(search) == 0)
+                {
+                    warn!("Ignoring reloaded search configuration, the query mix is empty");
+                    return;
+                }
+                control::apply_load(USECASE, search.concurrency, search.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(search.clone());
+            }
+        });
+        run_workers(
+            USECASE,
+            self.search_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let search_config = Arc::clone(&search_config.read().unwrap());
+                async move { perform_search(&connection, &search_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        Ok(())
+    }
+
+    async fn verify_seed(&self) -> Result<Vec<CollectionCount>> {
+        let connection = self.connection()?;
+        let db_name = database_name(&self.db_config);
+        let mut counts = Vec::new();
+        for coll_name in collection_names(&self.search_config) {
+            let expected = u64::from(self.search_config.number_of_documents);
+            counts.push(CollectionCount::fetch(&connection, &db_name, &coll_name, expected).await?);
+        }
+        Ok(counts)
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.search_config.drop_on_exit {
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&self.connection()?, &db_name).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rng;
+
+    #[test]
+    fn test_queries_match_view_type() {
+        let query = SearchQuery::Bm25.query(ViewType::Arangosearch);
+        assert_eq!(
+            query,
+            "FOR d IN v SEARCH ANALYZER(d.text IN TOKENS(@words, \"text_en\"), \"text_en\") \
+             SORT BM25(d) DESC LIMIT 10 RETURN d._key"
+        );
+        let query = SearchQuery::Phrase.query(ViewType::SearchAlias);
+        assert_eq!(
+            query,
+            "FOR d IN v SEARCH PHRASE(d.text, @phrase) LIMIT 10 RETURN d._key"
+        );
+        let bind_vars = SearchQuery::Phrase.bind_vars(&mut rng(), &LanguageMix::default());
+        assert_eq!(bind_vars["phrase"].as_str().unwrap().split(' ').count(), 2);
+    }
+}
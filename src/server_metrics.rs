@@ -0,0 +1,113 @@
+use crate::arangodb::{ApiRequest, Connection};
+use crate::config::{DatabaseConfig, ServerMetricsConfig};
+use crate::metrics::METRICS;
+use anyhow::Result;
+use log::{info, warn};
+use reqwest::Method;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// Periodically scrapes `/_admin/metrics/v2` of every endpoint and
+/// re-exports the selected metrics in `adb_loader_server_metric`, labelled
+/// with the endpoint. This way a single scrape of the loader shows the
+/// client side and the server side of the load at the same time.
+pub async fn scrape(
+    db_config: DatabaseConfig,
+    config: ServerMetricsConfig,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let connection = Connection::new(&db_config).await?;
+    info!(
+        "Scraping {} server metrics every {}s",
+        config.metrics.len(),
+        config.interval_secs
+    );
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_secs.max(1)));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => return Ok(()),
+        }
+        let urls: Vec<String> = connection
+            .endpoints()
+            .all()
+            .iter()
+            .map(|endpoint| endpoint.url().to_string())
+            .collect();
+        for url in urls {
+            if let Err(e) = scrape_endpoint(&connection, &url, &config.metrics).await {
+                warn!("Cannot scrape the metrics of {}: {}", url, e);
+            }
+        }
+    }
+}
+
+/// Scrapes the metrics of one endpoint and updates the selected ones
+async fn scrape_endpoint(conn: &Connection, url: &str, selected: &[String]) -> Result<()> {
+    let request = ApiRequest::new(Method::GET, "/_admin/metrics/v2").pinned_to(url);
+    let response = conn
+        .send(request)
+        .await?
+        .error_for_status("scrape metrics")?;
+    for line in response.text().lines() {
+        let Some((name, labels, value)) = parse_sample(line) else {
+            continue;
+        };
+        if selected.iter().any(|metric| metric == name) {
+            METRICS
+                .server_metrics
+                .with_label_values(&[url, name, labels])
+                .set(value);
+        }
+    }
+    Ok(())
+}
+
+/// Parses a sample line of the Prometheus text format into the metric
+/// name, the labels without braces and the value. Comments, empty lines
+/// and malformed lines yield `None`.
+fn parse_sample(line: &str) -> Option<(&str, &str, f64)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (name, labels, rest) = match line.find('{') {
+        Some(open) => {
+            let close = line.rfind('}')?;
+            (&line[..open], &line[open + 1..close], &line[close + 1..])
+        }
+        None => {
+            let (name, rest) = line.split_once(char::is_whitespace)?;
+            (name, "", rest)
+        }
+    };
+    // A timestamp may follow the value
+    let value = rest.split_whitespace().next()?.parse().ok()?;
+    Some((name.trim(), labels, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sample() {
+        assert_eq!(
+            parse_sample("arangodb_scheduler_queue_length 12"),
+            Some(("arangodb_scheduler_queue_length", "", 12.0))
+        );
+        assert_eq!(
+            parse_sample(
+                r#"rocksdb_block_cache_usage{role="COORDINATOR",shortname="Coordinator0001"} 1.5e3 1700000000"#
+            ),
+            Some((
+                "rocksdb_block_cache_usage",
+                r#"role="COORDINATOR",shortname="Coordinator0001""#,
+                1500.0
+            ))
+        );
+        assert_eq!(parse_sample("# TYPE rocksdb_block_cache_usage gauge"), None);
+        assert_eq!(parse_sample("broken_line"), None);
+        assert_eq!(parse_sample(""), None);
+    }
+}
@@ -0,0 +1,626 @@
+use crate::arangodb::{
+    collection_exists, create_collection_from, create_database, database_exists, drop_database,
+    run_query, ApiRequest, Connection,
+};
+use crate::config::{DatabaseConfig, Phase, SmartJoinConfig, UseCaseConfig};
+use crate::control;
+use crate::crud::generate_random_ascii;
+use crate::error_policy;
+use crate::metrics::{observe_request, METRICS};
+use crate::pacer::Pacer;
+use crate::random::stream_rng;
+use crate::usecase::{run_workers, send_observed, CollectionCount, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use log::{info, warn};
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "smartjoin";
+
+/// The sharded dimension collection, sharded by `_key`
+const CUSTOMERS: &str = "customers";
+
+/// The fact collection with `distributeShardsLike` on the customers and
+/// the SmartJoin attribute `customer`
+const SMART_FACTS: &str = "orders";
+
+/// The fact collection sharded by `customer` on its own, the baseline
+const PLAIN_FACTS: &str = "orders_plain";
+
+/// The satellite dimension collections
+const PRODUCTS: &str = "products";
+const REGIONS: &str = "regions";
+
+/// Number of distinct product categories
+const CATEGORIES: u32 = 20;
+
+/// Number of documents per insert request
+const BATCH_SIZE: u32 = 1000;
+
+/// The optimizer rule which turns a join into a SmartJoin
+const SMART_JOIN_RULE: &str = "smart-joins";
+
+/// The SmartJoin use case: seeds a sharded fact collection which is
+/// co-located with the sharded customers and joins it with them and with
+/// satellite dimension collections. The same facts in a collection which
+/// is only sharded by the same attribute serve as the baseline, so that
+/// the latencies show what SmartJoins save. Needs an Enterprise Edition
+/// cluster.
+pub struct SmartJoinUseCase {
+    smartjoin_config: SmartJoinConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl SmartJoinUseCase {
+    pub fn new(
+        smartjoin_config: SmartJoinConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        SmartJoinUseCase {
+            smartjoin_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("SmartJoin use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let smartjoin_config = &self.smartjoin_config;
+        info!("Starting SmartJoin use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!("Number of shards: {}", smartjoin_config.number_of_shards);
+        info!(
+            "Replication factor: {}",
+            smartjoin_config.replication_factor
+        );
+        info!(
+            "Customers: {}, facts: {}, products: {}, regions: {}",
+            smartjoin_config.number_of_customers,
+            smartjoin_config.number_of_facts,
+            smartjoin_config.number_of_products,
+            smartjoin_config.number_of_regions
+        );
+        info!("Segments: {}", smartjoin_config.number_of_segments);
+        info!("Fact size: {}", smartjoin_config.fact_size);
+        info!("Baseline: {}", smartjoin_config.baseline);
+        info!("Aggregations: {}%", smartjoin_config.aggregate_pct);
+        info!(
+            "Insert concurrency: {}",
+            smartjoin_config.insert_concurrency
+        );
+        info!("Concurrency: {}", smartjoin_config.concurrency);
+        if smartjoin_config.target_ops_per_sec > 0.0 {
+            info!("Target rate: {} ops/s", smartjoin_config.target_ops_per_sec);
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the SmartJoin use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("smartjoin")
+}
+
+/// The fact collections, the baseline only if configured
+fn fact_collections(smartjoin_config: &SmartJoinConfig) -> Vec<&'static str> {
+    if smartjoin_config.baseline {
+        vec![SMART_FACTS, PLAIN_FACTS]
+    } else {
+        vec![SMART_FACTS]
+    }
+}
+
+/// The definitions of all collections, the fact collections after the
+/// customers they are distributed like
+fn collection_definitions(smartjoin_config: &SmartJoinConfig) -> Vec<Value> {
+    let mut definitions = vec![
+        json!({
+            "name": CUSTOMERS,
+            "numberOfShards": smartjoin_config.number_of_shards,
+            "replicationFactor": smartjoin_config.replication_factor,
+            "shardKeys": ["_key"]
+        }),
+        // The shard of a fact is the one of the customer in its key prefix
+        json!({
+            "name": SMART_FACTS,
+            "distributeShardsLike": CUSTOMERS,
+            "shardKeys": ["_key:"],
+            "smartJoinAttribute": "customer"
+        }),
+        json!({"name": PRODUCTS, "replicationFactor": "satellite"}),
+        json!({"name": REGIONS, "replicationFactor": "satellite"}),
+    ];
+    if smartjoin_config.baseline {
+        definitions.push(json!({
+            "name": PLAIN_FACTS,
+            "numberOfShards": smartjoin_config.number_of_shards,
+            "replicationFactor": smartjoin_config.replication_factor,
+            "shardKeys": ["customer"]
+        }));
+    }
+    definitions
+}
+
+/// Generates the customer with the given key
+fn generate_customer(smartjoin_config: &SmartJoinConfig, key: u32) -> Value {
+    let mut rng = stream_rng(&format!("{}/{}", USECASE, CUSTOMERS), u64::from(key));
+    json!({
+        "_key": format!("C{}", key),
+        "name": generate_random_ascii(&mut rng, 20),
+        "segment": format!("s{}", key % smartjoin_config.number_of_segments.max(1)),
+        "region": format!("R{}", rng.random_range(1..=smartjoin_config.number_of_regions.max(1))),
+    })
+}
+
+/// Generates the fact with the given index. Both fact collections get the
+/// same facts, only the one with the SmartJoin attribute has keys
+/// prefixed with the customer.
+fn generate_fact(smartjoin_config: &SmartJoinConfig, index: u32, smart: bool) -> Value {
+    let mut rng = stream_rng(&format!("{}/{}", USECASE, SMART_FACTS), u64::from(index));
+    let customer = format!(
+        "C{}",
+        rng.random_range(1..=smartjoin_config.number_of_customers.max(1))
+    );
+    let mut fact = json!({
+        "customer": customer,
+        "product": format!("P{}", rng.random_range(1..=smartjoin_config.number_of_products.max(1))),
+        "amount": rng.random_range(1..=1000),
+        "payload": generate_random_ascii(&mut rng, smartjoin_config.fact_size as usize),
+    });
+    if smart {
+        fact["_key"] = json!(format!("{}:O{}", customer, index));
+    }
+    fact
+}
+
+fn generate_product(key: u32) -> Value {
+    json!({
+        "_key": format!("P{}", key),
+        "category": format!("cat{}", key % CATEGORIES),
+    })
+}
+
+fn generate_region(key: u32) -> Value {
+    json!({"_key": format!("R{}", key), "name": format!("region{}", key)})
+}
+
+/// Inserts the documents generated for the keys `1..=count` into a
+/// collection in batches, with up to `insert_concurrency` batches in
+/// flight. When shutdown is requested, no new batches are started.
+async fn insert_documents<F>(
+    conn: &Connection,
+    smartjoin_config: &SmartJoinConfig,
+    db_name: &str,
+    collection_name: &str,
+    count: u32,
+    generate: F,
+    shutdown: &CancellationToken,
+) -> Result<()>
+where
+    F: Fn(u32) -> Value + Sync,
+{
+    let path = format!("/_api/document/{}", collection_name);
+    let path = path.as_str();
+    let generate = &generate;
+    let batches = (1..=count)
+        .step_by(BATCH_SIZE as usize)
+        .map(|start| (start, (start + BATCH_SIZE - 1).min(count)));
+
+    stream::iter(batches)
+        .take_until(shutdown.cancelled())
+        .map(|(batch_start, batch_end)| async move {
+            let batch: Vec<Value> = (batch_start..=batch_end).map(generate).collect();
+            let request = ApiRequest::in_database(Method::POST, db_name, path).json(&batch);
+            if let Err(e) = send_observed(conn, USECASE, "insert_batch", request, &[]).await {
+                return error_policy::tolerate(e);
+            }
+            METRICS
+                .documents_inserted
+                .with_label_values(&[USECASE])
+                .inc_by(batch.len() as u64);
+            info!(
+                "Inserted documents {} to {} into collection {}",
+                batch_start, batch_end, collection_name
+            );
+            Ok::<_, anyhow::Error>(())
+        })
+        .buffer_unordered(smartjoin_config.insert_concurrency.max(1) as usize)
+        .try_collect::<Vec<()>>()
+        .await?;
+    Ok(())
+}
+
+/// Creates the database and the collections and seeds them. An existing
+/// database with all collections is used as it is, unless `drop_first`
+/// is set.
+async fn initialize_database(
+    conn: &Connection,
+    smartjoin_config: &SmartJoinConfig,
+    shutdown: &CancellationToken,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+    let definitions = collection_definitions(smartjoin_config);
+
+    if database_exists(conn, &db_name).await? {
+        if !smartjoin_config.drop_first {
+            let mut complete = true;
+            for definition in &definitions {
+                let name = definition["name"].as_str().unwrap_or_default();
+                if !collection_exists(conn, &db_name, name).await? {
+                    complete = false;
+                    break;
+                }
+            }
+            if complete {
+                info!("Using existing collections in database {}", db_name);
+                return Ok(());
+            }
+        }
+        info!("Dropping database {}", db_name);
+        drop_database(conn, &db_name).await?;
+    }
+
+    info!("Creating database {}", db_name);
+    create_database(conn, &db_name).await?;
+    for definition in &definitions {
+        create_collection_from(conn, &db_name, definition).await?;
+    }
+
+    insert_documents(
+        conn,
+        smartjoin_config,
+        &db_name,
+        CUSTOMERS,
+        smartjoin_config.number_of_customers,
+        |key| generate_customer(smartjoin_config, key),
+        shutdown,
+    )
+    .await?;
+    insert_documents(
+        conn,
+        smartjoin_config,
+        &db_name,
+        PRODUCTS,
+        smartjoin_config.number_of_products,
+        generate_product,
+        shutdown,
+    )
+    .await?;
+    insert_documents(
+        conn,
+        smartjoin_config,
+        &db_name,
+        REGIONS,
+        smartjoin_config.number_of_regions,
+        generate_region,
+        shutdown,
+    )
+    .await?;
+    for facts in fact_collections(smartjoin_config) {
+        insert_documents(
+            conn,
+            smartjoin_config,
+            &db_name,
+            facts,
+            smartjoin_config.number_of_facts,
+            |index| generate_fact(smartjoin_config, index, facts == SMART_FACTS),
+            shutdown,
+        )
+        .await?;
+    }
+    if shutdown.is_cancelled() {
+        info!("Seeding of database {} interrupted by shutdown", db_name);
+    }
+    Ok(())
+}
+
+/// The join queries the use case runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JoinQuery {
+    /// Joins the facts of one customer with their products
+    Lookup,
+    /// Sums up the facts of the customers of one segment per region and
+    /// product category, which joins all shards
+    Aggregate,
+}
+
+impl JoinQuery {
+    /// The name used as the `operation` metrics label, which tells the
+    /// SmartJoin and the baseline apart
+    fn operation(self, facts: &str) -> &'static str {
+        match (self, facts == SMART_FACTS) {
+            (JoinQuery::Lookup, true) => "smart_lookup",
+            (JoinQuery::Lookup, false) => "plain_lookup",
+            (JoinQuery::Aggregate, true) => "smart_aggregate",
+            (JoinQuery::Aggregate, false) => "plain_aggregate",
+        }
+    }
+
+    /// The AQL query string against one of the fact collections
+    fn query(self, facts: &str) -> String {
+        match self {
+            JoinQuery::Lookup => format!(
+                "FOR c IN {} FILTER c._key == @customer \
+                 FOR o IN {} FILTER o.customer == c._key \
+                 FOR p IN {} FILTER p._key == o.product \
+                 RETURN {{amount: o.amount, category: p.category}}",
+                CUSTOMERS, facts, PRODUCTS
+            ),
+            JoinQuery::Aggregate => format!(
+                "FOR c IN {} FILTER c.segment == @segment \
+                 FOR o IN {} FILTER o.customer == c._key \
+                 FOR p IN {} FILTER p._key == o.product \
+                 FOR r IN {} FILTER r._key == c.region \
+                 COLLECT region = r.name, category = p.category \
+                 AGGREGATE revenue = SUM(o.amount) \
+                 RETURN {{region, category, revenue}}",
+                CUSTOMERS, facts, PRODUCTS, REGIONS
+            ),
+        }
+    }
+
+    /// Generates the bind parameters for one execution of the query
+    fn bind_vars<R: Rng>(
+        self,
+        smartjoin_config: &SmartJoinConfig,
+        rng: &mut R,
+    ) -> Map<String, Value> {
+        let mut bind_vars = Map::new();
+        match self {
+            JoinQuery::Lookup => {
+                let customer = rng.random_range(1..=smartjoin_config.number_of_customers.max(1));
+                bind_vars.insert("customer".to_string(), json!(format!("C{}", customer)));
+            }
+            JoinQuery::Aggregate => {
+                let segment = rng.random_range(0..smartjoin_config.number_of_segments.max(1));
+                bind_vars.insert("segment".to_string(), json!(format!("s{}", segment)));
+            }
+        }
+        bind_vars
+    }
+}
+
+/// Checks the parameters of the steady-state operations
+fn check_workload(smartjoin_config: &SmartJoinConfig) -> Result<()> {
+    if smartjoin_config.aggregate_pct > 100 {
+        return Err(anyhow::anyhow!(
+            "The SmartJoin use case needs aggregate_pct <= 100"
+        ));
+    }
+    if smartjoin_config.number_of_customers == 0
+        || smartjoin_config.number_of_products == 0
+        || smartjoin_config.number_of_regions == 0
+    {
+        return Err(anyhow::anyhow!(
+            "The SmartJoin use case needs at least one customer, product and region"
+        ));
+    }
+    Ok(())
+}
+
+/// Warns if the optimizer does not turn the join with the co-located
+/// facts into a SmartJoin, e.g. on a single server, so that both fact
+/// collections would show the same latencies
+async fn check_smart_join(conn: &Connection) -> Result<()> {
+    let db_name = database_name(conn.config());
+    let request = ApiRequest::in_database(Method::POST, &db_name, "/_api/explain").json(&json!({
+        "query": JoinQuery::Aggregate.query(SMART_FACTS),
+        "bindVars": {"segment": "s0"},
+    }));
+    let plan: Value = conn
+        .send(request)
+        .await?
+        .error_for_status("explain")?
+        .json()?;
+    let rules = plan["plan"]["rules"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    if rules.iter().any(|rule| rule == SMART_JOIN_RULE) {
+        info!("The joins with {} are SmartJoins", SMART_FACTS);
+    } else {
+        warn!(
+            "The optimizer does not use a SmartJoin for {}, rules: {:?}",
+            SMART_FACTS, rules
+        );
+    }
+    Ok(())
+}
+
+/// Runs one join query against a fact collection, the SmartJoin one or,
+/// with the baseline, half of the time the plain one, and fetches all
+/// results
+async fn perform_join(
+    conn: &Connection,
+    smartjoin_config: &SmartJoinConfig,
+    mut rng: StdRng,
+) -> Result<()> {
+    let query = if rng.random_range(0..100) < smartjoin_config.aggregate_pct {
+        JoinQuery::Aggregate
+    } else {
+        JoinQuery::Lookup
+    };
+    let facts = if smartjoin_config.baseline && rng.random_bool(0.5) {
+        PLAIN_FACTS
+    } else {
+        SMART_FACTS
+    };
+    let bind_vars = query.bind_vars(smartjoin_config, &mut rng);
+    let db_name = database_name(conn.config());
+    let operation = query.operation(facts);
+
+    let start = Instant::now();
+    let result = run_query(
+        conn,
+        &db_name,
+        &query.query(facts),
+        &bind_vars,
+        BATCH_SIZE,
+        |_| {},
+    )
+    .await;
+    observe_request(USECASE, operation, start.elapsed(), result.is_ok());
+    result.map_err(|e| anyhow::anyhow!("Query {} failed: {}", operation, e))?;
+    Ok(())
+}
+
+#[async_trait]
+impl UseCase for SmartJoinUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+        check_workload(&self.smartjoin_config)?;
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize_database(&connection, &self.smartjoin_config, shutdown).await?;
+        if let Err(e) = check_smart_join(&connection).await {
+            warn!("Cannot check whether SmartJoins are used: {}", e);
+        }
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let pacer = Arc::new(Pacer::new(self.smartjoin_config.target_ops_per_sec));
+        // The query mix can be changed by reloading the configuration
+        let smartjoin_config = Arc::new(RwLock::new(Arc::new(self.smartjoin_config.clone())));
+        let reloaded = Arc::clone(&smartjoin_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(smartjoin) = &config.smartjoin {
+                if let Err(e) = check_workload(smartjoin) {
+                    warn!("Ignoring reloaded SmartJoin configuration: {}", e);
+                    return;
+                }
+                control::apply_load(USECASE, smartjoin.concurrency, smartjoin.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(smartjoin.clone());
+            }
+        });
+        run_workers(
+            USECASE,
+            self.smartjoin_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let smartjoin_config = Arc::clone(&smartjoin_config.read().unwrap());
+                async move { perform_join(&connection, &smartjoin_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        Ok(())
+    }
+
+    async fn verify_seed(&self) -> Result<Vec<CollectionCount>> {
+        let connection = self.connection()?;
+        let db_name = database_name(&self.db_config);
+        let smartjoin_config = &self.smartjoin_config;
+        let mut expected_counts = vec![
+            (CUSTOMERS, smartjoin_config.number_of_customers),
+            (PRODUCTS, smartjoin_config.number_of_products),
+            (REGIONS, smartjoin_config.number_of_regions),
+        ];
+        for facts in fact_collections(smartjoin_config) {
+            expected_counts.push((facts, smartjoin_config.number_of_facts));
+        }
+        let mut counts = Vec::new();
+        for (collection, expected) in expected_counts {
+            counts.push(
+                CollectionCount::fetch(&connection, &db_name, collection, u64::from(expected))
+                    .await?,
+            );
+        }
+        Ok(counts)
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.smartjoin_config.drop_on_exit {
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&self.connection()?, &db_name).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_facts_are_the_same_in_both_collections() {
+        let smartjoin_config: SmartJoinConfig = serde_yaml::from_str(
+            "{number_of_shards: 3, replication_factor: 2, number_of_customers: 10, \
+             number_of_facts: 100}",
+        )
+        .unwrap();
+        for index in 1..=100 {
+            let smart = generate_fact(&smartjoin_config, index, true);
+            let mut plain = generate_fact(&smartjoin_config, index, false);
+            assert!(plain.get("_key").is_none());
+            let customer = smart["customer"].as_str().unwrap();
+            assert!(smart["_key"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("{}:", customer)));
+            plain["_key"] = smart["_key"].clone();
+            assert_eq!(smart, plain);
+        }
+
+        let query = JoinQuery::Lookup.query(PLAIN_FACTS);
+        assert!(query.contains("FOR o IN orders_plain FILTER o.customer == c._key"));
+        assert_eq!(JoinQuery::Lookup.operation(PLAIN_FACTS), "plain_lookup");
+        let names: Vec<Value> = collection_definitions(&smartjoin_config)
+            .iter()
+            .map(|definition| definition["name"].clone())
+            .collect();
+        assert_eq!(
+            names,
+            [CUSTOMERS, SMART_FACTS, PRODUCTS, REGIONS, PLAIN_FACTS]
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::config::{Mode, SeedCheckConfig, SupervisionConfig};
+use crate::metrics::METRICS;
+use crate::usecase::{run_blocking, UseCase};
+use anyhow::Result;
+use log::{error, info, warn};
+use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The states of a supervised use case, exported in `adb_loader_usecase_state`
+const STATES: [&str; 4] = ["running", "restarting", "failed", "finished"];
+
+fn set_state(usecase: &str, state: &str) {
+    for candidate in STATES {
+        METRICS
+            .usecase_state
+            .with_label_values(&[usecase, candidate])
+            .set(if candidate == state { 1.0 } else { 0.0 });
+    }
+}
+
+/// The delay before the given restart in a row (starting at 1)
+fn restart_delay(supervision: &SupervisionConfig, restart: u32) -> Duration {
+    let exponent = restart.saturating_sub(1).min(31);
+    let secs = supervision
+        .initial_backoff_secs
+        .saturating_mul(1u64 << exponent)
+        .min(supervision.max_backoff_secs);
+    Duration::from_secs(secs)
+}
+
+/// Sleeps for `delay`, returns early if `shutdown` is cancelled
+fn sleep_unless_cancelled(delay: Duration, shutdown: &CancellationToken) {
+    let deadline = Instant::now() + delay;
+    while !shutdown.is_cancelled() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return;
+        }
+        thread::sleep(left.min(Duration::from_millis(100)));
+    }
+}
+
+/// Runs a use case on the calling thread like `run_blocking`, but restarts
+/// it with exponential backoff if it fails or panics, until it failed more
+/// than `max_restarts` times in a row. Every restart gets a new instance
+/// from `create`, which goes through the setup again. Use cases are not
+/// restarted after `shutdown` was cancelled. With `terminate_on_failure`,
+/// a use case which gives up cancels `shutdown` and so stops the others.
+///
+/// # Errors
+/// Returns the last error of the use case if it gave up
+pub fn supervise<F>(
+    name: &str,
+    create: F,
+    mode: Mode,
+    supervision: &SupervisionConfig,
+    seed_check: &SeedCheckConfig,
+    shutdown: CancellationToken,
+) -> Result<()>
+where
+    F: Fn() -> Option<Box<dyn UseCase>>,
+{
+    let mut restarts = 0;
+    loop {
+        let usecase =
+            create().ok_or_else(|| anyhow::anyhow!("Use case {} is not enabled", name))?;
+        set_state(name, "running");
+        let started = Instant::now();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            run_blocking(usecase, shutdown.clone(), mode, seed_check)
+        }))
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("Use case {} panicked", name)));
+        let e = match result {
+            Ok(()) => {
+                set_state(name, "finished");
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        error!("Use case {} failed: {:#}", name, e);
+        if shutdown.is_cancelled() {
+            set_state(name, "failed");
+            return Err(e);
+        }
+        if started.elapsed() >= Duration::from_secs(supervision.healthy_after_secs) {
+            restarts = 0;
+        }
+        restarts += 1;
+        if restarts > supervision.max_restarts {
+            set_state(name, "failed");
+            if supervision.terminate_on_failure {
+                error!(
+                    "Use case {} failed {} times in a row, stopping all use cases",
+                    name, restarts
+                );
+                shutdown.cancel();
+            }
+            return Err(e);
+        }
+        set_state(name, "restarting");
+        METRICS.usecase_restarts.with_label_values(&[name]).inc();
+        let delay = restart_delay(supervision, restarts);
+        warn!(
+            "Restarting use case {} in {:?} (restart {}/{})",
+            name, delay, restarts, supervision.max_restarts
+        );
+        sleep_unless_cancelled(delay, &shutdown);
+        if shutdown.is_cancelled() {
+            info!("Use case {} not restarted, shutdown requested", name);
+            set_state(name, "failed");
+            return Err(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_delay_grows_and_is_capped() {
+        let supervision = SupervisionConfig {
+            initial_backoff_secs: 2,
+            max_backoff_secs: 30,
+            ..SupervisionConfig::default()
+        };
+        assert_eq!(restart_delay(&supervision, 1), Duration::from_secs(2));
+        assert_eq!(restart_delay(&supervision, 2), Duration::from_secs(4));
+        assert_eq!(restart_delay(&supervision, 4), Duration::from_secs(16));
+        assert_eq!(restart_delay(&supervision, 5), Duration::from_secs(30));
+        assert_eq!(restart_delay(&supervision, 100), Duration::from_secs(30));
+    }
+}
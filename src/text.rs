@@ -0,0 +1,113 @@
+use crate::config::LanguageMix;
+use rand::Rng;
+use std::sync::LazyLock;
+
+/// The vocabularies texts are made of, separated by spaces. Words are drawn
+/// with a skewed distribution, so that the first ones are much more
+/// frequent than the last ones, like in natural language.
+const ENGLISH: &str =
+    "the data cluster query index search document server network storage shard replica \
+     leader follower request response latency throughput memory disk cache engine graph \
+     vertex edge traversal collection database transaction commit abort snapshot backup \
+     restore schema attribute value number string array object analyzer token phrase ranking \
+     score relevance frequency language english system process thread worker signal metric \
+     histogram percentile alert failure recovery endpoint coordinator agency supervision \
+     rebalance compaction compression encryption certificate authentication permission \
+     operation batch stream cursor result filter sort limit aggregate join subquery \
+     optimizer rule plan execution parallel distributed consistent durable available \
+     partition tolerance benchmark workload";
+
+/// Russian, two bytes per letter in UTF-8
+const CYRILLIC: &str =
+    "и в не на данные кластер запрос индекс поиск документ сервер сеть хранилище \
+     реплика лидер запрос ответ задержка память диск кэш движок граф вершина ребро \
+     коллекция база транзакция снимок резервная копия схема атрибут значение число \
+     строка массив объект анализатор токен фраза рейтинг частота язык система процесс \
+     поток сигнал метрика сбой восстановление координатор шифрование сертификат \
+     операция пакет курсор результат фильтр сортировка план выполнение нагрузка";
+
+/// Chinese, Japanese and Korean words, three bytes per character in UTF-8. They
+/// are separated by spaces like the others, although real texts in these
+/// languages are not.
+const CJK: &str =
+    "的 数据 集群 查询 索引 搜索 文档 服务器 网络 存储 分片 副本 领导者 请求 响应 延迟 \
+     吞吐量 内存 磁盘 缓存 引擎 图 顶点 边 集合 数据库 事务 提交 快照 备份 恢复 模式 \
+     属性 数值 字符串 数组 对象 分析器 短语 排名 频率 语言 系统 线程 指标 故障 \
+     データ 検索 東京 文書 索引 問い合わせ 分散 障害 回復 負荷 試験 日本語 처리 \
+     데이터 검색 서버";
+
+/// Emoji, four bytes each in UTF-8 and more for the sequences with skin
+/// tone modifiers, zero width joiners and regional indicators
+const EMOJI: &str = "😀 🚀 📈 🔥 ✨ 👍 🎉 💾 🌍 ⚡ 😂 ❤️ 🙏 💡 📦 🐛 ✅ ❌ ⏱️ 🧪 \
+     👍🏽 👩‍💻 👨‍👩‍👧 🏳️‍🌈 🇩🇪 🇯🇵 🇧🇷 🧑🏿‍🚀";
+
+/// Latin words with decomposed accents (a base letter followed by a
+/// combining character), which an analyzer has to normalize
+const COMBINING: &str =
+    "cafe\u{301} nai\u{308}ve re\u{301}sume\u{301} u\u{308}ber Sta\u{308}dte se\u{301}ance \
+     pin\u{303}ata Zu\u{308}rich fac\u{327}ade ele\u{300}ve Ma\u{30a}lo\u{308} \
+     ha\u{301}c\u{30c}ek Ko\u{308}ln Sa\u{303}o co\u{302}te\u{301} fiance\u{301}e \
+     a\u{300} gro\u{308}ße e\u{301}te\u{301} Dvor\u{30c}a\u{301}k";
+
+static VOCABULARIES: LazyLock<[Vec<&'static str>; 5]> = LazyLock::new(|| {
+    [ENGLISH, CYRILLIC, CJK, EMOJI, COMBINING].map(|words| words.split_whitespace().collect())
+});
+
+/// Picks a random word of one of the languages, weighted by the mix, and
+/// within the language preferring the words at the start of its vocabulary
+pub fn random_word<R: Rng>(rng: &mut R, mix: &LanguageMix) -> &'static str {
+    let weights = mix.weights();
+    let total: u32 = weights.iter().sum();
+    let mut language = 0;
+    if total > 0 {
+        let mut pick = rng.random_range(0..total);
+        while pick >= weights[language] {
+            pick -= weights[language];
+            language += 1;
+        }
+    }
+    let words = &VOCABULARIES[language];
+    let x: f64 = rng.random();
+    words[((x * x) * words.len() as f64) as usize % words.len()]
+}
+
+/// Generates a text of random words separated by spaces
+pub fn random_text<R: Rng>(rng: &mut R, words: u32, mix: &LanguageMix) -> String {
+    (0..words)
+        .map(|_| random_word(rng, mix))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rng;
+
+    #[test]
+    fn test_language_mix() {
+        let english = LanguageMix::default();
+        assert!(random_text(&mut rng(), 50, &english).is_ascii());
+
+        let cyrillic = LanguageMix {
+            english: 0,
+            cyrillic: 1,
+            ..LanguageMix::default()
+        };
+        let text = random_text(&mut rng(), 50, &cyrillic);
+        assert!(text
+            .chars()
+            .all(|c| c == ' ' || ('\u{400}'..='\u{4ff}').contains(&c)));
+
+        let mixed = LanguageMix {
+            english: 1,
+            cyrillic: 1,
+            cjk: 1,
+            emoji: 1,
+            combining: 1,
+        };
+        let text = random_text(&mut rng(), 1000, &mixed);
+        assert!(text.len() > text.chars().count());
+        assert!(text.contains('\u{301}') || text.contains('\u{308}'));
+    }
+}
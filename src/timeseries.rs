@@ -0,0 +1,349 @@
+use crate::arangodb::{
+    collection_exists, create_collection, create_database, create_index, database_exists,
+    drop_database, run_query, ApiRequest, Connection,
+};
+use crate::config::{CollectionProperties, DatabaseConfig, Phase, TimeseriesConfig, UseCaseConfig};
+use crate::control;
+use crate::metrics::{observe_request, METRICS};
+use crate::pacer::Pacer;
+use crate::random::stream_rng;
+use crate::usecase::{run_workers, send_observed, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{info, warn};
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::Method;
+use serde_json::{json, Map, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "timeseries";
+
+/// The collection with the data points
+const COLLECTION: &str = "points";
+
+/// Makes the keys of data points with the same timestamp unique
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// The time series use case: ingests data points of a number of devices
+/// with keys sorted by time, and periodically queries the points of the
+/// last seconds, which is a different access pattern than the random keys
+/// of the CRUD use case.
+pub struct TimeseriesUseCase {
+    ts_config: TimeseriesConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl TimeseriesUseCase {
+    pub fn new(
+        ts_config: TimeseriesConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        TimeseriesUseCase {
+            ts_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Time series use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let ts_config = &self.ts_config;
+        info!("Starting time series use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!("Number of shards: {}", ts_config.number_of_shards);
+        info!("Replication factor: {}", ts_config.replication_factor);
+        info!("Number of series: {}", ts_config.number_of_series);
+        info!("Values per point: {}", ts_config.values_per_point);
+        info!("Batch size: {}", ts_config.batch_size);
+        info!("Concurrency: {}", ts_config.concurrency);
+        if ts_config.target_ops_per_sec > 0.0 {
+            info!("Target rate: {} batches/s", ts_config.target_ops_per_sec);
+        } else {
+            info!("Target rate: unlimited");
+        }
+        if ts_config.query_interval_ms > 0 {
+            info!(
+                "Range queries every {}ms over the last {}s",
+                ts_config.query_interval_ms, ts_config.query_window_secs
+            );
+        } else {
+            info!("Range queries: off");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the time series use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("timeseries")
+}
+
+/// Milliseconds since the epoch
+fn now_ms() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64)
+}
+
+/// Returns the key of a data point. Keys start with the zero-padded
+/// timestamp, so that they sort by time.
+fn point_key(ts: u64, sequence: u64) -> String {
+    format!("T{:013}-{:010}", ts, sequence % 10_000_000_000)
+}
+
+/// Creates the database, the collection and the indexes for the range
+/// queries if needed
+async fn initialize_database(conn: &Connection, ts_config: &TimeseriesConfig) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    if database_exists(conn, &db_name).await? && ts_config.drop_first {
+        info!("Dropping database {}", db_name);
+        drop_database(conn, &db_name).await?;
+    }
+    if !database_exists(conn, &db_name).await? {
+        info!("Creating database {}", db_name);
+        create_database(conn, &db_name).await?;
+    }
+    if !collection_exists(conn, &db_name, COLLECTION).await? {
+        create_collection(
+            conn,
+            &db_name,
+            COLLECTION,
+            ts_config.number_of_shards,
+            ts_config.replication_factor,
+            &CollectionProperties::default(),
+        )
+        .await?;
+    }
+    // Creating an index which exists already just returns it
+    for fields in [json!(["ts"]), json!(["device", "ts"])] {
+        create_index(
+            conn,
+            &db_name,
+            COLLECTION,
+            &json!({"type": "persistent", "fields": fields}),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Inserts a batch of data points of random devices, all with the current
+/// time
+async fn perform_insert(
+    conn: &Connection,
+    ts_config: &TimeseriesConfig,
+    mut rng: StdRng,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+    let ts = now_ms()?;
+    let batch: Vec<Value> = (0..ts_config.batch_size.max(1))
+        .map(|_| {
+            let values: Vec<f64> = (0..ts_config.values_per_point)
+                .map(|_| rng.random_range(0.0..100.0))
+                .collect();
+            let device = rng.random_range(1..=ts_config.number_of_series.max(1));
+            json!({
+                "_key": point_key(ts, SEQUENCE.fetch_add(1, Ordering::Relaxed)),
+                "device": format!("d{}", device),
+                "ts": ts,
+                "values": values,
+            })
+        })
+        .collect();
+    let request = ApiRequest::in_database(
+        Method::POST,
+        &db_name,
+        &format!("/_api/document/{}", COLLECTION),
+    )
+    .query("silent", true)
+    .json(&batch);
+    send_observed(conn, USECASE, "insert_batch", request, &[]).await?;
+    METRICS
+        .documents_inserted
+        .with_label_values(&[USECASE])
+        .inc_by(batch.len() as u64);
+    Ok(())
+}
+
+/// Runs a range query over the last `query_window_secs` and records its
+/// latency under `operation`
+async fn range_query(
+    conn: &Connection,
+    db_name: &str,
+    operation: &str,
+    query: &str,
+    bind_vars: &Map<String, Value>,
+) -> Result<()> {
+    let start = Instant::now();
+    let result = run_query(conn, db_name, query, bind_vars, 1000, |_| {}).await;
+    observe_request(USECASE, operation, start.elapsed(), result.is_ok());
+    result.map_err(|e| anyhow::anyhow!("Query {} failed: {}", operation, e))?;
+    Ok(())
+}
+
+/// Periodically queries the recent data points, alternately of a single
+/// random device and aggregated over all devices
+async fn query_recent(conn: Connection, ts_config: TimeseriesConfig, shutdown: CancellationToken) {
+    let db_name = database_name(conn.config());
+    let mut rng = stream_rng(&format!("{}/query", USECASE), 0);
+    let mut ticker = tokio::time::interval(Duration::from_millis(ts_config.query_interval_ms));
+    let mut per_device = true;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => return,
+        }
+        let from = match now_ms() {
+            Ok(now) => now.saturating_sub(ts_config.query_window_secs * 1000),
+            Err(e) => {
+                warn!("Cannot determine the time: {}", e);
+                continue;
+            }
+        };
+        let mut bind_vars = Map::new();
+        bind_vars.insert("@collection".to_string(), json!(COLLECTION));
+        bind_vars.insert("from".to_string(), json!(from));
+        let result = if per_device {
+            let device = rng.random_range(1..=ts_config.number_of_series.max(1));
+            bind_vars.insert("device".to_string(), json!(format!("d{}", device)));
+            range_query(
+                &conn,
+                &db_name,
+                "range_device",
+                "FOR d IN @@collection FILTER d.device == @device AND d.ts >= @from \
+                 SORT d.ts RETURN d",
+                &bind_vars,
+            )
+            .await
+        } else {
+            range_query(
+                &conn,
+                &db_name,
+                "range_all",
+                "FOR d IN @@collection FILTER d.ts >= @from SORT d.ts \
+                 COLLECT device = d.device AGGREGATE points = COUNT(1), \
+                 first = MIN(d.ts), last = MAX(d.ts) RETURN { device, points, first, last }",
+                &bind_vars,
+            )
+            .await
+        };
+        if let Err(e) = result {
+            warn!("{}", e);
+        }
+        per_device = !per_device;
+    }
+}
+
+#[async_trait]
+impl UseCase for TimeseriesUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize_database(&connection, &self.ts_config).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let queries = (self.ts_config.query_interval_ms > 0).then(|| {
+            tokio::spawn(query_recent(
+                connection.clone(),
+                self.ts_config.clone(),
+                shutdown.clone(),
+            ))
+        });
+
+        let pacer = Arc::new(Pacer::new(self.ts_config.target_ops_per_sec));
+        let ts_config = Arc::new(RwLock::new(Arc::new(self.ts_config.clone())));
+        let reloaded = Arc::clone(&ts_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(timeseries) = &config.timeseries {
+                control::apply_load(
+                    USECASE,
+                    timeseries.concurrency,
+                    timeseries.target_ops_per_sec,
+                );
+                *reloaded.write().unwrap() = Arc::new(timeseries.clone());
+            }
+        });
+        run_workers(
+            USECASE,
+            self.ts_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let ts_config = Arc::clone(&ts_config.read().unwrap());
+                async move { perform_insert(&connection, &ts_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        if let Some(queries) = queries {
+            queries.await?;
+        }
+        Ok(())
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.ts_config.drop_on_exit {
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&self.connection()?, &db_name).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_keys_sort_by_time() {
+        let earlier = point_key(999_999_999_999, 42);
+        let later = point_key(1_000_000_000_000, 7);
+        assert!(earlier < later);
+        assert_eq!(later, "T1000000000000-0000000007");
+    }
+}
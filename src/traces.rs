@@ -0,0 +1,302 @@
+use crate::config::TracingConfig;
+use log::{info, warn};
+use rand::Rng;
+use serde_json::{json, Value};
+use std::num::{NonZeroU128, NonZeroU64};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+/// Finished spans beyond this many are dropped until the next export
+const MAX_PENDING_SPANS: usize = 100_000;
+
+/// The span kind `SPAN_KIND_CLIENT` of OTLP
+const SPAN_KIND_CLIENT: u8 = 3;
+
+/// The status code `STATUS_CODE_ERROR` of OTLP
+const STATUS_CODE_ERROR: u8 = 2;
+
+static TRACER: OnceLock<Tracer> = OnceLock::new();
+
+struct Tracer {
+    sample_pct: f64,
+    pending: Mutex<Vec<FinishedSpan>>,
+}
+
+/// Identifies a span within a trace, it is sent to the server in the W3C
+/// `traceparent` header, so that the traces of the server can be
+/// correlated with the ones of the loader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: u128,
+    span_id: u64,
+}
+
+impl TraceContext {
+    /// The value of the `traceparent` header, for a sampled span
+    pub fn traceparent(&self) -> String {
+        format!("00-{:032x}-{:016x}-01", self.trace_id, self.span_id)
+    }
+}
+
+/// The value of a span attribute
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attribute {
+    String(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl From<&str> for Attribute {
+    fn from(value: &str) -> Self {
+        Attribute::String(value.to_string())
+    }
+}
+
+impl From<String> for Attribute {
+    fn from(value: String) -> Self {
+        Attribute::String(value)
+    }
+}
+
+impl From<i64> for Attribute {
+    fn from(value: i64) -> Self {
+        Attribute::Int(value)
+    }
+}
+
+impl From<bool> for Attribute {
+    fn from(value: bool) -> Self {
+        Attribute::Bool(value)
+    }
+}
+
+/// A span which is running. It is recorded for export when it ends.
+#[derive(Debug)]
+pub struct Span {
+    context: TraceContext,
+    parent: Option<u64>,
+    name: String,
+    start: SystemTime,
+    attributes: Vec<(String, Attribute)>,
+    error: bool,
+}
+
+#[derive(Debug)]
+struct FinishedSpan {
+    span: Span,
+    end: SystemTime,
+}
+
+impl Span {
+    /// Starts the root span of a new trace, if tracing is on and the trace
+    /// is sampled
+    pub fn root(name: &str) -> Option<Span> {
+        let tracer = TRACER.get()?;
+        let mut rng = rand::rng();
+        if tracer.sample_pct < 100.0 && rng.random_range(0.0..100.0) >= tracer.sample_pct {
+            return None;
+        }
+        // An ID of all zeros is invalid, random ones are drawn again
+        Some(Span::new(rng.random::<NonZeroU128>().get(), None, name))
+    }
+
+    /// Starts a span within the trace of `parent`
+    pub fn child(parent: &TraceContext, name: &str) -> Span {
+        Span::new(parent.trace_id, Some(parent.span_id), name)
+    }
+
+    fn new(trace_id: u128, parent: Option<u64>, name: &str) -> Span {
+        Span {
+            context: TraceContext {
+                trace_id,
+                span_id: rand::rng().random::<NonZeroU64>().get(),
+            },
+            parent,
+            name: name.to_string(),
+            start: SystemTime::now(),
+            attributes: Vec::new(),
+            error: false,
+        }
+    }
+
+    pub fn context(&self) -> TraceContext {
+        self.context
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Into<Attribute>) {
+        self.attributes.push((key.to_string(), value.into()));
+    }
+
+    /// Marks the span as failed
+    pub fn set_error(&mut self) {
+        self.error = true;
+    }
+
+    /// Ends the span and queues it for the next export
+    pub fn end(self) {
+        let Some(tracer) = TRACER.get() else {
+            return;
+        };
+        let mut pending = tracer.pending.lock().unwrap();
+        if pending.len() < MAX_PENDING_SPANS {
+            pending.push(FinishedSpan {
+                span: self,
+                end: SystemTime::now(),
+            });
+        }
+    }
+}
+
+/// Turns tracing on, from now on operations start spans. Called once
+/// before the use cases start.
+pub fn init(config: &TracingConfig) {
+    let tracer = Tracer {
+        sample_pct: config.sample_pct,
+        pending: Mutex::new(Vec::new()),
+    };
+    if TRACER.set(tracer).is_err() {
+        warn!("Tracing is already initialized");
+    }
+}
+
+/// Periodically sends the finished spans to the OTLP/HTTP endpoint of a
+/// collector (`<otlp_endpoint>/v1/traces`, JSON encoded), until `shutdown`
+/// is cancelled. The last spans are sent on shutdown.
+pub async fn export(config: TracingConfig, shutdown: CancellationToken) {
+    let url = format!("{}/v1/traces", config.otlp_endpoint.trim_end_matches('/'));
+    info!(
+        "Exporting {}% of the operations as traces to {}",
+        config.sample_pct, url
+    );
+    let client = reqwest::Client::new();
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.export_interval_secs.max(1)));
+    ticker.tick().await;
+    loop {
+        let stopped = tokio::select! {
+            _ = ticker.tick() => false,
+            _ = shutdown.cancelled() => true,
+        };
+        let spans = match TRACER.get() {
+            Some(tracer) => std::mem::take(&mut *tracer.pending.lock().unwrap()),
+            None => Vec::new(),
+        };
+        if !spans.is_empty() {
+            let body = encode(&config.service_name, &spans);
+            let result = client.post(&url).json(&body).send().await;
+            match result.and_then(|response| response.error_for_status()) {
+                Ok(_) => {}
+                Err(e) => warn!("Cannot export {} spans to {}: {}", spans.len(), url, e),
+            }
+        }
+        if stopped {
+            return;
+        }
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn encode_attribute(key: &str, value: &Attribute) -> Value {
+    let value = match value {
+        Attribute::String(s) => json!({ "stringValue": s }),
+        // 64 bit integers are strings in the JSON encoding of OTLP
+        Attribute::Int(i) => json!({ "intValue": i.to_string() }),
+        Attribute::Bool(b) => json!({ "boolValue": b }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+/// Encodes spans as an OTLP `ExportTraceServiceRequest` in JSON
+fn encode(service_name: &str, spans: &[FinishedSpan]) -> Value {
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|FinishedSpan { span, end }| {
+            let mut encoded = json!({
+                "traceId": format!("{:032x}", span.context.trace_id),
+                "spanId": format!("{:016x}", span.context.span_id),
+                "name": span.name,
+                "kind": SPAN_KIND_CLIENT,
+                "startTimeUnixNano": unix_nanos(span.start),
+                "endTimeUnixNano": unix_nanos(*end),
+                "attributes": span
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| encode_attribute(key, value))
+                    .collect::<Vec<_>>(),
+            });
+            if let Some(parent) = span.parent {
+                encoded["parentSpanId"] = json!(format!("{:016x}", parent));
+            }
+            if span.error {
+                encoded["status"] = json!({ "code": STATUS_CODE_ERROR });
+            }
+            encoded
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [encode_attribute("service.name", &service_name.into())],
+            },
+            "scopeSpans": [{
+                "scope": { "name": "adb_loader" },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent() {
+        let context = TraceContext {
+            trace_id: 0x4bf92f3577b34da6a3ce929d0e0e4736,
+            span_id: 0x00f067aa0ba902b7,
+        };
+        assert_eq!(
+            context.traceparent(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+    }
+
+    #[test]
+    fn test_encode_spans() {
+        let mut root = Span::new(1, None, "crud insert");
+        root.set("usecase", "crud");
+        let mut attempt = Span::child(&root.context(), "POST /_api/document/c1");
+        attempt.set("attempt", 2i64);
+        attempt.set("retry", true);
+        attempt.set_error();
+        let end = SystemTime::now();
+        let parent_id = format!("{:016x}", root.context().span_id);
+        let spans = [
+            FinishedSpan { span: root, end },
+            FinishedSpan { span: attempt, end },
+        ];
+        let encoded = encode("loader", &spans);
+        let resource = &encoded["resourceSpans"][0];
+        assert_eq!(
+            resource["resource"]["attributes"][0]["value"]["stringValue"],
+            "loader"
+        );
+        let spans = &resource["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["traceId"], "00000000000000000000000000000001");
+        assert_eq!(spans[0]["name"], "crud insert");
+        assert!(spans[0].get("parentSpanId").is_none());
+        assert!(spans[0].get("status").is_none());
+        assert_eq!(spans[1]["traceId"], spans[0]["traceId"]);
+        assert_eq!(spans[1]["parentSpanId"], parent_id);
+        assert_eq!(spans[1]["attributes"][0]["value"]["intValue"], "2");
+        assert_eq!(spans[1]["attributes"][1]["value"]["boolValue"], true);
+        assert_eq!(spans[1]["status"]["code"], 2);
+    }
+}
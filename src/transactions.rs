@@ -0,0 +1,336 @@
+use crate::arangodb::{
+    collection_exists, create_collection, create_database, database_exists, drop_database,
+    ApiRequest, Connection,
+};
+use crate::config::{
+    CollectionProperties, DatabaseConfig, Phase, TransactionsConfig, UseCaseConfig,
+};
+use crate::control;
+use crate::crud::{generate_document, NUM_ATTRIBUTES};
+use crate::metrics::observe_request;
+use crate::pacer::Pacer;
+use crate::usecase::{run_workers, send_observed, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::info;
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "transactions";
+
+/// The header which associates a request with a stream transaction
+pub(crate) const TRX_ID_HEADER: &str = "x-arango-trx-id";
+
+/// The transactions use case: runs stream transactions, each of which
+/// performs a number of document reads and writes across several
+/// collections and is then committed or aborted.
+pub struct TransactionsUseCase {
+    trx_config: TransactionsConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl TransactionsUseCase {
+    pub fn new(
+        trx_config: TransactionsConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        TransactionsUseCase {
+            trx_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Transactions use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let trx_config = &self.trx_config;
+        info!("Starting transactions use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!(
+            "Number of collections: {}",
+            trx_config.number_of_collections
+        );
+        info!("Number of shards: {}", trx_config.number_of_shards);
+        info!("Replication factor: {}", trx_config.replication_factor);
+        info!("Number of keys: {}", trx_config.number_of_keys);
+        info!("Document size: {}", trx_config.document_size);
+        info!(
+            "Operations per transaction: {}, writes {}%, aborts {}%",
+            trx_config.operations_per_transaction, trx_config.write_pct, trx_config.abort_pct
+        );
+        if let Some(count) = trx_config.intermediate_commit_count {
+            info!("Intermediate commit count: {}", count);
+        }
+        if let Some(size) = trx_config.intermediate_commit_size {
+            info!("Intermediate commit size: {}", size);
+        }
+        info!("Concurrency: {}", trx_config.concurrency);
+        if trx_config.target_ops_per_sec > 0.0 {
+            info!(
+                "Target rate: {} transactions/s",
+                trx_config.target_ops_per_sec
+            );
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the transactions use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("transactions")
+}
+
+fn collection_names(trx_config: &TransactionsConfig) -> Vec<String> {
+    (1..=trx_config.number_of_collections)
+        .map(|i| format!("c{}", i))
+        .collect()
+}
+
+/// Creates the database and all collections which do not exist yet. The
+/// collections need no seeding, since writes replace or insert documents
+/// and reads of missing documents are fine.
+async fn initialize_database(conn: &Connection, trx_config: &TransactionsConfig) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    if database_exists(conn, &db_name).await? {
+        if !trx_config.drop_first {
+            for coll_name in collection_names(trx_config) {
+                if !collection_exists(conn, &db_name, &coll_name).await? {
+                    create_collection(
+                        conn,
+                        &db_name,
+                        &coll_name,
+                        trx_config.number_of_shards,
+                        trx_config.replication_factor,
+                        &CollectionProperties::default(),
+                    )
+                    .await?;
+                }
+            }
+            return Ok(());
+        }
+        info!("Dropping database {}", db_name);
+        drop_database(conn, &db_name).await?;
+    }
+
+    info!("Creating database {}", db_name);
+    create_database(conn, &db_name).await?;
+    for coll_name in collection_names(trx_config) {
+        create_collection(
+            conn,
+            &db_name,
+            &coll_name,
+            trx_config.number_of_shards,
+            trx_config.replication_factor,
+            &CollectionProperties::default(),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// A single document operation within a transaction
+struct TrxOperation {
+    collection: String,
+    key: u32,
+    write: bool,
+}
+
+/// Runs one transaction with random operations and commits or aborts it.
+/// Besides the individual requests, the latency of the whole transaction
+/// is recorded as operation `transaction`.
+async fn perform_transaction(
+    conn: &Connection,
+    trx_config: &TransactionsConfig,
+    mut rng: StdRng,
+) -> Result<()> {
+    let operations: Vec<TrxOperation> = (0..trx_config.operations_per_transaction)
+        .map(|_| TrxOperation {
+            collection: format!(
+                "c{}",
+                rng.random_range(1..=trx_config.number_of_collections)
+            ),
+            key: rng.random_range(1..=trx_config.number_of_keys),
+            write: rng.random_range(0..100) < trx_config.write_pct,
+        })
+        .collect();
+    let abort = rng.random_range(0..100) < trx_config.abort_pct;
+
+    let start = Instant::now();
+    let result = run_transaction(conn, trx_config, &operations, abort, &mut rng).await;
+    observe_request(USECASE, "transaction", start.elapsed(), result.is_ok());
+    result
+}
+
+async fn run_transaction(
+    conn: &Connection,
+    trx_config: &TransactionsConfig,
+    operations: &[TrxOperation],
+    abort: bool,
+    rng: &mut StdRng,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    let mut options = json!({
+        "collections": { "write": collection_names(trx_config) }
+    });
+    if let Some(count) = trx_config.intermediate_commit_count {
+        options["intermediateCommitCount"] = json!(count);
+    }
+    if let Some(size) = trx_config.intermediate_commit_size {
+        options["intermediateCommitSize"] = json!(size);
+    }
+    let request =
+        ApiRequest::in_database(Method::POST, &db_name, "/_api/transaction/begin").json(&options);
+    let response = send_observed(conn, USECASE, "begin", request, &[]).await?;
+
+    // The transaction lives on the coordinator which started it, so all
+    // further requests must go there
+    let endpoint = response.endpoint.clone();
+    let trx_id = response.json::<Value>()?["result"]["id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Transaction begin returned no id"))?
+        .to_string();
+    let trx_header = HeaderValue::from_str(&trx_id)?;
+
+    let mut result: Result<()> = Ok(());
+    for operation in operations {
+        let path = format!("/_api/document/{}", operation.collection);
+        let (name, request, accepted): (&str, ApiRequest, &[u16]) = if operation.write {
+            let document =
+                generate_document(rng, operation.key, trx_config.document_size, NUM_ATTRIBUTES);
+            let request = ApiRequest::in_database(Method::POST, &db_name, &path)
+                .query("overwriteMode", "replace")
+                .json(&document);
+            ("write", request, &[])
+        } else {
+            // Reads of keys which were never written are not an error
+            let path = format!("{}/K{}", path, operation.key);
+            let request = ApiRequest::in_database(Method::GET, &db_name, &path);
+            ("read", request, &[404])
+        };
+        let request = request
+            .header(HeaderName::from_static(TRX_ID_HEADER), trx_header.clone())
+            .pinned_to(&endpoint);
+        if let Err(e) = send_observed(conn, USECASE, name, request, accepted).await {
+            result = Err(e);
+            break;
+        }
+    }
+
+    // A transaction with a failed operation is always aborted
+    let (method, name) = if abort || result.is_err() {
+        (Method::DELETE, "abort")
+    } else {
+        (Method::PUT, "commit")
+    };
+    let request =
+        ApiRequest::in_database(method, &db_name, &format!("/_api/transaction/{}", trx_id))
+            .pinned_to(&endpoint);
+    let finished = send_observed(conn, USECASE, name, request, &[]).await;
+    result?;
+    finished?;
+    Ok(())
+}
+
+#[async_trait]
+impl UseCase for TransactionsUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        if self.trx_config.number_of_collections == 0 || self.trx_config.number_of_keys == 0 {
+            return Err(anyhow::anyhow!(
+                "The transactions use case needs at least one collection and one key"
+            ));
+        }
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize_database(&connection, &self.trx_config).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let pacer = Arc::new(Pacer::new(self.trx_config.target_ops_per_sec));
+        // The operation mix can be changed by reloading the configuration
+        let trx_config = Arc::new(RwLock::new(Arc::new(self.trx_config.clone())));
+        let reloaded = Arc::clone(&trx_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(transactions) = &config.transactions {
+                control::apply_load(
+                    USECASE,
+                    transactions.concurrency,
+                    transactions.target_ops_per_sec,
+                );
+                *reloaded.write().unwrap() = Arc::new(transactions.clone());
+            }
+        });
+        run_workers(
+            USECASE,
+            self.trx_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let trx_config = Arc::clone(&trx_config.read().unwrap());
+                async move { perform_transaction(&connection, &trx_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        Ok(())
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.trx_config.drop_on_exit {
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&self.connection()?, &db_name).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,286 @@
+use crate::arangodb::{
+    collection_count, collection_exists, create_collection, create_database, create_index,
+    database_exists, drop_database, ApiRequest, Connection,
+};
+use crate::config::{CollectionProperties, DatabaseConfig, Phase, TtlConfig, UseCaseConfig};
+use crate::control;
+use crate::crud::generate_random_ascii;
+use crate::metrics::METRICS;
+use crate::pacer::Pacer;
+use crate::usecase::{run_workers, send_observed, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{info, warn};
+use rand::rngs::StdRng;
+use reqwest::Method;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "ttl";
+
+/// The collection with the expiring documents
+const COLLECTION: &str = "expiring";
+
+/// The attribute with the expiry time, in seconds since the epoch
+const EXPIRY_ATTRIBUTE: &str = "expireAt";
+
+/// The TTL use case: continuously inserts documents which expire shortly
+/// after into a collection with a TTL index, and monitors whether the
+/// server's TTL thread keeps up with removing them.
+pub struct TtlUseCase {
+    ttl_config: TtlConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl TtlUseCase {
+    pub fn new(
+        ttl_config: TtlConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        TtlUseCase {
+            ttl_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("TTL use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let ttl_config = &self.ttl_config;
+        info!("Starting TTL use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!("Number of shards: {}", ttl_config.number_of_shards);
+        info!("Replication factor: {}", ttl_config.replication_factor);
+        info!("Document size: {}", ttl_config.document_size);
+        info!("Expire after: {}s", ttl_config.expire_after_secs);
+        info!("Batch size: {}", ttl_config.batch_size);
+        info!("Concurrency: {}", ttl_config.concurrency);
+        if ttl_config.target_ops_per_sec > 0.0 {
+            info!("Target rate: {} batches/s", ttl_config.target_ops_per_sec);
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Monitor interval: {}s", ttl_config.monitor_interval_secs);
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the TTL use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("ttl")
+}
+
+/// Creates the database, the collection and its TTL index if needed. The
+/// index removes documents as soon as the time in their expiry attribute
+/// has passed.
+async fn initialize_database(conn: &Connection, ttl_config: &TtlConfig) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    if database_exists(conn, &db_name).await? && ttl_config.drop_first {
+        info!("Dropping database {}", db_name);
+        drop_database(conn, &db_name).await?;
+    }
+    if !database_exists(conn, &db_name).await? {
+        info!("Creating database {}", db_name);
+        create_database(conn, &db_name).await?;
+    }
+    if !collection_exists(conn, &db_name, COLLECTION).await? {
+        create_collection(
+            conn,
+            &db_name,
+            COLLECTION,
+            ttl_config.number_of_shards,
+            ttl_config.replication_factor,
+            &CollectionProperties::default(),
+        )
+        .await?;
+    }
+    // Creating an index which exists already just returns it
+    create_index(
+        conn,
+        &db_name,
+        COLLECTION,
+        &json!({"type": "ttl", "fields": [EXPIRY_ATTRIBUTE], "expireAfter": 0}),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Inserts a batch of documents which expire `expire_after_secs` from now
+async fn perform_insert(conn: &Connection, ttl_config: &TtlConfig, mut rng: StdRng) -> Result<()> {
+    let db_name = database_name(conn.config());
+    let expire_at =
+        SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() + ttl_config.expire_after_secs;
+    let batch: Vec<Value> = (0..ttl_config.batch_size.max(1))
+        .map(|_| {
+            json!({
+                EXPIRY_ATTRIBUTE: expire_at,
+                "payload": generate_random_ascii(&mut rng, ttl_config.document_size as usize),
+            })
+        })
+        .collect();
+    let request = ApiRequest::in_database(
+        Method::POST,
+        &db_name,
+        &format!("/_api/document/{}", COLLECTION),
+    )
+    .query("silent", true)
+    .json(&batch);
+    send_observed(conn, USECASE, "insert_batch", request, &[]).await?;
+    METRICS
+        .documents_inserted
+        .with_label_values(&[USECASE])
+        .inc_by(batch.len() as u64);
+    Ok(())
+}
+
+/// Periodically compares the number of documents in the collection with
+/// the number inserted during the last `expire_after_secs`, which are the
+/// only ones which should still be there. The difference is the backlog of
+/// expired documents the TTL thread has not removed yet. It is approximate,
+/// documents inserted during the oldest monitor interval count as alive.
+async fn monitor_expiry(conn: Connection, ttl_config: TtlConfig, shutdown: CancellationToken) {
+    let db_name = database_name(conn.config());
+    let expire_after = Duration::from_secs(ttl_config.expire_after_secs);
+    let inserted = || {
+        METRICS
+            .documents_inserted
+            .with_label_values(&[USECASE])
+            .get()
+    };
+    // Samples of the insert counter, the oldest one is the newest sample
+    // taken at least `expire_after` ago
+    let mut samples: VecDeque<(Instant, u64)> = VecDeque::new();
+    let mut ticker =
+        tokio::time::interval(Duration::from_secs(ttl_config.monitor_interval_secs.max(1)));
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = shutdown.cancelled() => return,
+        }
+        let now = Instant::now();
+        samples.push_back((now, inserted()));
+        while samples.len() > 1 && now - samples[1].0 >= expire_after {
+            samples.pop_front();
+        }
+
+        let count = match collection_count(&conn, &db_name, COLLECTION).await {
+            Ok(count) => count,
+            Err(e) => {
+                warn!(
+                    "Cannot count the documents of {}/{}: {}",
+                    db_name, COLLECTION, e
+                );
+                continue;
+            }
+        };
+        let (since, inserted_before) = samples[0];
+        if now - since < expire_after {
+            info!("TTL collection holds {} documents", count);
+            continue;
+        }
+        let alive = inserted() - inserted_before;
+        let backlog = count.saturating_sub(alive);
+        METRICS
+            .ttl_backlog
+            .with_label_values(&[USECASE])
+            .set(backlog as f64);
+        info!(
+            "TTL collection holds {} documents, {} not yet expired, backlog {}",
+            count, alive, backlog
+        );
+    }
+}
+
+#[async_trait]
+impl UseCase for TtlUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize_database(&connection, &self.ttl_config).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let monitor = tokio::spawn(monitor_expiry(
+            connection.clone(),
+            self.ttl_config.clone(),
+            shutdown.clone(),
+        ));
+
+        let pacer = Arc::new(Pacer::new(self.ttl_config.target_ops_per_sec));
+        let ttl_config = Arc::new(RwLock::new(Arc::new(self.ttl_config.clone())));
+        let reloaded = Arc::clone(&ttl_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(ttl) = &config.ttl {
+                control::apply_load(USECASE, ttl.concurrency, ttl.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(ttl.clone());
+            }
+        });
+        run_workers(
+            USECASE,
+            self.ttl_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let ttl_config = Arc::clone(&ttl_config.read().unwrap());
+                async move { perform_insert(&connection, &ttl_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        monitor.await?;
+        Ok(())
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.ttl_config.drop_on_exit {
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&self.connection()?, &db_name).await?;
+        }
+        Ok(())
+    }
+}
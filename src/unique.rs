@@ -0,0 +1,261 @@
+use crate::arangodb::{
+    collection_exists, create_collection, create_database, create_index, database_exists,
+    drop_database, run_query, ApiRequest, Connection,
+};
+use crate::config::{CollectionProperties, DatabaseConfig, Phase, UniqueConfig, UseCaseConfig};
+use crate::control;
+use crate::metrics::{observe_request, METRICS};
+use crate::pacer::Pacer;
+use crate::usecase::{run_workers, send_observed, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::info;
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::Method;
+use serde_json::{json, Map};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "unique";
+
+/// The collection with the unique index
+const COLLECTION: &str = "values";
+
+/// The unique use case: many workers insert documents with values from a
+/// small value space into a collection with a unique index, so that many
+/// inserts collide. Removes of random values keep the value space from
+/// filling up completely.
+pub struct UniqueUseCase {
+    unique_config: UniqueConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl UniqueUseCase {
+    pub fn new(
+        unique_config: UniqueConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        UniqueUseCase {
+            unique_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Unique use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let unique_config = &self.unique_config;
+        info!("Starting unique use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!("Number of shards: {}", unique_config.number_of_shards);
+        info!("Replication factor: {}", unique_config.replication_factor);
+        info!("Value space: {}", unique_config.value_space);
+        info!("Unique index: {}", unique_config.unique);
+        info!("Removes: {}%", unique_config.remove_pct);
+        info!("Concurrency: {}", unique_config.concurrency);
+        if unique_config.target_ops_per_sec > 0.0 {
+            info!("Target rate: {} ops/s", unique_config.target_ops_per_sec);
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the unique use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("unique")
+}
+
+/// Creates the database, the collection and the index on `value` if
+/// needed. In a cluster, a unique index must contain the shard keys, so
+/// the collection is sharded by `value`.
+async fn initialize_database(conn: &Connection, unique_config: &UniqueConfig) -> Result<()> {
+    let db_name = database_name(conn.config());
+
+    if database_exists(conn, &db_name).await? && unique_config.drop_first {
+        info!("Dropping database {}", db_name);
+        drop_database(conn, &db_name).await?;
+    }
+    if !database_exists(conn, &db_name).await? {
+        info!("Creating database {}", db_name);
+        create_database(conn, &db_name).await?;
+    }
+    if !collection_exists(conn, &db_name, COLLECTION).await? {
+        create_collection(
+            conn,
+            &db_name,
+            COLLECTION,
+            unique_config.number_of_shards,
+            unique_config.replication_factor,
+            &CollectionProperties {
+                shard_keys: Some(vec!["value".to_string()]),
+                ..CollectionProperties::default()
+            },
+        )
+        .await?;
+    }
+    // Creating an index which exists already just returns it
+    create_index(
+        conn,
+        &db_name,
+        COLLECTION,
+        &json!({"type": "persistent", "fields": ["value"], "unique": unique_config.unique}),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Inserts a document with a random value, a unique constraint violation
+/// is counted but is no error
+async fn insert_value(conn: &Connection, db_name: &str, value: u32) -> Result<()> {
+    let request = ApiRequest::in_database(
+        Method::POST,
+        db_name,
+        &format!("/_api/document/{}", COLLECTION),
+    )
+    .json(&json!({"value": value}));
+    let response = send_observed(conn, USECASE, "insert", request, &[409]).await?;
+    if response.status.as_u16() == 409 {
+        METRICS
+            .unique_violations
+            .with_label_values(&[USECASE])
+            .inc();
+    }
+    Ok(())
+}
+
+/// Removes the documents with a random value, if there are any
+async fn remove_value(conn: &Connection, db_name: &str, value: u32) -> Result<()> {
+    let mut bind_vars = Map::new();
+    bind_vars.insert("@collection".to_string(), json!(COLLECTION));
+    bind_vars.insert("value".to_string(), json!(value));
+    let start = Instant::now();
+    let result = run_query(
+        conn,
+        db_name,
+        "FOR d IN @@collection FILTER d.value == @value REMOVE d IN @@collection",
+        &bind_vars,
+        1000,
+        |_| {},
+    )
+    .await;
+    observe_request(USECASE, "remove", start.elapsed(), result.is_ok());
+    result.map_err(|e| anyhow::anyhow!("Remove failed: {}", e))?;
+    Ok(())
+}
+
+/// Performs an insert or a remove of a random value
+async fn perform_operation(
+    conn: &Connection,
+    unique_config: &UniqueConfig,
+    mut rng: StdRng,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+    let value = rng.random_range(1..=unique_config.value_space.max(1));
+    if rng.random_range(0..100) < unique_config.remove_pct {
+        remove_value(conn, &db_name, value).await
+    } else {
+        insert_value(conn, &db_name, value).await
+    }
+}
+
+#[async_trait]
+impl UseCase for UniqueUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize_database(&connection, &self.unique_config).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let pacer = Arc::new(Pacer::new(self.unique_config.target_ops_per_sec));
+        let unique_config = Arc::new(RwLock::new(Arc::new(self.unique_config.clone())));
+        let reloaded = Arc::clone(&unique_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(unique) = &config.unique {
+                control::apply_load(USECASE, unique.concurrency, unique.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(unique.clone());
+            }
+        });
+        run_workers(
+            USECASE,
+            self.unique_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let unique_config = Arc::clone(&unique_config.read().unwrap());
+                async move { perform_operation(&connection, &unique_config, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+
+        let inserts = METRICS
+            .requests
+            .with_label_values(&[USECASE, "insert"])
+            .get();
+        let violations = METRICS
+            .unique_violations
+            .with_label_values(&[USECASE])
+            .get();
+        info!(
+            "Unique: {} of {} inserts violated the unique constraint ({:.1}%)",
+            violations,
+            inserts,
+            violations as f64 * 100.0 / inserts.max(1) as f64
+        );
+        Ok(())
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.unique_config.drop_on_exit {
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&self.connection()?, &db_name).await?;
+        }
+        Ok(())
+    }
+}
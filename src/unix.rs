@@ -0,0 +1,289 @@
+//! A client for `unix:///path/to/socket` endpoints, which talks HTTP/1.1
+//! over a Unix domain socket to an arangod on the same machine, so that a
+//! co-located benchmark does not measure the network stack.
+//!
+//! Each connection carries one request at a time, idle connections are
+//! kept per endpoint and reused, like the connection pool of the HTTP
+//! client. Bodies, compression and responses are handled like over HTTP.
+
+use crate::arangodb::{http_body, http_response, ApiRequest, ApiResponse, ArangoError, HttpBody};
+use crate::config::DatabaseConfig;
+use crate::endpoints::Endpoint;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Empty, Full};
+use hyper::client::conn::http1::SendRequest;
+use hyper::header::{HeaderValue, AUTHORIZATION, HOST};
+use hyper::Request;
+use log::debug;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::Mutex;
+use std::time::Duration;
+
+type Body = BoxBody<Bytes, Error>;
+
+/// The scheme of endpoints which are Unix domain sockets
+pub const UNIX_SCHEME: &str = "unix://";
+
+/// An HTTP client for all `unix://` endpoints of a deployment
+#[derive(Debug)]
+pub struct UnixClient {
+    authorization: HeaderValue,
+    connect_timeout: Option<Duration>,
+    max_idle_per_host: usize,
+    idle: Mutex<HashMap<String, Vec<SendRequest<Body>>>>,
+}
+
+impl UnixClient {
+    /// Creates a client, connections are opened when they are needed
+    ///
+    /// # Errors
+    /// * `ArangoError::InvalidConfig` - If the credentials cannot be used in a header
+    pub fn new(config: &DatabaseConfig) -> Result<Self, ArangoError> {
+        let credentials = BASE64.encode(format!("{}:{}", config.username, config.password));
+        let mut authorization = HeaderValue::from_str(&format!("Basic {}", credentials))
+            .map_err(|e| ArangoError::InvalidConfig(format!("Invalid credentials: {}", e)))?;
+        authorization.set_sensitive(true);
+        Ok(UnixClient {
+            authorization,
+            connect_timeout: config.connect_timeout_ms.map(Duration::from_millis),
+            max_idle_per_host: config.pool_max_idle_per_host.unwrap_or(usize::MAX),
+            idle: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Sends a request to a `unix://` endpoint and waits for the response
+    ///
+    /// # Errors
+    /// * `ArangoError::UnixError` - If the socket cannot be connected or
+    ///   the exchange fails
+    /// * Any error of preparing the body or reading the response
+    ///
+    /// The request timeout is applied by the caller, dropping the future
+    /// drops the connection with the request in flight.
+    pub async fn send(
+        &self,
+        endpoint: &Endpoint,
+        request: &ApiRequest,
+        config: &DatabaseConfig,
+    ) -> Result<ApiResponse, ArangoError> {
+        let url = endpoint.url();
+        let (headers, body) = http_body(request, config)?;
+        let mut builder = Request::builder()
+            .method(request.method().clone())
+            .uri(path_and_query(request)?);
+        if let Some(all) = builder.headers_mut() {
+            all.insert(HOST, HeaderValue::from_static("localhost"));
+            all.insert(AUTHORIZATION, self.authorization.clone());
+            // The request may authenticate as another user
+            all.extend(request.headers().clone());
+            all.extend(headers);
+        }
+        let body: Body = match body {
+            None => Empty::new().map_err(|never| match never {}).boxed(),
+            Some(HttpBody::Complete(bytes)) => {
+                Full::new(bytes).map_err(|never| match never {}).boxed()
+            }
+            Some(HttpBody::Stream(body)) => body,
+        };
+        let request = builder
+            .body(body)
+            .map_err(|e| ArangoError::UnixError(Error::new(ErrorKind::InvalidInput, e)))?;
+
+        let mut sender = match self.take_idle(url) {
+            Some(sender) => sender,
+            None => self.connect(url).await?,
+        };
+        // An idle connection may have been closed by the server meanwhile
+        if sender.ready().await.is_err() {
+            sender = self.connect(url).await?;
+        }
+        let response = sender.send_request(request).await.map_err(io_error)?;
+        let (parts, body) = response.into_parts();
+        let body = body.collect().await.map_err(io_error)?.to_bytes();
+        // A connection which failed is dropped instead
+        self.put_idle(url, sender);
+        http_response(endpoint, parts.status, parts.headers, body)
+    }
+
+    fn take_idle(&self, url: &str) -> Option<SendRequest<Body>> {
+        let mut idle = self.idle.lock().unwrap();
+        let senders = idle.get_mut(url)?;
+        std::iter::from_fn(|| senders.pop()).find(|sender| !sender.is_closed())
+    }
+
+    fn put_idle(&self, url: &str, sender: SendRequest<Body>) {
+        let mut idle = self.idle.lock().unwrap();
+        let senders = idle.entry(url.to_string()).or_default();
+        if senders.len() < self.max_idle_per_host {
+            senders.push(sender);
+        }
+    }
+
+    /// Opens a new connection to the socket of an endpoint
+    async fn connect(&self, url: &str) -> Result<SendRequest<Body>, ArangoError> {
+        let path = socket_path(url).ok_or_else(|| {
+            ArangoError::InvalidConfig(format!("Not a unix:// endpoint: {}", url))
+        })?;
+        let connect = connect(path);
+        let sender = match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect)
+                .await
+                .unwrap_or_else(|_| {
+                    Err(Error::new(
+                        ErrorKind::TimedOut,
+                        format!("Connecting to {} timed out", path),
+                    ))
+                }),
+            None => connect.await,
+        };
+        sender.map_err(ArangoError::UnixError)
+    }
+}
+
+/// Returns the path of the socket of a `unix://` endpoint
+pub fn socket_path(url: &str) -> Option<&str> {
+    url.strip_prefix(UNIX_SCHEME)
+        .filter(|path| !path.is_empty())
+}
+
+/// Connects to a socket and runs the HTTP/1.1 connection in the background
+#[cfg(unix)]
+async fn connect(path: &str) -> Result<SendRequest<Body>, Error> {
+    let stream = tokio::net::UnixStream::connect(path).await?;
+    let (sender, connection) =
+        hyper::client::conn::http1::handshake(hyper_util::rt::TokioIo::new(stream))
+            .await
+            .map_err(io_error)?;
+    let path = path.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!("Connection to {} closed: {}", path, e);
+        }
+    });
+    Ok(sender)
+}
+
+#[cfg(not(unix))]
+async fn connect(path: &str) -> Result<SendRequest<Body>, Error> {
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        format!("Unix domain sockets are not supported here: {}", path),
+    ))
+}
+
+/// Turns a failure of the HTTP exchange into an I/O error, which is
+/// retried like a broken connection
+fn io_error(error: hyper::Error) -> Error {
+    Error::other(error)
+}
+
+/// Returns the path of the request with its query string, encoded like
+/// the HTTP client encodes it
+fn path_and_query(request: &ApiRequest) -> Result<String, ArangoError> {
+    let mut url = reqwest::Url::parse(&format!("http://localhost{}", request.path()))
+        .map_err(|e| ArangoError::UnixError(Error::new(ErrorKind::InvalidInput, e)))?;
+    if !request.query_pairs().is_empty() {
+        url.query_pairs_mut().extend_pairs(request.query_pairs());
+    }
+    Ok(match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arangodb::Connection;
+    use crate::config::Config;
+    use reqwest::Method;
+    use serde_json::{json, Value};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_socket_path_and_request_target() {
+        assert_eq!(
+            socket_path("unix:///tmp/arangod.sock"),
+            Some("/tmp/arangod.sock")
+        );
+        assert_eq!(socket_path("unix://"), None);
+        assert_eq!(socket_path("http://localhost:8529"), None);
+
+        let request = ApiRequest::in_database(Method::GET, "crud", "/_api/document/c/a b")
+            .query("waitForSync", true)
+            .query("q", "x&y");
+        assert_eq!(
+            path_and_query(&request).unwrap(),
+            "/_db/crud/_api/document/c/a%20b?waitForSync=true&q=x%26y"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_requests_over_a_unix_socket() {
+        let path = std::env::temp_dir().join(format!("adb_loader_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        // Echoes the request head, several requests per connection
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut data = Vec::new();
+                    let mut buffer = [0u8; 4096];
+                    while let Ok(n) = stream.read(&mut buffer).await {
+                        if n == 0 {
+                            return;
+                        }
+                        data.extend_from_slice(&buffer[..n]);
+                        let Some(end) = data.windows(4).position(|w| w == b"\r\n\r\n") else {
+                            continue;
+                        };
+                        let head = String::from_utf8_lossy(&data[..end]).into_owned();
+                        let length = head
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length: "))
+                            .map_or(0, |length| length.parse().unwrap());
+                        if data.len() < end + 4 + length {
+                            continue;
+                        }
+                        data.drain(..end + 4 + length);
+                        let body = json!({"head": head}).to_string();
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                             content-length: {}\r\n\r\n{}",
+                            body.len(),
+                            body
+                        );
+                        if stream.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        let mut config = Config::from_file("config.yaml").unwrap().database;
+        config.endpoints = vec![format!("unix://{}", path.display())];
+        let conn = Connection::new(&config).await.unwrap();
+        for i in 0..3 {
+            let response = conn
+                .send(
+                    ApiRequest::in_database(Method::POST, "crud", "/_api/document/c")
+                        .query("waitForSync", true)
+                        .json(&json!({"i": i})),
+                )
+                .await
+                .unwrap();
+            assert!(response.is_success());
+            let body: Value = response.json().unwrap();
+            let head = body["head"].as_str().unwrap();
+            assert!(head.starts_with("POST /_db/crud/_api/document/c?waitForSync=true HTTP/1.1"));
+            assert!(head.contains("authorization: Basic "));
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,814 @@
+use crate::arangodb::{error_class, shard_counts, ApiRequest, ApiResponse, Connection};
+use crate::banking::BankingUseCase;
+use crate::config::{Config, MismatchAction, Mode, Phase, SeedCheckConfig};
+use crate::conflicts::ConflictsUseCase;
+use crate::control::{self, UseCaseControl};
+use crate::coordination;
+use crate::crud::CrudUseCase;
+use crate::ddl::DdlUseCase;
+use crate::error_policy::SetupFailed;
+use crate::graph::GraphUseCase;
+use crate::metrics::{self, observe_request, operation_stats, OperationStats, METRICS};
+use crate::pacer::Pacer;
+use crate::phases::run_phases;
+use crate::pregel::PregelUseCase;
+use crate::queries::QueriesUseCase;
+use crate::random::stream_rng;
+use crate::runtime;
+use crate::search::SearchUseCase;
+use crate::smartjoin::SmartJoinUseCase;
+use crate::supervisor::supervise;
+use crate::timeseries::TimeseriesUseCase;
+use crate::traces::Span;
+use crate::transactions::TransactionsUseCase;
+use crate::ttl::TtlUseCase;
+use crate::unique::UniqueUseCase;
+use crate::users::UsersUseCase;
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{debug, error, info, warn};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::borrow::Cow;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+
+/// A workload which can be run by the loader. Each use case gets its own
+/// multi-threaded tokio runtime and goes through two phases: `setup`, which
+/// creates and seeds its data, and `run`, which produces the actual load
+/// until shutdown is requested.
+#[async_trait]
+pub trait UseCase: Send + Sync {
+    /// The name of the use case, used in logs and as the `usecase` metrics label
+    fn name(&self) -> &str;
+
+    /// The number of worker threads for the runtime of this use case
+    fn threads(&self) -> usize;
+
+    /// The time after which `run` is stopped, `None` means no limit
+    fn duration(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The time at the beginning of `run` which is not counted in the
+    /// statistics
+    fn warmup(&self) -> Option<Duration> {
+        None
+    }
+
+    /// The phases of the load, an empty list means a constant load
+    fn phases(&self) -> Vec<Phase> {
+        Vec::new()
+    }
+
+    /// Whether `setup` splits the work among the loaders of a coordinated
+    /// run itself (see `coordination`). Otherwise the first loader sets up
+    /// the use case before the others, which find the data in place.
+    fn coordinated(&self) -> bool {
+        false
+    }
+
+    /// Creates databases and collections and seeds the data. Seeding should
+    /// stop early when `shutdown` is cancelled.
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()>;
+
+    /// Produces load until `shutdown` is cancelled
+    async fn run(&self, shutdown: CancellationToken) -> Result<()>;
+
+    /// Cleans up after the use case has stopped, whether or not `setup`
+    /// and `run` completed
+    async fn teardown(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Checks the data created by `setup`, used in seed mode. Returns the
+    /// expected and actual document count of every seeded collection.
+    async fn verify_seed(&self) -> Result<Vec<CollectionCount>> {
+        Ok(Vec::new())
+    }
+
+    /// Returns the request statistics of this use case so far
+    fn metrics(&self) -> Vec<OperationStats> {
+        operation_stats()
+            .into_iter()
+            .filter(|stats| stats.usecase == self.name())
+            .collect()
+    }
+}
+
+/// The number of documents in a seeded collection, compared to the number
+/// the use case meant to create
+#[derive(Debug, Clone)]
+pub struct CollectionCount {
+    pub database: String,
+    pub collection: String,
+    pub expected: u64,
+    pub actual: u64,
+    /// The number of documents in every shard, empty if unknown
+    pub shards: Vec<u64>,
+}
+
+impl CollectionCount {
+    /// Counts the documents of a collection and of each of its shards
+    ///
+    /// # Errors
+    /// Fails if the collection cannot be counted
+    pub async fn fetch(
+        conn: &Connection,
+        database: &str,
+        collection: &str,
+        expected: u64,
+    ) -> Result<Self> {
+        let shards: Vec<u64> = shard_counts(conn, database, collection)
+            .await?
+            .into_iter()
+            .map(|(_, count)| count)
+            .collect();
+        Ok(CollectionCount {
+            database: database.to_string(),
+            collection: collection.to_string(),
+            expected,
+            actual: shards.iter().sum(),
+            shards,
+        })
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.actual == self.expected
+    }
+
+    /// The difference between the fullest and the emptiest shard in
+    /// percent of the average shard, `None` with fewer than two shards
+    pub fn shard_imbalance_pct(&self) -> Option<f64> {
+        let (Some(max), Some(min)) = (self.shards.iter().max(), self.shards.iter().min()) else {
+            return None;
+        };
+        if self.shards.len() < 2 || self.actual == 0 {
+            return None;
+        }
+        let average = self.actual as f64 / self.shards.len() as f64;
+        Some((max - min) as f64 / average * 100.0)
+    }
+}
+
+/// Creates a use case from the configuration, or returns `None` if the
+/// use case is not enabled in the configuration
+pub type Factory = Arc<dyn Fn(&Config) -> Option<Box<dyn UseCase>> + Send + Sync>;
+
+/// The set of known use cases. The loader starts all enabled use cases
+/// generically through this registry, downstream crates can register their
+/// own workloads in addition to the built-in ones.
+pub struct Registry {
+    factories: Vec<(String, Factory)>,
+}
+
+impl Registry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Registry {
+            factories: Vec::new(),
+        }
+    }
+
+    /// Creates a registry containing all use cases shipped with the loader
+    pub fn with_builtin() -> Self {
+        let mut registry = Registry::new();
+        registry.register("crud", |config| {
+            config.active_usecases.crud.on.then(|| {
+                Box::new(CrudUseCase::new(
+                    config.crud.clone(),
+                    config.database.clone(),
+                    config.active_usecases.crud.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("graph", |config| {
+            config.active_usecases.graph.on.then(|| {
+                Box::new(GraphUseCase::new(
+                    config.graph.clone(),
+                    config.database.clone(),
+                    config.active_usecases.graph.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("queries", |config| {
+            let queries = config.queries.as_ref()?;
+            config.active_usecases.queries.on.then(|| {
+                Box::new(QueriesUseCase::new(
+                    queries.clone(),
+                    config.database.clone(),
+                    config.active_usecases.queries.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("transactions", |config| {
+            let transactions = config.transactions.as_ref()?;
+            config.active_usecases.transactions.on.then(|| {
+                Box::new(TransactionsUseCase::new(
+                    transactions.clone(),
+                    config.database.clone(),
+                    config.active_usecases.transactions.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("search", |config| {
+            let search = config.search.as_ref()?;
+            config.active_usecases.search.on.then(|| {
+                Box::new(SearchUseCase::new(
+                    search.clone(),
+                    config.database.clone(),
+                    config.active_usecases.search.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("pregel", |config| {
+            let pregel = config.pregel.as_ref()?;
+            config.active_usecases.pregel.on.then(|| {
+                Box::new(PregelUseCase::new(
+                    pregel.clone(),
+                    config.database.clone(),
+                    config.active_usecases.pregel.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("conflicts", |config| {
+            let conflicts = config.conflicts.as_ref()?;
+            config.active_usecases.conflicts.on.then(|| {
+                Box::new(ConflictsUseCase::new(
+                    conflicts.clone(),
+                    config.database.clone(),
+                    config.active_usecases.conflicts.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("ttl", |config| {
+            let ttl = config.ttl.as_ref()?;
+            config.active_usecases.ttl.on.then(|| {
+                Box::new(TtlUseCase::new(
+                    ttl.clone(),
+                    config.database.clone(),
+                    config.active_usecases.ttl.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("timeseries", |config| {
+            let timeseries = config.timeseries.as_ref()?;
+            config.active_usecases.timeseries.on.then(|| {
+                Box::new(TimeseriesUseCase::new(
+                    timeseries.clone(),
+                    config.database.clone(),
+                    config.active_usecases.timeseries.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("banking", |config| {
+            let banking = config.banking.as_ref()?;
+            config.active_usecases.banking.on.then(|| {
+                Box::new(BankingUseCase::new(
+                    banking.clone(),
+                    config.database.clone(),
+                    config.active_usecases.banking.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("unique", |config| {
+            let unique = config.unique.as_ref()?;
+            config.active_usecases.unique.on.then(|| {
+                Box::new(UniqueUseCase::new(
+                    unique.clone(),
+                    config.database.clone(),
+                    config.active_usecases.unique.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("ddl", |config| {
+            let ddl = config.ddl.as_ref()?;
+            config.active_usecases.ddl.on.then(|| {
+                Box::new(DdlUseCase::new(
+                    ddl.clone(),
+                    config.database.clone(),
+                    config.active_usecases.ddl.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("users", |config| {
+            let users = config.users.as_ref()?;
+            config.active_usecases.users.on.then(|| {
+                Box::new(UsersUseCase::new(
+                    users.clone(),
+                    config.database.clone(),
+                    config.active_usecases.users.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry.register("smartjoin", |config| {
+            let smartjoin = config.smartjoin.as_ref()?;
+            config.active_usecases.smartjoin.on.then(|| {
+                Box::new(SmartJoinUseCase::new(
+                    smartjoin.clone(),
+                    config.database.clone(),
+                    config.active_usecases.smartjoin.clone(),
+                )) as Box<dyn UseCase>
+            })
+        });
+        registry
+    }
+
+    /// Registers a use case under the given name, replacing an existing
+    /// registration with the same name
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&Config) -> Option<Box<dyn UseCase>> + Send + Sync + 'static,
+    {
+        self.factories.retain(|(existing, _)| existing != name);
+        self.factories.push((name.to_string(), Arc::new(factory)));
+    }
+
+    /// Returns the names of all registered use cases
+    pub fn names(&self) -> Vec<&str> {
+        self.factories
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Creates the named use case if it is registered and enabled
+    pub fn create(&self, name: &str, config: &Config) -> Option<Box<dyn UseCase>> {
+        self.factories
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .and_then(|(name, factory)| factory(&usecase_config(name, config)))
+    }
+
+    /// Creates all use cases which are enabled in the configuration
+    pub fn enabled(&self, config: &Config) -> Vec<Box<dyn UseCase>> {
+        self.factories
+            .iter()
+            .filter_map(|(name, factory)| factory(&usecase_config(name, config)))
+            .collect()
+    }
+
+    /// Starts every enabled use case on its own thread, supervised
+    /// according to `config.supervision` (see `supervisor::supervise`).
+    /// Failures are logged and also returned through the join handles.
+    pub fn start_enabled(
+        &self,
+        config: &Config,
+        shutdown: &CancellationToken,
+    ) -> Vec<JoinHandle<Result<()>>> {
+        if let Err(e) = runtime::configure(config) {
+            error!(
+                "Building the shared runtime failed, using one per use case: {}",
+                e
+            );
+        }
+        self.factories
+            .iter()
+            .filter(|(_, factory)| factory(config).is_some())
+            .map(|(name, factory)| {
+                let name = name.clone();
+                let factory = Arc::clone(factory);
+                let config = usecase_config(&name, config).into_owned();
+                let shutdown = shutdown.clone();
+                thread::spawn(move || {
+                    supervise(
+                        &name,
+                        || factory(&config),
+                        config.mode,
+                        &config.supervision,
+                        &config.seed_check,
+                        shutdown,
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Returns the configuration a use case is created with: with
+/// `active_usecases.<name>.one_shard`, its databases are OneShard databases
+fn usecase_config<'a>(name: &str, config: &'a Config) -> Cow<'a, Config> {
+    let one_shard = config
+        .active_usecases
+        .all()
+        .iter()
+        .any(|(usecase, usecase_config)| *usecase == name && usecase_config.one_shard);
+    if !one_shard {
+        return Cow::Borrowed(config);
+    }
+    let mut config = config.clone();
+    config.database.one_shard = true;
+    Cow::Owned(config)
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+/// Runs a use case on the calling thread: gets its runtime, performs
+/// the setup and then runs the load until `shutdown` is cancelled. In seed
+/// mode, the seeded data is verified after the setup instead, and the
+/// teardown is skipped, so that the data is kept.
+pub fn run_blocking(
+    mut usecase: Box<dyn UseCase>,
+    shutdown: CancellationToken,
+    mode: Mode,
+    seed_check: &SeedCheckConfig,
+) -> Result<()> {
+    let runtime = runtime::for_usecase(usecase.name(), usecase.threads())?;
+    // Tasks left behind on a shared runtime stop with the use case
+    let shutdown = shutdown.child_token();
+    let _stop = shutdown.clone().drop_guard();
+
+    runtime.block_on(async move {
+        let coordinator = coordination::current();
+        let usecase_name = usecase.name().to_string();
+        let barrier = |name: &str| format!("{}.{}", usecase_name, name);
+        let set_up_first = !usecase.coordinated();
+        if let Some(coordinator) = coordinator.filter(|c| set_up_first && !c.slot().is_first()) {
+            coordinator.barrier(&barrier("setup"), &shutdown).await?;
+        }
+        info!("Setting up use case {}", usecase.name());
+        let mut result = usecase.setup(&shutdown).await;
+        // A partial load would otherwise only show in the results
+        if result.is_ok() && mode == Mode::Load && !shutdown.is_cancelled() {
+            result = verify_seed(usecase.as_ref(), mode, seed_check).await;
+        }
+        if let Some(coordinator) = coordinator {
+            if result.is_ok() && set_up_first && coordinator.slot().is_first() {
+                result = coordinator.barrier(&barrier("setup"), &shutdown).await;
+            }
+            // The load starts everywhere at the same time, so that the
+            // phases of the loaders line up
+            if result.is_ok() && mode == Mode::Load {
+                result = coordinator.barrier(&barrier("run"), &shutdown).await;
+            }
+        }
+        let result = match result {
+            Ok(()) if shutdown.is_cancelled() => {
+                info!("Use case {} interrupted during setup", usecase.name());
+                match mode {
+                    Mode::Load => Ok(()),
+                    Mode::Seed => Err(anyhow::anyhow!("Seeding was interrupted")),
+                }
+            }
+            Ok(()) if mode == Mode::Seed => verify_seed(usecase.as_ref(), mode, seed_check).await,
+            Ok(()) => {
+                info!("Running use case {}", usecase.name());
+                // The use case may stop on its own, without stopping others
+                let run_shutdown = shutdown.child_token();
+                if let Some(duration) = usecase.duration() {
+                    let timer = run_shutdown.clone();
+                    let name = usecase.name().to_string();
+                    tokio::spawn(async move {
+                        tokio::select! {
+                            _ = tokio::time::sleep(duration) => {
+                                info!("Use case {} reached its duration of {:?}", name, duration);
+                                timer.cancel();
+                            }
+                            _ = timer.cancelled() => {}
+                        }
+                    });
+                }
+                if let Some(warmup) = usecase.warmup() {
+                    let name = usecase.name().to_string();
+                    let timer = run_shutdown.clone();
+                    metrics::set_warming_up(&name, true);
+                    tokio::spawn(async move {
+                        tokio::select! {
+                            _ = tokio::time::sleep(warmup) => {
+                                info!("Use case {} finished its warm-up of {:?}", name, warmup);
+                            }
+                            _ = timer.cancelled() => {}
+                        }
+                        metrics::set_warming_up(&name, false);
+                    });
+                }
+                let phases = usecase.phases();
+                if !phases.is_empty() {
+                    tokio::spawn(run_phases(
+                        usecase.name().to_string(),
+                        phases,
+                        run_shutdown.clone(),
+                    ));
+                }
+                usecase.run(run_shutdown).await
+            }
+            Err(e) => Err(e.context(SetupFailed(usecase.name().to_string()))),
+        };
+        if mode == Mode::Load {
+            // Only the first loader drops the data on exit, once all are done
+            if let Some(coordinator) = coordinator {
+                if let Err(e) = coordinator.barrier(&barrier("finished"), &shutdown).await {
+                    error!(
+                        "Use case {} did not finish everywhere: {:#}",
+                        usecase.name(),
+                        e
+                    );
+                }
+            }
+            if let Err(e) = usecase.teardown().await {
+                error!("Teardown of use case {} failed: {}", usecase.name(), e);
+            }
+        }
+        result
+    })
+}
+
+/// Verifies the seeded data of a use case and logs a summary. Collections
+/// with fewer or more documents than expected fail the seed mode, in load
+/// mode they and unbalanced shards only fail the use case with
+/// `on_mismatch: fail`.
+async fn verify_seed(
+    usecase: &dyn UseCase,
+    mode: Mode,
+    seed_check: &SeedCheckConfig,
+) -> Result<()> {
+    let counts = usecase.verify_seed().await?;
+    let mut incomplete = 0;
+    let mut problems = Vec::new();
+    for count in &counts {
+        let imbalance = count
+            .shard_imbalance_pct()
+            .filter(|pct| *pct > seed_check.max_shard_imbalance_pct);
+        if !count.is_complete() {
+            incomplete += 1;
+            problems.push(format!(
+                "Seeded {}/{}: {} documents, expected {}",
+                count.database, count.collection, count.actual, count.expected
+            ));
+        } else if let Some(imbalance) = imbalance {
+            problems.push(format!(
+                "Seeded {}/{}: the shards differ by {:.0}% of the average shard, \
+                 documents per shard: {:?}",
+                count.database, count.collection, imbalance, count.shards
+            ));
+        } else {
+            info!(
+                "Seeded {}/{}: {} documents in {} shards",
+                count.database,
+                count.collection,
+                count.actual,
+                count.shards.len()
+            );
+        }
+    }
+    if problems.is_empty() {
+        info!("Seeding of use case {} complete", usecase.name());
+        return Ok(());
+    }
+    let fail =
+        seed_check.on_mismatch == MismatchAction::Fail || (mode == Mode::Seed && incomplete > 0);
+    for problem in &problems {
+        if fail {
+            error!("{}", problem);
+        } else {
+            warn!("{}", problem);
+        }
+    }
+    if !fail {
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "{} of {} collections of use case {} are incomplete or unbalanced",
+        problems.len(),
+        counts.len(),
+        usecase.name()
+    ))
+}
+
+/// A failed operation of `send_observed`. Besides the message, it keeps
+/// what the structured log needs to tell failures apart.
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct OperationFailure {
+    pub operation: String,
+    /// The error class, see `arangodb::error_class`
+    pub class: &'static str,
+    pub latency: Duration,
+    message: String,
+}
+
+/// Sends a request, records its latency under the given use case and
+/// operation name and turns unsuccessful responses into errors. Status
+/// codes in `accepted` are not considered failures, even if they are not
+/// in the 2xx range. Requests the server rejected because of their queue
+/// time are only counted in `queue_time_rejections`.
+///
+/// # Errors
+/// Fails with an `OperationFailure` if the request fails or is not successful
+pub async fn send_observed(
+    conn: &Connection,
+    usecase: &str,
+    operation: &str,
+    request: ApiRequest,
+    accepted: &[u16],
+) -> Result<ApiResponse> {
+    let span = Span::root(&format!("{} {}", usecase, operation));
+    let request = match &span {
+        Some(span) => request.traced(span.context()),
+        None => request,
+    };
+    let start = Instant::now();
+    let response = conn.send(request).await;
+    let latency = start.elapsed();
+    let class = error_class(&response, accepted);
+    if let Some(mut span) = span {
+        span.set("usecase", usecase);
+        span.set("operation", operation);
+        if let Ok(response) = &response {
+            span.set("http.status_code", i64::from(response.status.as_u16()));
+        }
+        if let Some(class) = class {
+            span.set("error.class", class);
+            span.set_error();
+        }
+        span.end();
+    }
+    let failure = |class, message| OperationFailure {
+        operation: operation.to_string(),
+        class,
+        latency,
+        message,
+    };
+    // Requests shed by the server because of their queue time are counted
+    // separately and not as errors
+    if class == Some("queue_time") {
+        METRICS
+            .queue_time_rejections
+            .with_label_values(&[usecase, operation])
+            .inc();
+        return Err(failure(
+            "queue_time",
+            format!("Operation {} rejected, queue time exceeded", operation),
+        )
+        .into());
+    }
+    observe_request(usecase, operation, latency, class.is_none());
+    if let Some(class) = class {
+        METRICS
+            .error_classes
+            .with_label_values(&[usecase, operation, class])
+            .inc();
+    }
+    let response = response.map_err(|e| failure(e.class(), e.to_string()))?;
+
+    if let Some(class) = class {
+        return Err(failure(
+            class,
+            format!(
+                "Operation {} failed: {} - {}",
+                operation,
+                response.status,
+                response.text()
+            ),
+        )
+        .into());
+    }
+    Ok(response)
+}
+
+/// Runs `concurrency` workers on the current runtime, each of which
+/// repeatedly performs `operation` at the pace given by `pacer`, until
+/// `shutdown` is cancelled. Failed operations are logged and do not stop
+/// the worker. Every operation gets its own random generator, derived from
+/// a per worker stream, so that the operations of a worker are reproducible
+/// with a global seed. If `max_operations` is given, `shutdown` is
+/// cancelled once that many operations were started. While the workers run,
+/// they can be paused and scaled through the control API (see `control`).
+/// Returns when all workers have finished their last operation.
+pub async fn run_workers<F, Fut>(
+    usecase: &str,
+    concurrency: u32,
+    max_operations: Option<u64>,
+    pacer: Arc<Pacer>,
+    shutdown: &CancellationToken,
+    operation: F,
+) where
+    F: Fn(StdRng) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    info!(
+        "Starting {} with {} concurrent workers",
+        usecase, concurrency
+    );
+    let started = Arc::new(AtomicU64::new(0));
+    let control = Arc::new(UseCaseControl::new(concurrency, pacer));
+    control::register(usecase, Arc::clone(&control));
+
+    let spawn_worker = |worker: usize, generation: u64| {
+        // A worker started again after the concurrency was lowered draws
+        // from a stream of its own instead of replaying its operations
+        let stream = match generation {
+            0 => format!("{}/worker", usecase),
+            _ => format!("{}/worker/{}", usecase, generation),
+        };
+        let mut worker_rng = stream_rng(&stream, worker as u64);
+        let started = Arc::clone(&started);
+        let control = Arc::clone(&control);
+        let shutdown = shutdown.clone();
+        let operation = operation.clone();
+        let usecase = usecase.to_string();
+        tokio::spawn(async move {
+            let mut state = control.subscribe();
+            while !shutdown.is_cancelled() {
+                // Workers beyond the current concurrency stop, paused
+                // workers wait for a change of the state
+                let current = *state.borrow_and_update();
+                if worker >= current.concurrency as usize {
+                    break;
+                }
+                if current.paused {
+                    tokio::select! {
+                        _ = state.changed() => {}
+                        _ = shutdown.cancelled() => break,
+                    }
+                    continue;
+                }
+                let slot = tokio::select! {
+                    slot = control.pacer().wait() => slot,
+                    _ = shutdown.cancelled() => break,
+                };
+                if let Some(max) = max_operations {
+                    if started.fetch_add(1, Ordering::Relaxed) >= max {
+                        if !shutdown.is_cancelled() {
+                            info!("{} reached {} operations", usecase, max);
+                            shutdown.cancel();
+                        }
+                        break;
+                    }
+                }
+                let rng = StdRng::from_rng(&mut worker_rng);
+                // With a target rate, latencies are also measured from the
+                // intended start of the operation, which is in the past if
+                // all workers were busy
+                let result = if control.rate() > 0.0 {
+                    metrics::with_intended_start(slot.into_std(), operation(rng)).await
+                } else {
+                    operation(rng).await
+                };
+                if result.is_ok() {
+                    control.record_completed();
+                }
+                if let Err(e) = result {
+                    match e.downcast_ref::<OperationFailure>() {
+                        // Requests shed by the server are no errors
+                        Some(failure) if failure.class == "queue_time" => debug!(
+                            usecase = usecase.as_str(),
+                            operation = failure.operation.as_str();
+                            "{} operation rejected: {}", usecase, e
+                        ),
+                        Some(failure) => error!(
+                            usecase = usecase.as_str(),
+                            operation = failure.operation.as_str(),
+                            error_class = failure.class,
+                            latency_ms = failure.latency.as_secs_f64() * 1000.0;
+                            "{} operation failed: {}", usecase, e
+                        ),
+                        None => {
+                            error!(usecase = usecase.as_str(); "{} operation failed: {}", usecase, e)
+                        }
+                    }
+                }
+            }
+        })
+    };
+
+    // Start missing workers whenever the concurrency is raised, workers
+    // which stopped after it was lowered are started again
+    let mut state = control.subscribe();
+    let mut handles: Vec<tokio::task::JoinHandle<()>> = Vec::new();
+    let mut generations: Vec<u64> = Vec::new();
+    loop {
+        let target = state.borrow_and_update().concurrency as usize;
+        for worker in 0..target {
+            match handles.get(worker) {
+                Some(handle) if !handle.is_finished() => {}
+                Some(_) => {
+                    generations[worker] += 1;
+                    handles[worker] = spawn_worker(worker, generations[worker]);
+                }
+                None => {
+                    generations.push(0);
+                    handles.push(spawn_worker(worker, 0));
+                }
+            }
+        }
+        tokio::select! {
+            _ = state.changed() => {}
+            _ = shutdown.cancelled() => break,
+        }
+    }
+
+    // Wait for all workers, they finish their current operation after shutdown
+    futures::future::join_all(handles).await;
+    control::unregister(usecase);
+    info!("Workers of {} stopped", usecase);
+}
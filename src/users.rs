@@ -0,0 +1,322 @@
+use crate::arangodb::{
+    collection_exists, create_collection, create_database, database_exists, drop_database,
+    ApiRequest, Connection,
+};
+use crate::config::{CollectionProperties, DatabaseConfig, Phase, UseCaseConfig, UsersConfig};
+use crate::control;
+use crate::pacer::Pacer;
+use crate::random::stream_rng;
+use crate::usecase::{run_workers, send_observed, UseCase};
+use anyhow::Result;
+use async_trait::async_trait;
+use log::info;
+use rand::rngs::StdRng;
+use rand::Rng;
+use reqwest::Method;
+use serde_json::json;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// The use case name used to label metrics
+const USECASE: &str = "users";
+
+/// The permissions a user gets on a collection
+const GRANTS: [&str; 3] = ["rw", "ro", "none"];
+
+/// The users use case: manages a pool of users with per-collection
+/// permissions through `/_api/user` and performs document operations as
+/// these users. Dropping and recreating users and changing permissions
+/// invalidates the authentication caches of the coordinators.
+pub struct UsersUseCase {
+    users_config: UsersConfig,
+    db_config: DatabaseConfig,
+    usecase_config: UseCaseConfig,
+    connection: Option<Connection>,
+}
+
+impl UsersUseCase {
+    pub fn new(
+        users_config: UsersConfig,
+        db_config: DatabaseConfig,
+        usecase_config: UseCaseConfig,
+    ) -> Self {
+        UsersUseCase {
+            users_config,
+            db_config,
+            usecase_config,
+            connection: None,
+        }
+    }
+
+    /// Returns the connection created during setup
+    fn connection(&self) -> Result<Connection> {
+        self.connection
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Users use case was not set up"))
+    }
+
+    fn log_configuration(&self) {
+        let users_config = &self.users_config;
+        info!("Starting users use case with configuration:");
+        info!("Database: {}", database_name(&self.db_config));
+        info!("Number of users: {}", users_config.number_of_users);
+        info!(
+            "Number of collections: {}",
+            users_config.number_of_collections
+        );
+        info!(
+            "User churn: {}%, permission changes: {}%",
+            users_config.churn_pct, users_config.grant_pct
+        );
+        info!("Concurrency: {}", users_config.concurrency);
+        if users_config.target_ops_per_sec > 0.0 {
+            info!("Target rate: {} ops/s", users_config.target_ops_per_sec);
+        } else {
+            info!("Target rate: unlimited");
+        }
+        info!("Number of threads: {}", self.usecase_config.threads);
+    }
+}
+
+/// Returns the name of the database used by the users use case
+fn database_name(db_config: &DatabaseConfig) -> String {
+    db_config.database_name("users")
+}
+
+/// Returns the name of the `index`-th user, users are global, so their
+/// names carry the prefix as well
+fn user_name(db_config: &DatabaseConfig, index: u32) -> String {
+    format!("{}user_{}", db_config.prefix, index)
+}
+
+/// The password of the `index`-th user
+fn user_password(index: u32) -> String {
+    format!("adb_loader_{}", index)
+}
+
+/// Creates a user with read/write access to the database and a random
+/// permission on every collection. A user which exists already keeps its
+/// permissions.
+async fn create_user<R: Rng>(
+    conn: &Connection,
+    users_config: &UsersConfig,
+    index: u32,
+    rng: &mut R,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+    let user = user_name(conn.config(), index);
+    let request = ApiRequest::new(Method::POST, "/_api/user")
+        .json(&json!({"user": user, "passwd": user_password(index)}));
+    let response = send_observed(conn, USECASE, "create_user", request, &[409]).await?;
+    if response.status.as_u16() == 409 {
+        return Ok(());
+    }
+
+    let request = ApiRequest::new(
+        Method::PUT,
+        format!("/_api/user/{}/database/{}", user, db_name),
+    )
+    .json(&json!({"grant": "rw"}));
+    send_observed(conn, USECASE, "grant", request, &[]).await?;
+    for collection in 1..=users_config.number_of_collections {
+        grant_collection(conn, &user, &db_name, collection, rng).await?;
+    }
+    Ok(())
+}
+
+/// Gives a user a random permission on a collection. The user may have
+/// been dropped by a concurrent churn, which is not an error.
+async fn grant_collection<R: Rng>(
+    conn: &Connection,
+    user: &str,
+    db_name: &str,
+    collection: u32,
+    rng: &mut R,
+) -> Result<()> {
+    let grant = GRANTS[rng.random_range(0..GRANTS.len())];
+    let request = ApiRequest::new(
+        Method::PUT,
+        format!("/_api/user/{}/database/{}/c{}", user, db_name, collection),
+    )
+    .json(&json!({"grant": grant}));
+    send_observed(conn, USECASE, "grant", request, &[404]).await?;
+    Ok(())
+}
+
+/// Creates the database, the collections and the users if needed
+async fn initialize(conn: &Connection, users_config: &UsersConfig) -> Result<()> {
+    let db_name = database_name(conn.config());
+    if !database_exists(conn, &db_name).await? {
+        info!("Creating database {}", db_name);
+        create_database(conn, &db_name).await?;
+    }
+    for collection in 1..=users_config.number_of_collections {
+        let coll_name = format!("c{}", collection);
+        if !collection_exists(conn, &db_name, &coll_name).await? {
+            create_collection(
+                conn,
+                &db_name,
+                &coll_name,
+                users_config.number_of_shards,
+                users_config.replication_factor,
+                &CollectionProperties::default(),
+            )
+            .await?;
+        }
+    }
+
+    let mut rng = stream_rng(&format!("{}/setup", USECASE), 0);
+    for index in 1..=users_config.number_of_users {
+        create_user(conn, users_config, index, &mut rng).await?;
+    }
+    info!(
+        "Created {} users with access to {}",
+        users_config.number_of_users, db_name
+    );
+    Ok(())
+}
+
+/// Counts the document operations which were denied
+#[derive(Default)]
+struct AccessStats {
+    allowed: AtomicU64,
+    denied: AtomicU64,
+}
+
+/// Performs a random operation: drops and recreates a user, changes a
+/// permission, or reads or inserts a document as a random user. Document
+/// operations may be denied, depending on the current permission and on
+/// how fast the coordinators learn about changes.
+async fn perform_operation(
+    conn: &Connection,
+    users_config: &UsersConfig,
+    stats: &AccessStats,
+    mut rng: StdRng,
+) -> Result<()> {
+    let db_name = database_name(conn.config());
+    let index = rng.random_range(1..=users_config.number_of_users.max(1));
+    let user = user_name(conn.config(), index);
+    let collection = rng.random_range(1..=users_config.number_of_collections.max(1));
+    let pick = rng.random_range(0..100);
+
+    if pick < users_config.churn_pct {
+        let request = ApiRequest::new(Method::DELETE, format!("/_api/user/{}", user));
+        send_observed(conn, USECASE, "drop_user", request, &[404]).await?;
+        create_user(conn, users_config, index, &mut rng).await
+    } else if pick < users_config.churn_pct + users_config.grant_pct {
+        grant_collection(conn, &user, &db_name, collection, &mut rng).await
+    } else {
+        let path = format!("/_api/document/c{}", collection);
+        let (operation, request) = if rng.random_bool(0.5) {
+            let key = rng.random_range(1..=1000);
+            let path = format!("{}/K{}", path, key);
+            (
+                "read",
+                ApiRequest::in_database(Method::GET, &db_name, &path),
+            )
+        } else {
+            let request = ApiRequest::in_database(Method::POST, &db_name, &path)
+                .query("silent", true)
+                .json(&json!({"user": user}));
+            ("insert", request)
+        };
+        // Missing documents and missing permissions are expected, a user
+        // which is just being recreated may not authenticate
+        let request = request.basic_auth(&user, &user_password(index));
+        let response = send_observed(conn, USECASE, operation, request, &[401, 403, 404]).await?;
+        if matches!(response.status.as_u16(), 401 | 403) {
+            stats.denied.fetch_add(1, Ordering::Relaxed);
+        } else {
+            stats.allowed.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UseCase for UsersUseCase {
+    fn name(&self) -> &str {
+        USECASE
+    }
+
+    fn threads(&self) -> usize {
+        self.usecase_config.threads as usize
+    }
+
+    fn duration(&self) -> Option<Duration> {
+        self.usecase_config.duration_secs.map(Duration::from_secs)
+    }
+
+    fn warmup(&self) -> Option<Duration> {
+        self.usecase_config.warmup_secs.map(Duration::from_secs)
+    }
+
+    fn phases(&self) -> Vec<Phase> {
+        self.usecase_config.phases.clone()
+    }
+
+    async fn setup(&mut self, shutdown: &CancellationToken) -> Result<()> {
+        self.log_configuration();
+
+        let connection = Connection::new(&self.db_config).await?;
+        connection.spawn_health_checks(shutdown.clone());
+        connection.warm_up().await;
+        initialize(&connection, &self.users_config).await?;
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn run(&self, shutdown: CancellationToken) -> Result<()> {
+        let connection = self.connection()?;
+        let stats = Arc::new(AccessStats::default());
+        let pacer = Arc::new(Pacer::new(self.users_config.target_ops_per_sec));
+        let users_config = Arc::new(RwLock::new(Arc::new(self.users_config.clone())));
+        let reloaded = Arc::clone(&users_config);
+        control::on_reload(USECASE, move |config| {
+            if let Some(users) = &config.users {
+                control::apply_load(USECASE, users.concurrency, users.target_ops_per_sec);
+                *reloaded.write().unwrap() = Arc::new(users.clone());
+            }
+        });
+        let worker_stats = Arc::clone(&stats);
+        run_workers(
+            USECASE,
+            self.users_config.concurrency,
+            self.usecase_config.max_operations,
+            pacer,
+            &shutdown,
+            move |rng| {
+                let connection = connection.clone();
+                let users_config = Arc::clone(&users_config.read().unwrap());
+                let stats = Arc::clone(&worker_stats);
+                async move { perform_operation(&connection, &users_config, &stats, rng).await }
+            },
+        )
+        .await;
+        control::remove_reloader(USECASE);
+        info!(
+            "Users: {} document operations allowed, {} denied",
+            stats.allowed.load(Ordering::Relaxed),
+            stats.denied.load(Ordering::Relaxed)
+        );
+        Ok(())
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        if self.users_config.drop_on_exit {
+            let connection = self.connection()?;
+            for index in 1..=self.users_config.number_of_users {
+                let user = user_name(&self.db_config, index);
+                let request = ApiRequest::new(Method::DELETE, format!("/_api/user/{}", user));
+                send_observed(&connection, USECASE, "drop_user", request, &[404]).await?;
+            }
+            let db_name = database_name(&self.db_config);
+            info!("Dropping database {}", db_name);
+            drop_database(&connection, &db_name).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,460 @@
+//! A minimal VelocyPack codec, converting between `serde_json` values and
+//! the binary format used by ArangoDB's VelocyStream protocol.
+//!
+//! Encoding produces compact arrays and objects, which every VelocyPack
+//! reader understands. Decoding covers all types the server sends in
+//! responses, types without a JSON counterpart become `null`.
+
+use serde_json::{Map, Number, Value};
+use std::fmt;
+
+/// An error while decoding VelocyPack data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError(String);
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid VelocyPack: {}", self.0)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+fn invalid(what: &str) -> DecodeError {
+    DecodeError(what.to_string())
+}
+
+/// Encodes a JSON value as VelocyPack
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    encode_into(value, &mut out);
+    out
+}
+
+fn encode_into(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => out.push(0x18),
+        Value::Bool(false) => out.push(0x19),
+        Value::Bool(true) => out.push(0x1a),
+        Value::Number(number) => encode_number(number, out),
+        Value::String(s) => encode_string(s, out),
+        Value::Array(items) if items.is_empty() => out.push(0x01),
+        Value::Array(items) => {
+            let mut content = Vec::new();
+            for item in items {
+                encode_into(item, &mut content);
+            }
+            encode_compact(0x13, &content, items.len(), out);
+        }
+        Value::Object(attributes) if attributes.is_empty() => out.push(0x0a),
+        Value::Object(attributes) => {
+            let mut content = Vec::new();
+            for (key, value) in attributes {
+                encode_string(key, &mut content);
+                encode_into(value, &mut content);
+            }
+            encode_compact(0x14, &content, attributes.len(), out);
+        }
+    }
+}
+
+/// Builds a compact array or object item by item, e.g. a generated
+/// document without a JSON value in between
+#[derive(Debug, Default)]
+pub struct Builder {
+    content: Vec<u8>,
+    count: usize,
+}
+
+impl Builder {
+    /// Adds an item of an array
+    pub fn add_item(&mut self, value: &Value) {
+        encode_into(value, &mut self.content);
+        self.count += 1;
+    }
+
+    /// Adds an item of an array which is encoded already
+    pub fn add_encoded_item(&mut self, item: &[u8]) {
+        self.content.extend_from_slice(item);
+        self.count += 1;
+    }
+
+    /// Adds an attribute of an object
+    pub fn add_attribute(&mut self, key: &str, value: &Value) {
+        encode_string(key, &mut self.content);
+        encode_into(value, &mut self.content);
+        self.count += 1;
+    }
+
+    /// Adds a string attribute of an object
+    pub fn add_str_attribute(&mut self, key: &str, value: &str) {
+        encode_string(key, &mut self.content);
+        encode_string(value, &mut self.content);
+        self.count += 1;
+    }
+
+    /// Returns the items added so far as array
+    pub fn finish_array(self) -> Vec<u8> {
+        self.finish(0x01, 0x13)
+    }
+
+    /// Returns the attributes added so far as object
+    pub fn finish_object(self) -> Vec<u8> {
+        self.finish(0x0a, 0x14)
+    }
+
+    fn finish(self, empty: u8, head: u8) -> Vec<u8> {
+        if self.count == 0 {
+            return vec![empty];
+        }
+        let mut out = Vec::with_capacity(self.content.len() + 20);
+        encode_compact(head, &self.content, self.count, &mut out);
+        out
+    }
+}
+
+fn encode_number(number: &Number, out: &mut Vec<u8>) {
+    if let Some(i) = number.as_i64() {
+        match i {
+            0..=9 => out.push(0x30 + i as u8),
+            -6..=-1 => out.push((0x40 + i) as u8),
+            _ => {
+                let bytes = i.to_le_bytes();
+                // The smallest width which still sign-extends to the value
+                let width = (1..=8)
+                    .find(|&w| {
+                        let shift = 64 - 8 * w;
+                        (i << shift) >> shift == i
+                    })
+                    .unwrap_or(8);
+                out.push(0x1f + width as u8);
+                out.extend_from_slice(&bytes[..width]);
+            }
+        }
+    } else if let Some(u) = number.as_u64() {
+        out.push(0x2f);
+        out.extend_from_slice(&u.to_le_bytes());
+    } else {
+        out.push(0x1b);
+        out.extend_from_slice(&number.as_f64().unwrap_or(0.0).to_le_bytes());
+    }
+}
+
+fn encode_string(s: &str, out: &mut Vec<u8>) {
+    if s.len() <= 126 {
+        out.push(0x40 + s.len() as u8);
+    } else {
+        out.push(0xbf);
+        out.extend_from_slice(&(s.len() as u64).to_le_bytes());
+    }
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Writes a compact array or object: the head byte, the total byte length
+/// as variable-length integer, the items and the number of items as
+/// variable-length integer stored backwards
+fn encode_compact(head: u8, content: &[u8], count: usize, out: &mut Vec<u8>) {
+    let count_len = varint_len(count as u64);
+    let mut total = 1 + content.len() + count_len;
+    // The byte length includes its own encoding
+    let mut length_len = varint_len(total as u64);
+    while varint_len((total + length_len) as u64) != length_len {
+        length_len += 1;
+    }
+    total += length_len;
+
+    out.push(head);
+    write_varint(total as u64, out);
+    out.extend_from_slice(content);
+    let start = out.len();
+    write_varint(count as u64, out);
+    out[start..].reverse();
+}
+
+fn varint_len(mut value: u64) -> usize {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a variable-length integer, forwards from the start of `data` or
+/// backwards from its end. Returns the value and the number of bytes read.
+fn read_varint(data: &[u8], backwards: bool) -> Result<(u64, usize), DecodeError> {
+    let mut value = 0u64;
+    for i in 0..data.len().min(10) {
+        let byte = if backwards {
+            data[data.len() - 1 - i]
+        } else {
+            data[i]
+        };
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(invalid("truncated variable-length integer"))
+}
+
+fn read_uint(data: &[u8], width: usize) -> Result<u64, DecodeError> {
+    let bytes = data
+        .get(..width)
+        .ok_or_else(|| invalid("truncated integer"))?;
+    Ok(bytes
+        .iter()
+        .rev()
+        .fold(0u64, |value, &byte| (value << 8) | u64::from(byte)))
+}
+
+fn read_int(data: &[u8], width: usize) -> Result<i64, DecodeError> {
+    let shift = 64 - 8 * width as u32;
+    Ok(((read_uint(data, width)? << shift) as i64) >> shift)
+}
+
+/// Returns the width of the length fields of arrays and objects with the
+/// given head byte
+fn length_width(head: u8) -> usize {
+    match head {
+        0x02 | 0x06 | 0x0b | 0x0f => 1,
+        0x03 | 0x07 | 0x0c | 0x10 => 2,
+        0x04 | 0x08 | 0x0d | 0x11 => 4,
+        _ => 8,
+    }
+}
+
+/// Returns the number of bytes of the value at the start of `data`
+pub fn byte_size(data: &[u8]) -> Result<usize, DecodeError> {
+    let head = *data.first().ok_or_else(|| invalid("empty value"))?;
+    let size = match head {
+        0x00 | 0x01 | 0x0a | 0x17..=0x1a | 0x1e | 0x1f | 0x30..=0x3f => 1,
+        0x02..=0x09 | 0x0b..=0x12 => read_uint(&data[1..], length_width(head))? as usize,
+        0x13 | 0x14 => read_varint(&data[1..], false)?.0 as usize,
+        0x1b..=0x1d => 9,
+        0x20..=0x27 => 1 + (head - 0x1f) as usize,
+        0x28..=0x2f => 1 + (head - 0x27) as usize,
+        0x40..=0xbe => 1 + (head - 0x40) as usize,
+        0xbf => 9 + read_uint(&data[1..], 8)? as usize,
+        0xc0..=0xc7 => {
+            let width = (head - 0xbf) as usize;
+            1 + width + read_uint(&data[1..], width)? as usize
+        }
+        0xc8..=0xd7 => {
+            let width = ((head - 0xc8) % 8 + 1) as usize;
+            1 + width + 4 + read_uint(&data[1..], width)? as usize
+        }
+        0xf0 => 2,
+        0xf1 => 3,
+        0xf2 => 5,
+        0xf3 => 9,
+        0xf4..=0xff => {
+            let width = [1, 2, 4, 8][((head - 0xf4) / 3) as usize];
+            1 + width + read_uint(&data[1..], width)? as usize
+        }
+        _ => return Err(invalid(&format!("unknown type 0x{:02x}", head))),
+    };
+    if size > data.len() {
+        return Err(invalid("truncated value"));
+    }
+    Ok(size)
+}
+
+/// Decodes the VelocyPack value at the start of `data`
+pub fn decode(data: &[u8]) -> Result<Value, DecodeError> {
+    let size = byte_size(data)?;
+    let data = &data[..size];
+    let head = data[0];
+    Ok(match head {
+        0x01 => Value::Array(Vec::new()),
+        0x02..=0x09 | 0x13 => Value::Array(
+            array_items(data)?
+                .into_iter()
+                .map(decode)
+                .collect::<Result<_, _>>()?,
+        ),
+        0x0a => Value::Object(Map::new()),
+        0x0b..=0x12 | 0x14 => {
+            let mut attributes = Map::new();
+            for item in object_items(data)? {
+                let key = decode_key(item)?;
+                let value = decode(&item[byte_size(item)?..])?;
+                attributes.insert(key, value);
+            }
+            Value::Object(attributes)
+        }
+        0x19 => Value::Bool(false),
+        0x1a => Value::Bool(true),
+        0x1b => {
+            let bits = read_uint(&data[1..], 8)?;
+            Number::from_f64(f64::from_bits(bits)).map_or(Value::Null, Value::Number)
+        }
+        0x1c => Value::from(read_int(&data[1..], 8)?),
+        0x20..=0x27 => Value::from(read_int(&data[1..], (head - 0x1f) as usize)?),
+        0x28..=0x2f => Value::from(read_uint(&data[1..], (head - 0x27) as usize)?),
+        0x30..=0x39 => Value::from(head - 0x30),
+        0x3a..=0x3f => Value::from(i64::from(head) - 0x40),
+        0x40..=0xbf => Value::String(decode_string(data)?.to_string()),
+        _ => Value::Null,
+    })
+}
+
+fn decode_string(data: &[u8]) -> Result<&str, DecodeError> {
+    let bytes = match data[0] {
+        0x40..=0xbe => &data[1..],
+        0xbf => &data[9..],
+        _ => return Err(invalid("expected a string")),
+    };
+    std::str::from_utf8(bytes).map_err(|_| invalid("string is not UTF-8"))
+}
+
+/// Decodes an object key, which is either a string or a small integer
+/// standing for one of the system attributes
+fn decode_key(data: &[u8]) -> Result<String, DecodeError> {
+    let key = match data[0] {
+        0x31 => "_key",
+        0x32 => "_rev",
+        0x33 => "_id",
+        0x34 => "_from",
+        0x35 => "_to",
+        _ => decode_string(&data[..byte_size(data)?])?,
+    };
+    Ok(key.to_string())
+}
+
+/// Returns the slices of all items of an array
+fn array_items(data: &[u8]) -> Result<Vec<&[u8]>, DecodeError> {
+    let head = data[0];
+    match head {
+        0x02..=0x05 => {
+            // Equally sized items without index table, possibly after
+            // some zero bytes of padding
+            let width = length_width(head);
+            let mut start = 1 + width;
+            while start < data.len() && data[start] == 0 {
+                start += 1;
+            }
+            if start >= data.len() {
+                return Ok(Vec::new());
+            }
+            let item_size = byte_size(&data[start..])?;
+            Ok(data[start..].chunks(item_size).collect())
+        }
+        0x06..=0x09 => indexed_items(data),
+        _ => compact_items(data),
+    }
+}
+
+/// Returns the slices of all key value pairs of an object, each starting
+/// with the key
+fn object_items(data: &[u8]) -> Result<Vec<&[u8]>, DecodeError> {
+    match data[0] {
+        0x0b..=0x12 => indexed_items(data),
+        _ => compact_items(data),
+    }
+}
+
+/// Returns the items of an array or object with an index table
+fn indexed_items(data: &[u8]) -> Result<Vec<&[u8]>, DecodeError> {
+    let width = length_width(data[0]);
+    let (count, table_end) = if width == 8 {
+        (
+            read_uint(&data[data.len() - 8..], 8)? as usize,
+            data.len() - 8,
+        )
+    } else {
+        (read_uint(&data[1 + width..], width)? as usize, data.len())
+    };
+    let table_start = table_end
+        .checked_sub(count * width)
+        .ok_or_else(|| invalid("index table out of bounds"))?;
+    (0..count)
+        .map(|i| {
+            let offset = read_uint(&data[table_start + i * width..], width)? as usize;
+            data.get(offset..table_start)
+                .ok_or_else(|| invalid("index table entry out of bounds"))
+        })
+        .collect()
+}
+
+/// Returns the items of a compact array or object, or the key value pairs
+/// of a compact object
+fn compact_items(data: &[u8]) -> Result<Vec<&[u8]>, DecodeError> {
+    let (_, length_len) = read_varint(&data[1..], false)?;
+    let (count, count_len) = read_varint(data, true)?;
+    let end = data.len() - count_len;
+    let pair = data[0] == 0x14;
+    let mut items = Vec::with_capacity(count as usize);
+    let mut position = 1 + length_len;
+    for _ in 0..count {
+        let item = &data[position..end];
+        let mut size = byte_size(item)?;
+        if pair {
+            size += byte_size(&item[size..])?;
+        }
+        items.push(&item[..size]);
+        position += size;
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_roundtrip_and_server_formats() {
+        let value = json!({
+            "_key": "K1",
+            "small": 7,
+            "negative": -3,
+            "large": -123456789012i64,
+            "huge": u64::MAX,
+            "pi": 3.25,
+            "flags": [true, false, null],
+            "long": "x".repeat(200),
+            "nested": {"empty": {}, "list": []}
+        });
+        assert_eq!(decode(&encode(&value)).unwrap(), value);
+
+        // An array with index table as sent by the server: [1, "ab"]
+        let indexed = [0x06, 0x09, 0x02, 0x31, 0x42, b'a', b'b', 0x03, 0x04];
+        assert_eq!(decode(&indexed).unwrap(), json!([1, "ab"]));
+
+        // A sorted object with the translated key _key: {"_key": "a"}
+        let object = [0x0b, 0x07, 0x01, 0x31, 0x41, b'a', 0x03];
+        assert_eq!(decode(&object).unwrap(), json!({"_key": "a"}));
+    }
+
+    #[test]
+    fn test_builder() {
+        let mut document = Builder::default();
+        document.add_str_attribute("_key", "K1");
+        document.add_attribute("number", &json!(-42));
+        let document = document.finish_object();
+        assert_eq!(
+            decode(&document).unwrap(),
+            json!({"_key": "K1", "number": -42})
+        );
+
+        let mut batch = Builder::default();
+        batch.add_encoded_item(&document);
+        batch.add_item(&json!("x".repeat(300)));
+        let batch = batch.finish_array();
+        assert_eq!(byte_size(&batch).unwrap(), batch.len());
+        assert_eq!(
+            decode(&batch).unwrap(),
+            json!([{"_key": "K1", "number": -42}, "x".repeat(300)])
+        );
+        assert_eq!(Builder::default().finish_object(), encode(&json!({})));
+    }
+}
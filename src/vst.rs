@@ -0,0 +1,299 @@
+//! A client for ArangoDB's VelocyStream protocol (VST 1.1), used instead
+//! of HTTP with `database.protocol: vst`.
+//!
+//! Every message consists of a VelocyPack header, followed by the body,
+//! and is sent in chunks with a common message id. Each connection carries
+//! one request at a time, idle connections are kept per endpoint and
+//! reused, like the connection pool of the HTTP client.
+
+use crate::arangodb::{ApiRequest, ApiResponse};
+use crate::config::DatabaseConfig;
+use crate::velocypack;
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Method, StatusCode};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// The greeting which selects the protocol version on a new connection
+const PROTOCOL_HEADER: &[u8] = b"VST/1.1\r\n\r\n";
+
+/// The size of a chunk header: length, chunk number, message id and
+/// message length
+const CHUNK_HEADER_SIZE: usize = 24;
+
+/// The maximal size of a chunk, including its header
+const MAX_CHUNK_SIZE: usize = 30 * 1024;
+
+/// VST message types
+const MESSAGE_REQUEST: u64 = 1;
+const MESSAGE_AUTHENTICATION: u64 = 1000;
+
+fn invalid_data(what: impl ToString) -> Error {
+    Error::new(ErrorKind::InvalidData, what.to_string())
+}
+
+/// A VelocyStream client for all endpoints of a deployment
+#[derive(Debug)]
+pub struct VstClient {
+    username: String,
+    password: String,
+    connect_timeout: Option<Duration>,
+    max_idle_per_host: usize,
+    idle: Mutex<HashMap<String, Vec<TcpStream>>>,
+    next_message_id: AtomicU64,
+}
+
+impl VstClient {
+    /// Creates a client, connections are opened when they are needed.
+    /// Only plain `http://` (or `tcp://`) endpoints are supported.
+    pub fn new(config: &DatabaseConfig) -> Self {
+        VstClient {
+            username: config.username.clone(),
+            password: config.password.clone(),
+            connect_timeout: config.connect_timeout_ms.map(Duration::from_millis),
+            max_idle_per_host: config.pool_max_idle_per_host.unwrap_or(usize::MAX),
+            idle: Mutex::new(HashMap::new()),
+            next_message_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Sends a request to the endpoint with the given URL and waits for
+    /// the response. The JSON body of the request is sent as VelocyPack
+    /// and a VelocyPack response body is converted to JSON, so that the
+    /// response looks the same as one received via HTTP.
+    ///
+    /// # Errors
+    /// * `ErrorKind::InvalidData` - If the request cannot be encoded or the
+    ///   server sends something which is not valid VST
+    /// * `ErrorKind::PermissionDenied` - If the authentication fails
+    /// * Any other I/O error of the connection, including connect timeouts
+    ///
+    /// The request timeout is applied by the caller, dropping the future
+    /// drops the connection with the request in flight.
+    pub async fn send(&self, endpoint_url: &str, request: &ApiRequest) -> Result<ApiResponse> {
+        let message = request_message(request)?;
+        let mut stream = match self.take_idle(endpoint_url) {
+            Some(stream) => stream,
+            None => self.connect(endpoint_url).await?,
+        };
+        let response = self.exchange(&mut stream, &message).await?;
+        // A connection which failed is dropped instead
+        self.put_idle(endpoint_url, stream);
+        parse_response(&response, endpoint_url)
+    }
+
+    fn take_idle(&self, endpoint_url: &str) -> Option<TcpStream> {
+        self.idle
+            .lock()
+            .unwrap()
+            .get_mut(endpoint_url)
+            .and_then(|streams| streams.pop())
+    }
+
+    fn put_idle(&self, endpoint_url: &str, stream: TcpStream) {
+        let mut idle = self.idle.lock().unwrap();
+        let streams = idle.entry(endpoint_url.to_string()).or_default();
+        if streams.len() < self.max_idle_per_host {
+            streams.push(stream);
+        }
+    }
+
+    /// Opens a new connection to an endpoint and authenticates it
+    async fn connect(&self, endpoint_url: &str) -> Result<TcpStream> {
+        let address = endpoint_url
+            .strip_prefix("http://")
+            .or_else(|| endpoint_url.strip_prefix("tcp://"))
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("VST needs a http:// or tcp:// endpoint: {}", endpoint_url),
+                )
+            })?;
+        let connect = TcpStream::connect(address);
+        let mut stream = match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connect)
+                .await
+                .map_err(|_| Error::new(ErrorKind::TimedOut, "VST connect timed out"))??,
+            None => connect.await?,
+        };
+        stream.set_nodelay(true)?;
+        stream.write_all(PROTOCOL_HEADER).await?;
+
+        let authentication = velocypack::encode(&json!([
+            1,
+            MESSAGE_AUTHENTICATION,
+            "plain",
+            self.username,
+            self.password
+        ]));
+        let response = self.exchange(&mut stream, &authentication).await?;
+        let header = velocypack::decode(&response).map_err(invalid_data)?;
+        if header[2].as_u64() != Some(200) {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                format!("VST authentication at {} failed", endpoint_url),
+            ));
+        }
+        Ok(stream)
+    }
+
+    /// Sends a message in chunks and reads the chunks of the response
+    async fn exchange(&self, stream: &mut TcpStream, message: &[u8]) -> Result<Vec<u8>> {
+        let id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+        let parts: Vec<&[u8]> = message.chunks(MAX_CHUNK_SIZE - CHUNK_HEADER_SIZE).collect();
+        let mut buffer = Vec::with_capacity(message.len() + parts.len() * CHUNK_HEADER_SIZE);
+        for (i, part) in parts.iter().enumerate() {
+            // The first chunk carries the number of chunks, the others
+            // their index
+            let chunk_x = if i == 0 {
+                ((parts.len() as u32) << 1) | 1
+            } else {
+                (i as u32) << 1
+            };
+            buffer.extend_from_slice(&((part.len() + CHUNK_HEADER_SIZE) as u32).to_le_bytes());
+            buffer.extend_from_slice(&chunk_x.to_le_bytes());
+            buffer.extend_from_slice(&id.to_le_bytes());
+            buffer.extend_from_slice(&(message.len() as u64).to_le_bytes());
+            buffer.extend_from_slice(part);
+        }
+        stream.write_all(&buffer).await?;
+
+        let mut response = Vec::new();
+        loop {
+            let mut header = [0u8; CHUNK_HEADER_SIZE];
+            stream.read_exact(&mut header).await?;
+            let length = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+            let message_id = u64::from_le_bytes(header[8..16].try_into().unwrap());
+            let message_length = u64::from_le_bytes(header[16..24].try_into().unwrap());
+            if length < CHUNK_HEADER_SIZE || message_id != id {
+                return Err(invalid_data("Unexpected VST chunk"));
+            }
+            let start = response.len();
+            response.resize(start + length - CHUNK_HEADER_SIZE, 0);
+            stream.read_exact(&mut response[start..]).await?;
+            if response.len() as u64 >= message_length {
+                return Ok(response);
+            }
+        }
+    }
+}
+
+/// Returns the VST request type of an HTTP method
+fn request_type(method: &Method) -> Result<u64> {
+    Ok(match *method {
+        Method::DELETE => 0,
+        Method::GET => 1,
+        Method::POST => 2,
+        Method::PUT => 3,
+        Method::HEAD => 4,
+        Method::PATCH => 5,
+        Method::OPTIONS => 6,
+        _ => return Err(invalid_data(format!("Method {} not supported", method))),
+    })
+}
+
+/// Splits a path like `/_db/crud/_api/document` into the database and the
+/// path within it
+fn split_database(path: &str) -> (&str, &str) {
+    match path.strip_prefix("/_db/") {
+        Some(rest) => match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        },
+        None => ("_system", path),
+    }
+}
+
+fn is_json(content_type: Option<&HeaderValue>) -> bool {
+    content_type.is_some_and(|value| value.as_bytes().starts_with(b"application/json"))
+}
+
+/// Builds the request message: the VelocyPack header followed by the body
+fn request_message(request: &ApiRequest) -> Result<Vec<u8>> {
+    let (database, path) = split_database(request.path());
+    let parameters: Map<String, Value> = request
+        .query_pairs()
+        .iter()
+        .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+        .collect();
+
+    // JSON bodies are sent as VelocyPack, which is the default content
+    // type of VST, other bodies as they are
+    let json_body = is_json(request.headers().get(CONTENT_TYPE));
+    let mut meta = Map::new();
+    for (name, value) in request.headers() {
+        if json_body && name == CONTENT_TYPE {
+            continue;
+        }
+        let value = value.to_str().map_err(invalid_data)?;
+        meta.insert(name.as_str().to_string(), Value::String(value.to_string()));
+    }
+
+    let mut message = velocypack::encode(&json!([
+        1,
+        MESSAGE_REQUEST,
+        database,
+        request_type(request.method())?,
+        path,
+        parameters,
+        meta
+    ]));
+    if let Some(body) = request.payload()? {
+        if json_body {
+            let value: Value = serde_json::from_slice(&body).map_err(invalid_data)?;
+            message.extend_from_slice(&velocypack::encode(&value));
+        } else {
+            message.extend_from_slice(&body);
+        }
+    }
+    Ok(message)
+}
+
+/// Parses a response message, `[version, type, code, meta]` followed by
+/// the body
+fn parse_response(message: &[u8], endpoint_url: &str) -> Result<ApiResponse> {
+    let header_size = velocypack::byte_size(message).map_err(invalid_data)?;
+    let header = velocypack::decode(message).map_err(invalid_data)?;
+    let code = header[2]
+        .as_u64()
+        .ok_or_else(|| invalid_data("VST response without status code"))?;
+    let status = StatusCode::from_u16(code as u16).map_err(invalid_data)?;
+
+    let mut headers = HeaderMap::new();
+    if let Some(meta) = header[3].as_object() {
+        for (name, value) in meta {
+            if let (Ok(name), Some(Ok(value))) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                value.as_str().map(HeaderValue::from_str),
+            ) {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    let payload = &message[header_size..];
+    let velocypack_body = headers
+        .get(CONTENT_TYPE)
+        .is_none_or(|value| value.as_bytes().ends_with(b"x-velocypack"));
+    let body = if payload.is_empty() || !velocypack_body {
+        Bytes::copy_from_slice(payload)
+    } else {
+        let value = velocypack::decode(payload).map_err(invalid_data)?;
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Bytes::from(serde_json::to_vec(&value).map_err(invalid_data)?)
+    };
+
+    Ok(ApiResponse {
+        status,
+        headers,
+        body,
+        endpoint: endpoint_url.to_string(),
+    })
+}